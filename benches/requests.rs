@@ -0,0 +1,94 @@
+//! building requests and decoding responses, see `protocol`. run with `cargo bench`, or with
+//! `cargo bench -- --save-baseline main` before a change and `--baseline main` after it to
+//! see the difference.
+//!
+//! the numbers depend on the machine, only compare runs made on the same one.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use http::{HeaderMap, StatusCode};
+use neshan_rs::{protocol, Point, RouteOptions, Type};
+
+/// points around tehran with as many digits as a gps fix has.
+fn points(count: usize) -> Vec<Point> {
+    (0..count)
+        .map(|i| {
+            let i = i as f64;
+            Point::new_unchecked(35.6997 + i * 0.000_731_9, 51.338 + i * 0.001_093_7)
+        })
+        .collect()
+}
+
+fn requests(c: &mut Criterion) {
+    let mut group = c.benchmark_group("request");
+    let (origin, destination) = (points(2)[0], points(2)[1]);
+    let options = RouteOptions::new().avoid_traffic_zone(true);
+    group.bench_function("route", |b| {
+        b.iter(|| {
+            protocol::build_route_request(
+                "key",
+                Type::Car,
+                black_box(origin),
+                black_box(destination),
+                &options,
+            )
+            .unwrap()
+        })
+    });
+
+    for size in [10, 50] {
+        let points = points(size);
+        group.bench_with_input(
+            BenchmarkId::new("distance_matrix", size),
+            &points,
+            |b, points| {
+                b.iter(|| {
+                    protocol::build_distance_matrix_request("key", Type::Car, points, points)
+                        .unwrap()
+                })
+            },
+        );
+    }
+
+    let trace = points(500);
+    group.bench_function("map_match/500", |b| {
+        b.iter(|| protocol::build_map_match_request("key", black_box(&trace)).unwrap())
+    });
+    group.finish();
+}
+
+fn decoding(c: &mut Criterion) {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        "application/json".parse().unwrap(),
+    );
+    let mut group = c.benchmark_group("decode");
+
+    macro_rules! fixture {
+        ($name:literal, $parse:path) => {
+            let body = include_bytes!(concat!("../fixtures/", $name, ".json"));
+            group.bench_function($name, |b| {
+                b.iter(|| $parse(StatusCode::OK, &headers, black_box(body)).unwrap())
+            });
+        };
+    }
+    fixture!("route", protocol::parse_route_response);
+    fixture!("route_empty", protocol::parse_route_response);
+    fixture!("route_without_geometry", protocol::parse_route_response);
+    fixture!("reverse_geocode", protocol::parse_reverse_geocode_response);
+    fixture!(
+        "reverse_geocode_minimal",
+        protocol::parse_reverse_geocode_response
+    );
+    fixture!("distance_matrix", protocol::parse_distance_matrix_response);
+    fixture!(
+        "distance_matrix_unroutable",
+        protocol::parse_distance_matrix_response
+    );
+    fixture!("map_matching", protocol::parse_map_match_response);
+    fixture!("map_matching_detailed", protocol::parse_map_match_response);
+    group.finish();
+}
+
+criterion_group!(benches, requests, decoding);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+#!/usr/bin/env bash
+# build a representative matrix of feature sets, each one on its own so a feature that only
+# compiles next to another one shows up. run from the root of the repository.
+set -euo pipefail
+
+features=(disk-cache geo gpx otel test-utils tracing uom utm zones cli)
+
+echo "--- without any feature"
+cargo check --no-default-features --lib
+
+for feature in "${features[@]}"; do
+	echo "--- ${feature}"
+	cargo check --no-default-features --features "${feature}" --all-targets
+	cargo check --features "${feature}" --all-targets
+done
+
+echo "--- all features"
+cargo clippy --all-features --all-targets -- -D warnings
+
+# tests need an http backend, so they run with the default features.
+echo "--- tests"
+cargo test
+cargo test --all-features
@@ -0,0 +1,140 @@
+//! reverse geocoding with the components of v5 of the api, see `Client::reverse_geocode_v5`.
+
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::Point;
+use serde::{Deserialize, Serialize};
+
+/// address of a point from v5 of the reverse geocoding api. it has the fields of
+/// `PostalAddress`, but with `city` and `neighbourhood` left out for rural points, and adds
+/// `route_type`, `village`, `county` and `district` which only v5 sends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostalAddressV5 {
+    /// `OK` for a resolved point.
+    pub status: String,
+    pub formatted_address: String,
+    #[serde(default)]
+    pub route_name: Option<String>,
+    /// class of the road, e.g. `primary` or `residential`. v5 only.
+    #[serde(default)]
+    pub route_type: Option<String>,
+    #[serde(default)]
+    pub neighbourhood: Option<String>,
+    /// `None` outside cities, see `village`.
+    #[serde(default)]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub place: Option<String>,
+    #[serde(default)]
+    pub municipality_zone: Option<String>,
+    #[serde(default)]
+    pub in_traffic_zone: bool,
+    #[serde(default)]
+    pub in_odd_even_zone: bool,
+    /// v5 only, for rural points.
+    #[serde(default)]
+    pub village: Option<String>,
+    /// the county, `شهرستان`, of the point. v5 only.
+    #[serde(default)]
+    pub county: Option<String>,
+    /// the district, `بخش`, of the county. v5 only.
+    #[serde(default)]
+    pub district: Option<String>,
+}
+
+impl PostalAddressV5 {
+    /// the address of a `/v5/reverse` body, e.g. one saved to compare the two versions.
+    pub fn from_json(json: &str) -> Result<PostalAddressV5, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// the city of the point, or its village outside cities.
+    pub fn settlement(&self) -> Option<&str> {
+        self.city.as_deref().or(self.village.as_deref())
+    }
+}
+
+impl Client {
+    /// address of `point` with the components of v5 of the reverse geocoding api, such as
+    /// its county and district. `reverse_geocode` keeps calling v2.
+    /// https://platform.neshan.org/api/reverse-geocoding
+    pub async fn reverse_geocode_v5(
+        &self,
+        point: impl Into<Point>,
+    ) -> Result<PostalAddressV5, NeshanError> {
+        let point = point.into();
+        self.check(&[point])?;
+
+        let query = crate::protocol::reverse_geocode_query(point);
+        let call = self.get(Endpoint::ReverseGeocodeV5, &query);
+
+        crate::trace::instrument(Endpoint::ReverseGeocodeV5, &[point], call)
+            .await
+            .map(|(address, _)| address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostalAddressV5;
+    use crate::endpoint::Endpoint;
+    use crate::mock::{MockNeshan, MockResponse};
+    use crate::PostalAddress;
+
+    #[test]
+    fn urban_and_rural_points() {
+        let tehran =
+            PostalAddressV5::from_json(include_str!("../fixtures/reverse_geocode_v5.json"))
+                .unwrap();
+        assert_eq!(tehran.city.as_deref(), Some("تهران"));
+        assert_eq!(tehran.route_type.as_deref(), Some("primary"));
+        assert_eq!(tehran.district.as_deref(), Some("بخش مرکزی"));
+        assert_eq!(tehran.settlement(), Some("تهران"));
+
+        let rural = include_str!("../fixtures/reverse_geocode_v5_rural.json");
+        let yush = PostalAddressV5::from_json(rural).unwrap();
+        assert_eq!(
+            (yush.city.as_ref(), yush.neighbourhood.as_ref()),
+            (None, None)
+        );
+        assert_eq!(yush.village.as_deref(), Some("یوش"));
+        assert_eq!(yush.county.as_deref(), Some("نور"));
+        assert_eq!(yush.settlement(), Some("یوش"));
+        assert!(!yush.in_traffic_zone);
+        // the v2 model needs a city, so rural points only decode as v5.
+        assert!(PostalAddress::from_json(rural).is_err());
+    }
+
+    #[tokio::test]
+    async fn reverse_geocode_v5() {
+        let neshan = MockNeshan::start().await;
+        neshan
+            .respond(
+                Endpoint::ReverseGeocodeV5,
+                MockResponse::json(
+                    serde_json::from_str(include_str!("../fixtures/reverse_geocode_v5_rural.json"))
+                        .unwrap(),
+                ),
+            )
+            .await;
+        let client = neshan.client();
+
+        let address = client.reverse_geocode_v5((36.33, 51.56)).await.unwrap();
+        assert_eq!(address.village.as_deref(), Some("یوش"));
+        // v2 is left as it was.
+        assert_eq!(
+            client.reverse_geocode((35.7, 51.4)).await.unwrap().city,
+            "تهران"
+        );
+
+        let v5 = neshan.requests_to(Endpoint::ReverseGeocodeV5).await;
+        assert_eq!(v5.len(), 1);
+        assert_eq!(v5[0].path, "/v5/reverse");
+        assert_eq!(v5[0].query("lat"), Some("36.330000"));
+        assert_eq!(v5[0].query("lng"), Some("51.560000"));
+        assert_eq!(neshan.requests_to(Endpoint::ReverseGeocode).await.len(), 1);
+    }
+}
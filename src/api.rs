@@ -0,0 +1,275 @@
+//! the calls of `Client` as a trait, so code depending on the client can be tested with a fake.
+
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::{
+    DistanceMatrix, GeocodeResult, MapMatchOptions, MatchedTrace, Point, PostalAddress,
+    RouteOptions, Routes, SearchResults, StaticMapRequest, Type,
+};
+use async_trait::async_trait;
+
+/// the neshan apis wrapped by `Client`. code that takes an `Arc<dyn NeshanApi>` instead of a
+/// `Client` can be handed a fake in its tests.
+///
+/// the inputs are owned so the trait stays object safe and a fake can keep them around. on a
+/// `Client` itself the inherent methods of the same name win, call the trait ones with e.g.
+/// `NeshanApi::route(&client, ..)`.
+#[async_trait]
+pub trait NeshanApi: Send + Sync {
+    /// see `Client::route_with`.
+    async fn route(
+        &self,
+        vehicle: Type,
+        origin: Point,
+        destination: Point,
+        options: RouteOptions,
+    ) -> Result<Routes, NeshanError>;
+
+    /// see `Client::reverse_geocode`.
+    async fn reverse_geocode(&self, point: Point) -> Result<PostalAddress, NeshanError>;
+
+    /// see `Client::geocode`.
+    async fn geocode(&self, address: String) -> Result<Option<GeocodeResult>, NeshanError>;
+
+    /// see `Client::search`.
+    async fn search(&self, term: String, near: Point) -> Result<SearchResults, NeshanError>;
+
+    /// see `Client::distance_matrix`.
+    async fn distance_matrix(
+        &self,
+        vehicle: Type,
+        origins: Vec<Point>,
+        destinations: Vec<Point>,
+    ) -> Result<DistanceMatrix, NeshanError>;
+
+    /// the whole image, see `Client::static_map_to`.
+    async fn static_map(&self, request: StaticMapRequest) -> Result<Vec<u8>, NeshanError>;
+
+    /// see `Client::map_match_with`.
+    async fn map_match(
+        &self,
+        points: Vec<Point>,
+        options: MapMatchOptions,
+    ) -> Result<MatchedTrace, NeshanError>;
+}
+
+#[async_trait]
+impl NeshanApi for Client {
+    async fn route(
+        &self,
+        vehicle: Type,
+        origin: Point,
+        destination: Point,
+        options: RouteOptions,
+    ) -> Result<Routes, NeshanError> {
+        self.route_with(vehicle, origin, destination, &options)
+            .await
+    }
+
+    async fn reverse_geocode(&self, point: Point) -> Result<PostalAddress, NeshanError> {
+        Client::reverse_geocode(self, point).await
+    }
+
+    async fn geocode(&self, address: String) -> Result<Option<GeocodeResult>, NeshanError> {
+        Client::geocode(self, &address).await
+    }
+
+    async fn search(&self, term: String, near: Point) -> Result<SearchResults, NeshanError> {
+        Client::search(self, &term, near).await
+    }
+
+    async fn distance_matrix(
+        &self,
+        vehicle: Type,
+        origins: Vec<Point>,
+        destinations: Vec<Point>,
+    ) -> Result<DistanceMatrix, NeshanError> {
+        Client::distance_matrix(self, vehicle, &origins, &destinations).await
+    }
+
+    async fn static_map(&self, request: StaticMapRequest) -> Result<Vec<u8>, NeshanError> {
+        let mut image = Vec::new();
+        self.static_map_to(&request, &mut image).await?;
+
+        Ok(image)
+    }
+
+    async fn map_match(
+        &self,
+        points: Vec<Point>,
+        options: MapMatchOptions,
+    ) -> Result<MatchedTrace, NeshanError> {
+        self.map_match_with(&points, &options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NeshanApi;
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::{
+        DistanceMatrix, GeocodeResult, MapMatchOptions, MatchedTrace, Point, PostalAddress,
+        RouteOptions, Routes, SearchResults, StaticMapRequest, Type,
+    };
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn address(city: &str) -> PostalAddress {
+        PostalAddress {
+            formatted_address: format!("{}, azadi square", city),
+            route_name: "azadi".to_string(),
+            neighbourhood: None,
+            city: city.to_string(),
+            state: city.to_string(),
+            place: None,
+            municipality_zone: None,
+            in_traffic_zone: false,
+            in_odd_even_zone: false,
+        }
+    }
+
+    /// answers reverse geocoding from programmed addresses and fails everything else, keeping
+    /// the points it was asked about.
+    #[derive(Default)]
+    struct Fake {
+        addresses: HashMap<String, PostalAddress>,
+        asked: Mutex<Vec<Point>>,
+    }
+
+    impl Fake {
+        fn address(mut self, point: Point, address: PostalAddress) -> Fake {
+            self.addresses.insert(point.to_string(), address);
+            self
+        }
+    }
+
+    fn unprogrammed() -> NeshanError {
+        NeshanError::InvalidRequest("not programmed".to_string())
+    }
+
+    #[async_trait]
+    impl NeshanApi for Fake {
+        async fn route(
+            &self,
+            _: Type,
+            _: Point,
+            _: Point,
+            _: RouteOptions,
+        ) -> Result<Routes, NeshanError> {
+            Err(unprogrammed())
+        }
+
+        async fn reverse_geocode(&self, point: Point) -> Result<PostalAddress, NeshanError> {
+            self.asked.lock().unwrap().push(point);
+            self.addresses
+                .get(&point.to_string())
+                .cloned()
+                .ok_or_else(unprogrammed)
+        }
+
+        async fn geocode(&self, _: String) -> Result<Option<GeocodeResult>, NeshanError> {
+            Err(unprogrammed())
+        }
+
+        async fn search(&self, _: String, _: Point) -> Result<SearchResults, NeshanError> {
+            Err(unprogrammed())
+        }
+
+        async fn distance_matrix(
+            &self,
+            _: Type,
+            _: Vec<Point>,
+            _: Vec<Point>,
+        ) -> Result<DistanceMatrix, NeshanError> {
+            Err(unprogrammed())
+        }
+
+        async fn static_map(&self, _: StaticMapRequest) -> Result<Vec<u8>, NeshanError> {
+            Err(unprogrammed())
+        }
+
+        async fn map_match(
+            &self,
+            _: Vec<Point>,
+            _: MapMatchOptions,
+        ) -> Result<MatchedTrace, NeshanError> {
+            Err(unprogrammed())
+        }
+    }
+
+    /// what a downstream handler may look like, knowing only the trait.
+    async fn city_of(api: Arc<dyn NeshanApi>, point: Point) -> String {
+        match api.reverse_geocode(point).await {
+            Ok(address) => address.city,
+            Err(_) => "unknown".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn fake_behind_the_trait() {
+        let tehran = Point::new_unchecked(35.6997, 51.338);
+        let karaj = Point::new_unchecked(35.8355, 50.9915);
+        let fake = Arc::new(Fake::default().address(tehran, address("tehran")));
+
+        assert_eq!(city_of(fake.clone(), tehran).await, "tehran");
+        assert_eq!(city_of(fake.clone(), karaj).await, "unknown");
+        assert_eq!(*fake.asked.lock().unwrap(), vec![tehran, karaj]);
+    }
+
+    #[tokio::test]
+    async fn client_behind_the_trait() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(query_param("lat", "35.699700"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(address("tehran")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let api: Arc<dyn NeshanApi> = Arc::new(client);
+        assert_eq!(
+            city_of(api.clone(), Point::new_unchecked(35.6997, 51.338)).await,
+            "tehran"
+        );
+
+        Mock::given(method("GET"))
+            .and(path("/v4/geocoding"))
+            .and(query_param("address", "میدان آزادی"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/geocode.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let azadi = api.geocode("میدان آزادی".to_string()).await.unwrap();
+        assert_eq!(azadi.unwrap().title.as_deref(), Some("میدان آزادی"));
+
+        Mock::given(method("GET"))
+            .and(path("/v1/search"))
+            .and(query_param("term", "کافه"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/search.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let cafes = api
+            .search("کافه".to_string(), Point::new_unchecked(35.6997, 51.338))
+            .await
+            .unwrap();
+        assert_eq!(cafes.count, 3);
+    }
+}
@@ -0,0 +1,186 @@
+//! adding and scaling distances and durations, e.g. over the legs of a route.
+//!
+//! the values are floats: a sum beyond `f64::MAX` becomes infinite and a nan value makes the
+//! result nan, like any float arithmetic. results get a new persian text from `humanize`,
+//! which shows infinite values as the largest number and nan as zero.
+
+use crate::{Distance, Duration, Locale, Route};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul};
+
+macro_rules! arithmetic {
+    ($type:ident) => {
+        impl $type {
+            /// `value` with its text from `humanize` in persian, like the one of neshan.
+            pub(crate) fn humanized(value: f64) -> $type {
+                let mut quantity = $type {
+                    value,
+                    text: String::new(),
+                };
+                quantity.text = quantity.humanize(Locale::Persian);
+                quantity
+            }
+        }
+
+        /// zero, with its persian text.
+        impl Default for $type {
+            fn default() -> $type {
+                $type::humanized(0.0)
+            }
+        }
+
+        impl Add for $type {
+            type Output = $type;
+
+            fn add(self, other: $type) -> $type {
+                $type::humanized(self.value + other.value)
+            }
+        }
+
+        impl Add<&$type> for $type {
+            type Output = $type;
+
+            fn add(self, other: &$type) -> $type {
+                $type::humanized(self.value + other.value)
+            }
+        }
+
+        impl AddAssign for $type {
+            fn add_assign(&mut self, other: $type) {
+                *self = $type::humanized(self.value + other.value);
+            }
+        }
+
+        impl AddAssign<&$type> for $type {
+            fn add_assign(&mut self, other: &$type) {
+                *self = $type::humanized(self.value + other.value);
+            }
+        }
+
+        /// scale, e.g. by 1.2 for padding an eta by 20%.
+        impl Mul<f64> for $type {
+            type Output = $type;
+
+            fn mul(self, factor: f64) -> $type {
+                $type::humanized(self.value * factor)
+            }
+        }
+
+        /// the text is generated once for the total, an empty iterator sums to zero.
+        impl Sum for $type {
+            fn sum<I: Iterator<Item = $type>>(iter: I) -> $type {
+                $type::humanized(iter.map(|quantity| quantity.value).sum())
+            }
+        }
+
+        impl<'a> Sum<&'a $type> for $type {
+            fn sum<I: Iterator<Item = &'a $type>>(iter: I) -> $type {
+                $type::humanized(iter.map(|quantity| quantity.value).sum())
+            }
+        }
+    };
+}
+
+arithmetic!(Distance);
+arithmetic!(Duration);
+
+impl Route {
+    /// distance of all legs, the text is in persian like the one of neshan.
+    pub fn total_distance(&self) -> Distance {
+        self.legs().map(|leg| &leg.distance).sum()
+    }
+
+    /// duration of all legs, the text is in persian like the one of neshan.
+    pub fn total_duration(&self) -> Duration {
+        self.legs().map(|leg| &leg.duration).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Distance, Duration, Leg, Route};
+
+    fn distance(value: f64) -> Distance {
+        Distance {
+            value,
+            text: "neshan".to_string(),
+        }
+    }
+
+    fn duration(value: f64) -> Duration {
+        Duration {
+            value,
+            text: "neshan".to_string(),
+        }
+    }
+
+    #[test]
+    fn add_and_scale() {
+        let total = distance(400.0) + distance(800.0);
+        assert_eq!(total.value, 1200.0);
+        assert_eq!(total.text, "۱٫۲ کیلومتر");
+        assert_eq!((distance(400.0) + &distance(100.0)).text, "۵۰۰ متر");
+
+        let mut eta = duration(600.0);
+        eta += duration(300.0);
+        eta += &duration(300.0);
+        assert_eq!(eta.value, 1200.0);
+        assert_eq!(eta.text, "۲۰ دقیقه");
+
+        let padded = eta * 1.5;
+        assert_eq!(padded.value, 1800.0);
+        assert_eq!(padded.text, "۳۰ دقیقه");
+
+        assert_eq!(Distance::default().value, 0.0);
+        assert_eq!(Distance::default().text, "۰ متر");
+        assert_eq!(Duration::default().text, "۰ ثانیه");
+    }
+
+    #[test]
+    fn sum() {
+        let distances = vec![distance(100.0), distance(200.0), distance(300.0)];
+        let total: Distance = distances.iter().sum();
+        assert_eq!(total.value, 600.0);
+        assert_eq!(total.text, "۶۰۰ متر");
+        assert_eq!(distances.into_iter().sum::<Distance>().value, 600.0);
+
+        let empty: Duration = Vec::<Duration>::new().into_iter().sum();
+        assert_eq!(empty, Duration::default());
+    }
+
+    #[test]
+    fn overflow_and_nan() {
+        let infinite = distance(f64::MAX) + distance(f64::MAX);
+        assert_eq!(infinite.value, f64::INFINITY);
+        assert!((distance(f64::MAX) * 2.0).value.is_infinite());
+
+        let nan = duration(60.0) + duration(f64::NAN);
+        assert!(nan.value.is_nan());
+        assert_eq!(nan.text, "۰ ثانیه");
+        assert!((duration(60.0) * f64::NAN).value.is_nan());
+        assert!([duration(1.0), duration(f64::NAN)]
+            .iter()
+            .sum::<Duration>()
+            .value
+            .is_nan());
+    }
+
+    #[test]
+    fn route_totals() {
+        let leg = |meters: f64, seconds: f64| Leg {
+            summary: String::new(),
+            distance: distance(meters),
+            duration: duration(seconds),
+            steps: Vec::new(),
+        };
+        let route = Route {
+            legs: vec![leg(1500.0, 120.0), leg(500.0, 60.0)],
+            overview_polyline: None,
+        };
+
+        assert_eq!(route.total_distance().value, 2000.0);
+        assert_eq!(route.total_distance().text, "۲ کیلومتر");
+        assert_eq!(route.total_duration().value, 180.0);
+        assert_eq!(route.total_duration().text, "۳ دقیقه");
+    }
+}
@@ -0,0 +1,469 @@
+//! audit trail of the requests a client sends and what neshan answered, see `AuditSink`.
+
+use crate::endpoint::Endpoint;
+use crate::error::ErrorKind;
+use serde::{Serialize, Serializer};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// what replaces the api key wherever it shows up in an entry.
+const REDACTED: &str = "[redacted]";
+
+/// one request sent to neshan, retries are separate entries.
+///
+/// the api key is never part of an entry, it is sent as a header and any copy of it in the
+/// parameters or the body is replaced with `[redacted]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct AuditEntry {
+    /// when the request was sent, written as rfc 3339 in utc.
+    #[serde(serialize_with = "rfc3339")]
+    pub timestamp: SystemTime,
+    #[serde(serialize_with = "endpoint_label")]
+    pub endpoint: Endpoint,
+    /// path of the request under the base url, e.g. `/v2/reverse`.
+    pub path: String,
+    /// query parameters sorted by name, decoded from the url.
+    pub params: Vec<(String, String)>,
+    /// http status, `None` when neshan didn't respond.
+    pub status: Option<u16>,
+    /// from sending the request until its whole body was read.
+    #[serde(rename = "duration_ms", serialize_with = "millis")]
+    pub duration: Duration,
+    /// id neshan gave the request, see `NeshanError::request_id`.
+    pub request_id: Option<String>,
+    /// kind of the failure, `None` when the request succeeded.
+    #[serde(serialize_with = "error_label")]
+    pub error: Option<ErrorKind>,
+    /// response body up to `AuditSink::body_limit` bytes, `None` when bodies are left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// whether `body` was cut at the limit.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub body_truncated: bool,
+}
+
+impl AuditEntry {
+    /// entry of a request that `status` answered, or that failed before a response when it
+    /// is `None`. `api_key` is redacted wherever it appears.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        timestamp: SystemTime,
+        endpoint: Endpoint,
+        url: &url::Url,
+        status: Option<u16>,
+        duration: Duration,
+        request_id: Option<&str>,
+        error: Option<ErrorKind>,
+        api_key: &str,
+    ) -> AuditEntry {
+        let redact = |text: &str| match api_key {
+            "" => text.to_string(),
+            key => text.replace(key, REDACTED),
+        };
+
+        let mut params: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| (redact(&name), redact(&value)))
+            .collect();
+        params.sort();
+
+        AuditEntry {
+            timestamp,
+            endpoint,
+            path: redact(url.path()),
+            params,
+            status,
+            duration,
+            request_id: request_id.map(str::to_string),
+            error,
+            body: None,
+            body_truncated: false,
+        }
+    }
+
+    /// keep at most `limit` bytes of `body`, cut at a character boundary.
+    pub(crate) fn body(mut self, body: &[u8], limit: usize, api_key: &str) -> AuditEntry {
+        let text = String::from_utf8_lossy(body);
+        let text = match api_key {
+            "" => text.into_owned(),
+            key => text.replace(key, REDACTED),
+        };
+
+        let mut end = text.len().min(limit);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.body_truncated = end < text.len();
+        self.body = Some(text[..end].to_string());
+
+        self
+    }
+}
+
+fn rfc3339<S: Serializer>(timestamp: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // days since the epoch to a civil date, from howard hinnant's date algorithms.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    serializer.collect_str(&format_args!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    ))
+}
+
+fn endpoint_label<S: Serializer>(endpoint: &Endpoint, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(endpoint.as_str())
+}
+
+fn error_label<S: Serializer>(kind: &Option<ErrorKind>, serializer: S) -> Result<S::Ok, S::Error> {
+    match kind {
+        Some(kind) => serializer.serialize_some(kind.as_str()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+/// receives an `AuditEntry` for every request a client sends, retries and downloads included,
+/// set with `ClientBuilder::audit`. entries of cached responses aren't recorded since nothing
+/// was sent.
+///
+/// `record` runs inline with the request, so slow sinks should hand entries off, e.g. to a
+/// channel.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, entry: AuditEntry);
+
+    /// bytes of each response body to keep in the entries, bodies are left out by default.
+    /// downloads such as static maps never carry their body.
+    fn body_limit(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// sink appending entries as json lines to a file, rotating it once it grows past
+/// `max_file_size`: `audit.jsonl` becomes `audit.jsonl.1`, the previous `.1` becomes `.2` and
+/// so on, up to `keep` rotated files.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// use neshan_rs::{Client, JsonlAudit};
+///
+/// let audit = JsonlAudit::open("/var/log/neshan/audit.jsonl")?
+///     .max_file_size(16 * 1024 * 1024)
+///     .body_limit(4096);
+/// let client = Client::builder("api-key").audit(Arc::new(audit)).build()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct JsonlAudit {
+    path: PathBuf,
+    max_file_size: u64,
+    keep: usize,
+    body_limit: Option<usize>,
+    file: Mutex<Current>,
+    failed_writes: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Current {
+    file: File,
+    size: u64,
+}
+
+fn append(path: &Path) -> io::Result<Current> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+
+    Ok(Current { file, size })
+}
+
+impl JsonlAudit {
+    /// append to the file at `path`, creating it when it doesn't exist. files rotate at 64 MiB
+    /// and five rotated files are kept by default.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<JsonlAudit> {
+        let path = path.into();
+        let file = append(&path)?;
+
+        Ok(JsonlAudit {
+            path,
+            max_file_size: 64 * 1024 * 1024,
+            keep: 5,
+            body_limit: None,
+            file: Mutex::new(file),
+            failed_writes: AtomicU64::new(0),
+        })
+    }
+
+    /// rotate the file before it grows past `bytes`.
+    pub fn max_file_size(mut self, bytes: u64) -> JsonlAudit {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// rotated files to keep, older ones are deleted. zero truncates the file instead.
+    pub fn keep(mut self, files: usize) -> JsonlAudit {
+        self.keep = files;
+        self
+    }
+
+    /// also write up to `bytes` of each response body.
+    pub fn body_limit(mut self, bytes: usize) -> JsonlAudit {
+        self.body_limit = Some(bytes);
+        self
+    }
+
+    /// entries that could not be written, e.g. because the disk is full.
+    pub fn failed_writes(&self) -> u64 {
+        self.failed_writes.load(Ordering::Relaxed)
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&self) -> io::Result<Current> {
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+            return append(&self.path);
+        }
+
+        let _ = fs::remove_file(self.rotated(self.keep));
+        for index in (1..self.keep).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))?;
+
+        append(&self.path)
+    }
+
+    fn write(&self, line: &[u8]) -> io::Result<()> {
+        let mut current = self.file.lock().unwrap();
+        if current.size > 0 && current.size + line.len() as u64 > self.max_file_size {
+            *current = self.rotate()?;
+        }
+
+        current.file.write_all(line)?;
+        current.size += line.len() as u64;
+
+        Ok(())
+    }
+}
+
+impl AuditSink for JsonlAudit {
+    fn record(&self, entry: AuditEntry) {
+        let written = serde_json::to_vec(&entry)
+            .map_err(io::Error::from)
+            .and_then(|mut line| {
+                line.push(b'\n');
+                self.write(&line)
+            });
+
+        if written.is_err() {
+            self.failed_writes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn body_limit(&self) -> Option<usize> {
+        self.body_limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditEntry, JsonlAudit};
+    use crate::client::Client;
+    use crate::endpoint::Endpoint;
+    use crate::retry::RetryPolicy;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let dir = std::env::temp_dir().join(format!(
+                "neshan-rs-audit-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            TempFile(dir.join("audit.jsonl"))
+        }
+
+        fn lines(&self) -> Vec<serde_json::Value> {
+            std::fs::read_to_string(&self.0)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.0.parent().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn jsonl_of_every_attempt() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("busy"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{"city": "تهران", "echo": "secret-api-key"}"#)
+                    .insert_header("X-Request-Id", "req-1"),
+            )
+            .mount(&server)
+            .await;
+
+        let file = TempFile::new("attempts");
+        let audit = Arc::new(JsonlAudit::open(&file.0).unwrap().body_limit(20));
+        let client = Client::builder("secret-api-key")
+            .base_url(&server.uri())
+            .retry(RetryPolicy::new().initial_backoff(Duration::from_millis(1)))
+            .audit(audit.clone())
+            .build()
+            .unwrap();
+
+        let body = client
+            .get_bytes("/v2/reverse", &[("lng", "51.4"), ("lat", "35.7")])
+            .await
+            .unwrap();
+        assert!(body.starts_with(b"{\"city\""));
+
+        let lines = file.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["endpoint"], "custom");
+        assert_eq!(lines[0]["path"], "/v2/reverse");
+        assert_eq!(
+            lines[0]["params"],
+            serde_json::json!([["lat", "35.7"], ["lng", "51.4"]])
+        );
+        assert_eq!(lines[0]["status"], 503);
+        assert_eq!(lines[0]["error"], "server");
+        assert_eq!(lines[0]["body"], "busy");
+
+        assert_eq!(lines[1]["status"], 200);
+        assert_eq!(lines[1]["error"], serde_json::Value::Null);
+        assert_eq!(lines[1]["request_id"], "req-1");
+        // 20 bytes cut at a character boundary, persian letters take two.
+        assert_eq!(lines[1]["body"], r#"{"city": "تهران"#);
+        assert_eq!(lines[1]["body_truncated"], true);
+        assert!(lines[1]["duration_ms"].is_u64());
+        assert!(lines[1]["timestamp"].as_str().unwrap().ends_with('Z'));
+
+        let written = std::fs::read_to_string(&file.0).unwrap();
+        assert!(!written.contains("secret-api-key"));
+        assert_eq!(audit.failed_writes(), 0);
+    }
+
+    #[tokio::test]
+    async fn api_key_is_redacted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{\"key\": \"k3y\"}"))
+            .mount(&server)
+            .await;
+
+        let file = TempFile::new("redacted");
+        let client = Client::builder("k3y")
+            .base_url(&server.uri())
+            .audit(Arc::new(
+                JsonlAudit::open(&file.0).unwrap().body_limit(1024),
+            ))
+            .build()
+            .unwrap();
+        client
+            .get_bytes("/v1/echo", &[("api_key", "k3y")])
+            .await
+            .unwrap();
+
+        let lines = file.lines();
+        assert_eq!(
+            lines[0]["params"],
+            serde_json::json!([["api_key", "[redacted]"]])
+        );
+        assert_eq!(lines[0]["body"], "{\"key\": \"[redacted]\"}");
+    }
+
+    #[test]
+    fn rotate_by_size() {
+        let file = TempFile::new("rotate");
+        let audit = JsonlAudit::open(&file.0).unwrap().max_file_size(10).keep(2);
+        let rotated = |index: usize| audit.rotated(index);
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            audit.write(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&file.0).unwrap(), "fourth\n");
+        assert_eq!(std::fs::read_to_string(rotated(1)).unwrap(), "third\n");
+        assert_eq!(std::fs::read_to_string(rotated(2)).unwrap(), "second\n");
+        assert!(!rotated(3).exists());
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339() {
+        let url = url::Url::parse("https://api.neshan.org/v2/reverse?lat=1").unwrap();
+        let entry = AuditEntry::new(
+            UNIX_EPOCH + Duration::from_millis(1_709_251_199_250),
+            Endpoint::ReverseGeocode,
+            &url,
+            None,
+            Duration::from_millis(12),
+            None,
+            None,
+            "key",
+        );
+
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["timestamp"], "2024-02-29T23:59:59.250Z");
+        assert_eq!(json["endpoint"], "reverse_geocode");
+        assert_eq!(json["status"], serde_json::Value::Null);
+        assert_eq!(json["duration_ms"], 12);
+        assert!(json.get("body").is_none());
+    }
+}
@@ -0,0 +1,250 @@
+//! the http transport under the client, see `HttpBackend`.
+
+use crate::error::NeshanError;
+use crate::middleware::{Request, Response};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream::{self, Stream};
+use http::{HeaderMap, StatusCode};
+use std::pin::Pin;
+
+/// body of a `StreamingResponse`, a failed chunk ends it.
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, NeshanError>> + Send>>;
+
+/// http response whose body is read as it arrives.
+pub struct StreamingResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: BodyStream,
+}
+
+/// sends the http requests of a client, see `ClientBuilder::backend`.
+///
+/// everything specific to neshan, e.g. building urls, mapping error statuses and decoding
+/// bodies, happens above the backend, so a backend only has to move bytes. reqwest is used
+/// by default with the `reqwest` feature.
+#[async_trait]
+pub trait HttpBackend: Send + Sync {
+    /// send the request and read its whole response. error statuses are responses too, only
+    /// transport failures are errors.
+    async fn send(&self, req: Request) -> Result<Response, NeshanError>;
+
+    /// send the request and read the body chunk by chunk, used for downloads such as static
+    /// maps. by default the whole body is read with `send` and handed out as a single chunk.
+    async fn send_streaming(&self, req: Request) -> Result<StreamingResponse, NeshanError> {
+        let Response {
+            status,
+            headers,
+            body,
+        } = self.send(req).await?;
+
+        Ok(StreamingResponse {
+            status,
+            headers,
+            body: Box::pin(stream::once(async move { Ok(body) })),
+        })
+    }
+}
+
+/// the default backend, compiled only with the `reqwest` feature.
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone)]
+pub struct ReqwestBackend {
+    http: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestBackend {
+    pub fn new() -> Result<ReqwestBackend, NeshanError> {
+        let http = reqwest::Client::builder()
+            .user_agent("neshan-rs")
+            .build()
+            .map_err(|err| NeshanError::Config(err.to_string()))?;
+
+        Ok(ReqwestBackend { http })
+    }
+
+    /// send with an already configured reqwest client, e.g. one with a proxy.
+    pub fn with_client(http: reqwest::Client) -> ReqwestBackend {
+        ReqwestBackend { http }
+    }
+
+    async fn request(&self, req: Request) -> Result<reqwest::Response, NeshanError> {
+        self.http
+            .request(req.method, req.url)
+            .headers(req.headers)
+            .send()
+            .await
+            .map_err(NeshanError::from_reqwest)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+#[async_trait]
+impl HttpBackend for ReqwestBackend {
+    async fn send(&self, req: Request) -> Result<Response, NeshanError> {
+        let res = self.request(req).await?;
+
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes().await.map_err(NeshanError::from_reqwest)?;
+
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    async fn send_streaming(&self, req: Request) -> Result<StreamingResponse, NeshanError> {
+        let res = self.request(req).await?;
+
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = stream::unfold(Some(res), |res| async move {
+            let mut res = res?;
+            match res.chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(res))),
+                Ok(None) => None,
+                Err(err) => Some((Err(NeshanError::from_reqwest(err)), None)),
+            }
+        });
+
+        Ok(StreamingResponse {
+            status,
+            headers,
+            body: Box::pin(body),
+        })
+    }
+}
+
+/// answers from a table of canned responses by path, without any network.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemoryBackend {
+    responses: std::collections::HashMap<String, (u16, &'static str, Bytes)>,
+    sent: std::sync::Mutex<Vec<Request>>,
+}
+
+#[cfg(test)]
+impl MemoryBackend {
+    /// answer requests to `path` with the status, content type and body.
+    pub(crate) fn respond(
+        mut self,
+        path: &str,
+        status: u16,
+        content_type: &'static str,
+        body: impl Into<Bytes>,
+    ) -> MemoryBackend {
+        self.responses
+            .insert(path.to_string(), (status, content_type, body.into()));
+        self
+    }
+
+    pub(crate) fn sent(&self) -> Vec<Request> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl HttpBackend for MemoryBackend {
+    async fn send(&self, req: Request) -> Result<Response, NeshanError> {
+        let path = req.url.path().to_string();
+        self.sent.lock().unwrap().push(req);
+
+        let (status, content_type, body) = self.responses.get(&path).cloned().unwrap_or((
+            404,
+            "text/plain",
+            Bytes::from_static(b"not found"),
+        ));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(content_type),
+        );
+
+        Ok(Response {
+            status: StatusCode::from_u16(status).unwrap(),
+            headers,
+            body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryBackend;
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::{Point, RouteOptions, StaticMapRequest, Type};
+    use std::sync::Arc;
+
+    const TEHRAN: Point = Point {
+        latitude: 35.6997,
+        longitude: 51.338,
+    };
+
+    fn client(backend: MemoryBackend) -> (Client, Arc<MemoryBackend>) {
+        let backend = Arc::new(backend);
+        let client = Client::builder("key")
+            .backend(backend.clone())
+            .build()
+            .unwrap();
+
+        (client, backend)
+    }
+
+    #[tokio::test]
+    async fn memory_backend() {
+        let (client, backend) = client(
+            MemoryBackend::default()
+                .respond(
+                    "/v3/direction",
+                    200,
+                    "application/json",
+                    include_str!("../fixtures/route.json"),
+                )
+                .respond(
+                    "/v2/reverse",
+                    470,
+                    "application/json",
+                    r#"{"status": "ERROR", "code": 480, "message": "Key not found"}"#,
+                ),
+        );
+
+        let routes = client
+            .route_with(Type::Car, TEHRAN, TEHRAN, &RouteOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(routes.len(), 2);
+
+        let err = client.reverse_geocode(TEHRAN).await.unwrap_err();
+        assert!(matches!(err, NeshanError::Api(ref api) if api.error().code() == 480));
+
+        // urls and headers are built above the backend.
+        let sent = backend.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].url.host_str(), Some("api.neshan.org"));
+        assert_eq!(sent[0].url.path(), "/v3/direction");
+        assert_eq!(sent[0].headers["api-key"], "key");
+    }
+
+    #[tokio::test]
+    async fn downloads_fall_back_to_send() {
+        let (client, _) = client(MemoryBackend::default().respond(
+            "/v4/static",
+            200,
+            "image/png",
+            &b"\x89PNG image"[..],
+        ));
+
+        let mut image = Vec::new();
+        let written = client
+            .static_map_to(&StaticMapRequest::new(TEHRAN, 14, 64, 64), &mut image)
+            .await
+            .unwrap();
+        assert_eq!(written, 10);
+        assert_eq!(image, b"\x89PNG image");
+    }
+}
@@ -0,0 +1,575 @@
+//! bounded concurrency execution of many requests, see `Client::run_batch`.
+
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::point::collect_points;
+use crate::{Point, PostalAddress, RouteOptions, Routes, Type};
+use futures_util::stream::{self, StreamExt};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+type Progress = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+/// concurrency of the prefetch calls.
+const PREFETCH_CONCURRENCY: usize = 4;
+
+/// options of a batch run.
+#[derive(Clone)]
+pub struct BatchOptions {
+    concurrency: usize,
+    deadline: Option<Duration>,
+    progress: Option<Progress>,
+}
+
+impl BatchOptions {
+    /// run at most `concurrency` items at the same time.
+    pub fn new(concurrency: usize) -> BatchOptions {
+        BatchOptions {
+            concurrency: concurrency.max(1),
+            deadline: None,
+            progress: None,
+        }
+    }
+
+    /// bound the whole batch, items that didn't finish in time fail with
+    /// `NeshanError::DeadlineExceeded`.
+    pub fn deadline(mut self, deadline: Duration) -> BatchOptions {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// called with the number of finished items and the total after each item finishes.
+    pub fn progress<F>(mut self, progress: F) -> BatchOptions
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+impl fmt::Debug for BatchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchOptions")
+            .field("concurrency", &self.concurrency)
+            .field("deadline", &self.deadline)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// a route request of a batch together with its outcome, see `Client::route_many`. the
+/// request fields are copies of the inputs, not values read back from the response.
+#[derive(Debug, Clone)]
+pub struct RoutedPair {
+    pub origin: Point,
+    pub destination: Point,
+    pub vehicle: Type,
+    pub options: RouteOptions,
+    pub result: Result<Routes, NeshanError>,
+}
+
+impl RoutedPair {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// outcome of warming the cache, see `Client::prefetch_routes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrefetchSummary {
+    /// responses that are now in the cache.
+    pub warmed: usize,
+    /// requests that failed and were left out of the cache.
+    pub failed: usize,
+}
+
+impl PrefetchSummary {
+    fn of(succeeded: impl ExactSizeIterator<Item = bool>) -> PrefetchSummary {
+        let total = succeeded.len();
+        let warmed = succeeded.filter(|ok| *ok).count();
+
+        PrefetchSummary {
+            warmed,
+            failed: total - warmed,
+        }
+    }
+}
+
+fn ensure_cached(client: &Client, endpoint: Endpoint) -> Result<(), NeshanError> {
+    if client.caches(endpoint) {
+        Ok(())
+    } else {
+        Err(NeshanError::Config(format!(
+            "{} responses are not cached, there is nothing to prefetch",
+            endpoint
+        )))
+    }
+}
+
+impl Client {
+    /// run `f` for every item with bounded concurrency. results are in the order of `items`
+    /// and each item fails on its own without affecting the others.
+    pub async fn run_batch<I, T, F, Fut>(
+        &self,
+        items: impl IntoIterator<Item = I>,
+        options: &BatchOptions,
+        f: F,
+    ) -> Vec<Result<T, NeshanError>>
+    where
+        F: Fn(Client, I) -> Fut,
+        Fut: Future<Output = Result<T, NeshanError>>,
+    {
+        let items: Vec<I> = items.into_iter().collect();
+        let total = items.len();
+        let mut results: Vec<Option<Result<T, NeshanError>>> = (0..total).map(|_| None).collect();
+
+        let f = &f;
+        let mut running = stream::iter(items.into_iter().enumerate())
+            .map(|(index, item)| async move { (index, f(self.clone(), item).await) })
+            .buffer_unordered(options.concurrency);
+
+        let deadline = options.deadline.map(|deadline| Instant::now() + deadline);
+        let mut done = 0;
+
+        loop {
+            let next = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, running.next()).await {
+                    Ok(next) => next,
+                    Err(_) => break,
+                },
+                None => running.next().await,
+            };
+
+            let (index, result) = match next {
+                Some(next) => next,
+                None => break,
+            };
+
+            results[index] = Some(result);
+            done += 1;
+
+            if let Some(progress) = &options.progress {
+                progress(done, total);
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| {
+                    Err(NeshanError::DeadlineExceeded {
+                        deadline: options.deadline.unwrap_or_default(),
+                        attempts: 0,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// find routes for many origin and destination pairs. each result carries its request, in
+    /// the order of `pairs`. each pair is a route of its own, the waypoints and the bearing of
+    /// `options` are left out.
+    pub async fn route_many(
+        &self,
+        vehicle: Type,
+        pairs: &[(Point, Point)],
+        options: &RouteOptions,
+        batch: &BatchOptions,
+    ) -> Vec<RoutedPair> {
+        let options = options.between_stops();
+        self.route_pairs(vehicle, pairs, &options, &options, batch)
+            .await
+    }
+
+    /// same as `route_many` with the `first` options for the first pair, e.g. the bearing at
+    /// the start of a trip.
+    pub(crate) async fn route_pairs(
+        &self,
+        vehicle: Type,
+        pairs: &[(Point, Point)],
+        first: &RouteOptions,
+        options: &RouteOptions,
+        batch: &BatchOptions,
+    ) -> Vec<RoutedPair> {
+        let options_of = |i: usize| if i == 0 { first } else { options };
+        let results = {
+            let vehicle = &vehicle;
+            self.run_batch(
+                pairs.iter().copied().enumerate(),
+                batch,
+                |client, (i, (origin, destination))| async move {
+                    client
+                        .route_with(vehicle.clone(), origin, destination, options_of(i))
+                        .await
+                },
+            )
+            .await
+        };
+
+        pairs
+            .iter()
+            .zip(results)
+            .enumerate()
+            .map(|(i, ((origin, destination), result))| RoutedPair {
+                origin: *origin,
+                destination: *destination,
+                vehicle: vehicle.clone(),
+                options: options_of(i).clone(),
+                result,
+            })
+            .collect()
+    }
+
+    /// fill the response cache with routes of the given pairs ahead of time. fails without
+    /// sending anything when routes aren't cached, which is the default of `CacheConfig`.
+    pub async fn prefetch_routes(
+        &self,
+        pairs: &[(Point, Point)],
+        vehicle: Type,
+        options: &RouteOptions,
+    ) -> Result<PrefetchSummary, NeshanError> {
+        ensure_cached(self, options.version.endpoint())?;
+
+        let results = self
+            .route_many(
+                vehicle,
+                pairs,
+                options,
+                &BatchOptions::new(PREFETCH_CONCURRENCY),
+            )
+            .await;
+
+        Ok(PrefetchSummary::of(results.iter().map(RoutedPair::is_ok)))
+    }
+
+    /// fill the response cache with postal addresses of the given points ahead of time.
+    pub async fn prefetch_reverse_geocodes(
+        &self,
+        points: impl IntoIterator<Item = impl Into<Point>>,
+    ) -> Result<PrefetchSummary, NeshanError> {
+        ensure_cached(self, Endpoint::ReverseGeocode)?;
+
+        let results = self
+            .reverse_geocode_many(points, &BatchOptions::new(PREFETCH_CONCURRENCY))
+            .await;
+
+        Ok(PrefetchSummary::of(results.iter().map(Result::is_ok)))
+    }
+
+    /// find postal addresses of many points.
+    pub async fn reverse_geocode_many(
+        &self,
+        points: impl IntoIterator<Item = impl Into<Point>>,
+        batch: &BatchOptions,
+    ) -> Vec<Result<PostalAddress, NeshanError>> {
+        self.run_batch(collect_points(points), batch, |client, point| async move {
+            client.reverse_geocode(point).await
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchOptions;
+    use crate::client::Client;
+    use crate::error::{ErrorKind, NeshanError};
+    use crate::Point;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn client() -> Client {
+        Client::new("key")
+    }
+
+    #[tokio::test]
+    async fn results_keep_input_order() {
+        let results = client()
+            .run_batch(0..10u64, &BatchOptions::new(4), |_, item| async move {
+                // later items finish first.
+                tokio::time::sleep(Duration::from_millis(50 - item * 5)).await;
+                if item == 3 {
+                    Err(NeshanError::Config("three".to_string()))
+                } else {
+                    Ok(item)
+                }
+            })
+            .await;
+
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(item) => assert_eq!(*item, i as u64),
+                Err(_) => assert_eq!(i, 3),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrency_is_capped() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        client()
+            .run_batch(0..20, &BatchOptions::new(3), |_, _| {
+                let running = running.clone();
+                let peak = peak.clone();
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn progress_is_reported() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let options = {
+            let reports = reports.clone();
+            BatchOptions::new(2).progress(move |done, total| {
+                reports.lock().unwrap().push((done, total));
+            })
+        };
+
+        client()
+            .run_batch(0..4, &options, |_, _| async { Ok(()) })
+            .await;
+
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![(1, 4), (2, 4), (3, 4), (4, 4)]
+        );
+    }
+
+    #[tokio::test]
+    async fn reverse_geocode_many_reports_per_item_errors() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(query_param("lat", "1.000000"))
+            .respond_with(ResponseTemplate::new(470))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "formatted_address": "تهران، خیابان آزادی",
+                "route_name": "خیابان آزادی",
+                "city": "تهران",
+                "state": "استان تهران",
+                "in_traffic_zone": false,
+                "in_odd_even_zone": false
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let points: Vec<Point> = [35.0, 1.0, 36.0]
+            .iter()
+            .map(|latitude| Point {
+                latitude: *latitude,
+                longitude: 51.0,
+            })
+            .collect();
+
+        let results = client
+            .reverse_geocode_many(&points, &BatchOptions::new(2))
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            ErrorKind::InvalidRequest
+        );
+        assert!(results[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn route_many_echoes_requests() {
+        use crate::{RouteOptions, Type};
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("origin", "2.000000,51.000000"))
+            .respond_with(ResponseTemplate::new(470))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": []
+            })))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let pairs: Vec<(Point, Point)> = [1.0, 2.0, 3.0]
+            .iter()
+            .map(|latitude| {
+                (
+                    Point::new_unchecked(*latitude, 51.0),
+                    Point::new_unchecked(*latitude + 10.0, 52.0),
+                )
+            })
+            .collect();
+        let options = RouteOptions::new().avoid_traffic_zone(true);
+
+        let results = client
+            .route_many(
+                Type::Motorcycle,
+                &pairs,
+                &options
+                    .clone()
+                    .waypoints(&[Point::new_unchecked(5.0, 51.5)])
+                    .bearing(90),
+                &BatchOptions::new(3),
+            )
+            .await;
+
+        assert_eq!(results.len(), 3);
+        for ((origin, destination), pair) in pairs.iter().zip(&results) {
+            assert_eq!(pair.origin, *origin);
+            assert_eq!(pair.destination, *destination);
+            assert_eq!(pair.vehicle, Type::Motorcycle);
+            assert_eq!(pair.options, options);
+        }
+        // the waypoints and the bearing belong to a single route, not to every pair.
+        for request in server.received_requests().await.unwrap() {
+            assert!(!request
+                .url
+                .query_pairs()
+                .any(|(key, _)| key == "waypoints" || key == "bearing"));
+        }
+        assert!(results[0].is_ok());
+        assert!(!results[1].is_ok());
+        assert!(results[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn prefetch_warms_the_cache() {
+        use crate::cache::CacheConfig;
+        use crate::endpoint::Endpoint;
+        use crate::{RouteOptions, Type};
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("origin", "3.000000,51.000000"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": []
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16).ttl(Endpoint::Route, Duration::from_secs(60)))
+            .build()
+            .unwrap();
+        let pairs: Vec<(Point, Point)> = [1.0, 2.0, 3.0]
+            .iter()
+            .map(|latitude| {
+                let origin = Point {
+                    latitude: *latitude,
+                    longitude: 51.0,
+                };
+                (origin, origin)
+            })
+            .collect();
+        let options = RouteOptions::new();
+
+        let summary = client
+            .prefetch_routes(&pairs, Type::Car, &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            super::PrefetchSummary {
+                warmed: 2,
+                failed: 1
+            }
+        );
+
+        // the warmed pairs are served from the cache, the failed one goes out again.
+        for (origin, destination) in &pairs {
+            let _ = client
+                .route_with(Type::Car, *origin, *destination, &options)
+                .await;
+        }
+        assert_eq!(client.cache_stats().unwrap().hits, 2);
+    }
+
+    #[tokio::test]
+    async fn prefetch_needs_the_cache() {
+        let err = client()
+            .prefetch_reverse_geocodes(&[Point {
+                latitude: 35.7,
+                longitude: 51.4,
+            }])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::Config(_)));
+
+        // routes aren't cached by default.
+        let client = Client::builder("key")
+            .cache(crate::cache::CacheConfig::new(16))
+            .build()
+            .unwrap();
+        let err = client
+            .prefetch_routes(&[], crate::Type::Car, &crate::RouteOptions::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::Config(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_fails_unfinished_items() {
+        let options = BatchOptions::new(2).deadline(Duration::from_millis(150));
+
+        let results = client()
+            .run_batch(0..4u64, &options, |_, item| async move {
+                tokio::time::sleep(Duration::from_millis(100 * (item + 1))).await;
+                Ok(item)
+            })
+            .await;
+
+        assert_eq!(*results[0].as_ref().unwrap(), 0);
+        for result in &results[1..] {
+            assert_eq!(result.as_ref().unwrap_err().kind(), ErrorKind::Timeout);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! command line client of neshan, built with the `cli` feature.
+//!
+//! ```text
+//! NESHAN_API_KEY=... neshan route --from 35.73,51.39 --to 35.72,50.95 --vehicle car
+//! NESHAN_API_KEY=... neshan reverse 35.73,51.39 --json
+//! ```
+
+use clap::{Parser, Subcommand, ValueEnum};
+use neshan_rs::{Client, ErrorKind, Locale, NeshanError, Point, RouteOptions, Type};
+use std::process::ExitCode;
+
+/// exit codes, other failures exit with 1 and invalid arguments with 2.
+const EXIT_AUTH: u8 = 3;
+const EXIT_QUOTA: u8 = 4;
+const EXIT_NOT_FOUND: u8 = 5;
+
+#[derive(Parser)]
+#[command(
+    name = "neshan",
+    version,
+    about = "neshan.org map apis from the command line"
+)]
+struct Cli {
+    /// api key of neshan.
+    #[arg(long, env = "NESHAN_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    /// where neshan is, e.g. a local mock server.
+    #[arg(long, env = "NESHAN_BASE_URL", hide = true)]
+    base_url: Option<String>,
+    /// print the response as json instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    /// language of the text output.
+    #[arg(long, global = true, value_enum, default_value_t = Language::En)]
+    locale: Language,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// routes from one point to another.
+    Route {
+        /// origin as latitude,longitude.
+        #[arg(long)]
+        from: Point,
+        /// destination as latitude,longitude.
+        #[arg(long)]
+        to: Point,
+        #[arg(long, value_enum, default_value_t = Vehicle::Car)]
+        vehicle: Vehicle,
+        /// also show alternatives besides the suggested route.
+        #[arg(long)]
+        alternatives: bool,
+        #[arg(long)]
+        avoid_traffic_zone: bool,
+        #[arg(long)]
+        avoid_odd_even_zone: bool,
+    },
+    /// postal address of a point.
+    Reverse {
+        /// the point as latitude,longitude.
+        point: Point,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Vehicle {
+    Car,
+    Motorcycle,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Language {
+    En,
+    Fa,
+}
+
+/// failure of a command with the code to exit with.
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl From<NeshanError> for Failure {
+    fn from(err: NeshanError) -> Failure {
+        let code = match err.kind() {
+            ErrorKind::Auth => EXIT_AUTH,
+            ErrorKind::Quota | ErrorKind::RateLimited => EXIT_QUOTA,
+            ErrorKind::NotFound => EXIT_NOT_FOUND,
+            _ => 1,
+        };
+
+        Failure {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+fn json(value: &impl serde::Serialize) -> Result<String, Failure> {
+    serde_json::to_string_pretty(value).map_err(|err| NeshanError::from(err).into())
+}
+
+async fn run(cli: Cli) -> Result<String, Failure> {
+    let api_key = cli.api_key.ok_or_else(|| Failure {
+        code: EXIT_AUTH,
+        message: "no api key, set NESHAN_API_KEY or pass --api-key".to_string(),
+    })?;
+    let mut builder = Client::builder(&api_key);
+    if let Some(base_url) = &cli.base_url {
+        builder = builder.base_url(base_url);
+    }
+    let client = builder.build()?;
+    let locale = match cli.locale {
+        Language::En => Locale::English,
+        Language::Fa => Locale::Persian,
+    };
+
+    match cli.command {
+        Command::Route {
+            from,
+            to,
+            vehicle,
+            alternatives,
+            avoid_traffic_zone,
+            avoid_odd_even_zone,
+        } => {
+            let vehicle = match vehicle {
+                Vehicle::Car => Type::Car,
+                Vehicle::Motorcycle => Type::Motorcycle,
+            };
+            let options = RouteOptions::new()
+                .alternative_paths(alternatives)
+                .avoid_traffic_zone(avoid_traffic_zone)
+                .avoid_odd_even_zone(avoid_odd_even_zone);
+            let routes = client.route_with(vehicle, from, to, &options).await?;
+            if routes.is_empty() {
+                return Err(Failure {
+                    code: EXIT_NOT_FOUND,
+                    message: format!("no route from {} to {}", from, to),
+                });
+            }
+            if cli.json {
+                return json(&routes);
+            }
+
+            let lines: Vec<String> = routes
+                .all_legs()
+                .map(|(route, leg)| {
+                    format!(
+                        "{}. {}: {}, {}",
+                        route + 1,
+                        leg.summary,
+                        leg.distance.humanize(locale),
+                        leg.duration.humanize(locale)
+                    )
+                })
+                .collect();
+            Ok(lines.join("\n"))
+        }
+        Command::Reverse { point } => {
+            let address = client.reverse_geocode(point).await?;
+            if cli.json {
+                return json(&address);
+            }
+
+            let mut lines = vec![address.formatted_address.clone()];
+            let mut area = vec![address.state.as_str(), address.city.as_str()];
+            area.extend(address.neighbourhood.as_deref());
+            lines.push(area.join(", "));
+            if address.in_traffic_zone {
+                lines.push("in the traffic zone".to_string());
+            }
+            if address.in_odd_even_zone {
+                lines.push("in the odd even zone".to_string());
+            }
+            Ok(lines.join("\n"))
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(failure) => {
+            eprintln!("neshan: {}", failure.message);
+            ExitCode::from(failure.code)
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use crate::point::{InvalidCoordinate, EARTH_RADIUS};
+use crate::{Point, Route};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// rectangle between two corners in degrees, e.g. a map viewport.
+///
+/// boxes never cross the antimeridian, the west edge is always at a lower longitude than
+/// the east one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    south_west: Point,
+    north_east: Point,
+}
+
+impl BoundingBox {
+    /// box between the given corners, failing when a corner isn't a valid point or
+    /// `south_west` is north or east of `north_east`.
+    pub fn new(south_west: Point, north_east: Point) -> Result<BoundingBox, BoundingBoxError> {
+        south_west.validate()?;
+        north_east.validate()?;
+        if south_west.latitude > north_east.latitude || south_west.longitude > north_east.longitude
+        {
+            return Err(BoundingBoxError::Inverted {
+                south_west,
+                north_east,
+            });
+        }
+
+        Ok(BoundingBox {
+            south_west,
+            north_east,
+        })
+    }
+
+    /// smallest box around the points, `None` when there are none. a single point gives an
+    /// empty box at that point.
+    pub fn from_points(points: &[Point]) -> Option<BoundingBox> {
+        let (first, rest) = points.split_first()?;
+
+        let mut bounds = BoundingBox {
+            south_west: *first,
+            north_east: *first,
+        };
+        for point in rest {
+            bounds.south_west.latitude = bounds.south_west.latitude.min(point.latitude);
+            bounds.south_west.longitude = bounds.south_west.longitude.min(point.longitude);
+            bounds.north_east.latitude = bounds.north_east.latitude.max(point.latitude);
+            bounds.north_east.longitude = bounds.north_east.longitude.max(point.longitude);
+        }
+
+        Some(bounds)
+    }
+
+    pub fn south_west(&self) -> Point {
+        self.south_west
+    }
+
+    pub fn north_east(&self) -> Point {
+        self.north_east
+    }
+
+    /// whether the point is inside the box or on its edges.
+    pub fn contains(&self, point: &Point) -> bool {
+        (self.south_west.latitude..=self.north_east.latitude).contains(&point.latitude)
+            && (self.south_west.longitude..=self.north_east.longitude).contains(&point.longitude)
+    }
+
+    /// whether the boxes share any point, touching edges included.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.south_west.latitude <= other.north_east.latitude
+            && other.south_west.latitude <= self.north_east.latitude
+            && self.south_west.longitude <= other.north_east.longitude
+            && other.south_west.longitude <= self.north_east.longitude
+    }
+
+    pub fn center(&self) -> Point {
+        Point {
+            latitude: (self.south_west.latitude + self.north_east.latitude) / 2.0,
+            longitude: (self.south_west.longitude + self.north_east.longitude) / 2.0,
+        }
+    }
+
+    /// box grown by at least `meters` on every side, or shrunk for a negative value.
+    ///
+    /// the longitude margin is measured at the edge farthest from the equator, where a degree
+    /// is the shortest. growing past a pole or the antimeridian is an error instead of
+    /// wrapping around, as is shrinking the box past its center.
+    pub fn expand_by_meters(&self, meters: f64) -> Result<BoundingBox, BoundingBoxError> {
+        let latitude = (meters / EARTH_RADIUS).to_degrees();
+        let widest = self
+            .south_west
+            .latitude
+            .abs()
+            .max(self.north_east.latitude.abs())
+            .to_radians();
+        let longitude = (meters / (EARTH_RADIUS * widest.cos())).to_degrees();
+
+        BoundingBox::new(
+            Point {
+                latitude: self.south_west.latitude - latitude,
+                longitude: self.south_west.longitude - longitude,
+            },
+            Point {
+                latitude: self.north_east.latitude + latitude,
+                longitude: self.north_east.longitude + longitude,
+            },
+        )
+    }
+}
+
+impl Route {
+    /// bounds of the overview geometry, `None` when the route has none or it is malformed.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::from_points(&self.geometry().ok()?)
+    }
+}
+
+/// box rejected by `BoundingBox::new` or `BoundingBox::expand_by_meters`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundingBoxError {
+    /// a corner is not a valid point.
+    InvalidCoordinate(InvalidCoordinate),
+    /// the south west corner is north or east of the north east one.
+    Inverted {
+        south_west: Point,
+        north_east: Point,
+    },
+}
+
+impl From<InvalidCoordinate> for BoundingBoxError {
+    fn from(err: InvalidCoordinate) -> BoundingBoxError {
+        BoundingBoxError::InvalidCoordinate(err)
+    }
+}
+
+impl fmt::Display for BoundingBoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoundingBoxError::InvalidCoordinate(err) => write!(f, "invalid corner: {}", err),
+            BoundingBoxError::Inverted {
+                south_west,
+                north_east,
+            } => write!(
+                f,
+                "south west corner {} is not south west of north east corner {}",
+                south_west, north_east
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BoundingBoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BoundingBoxError::InvalidCoordinate(err) => Some(err),
+            BoundingBoxError::Inverted { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundingBox, BoundingBoxError};
+    use crate::point::Axis;
+    use crate::{EncodedPolyline, Point, Route};
+
+    fn tehran() -> BoundingBox {
+        BoundingBox::new(
+            Point::new_unchecked(35.56, 51.09),
+            Point::new_unchecked(35.83, 51.61),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reject_inverted_corners() {
+        let south_west = Point::new_unchecked(35.83, 51.09);
+        let north_east = Point::new_unchecked(35.56, 51.61);
+
+        let err = BoundingBox::new(south_west, north_east).unwrap_err();
+        assert_eq!(
+            err,
+            BoundingBoxError::Inverted {
+                south_west,
+                north_east
+            }
+        );
+        assert!(BoundingBox::new(north_east, south_west).is_err());
+        assert!(matches!(
+            BoundingBox::new(Point::new_unchecked(-91.0, 0.0), north_east),
+            Err(BoundingBoxError::InvalidCoordinate(_))
+        ));
+    }
+
+    #[test]
+    fn from_points() {
+        let bounds = BoundingBox::from_points(&[
+            Point::new_unchecked(35.7, 51.4),
+            Point::new_unchecked(35.6, 51.5),
+            Point::new_unchecked(35.8, 51.2),
+        ])
+        .unwrap();
+
+        assert_eq!(bounds.south_west(), Point::new_unchecked(35.6, 51.2));
+        assert_eq!(bounds.north_east(), Point::new_unchecked(35.8, 51.5));
+        assert!(BoundingBox::from_points(&[]).is_none());
+    }
+
+    #[test]
+    fn contains_and_intersects() {
+        let bounds = tehran();
+        assert!(bounds.contains(&Point::new_unchecked(35.7, 51.4)));
+        assert!(bounds.contains(&bounds.south_west()));
+        assert!(!bounds.contains(&Point::new_unchecked(35.84, 50.94)));
+
+        let karaj = BoundingBox::new(
+            Point::new_unchecked(35.75, 50.85),
+            Point::new_unchecked(35.9, 51.1),
+        )
+        .unwrap();
+        let qom = BoundingBox::new(
+            Point::new_unchecked(34.55, 50.8),
+            Point::new_unchecked(34.7, 51.0),
+        )
+        .unwrap();
+        assert!(bounds.intersects(&karaj));
+        assert!(karaj.intersects(&bounds));
+        assert!(!bounds.intersects(&qom));
+        assert!(bounds.intersects(&bounds));
+    }
+
+    #[test]
+    fn single_point_box() {
+        let point = Point::new_unchecked(35.7, 51.4);
+        let bounds = BoundingBox::from_points(&[point]).unwrap();
+
+        assert_eq!(bounds.center(), point);
+        assert!(bounds.contains(&point));
+        assert!(bounds.intersects(&bounds));
+
+        let grown = bounds.expand_by_meters(1000.0).unwrap();
+        assert!(grown.contains(&point));
+        assert_eq!(grown.center().latitude, point.latitude);
+        let height = Point::new_unchecked(grown.south_west().latitude, 51.4)
+            .haversine_distance_to(&Point::new_unchecked(grown.north_east().latitude, 51.4));
+        assert!((height - 2000.0).abs() < 1e-6, "{}", height);
+        let width = Point::new_unchecked(35.7, grown.south_west().longitude)
+            .haversine_distance_to(&Point::new_unchecked(35.7, grown.north_east().longitude));
+        assert!((width - 2000.0).abs() < 1.0, "{}", width);
+
+        let shrunk = grown.expand_by_meters(-900.0).unwrap();
+        assert!(shrunk.contains(&point));
+        assert!(grown.contains(&shrunk.south_west()) && grown.contains(&shrunk.north_east()));
+        assert!(matches!(
+            bounds.expand_by_meters(-1.0),
+            Err(BoundingBoxError::Inverted { .. })
+        ));
+    }
+
+    #[test]
+    fn expansion_rejects_antimeridian() {
+        let bounds = BoundingBox::new(
+            Point::new_unchecked(-17.0, 179.99),
+            Point::new_unchecked(-16.9, 179.999),
+        )
+        .unwrap();
+        assert!(bounds.expand_by_meters(100.0).is_ok());
+
+        match bounds.expand_by_meters(10_000.0).unwrap_err() {
+            BoundingBoxError::InvalidCoordinate(err) => {
+                assert_eq!(err.axis(), Axis::Longitude);
+                assert!(err.value() > 180.0);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let west = BoundingBox::from_points(&[Point::new_unchecked(0.0, -180.0)]).unwrap();
+        assert!(west.expand_by_meters(1.0).is_err());
+    }
+
+    #[test]
+    fn route_bounding_box() {
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: "_p~iF~ps|U_ulLnnqC_mqNvxq`@".to_string(),
+            }),
+        };
+
+        let bounds = route.bounding_box().unwrap();
+        assert_eq!(bounds.south_west(), Point::new_unchecked(38.5, -126.453));
+        assert_eq!(bounds.north_east(), Point::new_unchecked(43.252, -120.2));
+
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: None,
+        };
+        assert!(route.bounding_box().is_none());
+    }
+}
@@ -0,0 +1,588 @@
+#[cfg(feature = "disk-cache")]
+use crate::disk_cache::DiskCache;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "disk-cache")]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// configuration of the in-memory response cache, enabled with `ClientBuilder::cache`.
+///
+/// reverse geocoding results are cached for 10 minutes by default. routes depend on live
+/// traffic, so they are not cached unless a ttl is set for `Endpoint::Route` explicitly.
+///
+/// responses with an `ETag` or `Last-Modified` header are kept for another ttl once they
+/// expire. the next call revalidates them with `If-None-Match` or `If-Modified-Since`, and a
+/// `304 Not Modified` serves the cached body for a new ttl without downloading it again.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    capacity: usize,
+    ttls: HashMap<Endpoint, Duration>,
+    #[cfg(feature = "disk-cache")]
+    dir: Option<PathBuf>,
+}
+
+impl CacheConfig {
+    /// cache at most `capacity` responses, evicting the least recently used ones.
+    pub fn new(capacity: usize) -> CacheConfig {
+        let mut ttls = HashMap::new();
+        ttls.insert(Endpoint::ReverseGeocode, Duration::from_secs(600));
+
+        CacheConfig {
+            capacity,
+            ttls,
+            #[cfg(feature = "disk-cache")]
+            dir: None,
+        }
+    }
+
+    /// time to live of the given endpoint's responses, a zero ttl disables caching them.
+    pub fn ttl(mut self, endpoint: Endpoint, ttl: Duration) -> CacheConfig {
+        self.ttls.insert(endpoint, ttl);
+        self
+    }
+
+    /// also keep responses as files in the given directory, so they survive restarts.
+    /// the directory holds at most `capacity` entries as well. ignored with a
+    /// `ClientBuilder::cache_store`.
+    #[cfg(feature = "disk-cache")]
+    pub fn persist(mut self, dir: impl Into<PathBuf>) -> CacheConfig {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    fn ttl_of(&self, endpoint: Endpoint) -> Option<Duration> {
+        self.ttls
+            .get(&endpoint)
+            .copied()
+            .filter(|ttl| *ttl > Duration::ZERO)
+    }
+}
+
+/// hit and miss counters of the response cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// expired entries neshan confirmed as unchanged, each also counted as a miss.
+    pub revalidated: u64,
+    /// entries held in memory, always zero with a `ClientBuilder::cache_store`.
+    pub entries: usize,
+}
+
+/// response kept by a `ResponseCache`. entries go through serde, so external stores can
+/// hold them as json, bincode or whatever else they like.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CachedEntry {
+    /// body of the response as neshan sent it. only utf-8 bodies are cached, serde writes
+    /// them as a string.
+    #[serde(with = "text")]
+    pub body: Bytes,
+    /// http status of the response, `None` for entries stored without one. only successful
+    /// responses are cached, such entries are served as `200 OK`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// headers of the response, leaving out the ones whose value isn't text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+    /// `ETag` header of the response, sent as `If-None-Match` to revalidate the entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// `Last-Modified` header of the response, sent as `If-Modified-Since` to revalidate the
+    /// entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// end of the ttl of an entry with validators. the store is asked to keep such entries
+    /// for twice their ttl, after this time they are revalidated before being served.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fresh_until: Option<SystemTime>,
+}
+
+impl CachedEntry {
+    pub fn new(body: impl Into<Bytes>) -> CachedEntry {
+        CachedEntry {
+            body: body.into(),
+            status: None,
+            headers: Vec::new(),
+            etag: None,
+            last_modified: None,
+            fresh_until: None,
+        }
+    }
+
+    /// entry of a response, with the validators among its headers.
+    pub(crate) fn from_response(
+        status: StatusCode,
+        body: Bytes,
+        headers: &HeaderMap,
+    ) -> CachedEntry {
+        CachedEntry {
+            status: Some(status.as_u16()),
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            etag: header_text(headers, header::ETAG),
+            last_modified: header_text(headers, header::LAST_MODIFIED),
+            ..CachedEntry::new(body)
+        }
+    }
+
+    /// status the entry is served with.
+    pub(crate) fn response_status(&self) -> StatusCode {
+        self.status
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .unwrap_or(StatusCode::OK)
+    }
+
+    /// headers the entry is served with, skipping any that got mangled in a store.
+    pub(crate) fn response_headers(&self) -> HeaderMap {
+        self.headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    header::HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect()
+    }
+
+    fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+
+    /// entries without validators are fresh until the store drops them.
+    pub(crate) fn is_fresh(&self) -> bool {
+        self.fresh_until
+            .is_none_or(|fresh_until| fresh_until > SystemTime::now())
+    }
+
+    /// headers that make the request conditional on the entry being outdated.
+    pub(crate) fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let validators = [
+            (header::IF_NONE_MATCH, &self.etag),
+            (header::IF_MODIFIED_SINCE, &self.last_modified),
+        ];
+        for (name, value) in validators {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+
+        headers
+    }
+}
+
+fn header_text(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+mod text {
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(body: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        let text = std::str::from_utf8(body).map_err(serde::ser::Error::custom)?;
+
+        serializer.serialize_str(text)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Bytes, D::Error> {
+        String::deserialize(deserializer).map(Bytes::from)
+    }
+}
+
+/// where the client keeps cached responses, in memory by default. implement it to share the
+/// cache between instances of a service, e.g. in redis, and set it with
+/// `ClientBuilder::cache_store`. `CacheConfig` still decides what is cached and for how long.
+///
+/// keys are stable across releases: the endpoint label of `Endpoint::as_str` followed by the
+/// query parameters sorted by name, as in `reverse_geocode?lat=35.7&lng=51.4`. parameter
+/// values are not percent encoded and the api key is never part of a key. custom calls use
+/// `custom` followed by their path, e.g. `custom/v1/search?term=cafe`.
+///
+/// the store has no way to report failures, a failed `get` should count as a miss and a
+/// failed `put` can be dropped.
+#[async_trait]
+pub trait ResponseCache: Send + Sync {
+    /// entry stored under `key`, `None` when it is missing or expired.
+    async fn get(&self, key: &str) -> Option<CachedEntry>;
+
+    /// store `entry` under `key`, it should expire after `ttl`.
+    async fn put(&self, key: &str, entry: CachedEntry, ttl: Duration);
+}
+
+/// cache of a client, the policy of its `CacheConfig` over a store.
+pub(crate) struct Cache {
+    config: CacheConfig,
+    store: Arc<dyn ResponseCache>,
+    /// the store when it is the built-in one, for counting its entries.
+    memory: Option<Arc<MemoryCache>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    revalidated: AtomicU64,
+}
+
+impl Cache {
+    pub(crate) fn new(config: CacheConfig) -> Result<Cache, NeshanError> {
+        let memory = Arc::new(MemoryCache::new(&config)?);
+
+        Ok(Cache {
+            config,
+            store: memory.clone(),
+            memory: Some(memory),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            revalidated: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn with_store(config: CacheConfig, store: Arc<dyn ResponseCache>) -> Cache {
+        Cache {
+            config,
+            store,
+            memory: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            revalidated: AtomicU64::new(0),
+        }
+    }
+
+    /// whether responses of the endpoint are cached at all.
+    pub(crate) fn caches(&self, endpoint: Endpoint) -> bool {
+        self.config.ttl_of(endpoint).is_some()
+    }
+
+    /// the entry stored under `key`, which may be an expired one to revalidate, see
+    /// `CachedEntry::is_fresh`. only fresh entries count as hits.
+    pub(crate) async fn get(&self, key: &str) -> Option<CachedEntry> {
+        let entry = self.store.get(key).await;
+
+        match &entry {
+            Some(entry) if entry.is_fresh() => self.hits.fetch_add(1, Ordering::Relaxed),
+            _ => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        entry
+    }
+
+    pub(crate) async fn put(&self, endpoint: Endpoint, key: String, mut entry: CachedEntry) {
+        let ttl = match self.config.ttl_of(endpoint) {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        if std::str::from_utf8(&entry.body).is_err() {
+            return;
+        }
+
+        // a ttl past the end of time keeps the entry fresh, and kept, for good.
+        let mut kept = ttl;
+        entry.fresh_until = None;
+        if entry.has_validators() {
+            entry.fresh_until = SystemTime::now().checked_add(ttl);
+            kept = ttl.saturating_mul(2);
+        }
+        self.store.put(&key, entry, kept).await;
+    }
+
+    /// keep `stale` for another ttl after a `304 Not Modified`, with any validators of the
+    /// new `headers` replacing its own. returns its body.
+    pub(crate) async fn revalidated(
+        &self,
+        endpoint: Endpoint,
+        key: String,
+        stale: CachedEntry,
+        headers: &HeaderMap,
+    ) -> Bytes {
+        self.revalidated.fetch_add(1, Ordering::Relaxed);
+
+        // the headers of a 304 update the stored ones, the status stays the original one.
+        let mut updated = stale.response_headers();
+        for (name, value) in headers {
+            updated.insert(name, value.clone());
+        }
+        let mut entry =
+            CachedEntry::from_response(stale.response_status(), stale.body.clone(), &updated);
+        if !entry.has_validators() {
+            entry.etag = stale.etag;
+            entry.last_modified = stale.last_modified;
+        }
+        self.put(endpoint, key, entry).await;
+
+        stale.body
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            revalidated: self.revalidated.load(Ordering::Relaxed),
+            entries: self.memory.as_ref().map_or(0, |memory| memory.len()),
+        }
+    }
+}
+
+/// the built-in store, least recently used entries in memory with an optional directory
+/// behind them.
+pub(crate) struct MemoryCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    #[cfg(feature = "disk-cache")]
+    disk: Option<DiskCache>,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    clock: u64,
+}
+
+struct Entry {
+    entry: CachedEntry,
+    /// `None` past what an `Instant` can hold, the entry is only ever evicted.
+    expires: Option<Instant>,
+    used: u64,
+}
+
+impl Entry {
+    fn is_alive(&self, now: Instant) -> bool {
+        self.expires.is_none_or(|expires| expires > now)
+    }
+}
+
+#[async_trait]
+impl ResponseCache for MemoryCache {
+    async fn get(&self, key: &str) -> Option<CachedEntry> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        let found = match entries.map.get_mut(key) {
+            Some(entry) if entry.is_alive(Instant::now()) => {
+                entry.used = clock;
+                Some(entry.entry.clone())
+            }
+            Some(_) => {
+                entries.map.remove(key);
+                None
+            }
+            None => None,
+        };
+
+        #[cfg(feature = "disk-cache")]
+        let found = match (found, &self.disk) {
+            (None, Some(disk)) => disk.get(key).map(|(entry, ttl)| {
+                self.insert(&mut entries, key.to_string(), entry.clone(), ttl);
+                entry
+            }),
+            (found, _) => found,
+        };
+
+        found
+    }
+
+    async fn put(&self, key: &str, entry: CachedEntry, ttl: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        #[cfg(feature = "disk-cache")]
+        if let Some(disk) = &self.disk {
+            disk.put(key, &entry, ttl);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        self.insert(&mut entries, key.to_string(), entry, ttl);
+    }
+}
+
+impl MemoryCache {
+    fn new(config: &CacheConfig) -> Result<MemoryCache, NeshanError> {
+        #[cfg(feature = "disk-cache")]
+        let disk =
+            match &config.dir {
+                Some(dir) => Some(DiskCache::open(dir.clone(), config.capacity).map_err(
+                    |err| NeshanError::Config(format!("invalid cache directory: {}", err)),
+                )?),
+                None => None,
+            };
+
+        Ok(MemoryCache {
+            capacity: config.capacity,
+            entries: Mutex::new(Entries::default()),
+            #[cfg(feature = "disk-cache")]
+            disk,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    fn insert(&self, entries: &mut Entries, key: String, entry: CachedEntry, ttl: Duration) {
+        entries.clock += 1;
+        let used = entries.clock;
+
+        if !entries.map.contains_key(&key) && entries.map.len() >= self.capacity {
+            let now = Instant::now();
+            entries.map.retain(|_, entry| entry.is_alive(now));
+        }
+        if !entries.map.contains_key(&key) && entries.map.len() >= self.capacity {
+            let oldest = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.map.remove(&oldest);
+            }
+        }
+
+        entries.map.insert(
+            key,
+            Entry {
+                entry,
+                expires: Instant::now().checked_add(ttl),
+                used,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, CacheConfig, CachedEntry};
+    use crate::endpoint::{request_key, Endpoint};
+    use crate::protocol::Query;
+    use bytes::Bytes;
+    use http::{header, HeaderMap, HeaderValue, StatusCode};
+    use std::time::Duration;
+
+    #[test]
+    fn key_ignores_parameter_order() {
+        let (mut a, mut b) = (Query::new(), Query::new());
+        a.push("lat", "1").push("lng", "2");
+        b.push("lng", "2").push("lat", "1");
+        let (a, b) = (
+            request_key(Endpoint::ReverseGeocode, &a),
+            request_key(Endpoint::ReverseGeocode, &b),
+        );
+
+        assert_eq!(a, b);
+        assert_eq!(a, "reverse_geocode?lat=1&lng=2");
+    }
+
+    #[test]
+    fn routes_are_not_cached_by_default() {
+        let cache = Cache::new(CacheConfig::new(10)).unwrap();
+
+        assert!(!cache.caches(Endpoint::Route));
+        assert!(cache.caches(Endpoint::ReverseGeocode));
+    }
+
+    #[tokio::test]
+    async fn evict_least_recently_used() {
+        let cache = Cache::new(CacheConfig::new(2)).unwrap();
+        let put = |key: &str| {
+            cache.put(
+                Endpoint::ReverseGeocode,
+                key.to_string(),
+                CachedEntry::new("{}"),
+            )
+        };
+
+        put("a").await;
+        put("b").await;
+        cache.get("a").await;
+        put("c").await;
+
+        assert!(cache.get("a").await.is_some());
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("c").await.is_some());
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[tokio::test]
+    async fn entries_with_validators_go_stale() {
+        let ttl = Duration::from_millis(20);
+        let cache = Cache::new(CacheConfig::new(4).ttl(Endpoint::ReverseGeocode, ttl)).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+
+        let validated = CachedEntry::from_response(StatusCode::OK, Bytes::from("{}"), &headers);
+        cache
+            .put(Endpoint::ReverseGeocode, "a".to_string(), validated)
+            .await;
+        cache
+            .put(
+                Endpoint::ReverseGeocode,
+                "b".to_string(),
+                CachedEntry::new("{}"),
+            )
+            .await;
+        assert!(cache.get("a").await.unwrap().is_fresh());
+        assert!(cache.get("b").await.unwrap().is_fresh());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let stale = cache.get("a").await.unwrap();
+        assert!(!stale.is_fresh());
+        assert_eq!(stale.conditional_headers()[header::IF_NONE_MATCH], "\"v1\"");
+        assert!(!stale
+            .conditional_headers()
+            .contains_key(header::IF_MODIFIED_SINCE));
+        assert!(cache.get("b").await.is_none());
+        assert_eq!((cache.stats().hits, cache.stats().misses), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn endless_ttl() {
+        let config = CacheConfig::new(4).ttl(Endpoint::ReverseGeocode, Duration::MAX);
+        let cache = Cache::new(config).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+
+        let validated = CachedEntry::from_response(StatusCode::OK, Bytes::from("{}"), &headers);
+        cache
+            .put(Endpoint::ReverseGeocode, "a".to_string(), validated)
+            .await;
+        cache
+            .put(
+                Endpoint::ReverseGeocode,
+                "b".to_string(),
+                CachedEntry::new("{}"),
+            )
+            .await;
+
+        let validated = cache.get("a").await.unwrap();
+        assert!(validated.is_fresh());
+        assert_eq!(validated.fresh_until, None);
+        assert!(cache.get("b").await.unwrap().is_fresh());
+    }
+
+    #[test]
+    fn entries_serialize_as_text() {
+        let entry = CachedEntry::new(r#"{"city":"تهران"}"#);
+        let json = serde_json::to_string(&entry).unwrap();
+
+        assert_eq!(json, r#"{"body":"{\"city\":\"تهران\"}"}"#);
+        assert_eq!(serde_json::from_str::<CachedEntry>(&json).unwrap(), entry);
+        assert!(serde_json::to_string(&CachedEntry::new(vec![0xff, 0xfe])).is_err());
+    }
+}
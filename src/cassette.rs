@@ -0,0 +1,357 @@
+//! recording responses of neshan to a file and replaying them later, see `Cassette`.
+
+use crate::error::NeshanError;
+use crate::middleware::{Middleware, Next, Request, Response};
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// query parameters that are never written to a cassette, in case a key ends up in the url.
+const SECRET_PARAMS: [&str; 3] = ["key", "api_key", "api-key"];
+
+/// whether a `Cassette` talks to neshan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// send every request and append its response to the cassette.
+    Record,
+    /// answer from the cassette without sending anything, failing requests it has no
+    /// response for.
+    Replay,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Interaction {
+    /// path and query parameters of the request, see `normalize`.
+    request: String,
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// a middleware recording the responses of neshan to a json file, or replaying them from it,
+/// e.g. to record once with a real api key and replay in ci without one.
+///
+/// requests match on their path and query parameters regardless of the order of the
+/// parameters. the api key is sent as a header, which is never recorded. a request made
+/// several times is answered with its recorded responses in order, then with the last one.
+///
+/// only calls whose whole response goes through the middlewares are recorded, static map
+/// downloads are streamed past them. bodies are kept as text, recording one that isn't utf-8,
+/// e.g. an image of `Client::get_bytes`, fails the call with `NeshanError::Config`.
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    interactions: Mutex<Vec<Interaction>>,
+    /// how many times each interaction was replayed, by index.
+    replayed: Mutex<Vec<usize>>,
+}
+
+impl Cassette {
+    /// record into a new cassette at `path`, replacing any file there. the file is rewritten
+    /// after every response so it is complete even when the process doesn't exit cleanly.
+    pub fn record(path: impl AsRef<Path>) -> Cassette {
+        Cassette {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Record,
+            interactions: Mutex::new(Vec::new()),
+            replayed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// replay the cassette at `path`.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Cassette, NeshanError> {
+        let path = path.as_ref().to_path_buf();
+        let json = std::fs::read_to_string(&path).map_err(|err| {
+            NeshanError::Config(format!("cannot read cassette {}: {}", path.display(), err))
+        })?;
+        let interactions: Vec<Interaction> = serde_json::from_str(&json).map_err(|err| {
+            NeshanError::Config(format!("invalid cassette {}: {}", path.display(), err))
+        })?;
+
+        Ok(Cassette {
+            path,
+            mode: CassetteMode::Replay,
+            replayed: Mutex::new(vec![0; interactions.len()]),
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// number of recorded responses.
+    pub fn len(&self) -> usize {
+        self.interactions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn save(&self, interactions: &[Interaction]) -> Result<(), NeshanError> {
+        let json = serde_json::to_string_pretty(interactions)?;
+        std::fs::write(&self.path, json).map_err(|err| {
+            NeshanError::Config(format!(
+                "cannot write cassette {}: {}",
+                self.path.display(),
+                err
+            ))
+        })
+    }
+
+    fn find(&self, request: &str) -> Option<Interaction> {
+        let interactions = self.interactions.lock().unwrap();
+        let mut replayed = self.replayed.lock().unwrap();
+
+        let matching: Vec<usize> = (0..interactions.len())
+            .filter(|i| interactions[*i].request == request)
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|i| replayed[*i] == 0)
+            .or_else(|| matching.last().copied())?;
+        replayed[index] += 1;
+
+        Some(interactions[index].clone())
+    }
+}
+
+/// path and query parameters of the request sorted by name, without any secrets.
+fn normalize(req: &Request) -> String {
+    let mut params: Vec<(String, String)> = req
+        .url
+        .query_pairs()
+        .into_owned()
+        .filter(|(name, _)| !SECRET_PARAMS.contains(&name.to_ascii_lowercase().as_str()))
+        .collect();
+    params.sort();
+
+    let mut normalized = req.url.path().to_string();
+    for (i, (name, value)) in params.iter().enumerate() {
+        normalized.push(if i == 0 { '?' } else { '&' });
+        normalized.push_str(name);
+        normalized.push('=');
+        normalized.push_str(value);
+    }
+
+    normalized
+}
+
+#[async_trait]
+impl Middleware for Cassette {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result<Response, NeshanError> {
+        let request = normalize(&req);
+
+        match self.mode {
+            CassetteMode::Record => {
+                let res = next.run(req).await?;
+                let body = match std::str::from_utf8(&res.body) {
+                    Ok(body) => body.to_string(),
+                    Err(_) => {
+                        return Err(NeshanError::Config(format!(
+                            "cannot record the binary response of {} into cassette {}",
+                            request,
+                            self.path.display()
+                        )))
+                    }
+                };
+
+                let interaction = Interaction {
+                    request,
+                    status: res.status.as_u16(),
+                    headers: res
+                        .headers
+                        .iter()
+                        .filter_map(|(name, value)| {
+                            Some((name.to_string(), value.to_str().ok()?.to_string()))
+                        })
+                        .collect(),
+                    body,
+                };
+                let mut interactions = self.interactions.lock().unwrap();
+                interactions.push(interaction);
+                self.save(&interactions)?;
+
+                Ok(res)
+            }
+            CassetteMode::Replay => {
+                let interaction = self.find(&request).ok_or_else(|| {
+                    NeshanError::InvalidRequest(format!(
+                        "{} has no response recorded for {}",
+                        self.path.display(),
+                        request
+                    ))
+                })?;
+
+                let mut headers = HeaderMap::new();
+                for (name, value) in &interaction.headers {
+                    if let (Ok(name), Ok(value)) = (
+                        HeaderName::from_bytes(name.as_bytes()),
+                        HeaderValue::from_str(value),
+                    ) {
+                        headers.append(name, value);
+                    }
+                }
+
+                Ok(Response {
+                    status: StatusCode::from_u16(interaction.status).map_err(|_| {
+                        NeshanError::Config(format!(
+                            "invalid status {} in cassette {}",
+                            interaction.status,
+                            self.path.display()
+                        ))
+                    })?,
+                    headers,
+                    body: Bytes::from(interaction.body),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cassette, CassetteMode};
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::{Endpoint, MockNeshan, MockResponse, Point, RouteOptions, Type};
+    use std::path::PathBuf;
+
+    const TEHRAN: Point = Point {
+        latitude: 35.6997,
+        longitude: 51.338,
+    };
+    const KARAJ: Point = Point {
+        latitude: 35.8355,
+        longitude: 50.9915,
+    };
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("neshan-rs-{}-{}.json", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn record_then_replay() {
+        let path = cassette_path("record-then-replay");
+        let neshan = MockNeshan::start().await;
+        neshan
+            .respond(
+                Endpoint::ReverseGeocode,
+                MockResponse::error(470, 480, "Key not found"),
+            )
+            .await;
+
+        let recording = Client::builder("secret-key")
+            .base_url(&neshan.uri())
+            .middleware(Cassette::record(&path))
+            .build()
+            .unwrap();
+        let options = RouteOptions::new().alternative_paths(true);
+        let recorded = recording
+            .route_with(Type::Car, TEHRAN, KARAJ, &options)
+            .await
+            .unwrap();
+        assert!(recording.reverse_geocode(TEHRAN).await.is_err());
+
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains("secret-key"));
+
+        // without the server, and with a client sending its parameters in another order.
+        drop(neshan);
+        let cassette = Cassette::replay(&path).unwrap();
+        assert_eq!(cassette.mode(), CassetteMode::Replay);
+        assert_eq!(cassette.len(), 2);
+        let replaying = Client::builder("other-key")
+            .base_url("http://127.0.0.1:9")
+            .middleware(cassette)
+            .build()
+            .unwrap();
+
+        let replayed = replaying
+            .route_with(Type::Car, TEHRAN, KARAJ, &options)
+            .await
+            .unwrap();
+        assert_eq!(replayed, recorded);
+        let err = replaying.reverse_geocode(TEHRAN).await.unwrap_err();
+        assert!(matches!(err, NeshanError::Api(ref api) if api.error().code() == 480));
+
+        let err = replaying
+            .route_with(Type::Motorcycle, TEHRAN, KARAJ, &options)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, NeshanError::InvalidRequest(ref message) if message.contains("type=motorcycle"))
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parameter_order_doesnt_matter() {
+        let request = |url: &str| crate::middleware::Request {
+            method: http::Method::GET,
+            url: url::Url::parse(url).unwrap(),
+            headers: http::HeaderMap::new(),
+        };
+
+        assert_eq!(
+            super::normalize(&request(
+                "http://neshan/v2/reverse?lng=51.4&lat=35.7&key=secret"
+            )),
+            "/v2/reverse?lat=35.7&lng=51.4"
+        );
+        assert_eq!(
+            super::normalize(&request("http://neshan/v2/reverse?lat=35.7&lng=51.4")),
+            super::normalize(&request("http://other/v2/reverse?lng=51.4&lat=35.7"))
+        );
+    }
+
+    #[tokio::test]
+    async fn binary_bodies_are_not_recorded() {
+        use wiremock::matchers::path;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(path("/v1/blob"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0x89, b'P', 0xff, 0xfe]))
+            .mount(&server)
+            .await;
+        Mock::given(path("/v1/text"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&server)
+            .await;
+
+        let path = cassette_path("binary-bodies");
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .middleware(Cassette::record(&path))
+            .build()
+            .unwrap();
+
+        let err = client.get_bytes("/v1/blob", &()).await.unwrap_err();
+        assert!(
+            matches!(err, NeshanError::Config(ref message) if message.contains("/v1/blob")),
+            "{}",
+            err
+        );
+        assert_eq!(client.get_bytes("/v1/text", &()).await.unwrap(), "ok");
+
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains("/v1/blob"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replaying_a_missing_cassette_fails() {
+        assert!(matches!(
+            Cassette::replay(cassette_path("missing")),
+            Err(NeshanError::Config(_))
+        ));
+    }
+}
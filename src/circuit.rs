@@ -0,0 +1,194 @@
+use crate::error::{ErrorKind, NeshanError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// circuit breaker configuration, enabled with `ClientBuilder::circuit_breaker`.
+///
+/// after `failure_threshold` consecutive upstream failures (timeouts, connect errors and 5xx)
+/// the circuit opens and calls fail fast with `NeshanError::CircuitOpen` for `cool_down`.
+/// then a single probe request is let through, its success closes the circuit again and its
+/// failure opens it for another cool down.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+        }
+    }
+}
+
+/// state of the circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// requests go through.
+    Closed,
+    /// requests fail fast.
+    Open,
+    /// the cool down is over and the next request probes neshan.
+    HalfOpen,
+}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: bool },
+}
+
+pub(crate) struct Breaker {
+    config: CircuitBreaker,
+    state: Mutex<State>,
+}
+
+/// permission to send one request, its outcome has to be reported back.
+pub(crate) struct Permit<'a> {
+    breaker: &'a Breaker,
+    probe: bool,
+    reported: bool,
+}
+
+impl Breaker {
+    pub(crate) fn new(config: CircuitBreaker) -> Breaker {
+        Breaker {
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    pub(crate) fn config(&self) -> &CircuitBreaker {
+        &self.config
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        match &*self.state.lock().unwrap() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if *until > Instant::now() => CircuitState::Open,
+            State::Open { .. } | State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    pub(crate) fn acquire(&self) -> Result<Permit<'_>, NeshanError> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let probe = match &*state {
+            State::Closed { .. } => false,
+            State::Open { until } if *until > now => {
+                return Err(NeshanError::CircuitOpen {
+                    retry_in: *until - now,
+                })
+            }
+            State::Open { .. } | State::HalfOpen { probing: false } => {
+                *state = State::HalfOpen { probing: true };
+                true
+            }
+            State::HalfOpen { probing: true } => {
+                return Err(NeshanError::CircuitOpen {
+                    retry_in: Duration::ZERO,
+                })
+            }
+        };
+
+        Ok(Permit {
+            breaker: self,
+            probe,
+            reported: false,
+        })
+    }
+}
+
+impl Permit<'_> {
+    /// report the outcome of the request, `None` meaning success.
+    pub(crate) fn report(mut self, error: Option<ErrorKind>) {
+        self.reported = true;
+
+        let failed = matches!(
+            error,
+            Some(ErrorKind::Timeout) | Some(ErrorKind::Connect) | Some(ErrorKind::Server)
+        );
+        let config = &self.breaker.config;
+        let mut state = self.breaker.state.lock().unwrap();
+
+        *state = match (&*state, failed) {
+            (_, false) => State::Closed { failures: 0 },
+            (State::Closed { failures }, true) if failures + 1 < config.failure_threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            // a request that started before the circuit opened doesn't extend the cool down.
+            (State::Open { until }, true) if !self.probe => State::Open { until: *until },
+            (_, true) => State::Open {
+                until: Instant::now() + config.cool_down,
+            },
+        };
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.reported {
+            // the probe was cancelled, let the next request probe instead.
+            let mut state = self.breaker.state.lock().unwrap();
+            if let State::HalfOpen { .. } = &*state {
+                *state = State::HalfOpen { probing: false };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Breaker, CircuitBreaker, CircuitState};
+    use crate::error::ErrorKind;
+    use std::time::Duration;
+
+    #[test]
+    fn client_errors_do_not_trip() {
+        let breaker = Breaker::new(CircuitBreaker::new(2, Duration::from_secs(10)));
+
+        for _ in 0..5 {
+            breaker
+                .acquire()
+                .unwrap()
+                .report(Some(ErrorKind::InvalidRequest));
+        }
+
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = Breaker::new(CircuitBreaker::new(2, Duration::from_secs(10)));
+
+        breaker.acquire().unwrap().report(Some(ErrorKind::Server));
+        breaker.acquire().unwrap().report(None);
+        breaker.acquire().unwrap().report(Some(ErrorKind::Server));
+
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn single_probe_when_half_open() {
+        let breaker = Breaker::new(CircuitBreaker::new(1, Duration::ZERO));
+        breaker.acquire().unwrap().report(Some(ErrorKind::Timeout));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        let probe = breaker.acquire().unwrap();
+        assert!(breaker.acquire().is_err());
+
+        // a cancelled probe lets the next request probe.
+        drop(probe);
+        let probe = breaker.acquire().unwrap();
+        probe.report(Some(ErrorKind::Server));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        breaker.acquire().unwrap().report(None);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,2737 @@
+use crate::audit::{AuditEntry, AuditSink};
+use crate::backend::HttpBackend;
+use crate::cache::{Cache, CacheConfig, CacheStats, CachedEntry, ResponseCache};
+use crate::circuit::{Breaker, CircuitBreaker, CircuitState};
+use crate::endpoint::{request_key, Endpoint};
+use crate::error::{ErrorKind, NeshanError};
+use crate::meta::ResponseMeta;
+use crate::middleware::{Middleware, Next, Request, Response};
+use crate::observer::{NoopObserver, RequestObserver};
+use crate::protocol::{self, Query};
+use crate::quota::QuotaInfo;
+use crate::rate_limit::{Priority, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::single_flight::SingleFlight;
+use crate::stats::{Stats, Usage};
+use crate::trace;
+use crate::{
+    DirectionVersion, Point, PostalAddress, RouteOptions, RouteSummaries, RouteSummary, Routes,
+    Type,
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use url::Url;
+
+/// Neshan client based on its api documentation.
+/// <https://platform.neshan.org/api/getting-started>
+///
+/// cloning a client is cheap and clones share their connection pool and configuration.
+///
+/// every call is cancel safe: dropping its future, e.g. because of an outer timeout, drops
+/// the in-flight http request, retry backoff and rate limiter wait with it. the client never
+/// spawns background tasks for a call, so nothing keeps running after the drop. the only
+/// work that outlives a dropped caller is a coalesced request that other callers are still
+/// waiting for, see `ClientBuilder::single_flight`.
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+    priority: Priority,
+}
+
+struct Inner {
+    http: Arc<dyn HttpBackend>,
+    api_key: HeaderValue,
+    base_url: String,
+    retry: Option<RetryPolicy>,
+    rate_limit: Option<(f64, u32)>,
+    rate_limiter: Option<RateLimiter>,
+    cache: Option<Arc<Cache>>,
+    single_flight: Option<SingleFlight>,
+    observer: Arc<dyn RequestObserver>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    audit: Option<Arc<dyn AuditSink>>,
+    deadline: Option<Duration>,
+    breaker: Option<Breaker>,
+    validate_points: bool,
+    usage: Usage,
+    last_quota: Mutex<Option<QuotaInfo>>,
+}
+
+/// builder for configuring a `Client`.
+pub struct ClientBuilder {
+    api_key: String,
+    base_url: String,
+    retry: Option<RetryPolicy>,
+    rate_limit: Option<(f64, u32)>,
+    cache: Option<CacheConfig>,
+    cache_store: Option<Arc<dyn ResponseCache>>,
+    single_flight: bool,
+    observer: Arc<dyn RequestObserver>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    audit: Option<Arc<dyn AuditSink>>,
+    deadline: Option<Duration>,
+    circuit_breaker: Option<CircuitBreaker>,
+    validate_points: bool,
+    backend: Option<Arc<dyn HttpBackend>>,
+    #[cfg(feature = "otel")]
+    meter: Option<opentelemetry::metrics::Meter>,
+}
+
+impl ClientBuilder {
+    /// base url of neshan api, mostly useful for pointing the client to a proxy or a mock server.
+    pub fn base_url(mut self, base_url: &str) -> ClientBuilder {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// retry transient failures based on the given policy. requests are not retried by default.
+    pub fn retry(mut self, policy: RetryPolicy) -> ClientBuilder {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// limit outgoing requests with a token bucket that refills `per_second` tokens each second
+    /// and holds at most `burst` of them. every attempt, retries included, takes a token.
+    /// the limit is shared between clones of the built client, waiting requests are admitted
+    /// by their priority, see `Client::with_priority`. clients of `Client::with_api_key`
+    /// have their own.
+    pub fn rate_limit(mut self, per_second: f64, burst: u32) -> ClientBuilder {
+        self.rate_limit = Some((per_second, burst));
+        self
+    }
+
+    /// cache successful responses, in memory unless a `cache_store` is set. see `CacheConfig`
+    /// for which endpoints are cached.
+    pub fn cache(mut self, config: CacheConfig) -> ClientBuilder {
+        self.cache = Some(config);
+        self
+    }
+
+    /// keep cached responses in `store` instead of memory, e.g. to share them between
+    /// instances of a service. what is cached and for how long still comes from `cache`,
+    /// or the defaults of `CacheConfig` when it isn't called.
+    pub fn cache_store(mut self, store: Arc<dyn ResponseCache>) -> ClientBuilder {
+        self.cache_store = Some(store);
+        self
+    }
+
+    /// share the result of identical requests that are in flight at the same time, so only
+    /// one of them hits the network. every waiting caller receives the same response or error.
+    pub fn single_flight(mut self, enabled: bool) -> ClientBuilder {
+        self.single_flight = enabled;
+        self
+    }
+
+    /// observe every http call of the client, e.g. for exporting metrics.
+    pub fn observer(mut self, observer: Arc<dyn RequestObserver>) -> ClientBuilder {
+        self.observer = observer;
+        self
+    }
+
+    /// record every request and its outcome in `sink`, e.g. a `JsonlAudit` for keeping what
+    /// was asked and answered. nothing is recorded by default.
+    pub fn audit(mut self, sink: Arc<dyn AuditSink>) -> ClientBuilder {
+        self.audit = Some(sink);
+        self
+    }
+
+    /// add a middleware, middlewares run in the order they were added.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> ClientBuilder {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// bound the total time of each call, including retries, backoff and rate limiter waits.
+    /// calls that run out of time fail with `NeshanError::DeadlineExceeded`.
+    pub fn deadline(mut self, deadline: Duration) -> ClientBuilder {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// record opentelemetry metrics with a meter of the given provider instead of the globally
+    /// installed one, see the `otel` feature.
+    #[cfg(feature = "otel")]
+    pub fn meter_provider(
+        mut self,
+        provider: &impl opentelemetry::metrics::MeterProvider,
+    ) -> ClientBuilder {
+        self.meter = Some(provider.meter("neshan-rs"));
+        self
+    }
+
+    /// stop sending requests for a while after repeated upstream failures, see `CircuitBreaker`.
+    /// the circuit is shared between clones of the built client.
+    pub fn circuit_breaker(mut self, config: CircuitBreaker) -> ClientBuilder {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// check every point before sending it, failing the call with
+    /// `NeshanError::InvalidCoordinate` instead of asking neshan about an invalid location.
+    /// points are not checked by default, see `Point::new`.
+    pub fn validate_points(mut self, enabled: bool) -> ClientBuilder {
+        self.validate_points = enabled;
+        self
+    }
+
+    /// send the http requests with the given backend instead of the default one of the
+    /// `reqwest` feature. middlewares, retries and everything else still run above it.
+    pub fn backend(mut self, backend: Arc<dyn HttpBackend>) -> ClientBuilder {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// create the client, failing when the api key isn't a valid header value or there is no
+    /// http backend.
+    pub fn build(self) -> Result<Client, NeshanError> {
+        Url::parse(&self.base_url)
+            .map_err(|err| NeshanError::Config(format!("invalid base url: {}", err)))?;
+
+        let rate_limiter = rate_limiter(self.rate_limit)?;
+
+        let api_key = protocol::api_key_header(&self.api_key)?;
+
+        // metrics go to the provider that is installed globally when the client is built,
+        // unless one was given to the builder.
+        #[cfg(feature = "otel")]
+        let observer: Arc<dyn RequestObserver> = {
+            let meter = self
+                .meter
+                .unwrap_or_else(|| opentelemetry::global::meter("neshan-rs"));
+            Arc::new(crate::otel::MetricsObserver::new(&meter, self.observer))
+        };
+        #[cfg(not(feature = "otel"))]
+        let observer = self.observer;
+
+        let http: Arc<dyn HttpBackend> = match self.backend {
+            Some(backend) => backend,
+            #[cfg(feature = "reqwest")]
+            None => Arc::new(crate::backend::ReqwestBackend::new()?),
+            #[cfg(not(feature = "reqwest"))]
+            None => {
+                return Err(NeshanError::Config(
+                    "no http backend, enable the reqwest feature or set one with \
+                     ClientBuilder::backend"
+                        .to_string(),
+                ))
+            }
+        };
+        let cache = match (self.cache, self.cache_store) {
+            (config, Some(store)) => Some(Arc::new(Cache::with_store(
+                config.unwrap_or_else(|| CacheConfig::new(0)),
+                store,
+            ))),
+            (Some(config), None) => Some(Arc::new(Cache::new(config)?)),
+            (None, None) => None,
+        };
+
+        Ok(Client {
+            inner: Arc::new(Inner {
+                http,
+                api_key,
+                base_url: self.base_url,
+                retry: self.retry,
+                rate_limit: self.rate_limit,
+                rate_limiter,
+                cache,
+                single_flight: if self.single_flight {
+                    Some(SingleFlight::default())
+                } else {
+                    None
+                },
+                observer,
+                middlewares: self.middlewares,
+                audit: self.audit,
+                deadline: self.deadline,
+                breaker: self.circuit_breaker.map(Breaker::new),
+                validate_points: self.validate_points,
+                usage: Usage::default(),
+                last_quota: Mutex::new(None),
+            }),
+            priority: Priority::Normal,
+        })
+    }
+}
+
+fn rate_limiter(rate_limit: Option<(f64, u32)>) -> Result<Option<RateLimiter>, NeshanError> {
+    match rate_limit {
+        Some((per_second, _)) if !(per_second.is_finite() && per_second > 0.0) => {
+            Err(NeshanError::Config(format!(
+                "rate limit must be a positive number of requests per second, got {}",
+                per_second
+            )))
+        }
+        Some((per_second, burst)) => Ok(Some(RateLimiter::new(per_second, burst))),
+        None => Ok(None),
+    }
+}
+
+impl Client {
+    /// create client for communicating with neshan.
+    pub fn new(api_key: &str) -> Client {
+        Client::builder(api_key).build().unwrap()
+    }
+
+    /// create a builder for configuring the client beyond its api key.
+    pub fn builder(api_key: &str) -> ClientBuilder {
+        ClientBuilder {
+            api_key: api_key.to_string(),
+            base_url: protocol::BASE_URL.to_string(),
+            retry: None,
+            rate_limit: None,
+            cache: None,
+            cache_store: None,
+            single_flight: false,
+            observer: Arc::new(NoopObserver),
+            middlewares: Vec::new(),
+            audit: None,
+            deadline: None,
+            circuit_breaker: None,
+            validate_points: false,
+            backend: None,
+            #[cfg(feature = "otel")]
+            meter: None,
+        }
+    }
+
+    /// handle to the same client whose calls wait for the rate limiter with the given
+    /// priority, e.g. for keeping background jobs behind user facing requests.
+    pub fn with_priority(&self, priority: Priority) -> Client {
+        Client {
+            inner: self.inner.clone(),
+            priority,
+        }
+    }
+
+    /// client with another api key, e.g. one for each customer of a multi-tenant service.
+    /// it shares the http backend and its connection pool, the cache, middlewares, observer
+    /// and audit sink with this client, so creating one is cheap.
+    ///
+    /// whatever is accounted per key is its own: a rate limiter with the same limit and a
+    /// full bucket, usage stats, the last quota, the circuit breaker and the calls coalesced
+    /// by `ClientBuilder::single_flight`. cached responses don't depend on the key, so
+    /// tenants are served each other's cached responses. fails when the key isn't a valid
+    /// header value.
+    pub fn with_api_key(&self, api_key: &str) -> Result<Client, NeshanError> {
+        self.tenant(api_key, self.inner.rate_limit)
+    }
+
+    /// same as `with_api_key` with a rate limit of its own, see `ClientBuilder::rate_limit`.
+    pub fn with_api_key_and_rate_limit(
+        &self,
+        api_key: &str,
+        per_second: f64,
+        burst: u32,
+    ) -> Result<Client, NeshanError> {
+        self.tenant(api_key, Some((per_second, burst)))
+    }
+
+    fn tenant(&self, api_key: &str, rate_limit: Option<(f64, u32)>) -> Result<Client, NeshanError> {
+        let inner = &self.inner;
+
+        Ok(Client {
+            inner: Arc::new(Inner {
+                http: inner.http.clone(),
+                api_key: protocol::api_key_header(api_key)?,
+                base_url: inner.base_url.clone(),
+                retry: inner.retry.clone(),
+                rate_limit,
+                rate_limiter: rate_limiter(rate_limit)?,
+                cache: inner.cache.clone(),
+                single_flight: inner
+                    .single_flight
+                    .as_ref()
+                    .map(|_| SingleFlight::default()),
+                observer: inner.observer.clone(),
+                middlewares: inner.middlewares.clone(),
+                audit: inner.audit.clone(),
+                deadline: inner.deadline,
+                breaker: inner
+                    .breaker
+                    .as_ref()
+                    .map(|breaker| Breaker::new(breaker.config().clone())),
+                validate_points: inner.validate_points,
+                usage: Usage::default(),
+                last_quota: Mutex::new(None),
+            }),
+            priority: self.priority,
+        })
+    }
+
+    /// route finds route(s) from origin to destination.
+    ///
+    /// avoid_traffic_zone finds route(s) that doesn't cross the traffic zone.
+    /// avoid_odd_even_zone finds route(s) that doesn's cross the odd_even_zone.
+    /// alternative_paths returns alternative routes besides the primary route.
+    #[deprecated(
+        since = "0.3.0",
+        note = "the flags are easy to swap, use `route_with` with a `RouteOptions`"
+    )]
+    pub async fn route(
+        &self,
+        vehicle: Type,
+        origin: impl Into<Point>,
+        destination: impl Into<Point>,
+        avoid_traffic_zone: bool,
+        avoid_odd_even_zone: bool,
+        alternative_paths: bool,
+    ) -> Result<Routes, NeshanError> {
+        let options = RouteOptions::new()
+            .avoid_traffic_zone(avoid_traffic_zone)
+            .avoid_odd_even_zone(avoid_odd_even_zone)
+            .alternative_paths(alternative_paths);
+
+        self.route_with(vehicle, origin, destination, &options)
+            .await
+    }
+
+    /// route finds route(s) from origin to destination based on the given options.
+    pub async fn route_with(
+        &self,
+        vehicle: Type,
+        origin: impl Into<Point>,
+        destination: impl Into<Point>,
+        options: &RouteOptions,
+    ) -> Result<Routes, NeshanError> {
+        self.route_with_meta(vehicle, origin, destination, options)
+            .await
+            .map(|(routes, _)| routes)
+    }
+
+    /// same as `route_with`, also returning details of the http response.
+    pub async fn route_with_meta(
+        &self,
+        vehicle: Type,
+        origin: impl Into<Point>,
+        destination: impl Into<Point>,
+        options: &RouteOptions,
+    ) -> Result<(Routes, ResponseMeta), NeshanError> {
+        let endpoint = options.version.endpoint();
+        self.route_as(
+            endpoint,
+            vehicle,
+            origin.into(),
+            destination.into(),
+            options,
+        )
+        .await
+    }
+
+    /// same as `route_with`, returning the response json as is. useful for reading fields
+    /// that `Routes` doesn't model yet.
+    pub async fn route_raw(
+        &self,
+        vehicle: Type,
+        origin: impl Into<Point>,
+        destination: impl Into<Point>,
+        options: &RouteOptions,
+    ) -> Result<Value, NeshanError> {
+        let endpoint = options.version.endpoint();
+        self.route_as(
+            endpoint,
+            vehicle,
+            origin.into(),
+            destination.into(),
+            options,
+        )
+        .await
+        .map(|(value, _)| value)
+    }
+
+    /// distance, duration and summary of each route, for when the routes themselves aren't
+    /// needed. neshan has no parameter for leaving the geometry out of the response, so it is
+    /// still transferred but skipped while decoding.
+    pub async fn route_summary(
+        &self,
+        vehicle: Type,
+        origin: impl Into<Point>,
+        destination: impl Into<Point>,
+        options: &RouteOptions,
+    ) -> Result<Vec<RouteSummary>, NeshanError> {
+        let (summaries, _) = self
+            .route_as::<RouteSummaries>(
+                options.version.endpoint(),
+                vehicle,
+                origin.into(),
+                destination.into(),
+                options,
+            )
+            .await?;
+
+        Ok(summaries
+            .routes
+            .into_iter()
+            .map(RouteSummary::from)
+            .collect())
+    }
+
+    /// route(s) from origin to destination at free flow, ignoring live traffic, e.g. for how
+    /// long a trip takes at night. the durations are estimates without traffic, which is why
+    /// this is a method of its own rather than an option of `route_with`.
+    ///
+    /// neshan has the no traffic variant on v4 of the direction api only, so
+    /// `RouteOptions::version` doesn't apply.
+    pub async fn route_no_traffic(
+        &self,
+        vehicle: Type,
+        origin: impl Into<Point>,
+        destination: impl Into<Point>,
+        options: &RouteOptions,
+    ) -> Result<Routes, NeshanError> {
+        let options = options.clone().version(DirectionVersion::V4);
+        self.route_as(
+            Endpoint::RouteNoTraffic,
+            vehicle,
+            origin.into(),
+            destination.into(),
+            &options,
+        )
+        .await
+        .map(|(routes, _)| routes)
+    }
+
+    async fn route_as<T: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        vehicle: Type,
+        origin: Point,
+        destination: Point,
+        options: &RouteOptions,
+    ) -> Result<(T, ResponseMeta), NeshanError> {
+        let points: Vec<Point> = std::iter::once(origin)
+            .chain(options.waypoints.iter().copied())
+            .chain(std::iter::once(destination))
+            .collect();
+        self.check(&points)?;
+        options.check()?;
+
+        let query = protocol::route_query(vehicle, origin, destination, options);
+        let client = match options.priority {
+            Some(priority) => self.with_priority(priority),
+            None => self.clone(),
+        };
+        let call = client.get(endpoint, &query);
+
+        trace::instrument(endpoint, &points, call).await
+    }
+
+    /// find postal address for the given point.
+    /// https://platform.neshan.org/api/reverse-geocoding
+    pub async fn reverse_geocode(
+        &self,
+        point: impl Into<Point>,
+    ) -> Result<PostalAddress, NeshanError> {
+        self.reverse_geocode_with_meta(point)
+            .await
+            .map(|(postal_address, _)| postal_address)
+    }
+
+    /// same as `reverse_geocode`, also returning details of the http response.
+    pub async fn reverse_geocode_with_meta(
+        &self,
+        point: impl Into<Point>,
+    ) -> Result<(PostalAddress, ResponseMeta), NeshanError> {
+        self.reverse_geocode_as(point.into()).await
+    }
+
+    /// same as `reverse_geocode`, returning the response json as is.
+    pub async fn reverse_geocode_raw(&self, point: impl Into<Point>) -> Result<Value, NeshanError> {
+        self.reverse_geocode_as(point.into())
+            .await
+            .map(|(value, _)| value)
+    }
+
+    async fn reverse_geocode_as<T: DeserializeOwned>(
+        &self,
+        point: Point,
+    ) -> Result<(T, ResponseMeta), NeshanError> {
+        self.check(&[point])?;
+
+        let query = protocol::reverse_geocode_query(point);
+        let call = self.get(Endpoint::ReverseGeocode, &query);
+
+        trace::instrument(Endpoint::ReverseGeocode, &[point], call).await
+    }
+
+    /// hit and miss counters of the response cache, `None` when caching is disabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// usage counters of each endpoint since the client was built or last reset.
+    /// the counters are shared between clones of the client.
+    pub fn stats(&self) -> Stats {
+        self.inner.usage.snapshot()
+    }
+
+    /// set every usage counter back to zero.
+    pub fn reset_stats(&self) {
+        self.inner.usage.reset();
+    }
+
+    /// quota headers of the most recent response that carried any of them.
+    pub fn last_quota(&self) -> Option<QuotaInfo> {
+        *self.inner.last_quota.lock().unwrap()
+    }
+
+    /// reject invalid points when the client validates them.
+    pub(crate) fn check(&self, points: &[Point]) -> Result<(), NeshanError> {
+        if self.inner.validate_points {
+            for point in points {
+                point.validate()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// whether responses of the endpoint end up in the cache.
+    pub(crate) fn caches(&self, endpoint: Endpoint) -> bool {
+        self.inner
+            .cache
+            .as_ref()
+            .is_some_and(|cache| cache.caches(endpoint))
+    }
+
+    /// current state of the circuit breaker, `None` when it is disabled.
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.inner.breaker.as_ref().map(Breaker::state)
+    }
+
+    pub(crate) fn base_url(&self) -> &str {
+        &self.inner.base_url
+    }
+
+    pub(crate) async fn get<T: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        query: &Query,
+    ) -> Result<(T, ResponseMeta), NeshanError> {
+        let url = self.url(endpoint, query)?;
+
+        self.fetch(endpoint, url, request_key(endpoint, query), |body| {
+            Ok(serde_json::from_slice(body)?)
+        })
+        .await
+    }
+
+    /// send the request to `url`, going through the cache and coalescing identical calls by
+    /// their `key`. a body that fails to `decode` is neither returned nor cached. expired
+    /// entries with validators are revalidated with a conditional request.
+    pub(crate) async fn fetch<T>(
+        &self,
+        endpoint: Endpoint,
+        url: Url,
+        key: String,
+        decode: impl Fn(&Bytes) -> Result<T, NeshanError>,
+    ) -> Result<(T, ResponseMeta), NeshanError> {
+        let start = Instant::now();
+        let cache = self
+            .inner
+            .cache
+            .as_ref()
+            .filter(|cache| cache.caches(endpoint));
+
+        let mut stale = None;
+        if let Some(cache) = cache {
+            match cache.get(&key).await {
+                Some(entry) if entry.is_fresh() => {
+                    let meta = ResponseMeta {
+                        status: entry.response_status(),
+                        headers: entry.response_headers(),
+                        elapsed: start.elapsed(),
+                        url,
+                        cached: true,
+                    };
+
+                    return Ok((decode(&entry.body)?, meta));
+                }
+                entry => stale = entry,
+            }
+        }
+        let conditional = stale
+            .as_ref()
+            .map(CachedEntry::conditional_headers)
+            .unwrap_or_default();
+
+        let res = match &self.inner.single_flight {
+            Some(flights) => {
+                let client = self.clone();
+                let owned = url.clone();
+                let request = async move { client.execute(endpoint, &owned, &conditional).await };
+
+                flights.run(key.clone(), request).await?
+            }
+            None => self.execute(endpoint, &url, &conditional).await?,
+        };
+
+        // only conditional requests get through with a 304, so there is a stale entry.
+        if let (Some(stale), Some(cache)) = (stale, cache) {
+            if res.status == StatusCode::NOT_MODIFIED {
+                let body = cache.revalidated(endpoint, key, stale, &res.headers).await;
+                let meta = ResponseMeta {
+                    status: res.status,
+                    headers: res.headers,
+                    elapsed: start.elapsed(),
+                    url,
+                    cached: true,
+                };
+
+                return Ok((decode(&body)?, meta));
+            }
+        }
+
+        let value = decode(&res.body)
+            .map_err(|err| err.with_request_id(protocol::request_id(&res.headers)))?;
+        let entry = CachedEntry::from_response(res.status, res.body, &res.headers);
+        let meta = ResponseMeta {
+            status: res.status,
+            headers: res.headers,
+            elapsed: start.elapsed(),
+            url,
+            cached: false,
+        };
+
+        if let Some(cache) = cache {
+            cache.put(endpoint, key, entry).await;
+        }
+
+        Ok((value, meta))
+    }
+
+    /// send the request with the `conditional` headers within the configured deadline.
+    async fn execute(
+        &self,
+        endpoint: Endpoint,
+        url: &Url,
+        conditional: &HeaderMap,
+    ) -> Result<Response, NeshanError> {
+        let attempts = AtomicU32::new(0);
+        let attempt = self.attempt(endpoint, url, conditional, &attempts);
+
+        let deadline = match self.inner.deadline {
+            Some(deadline) => deadline,
+            None => return attempt.await,
+        };
+
+        match tokio::time::timeout(deadline, attempt).await {
+            Ok(result) => result,
+            Err(_) => Err(NeshanError::DeadlineExceeded {
+                deadline,
+                attempts: attempts.load(Ordering::Relaxed),
+            }),
+        }
+    }
+
+    /// send the request, pacing it with the rate limiter and retrying it based on the
+    /// configured policy. `attempts` counts the requests that went out.
+    async fn attempt(
+        &self,
+        endpoint: Endpoint,
+        url: &Url,
+        conditional: &HeaderMap,
+        attempts: &AtomicU32,
+    ) -> Result<Response, NeshanError> {
+        let observer = &self.inner.observer;
+        let mut attempt = 1;
+
+        loop {
+            if let Some(limiter) = &self.inner.rate_limiter {
+                limiter.acquire(self.priority).await;
+            }
+
+            let permit = match &self.inner.breaker {
+                Some(breaker) => Some(breaker.acquire()?),
+                None => None,
+            };
+
+            attempts.store(attempt, Ordering::Relaxed);
+            observer.on_request_start(endpoint, attempt);
+            self.inner.usage.request(endpoint);
+            trace::attempt_started(endpoint, attempt);
+            let start = Instant::now();
+
+            let result = self.send(endpoint, url, conditional).await;
+            let error = result.as_ref().err().map(NeshanError::kind);
+            self.inner.usage.finished(endpoint, error, start.elapsed());
+            if let Some(permit) = permit {
+                permit.report(error);
+            }
+
+            let err = match result {
+                Ok(res) => {
+                    let elapsed = start.elapsed();
+                    let status = res.status.as_u16();
+                    observer.on_response(endpoint, status, elapsed, attempt);
+                    let request_id = protocol::request_id(&res.headers);
+                    trace::attempt_finished(endpoint, Some(status), request_id, elapsed);
+                    return Ok(res);
+                }
+                Err(err) => err,
+            };
+
+            let elapsed = start.elapsed();
+            if let Some(status) = err.status() {
+                observer.on_response(endpoint, status, elapsed, attempt);
+            }
+            trace::attempt_finished(endpoint, err.status(), err.request_id(), elapsed);
+            observer.on_error(endpoint, err.kind(), attempt);
+
+            let delay = match &self.inner.retry {
+                Some(policy) if attempt < policy.attempts() && policy.should_retry(err.kind()) => {
+                    policy.delay(attempt, err.retry_after())
+                }
+                _ => None,
+            };
+            let delay = match delay {
+                Some(delay) => delay,
+                None => return Err(err),
+            };
+            tokio::time::sleep(delay).await;
+
+            attempt += 1;
+        }
+    }
+
+    fn url(&self, endpoint: Endpoint, query: &Query) -> Result<Url, NeshanError> {
+        protocol::url(&self.inner.base_url, endpoint, query)
+    }
+
+    /// send a single attempt through the middlewares, turning error statuses into errors.
+    /// a `304 Not Modified` to a request with `conditional` headers is not an error.
+    async fn send(
+        &self,
+        endpoint: Endpoint,
+        url: &Url,
+        conditional: &HeaderMap,
+    ) -> Result<Response, NeshanError> {
+        let mut req = Request {
+            method: Method::GET,
+            url: url.clone(),
+            headers: conditional.clone(),
+        };
+        req.headers.insert("Api-Key", self.inner.api_key.clone());
+
+        let sent = SystemTime::now();
+        let start = Instant::now();
+        let res = match Next::new(self.inner.http.as_ref(), &self.inner.middlewares)
+            .run(req)
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                self.audit(endpoint, url, sent, start.elapsed(), &Err(&err), None);
+                return Err(err);
+            }
+        };
+        self.inner.usage.received(endpoint, res.body.len());
+
+        let quota = QuotaInfo::from_headers(&res.headers);
+        if quota.is_some() {
+            *self.inner.last_quota.lock().unwrap() = quota;
+        }
+        let checked = match res.status {
+            StatusCode::NOT_MODIFIED if !conditional.is_empty() => Ok(()),
+            status => protocol::check(status, &res.headers, &res.body),
+        };
+        let outcome = match &checked {
+            Ok(()) => Ok((res.status.as_u16(), protocol::request_id(&res.headers))),
+            Err(err) => Err(err),
+        };
+        self.audit(
+            endpoint,
+            url,
+            sent,
+            start.elapsed(),
+            &outcome,
+            Some(&res.body),
+        );
+        checked?;
+
+        Ok(res)
+    }
+
+    /// hand an attempt to the audit sink, `outcome` is the status and request id of a
+    /// successful response or the error of a failed one.
+    fn audit(
+        &self,
+        endpoint: Endpoint,
+        url: &Url,
+        sent: SystemTime,
+        duration: Duration,
+        outcome: &Result<(u16, Option<&str>), &NeshanError>,
+        body: Option<&[u8]>,
+    ) {
+        let sink = match &self.inner.audit {
+            Some(sink) => sink,
+            None => return,
+        };
+
+        let (status, request_id, error) = match outcome {
+            Ok((status, request_id)) => (Some(*status), *request_id, None),
+            Err(err) => (err.status(), err.request_id(), Some(err.kind())),
+        };
+        let api_key = self.inner.api_key.to_str().unwrap_or_default();
+        let mut entry = AuditEntry::new(
+            sent, endpoint, url, status, duration, request_id, error, api_key,
+        );
+        if let (Some(body), Some(limit)) = (body, sink.body_limit()) {
+            entry = entry.body(body, limit, api_key);
+        }
+
+        sink.record(entry);
+    }
+
+    /// stream the response body into `writer` as it arrives, returning the number of bytes
+    /// written and the content type. the response must have a content type starting with
+    /// `content_type`, otherwise nothing is written.
+    ///
+    /// downloads are too large for the middleware chain, which holds whole bodies, so they go
+    /// straight to the backend. they are rate limited and observed but neither retried
+    /// nor cached.
+    pub(crate) async fn download<W>(
+        &self,
+        endpoint: Endpoint,
+        query: &Query,
+        content_type: &str,
+        writer: &mut W,
+    ) -> Result<(u64, String), NeshanError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let url = self.url(endpoint, query)?;
+        if let Some(limiter) = &self.inner.rate_limiter {
+            limiter.acquire(self.priority).await;
+        }
+
+        let observer = &self.inner.observer;
+        observer.on_request_start(endpoint, 1);
+        self.inner.usage.request(endpoint);
+        let sent = SystemTime::now();
+        let start = Instant::now();
+
+        let result = self.stream(endpoint, &url, content_type, writer).await;
+
+        let elapsed = start.elapsed();
+        let error = result.as_ref().err().map(NeshanError::kind);
+        self.inner.usage.finished(endpoint, error, elapsed);
+        let outcome = match &result {
+            Ok((status, request_id, _, _)) => Ok((*status, request_id.as_deref())),
+            Err(err) => Err(err),
+        };
+        self.audit(endpoint, &url, sent, elapsed, &outcome, None);
+        match &result {
+            Ok((status, _, _, _)) => observer.on_response(endpoint, *status, elapsed, 1),
+            Err(err) => {
+                if let Some(status) = err.status() {
+                    observer.on_response(endpoint, status, elapsed, 1);
+                }
+                observer.on_error(endpoint, err.kind(), 1);
+            }
+        }
+
+        result.map(|(_, _, written, content_type)| (written, content_type))
+    }
+
+    /// the status, request id, length and content type of a download.
+    async fn stream<W>(
+        &self,
+        endpoint: Endpoint,
+        url: &Url,
+        content_type: &str,
+        writer: &mut W,
+    ) -> Result<(u16, Option<String>, u64, String), NeshanError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let mut req = Request {
+            method: Method::GET,
+            url: url.clone(),
+            headers: http::HeaderMap::new(),
+        };
+        req.headers.insert("Api-Key", self.inner.api_key.clone());
+        let mut res = self.inner.http.send_streaming(req).await?;
+
+        let status = res.status;
+        let quota = QuotaInfo::from_headers(&res.headers);
+        if quota.is_some() {
+            *self.inner.last_quota.lock().unwrap() = quota;
+        }
+
+        if !status.is_success() {
+            let mut body = Vec::new();
+            while let Some(chunk) = res.body.next().await {
+                body.extend_from_slice(&chunk?);
+            }
+            self.inner.usage.received(endpoint, body.len());
+
+            return Err(protocol::api_error(status, &res.headers, &body));
+        }
+        protocol::check_content_type(&res.headers, content_type)?;
+        let content_type = res
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let mut written = 0;
+        let request_id = protocol::request_id(&res.headers).map(str::to_string);
+        let interrupted = |written, kind, source| NeshanError::Interrupted {
+            written,
+            kind,
+            source,
+            request_id: request_id.clone(),
+        };
+        loop {
+            let chunk = match res.body.next().await {
+                Some(Ok(chunk)) => chunk,
+                None => break,
+                Some(Err(err)) => return Err(interrupted(written, err.kind(), Arc::new(err))),
+            };
+
+            self.inner.usage.received(endpoint, chunk.len());
+            writer
+                .write_all(&chunk)
+                .await
+                .map_err(|err| interrupted(written, ErrorKind::Other, Arc::new(err)))?;
+            written += chunk.len() as u64;
+        }
+        writer
+            .flush()
+            .await
+            .map_err(|err| interrupted(written, ErrorKind::Other, Arc::new(err)))?;
+
+        Ok((status.as_u16(), request_id, written, content_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Client;
+    use crate::cache::{CacheConfig, CacheStats, CachedEntry, ResponseCache};
+    use crate::circuit::{CircuitBreaker, CircuitState};
+    use crate::endpoint::Endpoint;
+    use crate::error::{ErrorKind, NeshanError};
+    use crate::middleware::{Middleware, Next, Request, Response};
+    use crate::observer::{CountingObserver, RequestObserver};
+    use crate::quota::QuotaInfo;
+    use crate::rate_limit::Priority;
+    use crate::retry::RetryPolicy;
+    use crate::stats::EndpointStats;
+    use crate::Point;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn postal_address() -> serde_json::Value {
+        serde_json::json!({
+            "formatted_address": "تهران، خیابان آزادی",
+            "route_name": "خیابان آزادی",
+            "neighbourhood": "قزل قلعه",
+            "city": "تهران",
+            "state": "استان تهران",
+            "place": null,
+            "municipality_zone": "6",
+            "in_traffic_zone": true,
+            "in_odd_even_zone": true
+        })
+    }
+
+    fn point() -> Point {
+        Point {
+            latitude: 35.731984409609694,
+            longitude: 51.392684661470156,
+        }
+    }
+
+    fn client(server: &MockServer, policy: RetryPolicy) -> Client {
+        Client::builder("key")
+            .base_url(&server.uri())
+            .retry(policy)
+            .build()
+            .unwrap()
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy::new()
+            .max_attempts(3)
+            .initial_backoff(Duration::from_millis(50))
+            .jitter(false)
+    }
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let start = Instant::now();
+        let postal_address = client(&server, fast_policy())
+            .reverse_geocode(point())
+            .await
+            .unwrap();
+
+        assert_eq!(postal_address.city, "تهران");
+        // 50ms before the first retry and 100ms before the second.
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_attempts() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let err = client(&server, fast_policy())
+            .reverse_geocode(point())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Server);
+        assert_eq!(err.status(), Some(502));
+    }
+
+    #[tokio::test]
+    async fn no_retry_on_auth_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(480).set_body_json(serde_json::json!({
+                "status": "ERROR",
+                "code": 480,
+                "message": "Key not found"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let start = Instant::now();
+        let err = client(
+            &server,
+            fast_policy().initial_backoff(Duration::from_secs(5)),
+        )
+        .reverse_geocode(point())
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Auth);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn no_retry_on_validation_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(470).set_body_json(serde_json::json!({
+                "status": "ERROR",
+                "code": 470,
+                "message": "Coordinate Parse Error"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let err = client(&server, fast_policy())
+            .reverse_geocode(point())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidRequest);
+    }
+
+    #[tokio::test]
+    async fn honor_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let start = Instant::now();
+        client(&server, fast_policy())
+            .reverse_geocode(point())
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_returned() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "86400"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let start = Instant::now();
+        let err = client(&server, fast_policy())
+            .reverse_geocode(point())
+            .await
+            .unwrap_err();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(err.kind(), ErrorKind::RateLimited);
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(86400)));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_paces_burst() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(8)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .rate_limit(20.0, 2)
+            .build()
+            .unwrap();
+
+        let start = Instant::now();
+        let calls = (0..8).map(|_| {
+            // clones share the same bucket.
+            let client = client.clone();
+            async move { client.reverse_geocode(point()).await }
+        });
+        for result in futures_util::future::join_all(calls).await {
+            result.unwrap();
+        }
+
+        // two requests go out immediately and the remaining six are 50ms apart.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(290), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+    }
+
+    /// records the latitude of every request that reaches it.
+    struct Dispatched(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl Middleware for Dispatched {
+        async fn handle(&self, req: Request, next: Next<'_>) -> Result<Response, NeshanError> {
+            let latitude = req
+                .url
+                .query_pairs()
+                .find(|(name, _)| name == "lat")
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default();
+            self.0.lock().unwrap().push(latitude);
+
+            next.run(req).await
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limit_admits_by_priority() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .mount(&server)
+            .await;
+
+        let dispatched = Arc::new(Mutex::new(Vec::new()));
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .rate_limit(5.0, 1)
+            .middleware(Dispatched(dispatched.clone()))
+            .build()
+            .unwrap();
+        let at = |latitude: f64| Point {
+            latitude,
+            longitude: 51.0,
+        };
+
+        // empty the bucket, then queue calls in the opposite order of their priority.
+        client.reverse_geocode(at(0.0)).await.unwrap();
+        let low = client.with_priority(Priority::Low);
+        let high = client.with_priority(Priority::High);
+        let calls = vec![
+            low.reverse_geocode(at(1.0)),
+            low.reverse_geocode(at(2.0)),
+            client.reverse_geocode(at(3.0)),
+            high.reverse_geocode(at(4.0)),
+        ];
+        for result in futures_util::future::join_all(calls).await {
+            result.unwrap();
+        }
+
+        assert_eq!(
+            *dispatched.lock().unwrap(),
+            vec!["0.000000", "4.000000", "3.000000", "1.000000", "2.000000"]
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_hit_skips_network() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16))
+            .build()
+            .unwrap();
+
+        let first = client.reverse_geocode(point()).await.unwrap();
+        let second = client.reverse_geocode(point()).await.unwrap();
+
+        assert_eq!(first.formatted_address, second.formatted_address);
+        assert_eq!(
+            client.cache_stats(),
+            Some(CacheStats {
+                hits: 1,
+                misses: 1,
+                revalidated: 0,
+                entries: 1
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_entry_expires() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16).ttl(Endpoint::ReverseGeocode, Duration::from_millis(50)))
+            .build()
+            .unwrap();
+
+        client.reverse_geocode(point()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.reverse_geocode(point()).await.unwrap();
+
+        // without validators the request after the ttl is a plain one.
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests[1].headers.get("if-none-match").is_none());
+        assert!(requests[1].headers.get("if-modified-since").is_none());
+        assert_eq!(client.cache_stats().unwrap().revalidated, 0);
+    }
+
+    /// entries with validators are kept for twice their ttl of 50ms, so they are stale
+    /// but still there after 70ms.
+    fn revalidating_client(server: &MockServer) -> Client {
+        Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16).ttl(Endpoint::ReverseGeocode, Duration::from_millis(50)))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn not_modified_serves_cached_body() {
+        let server = MockServer::start().await;
+        let modified = "Wed, 14 Oct 2026 08:00:00 GMT";
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .insert_header("etag", "\"v1\"")
+                    .insert_header("last-modified", modified),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = revalidating_client(&server);
+
+        client.reverse_geocode(point()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        let (address, meta) = client.reverse_geocode_with_meta(point()).await.unwrap();
+        assert_eq!(address.city, "تهران");
+        assert_eq!(meta.status(), 304);
+        assert!(meta.from_cache());
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[1].headers["if-modified-since"], modified);
+
+        // the 304 refreshed the ttl, so the next call doesn't go out at all.
+        let (_, meta) = client.reverse_geocode_with_meta(point()).await.unwrap();
+        assert_eq!(meta.status(), 200);
+        assert!(meta.from_cache());
+        assert_eq!(
+            client.cache_stats(),
+            Some(CacheStats {
+                hits: 1,
+                misses: 2,
+                revalidated: 1,
+                entries: 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn changed_etag_replaces_entry() {
+        let server = MockServer::start().await;
+        let mut moved = postal_address();
+        moved["city"] = "کرج".into();
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(header("if-none-match", "\"v1\""))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(moved)
+                    .insert_header("etag", "\"v2\""),
+            )
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(header("if-none-match", "\"v2\""))
+            .respond_with(ResponseTemplate::new(304))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .insert_header("etag", "\"v1\""),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = revalidating_client(&server);
+
+        assert_eq!(client.reverse_geocode(point()).await.unwrap().city, "تهران");
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        let (address, meta) = client.reverse_geocode_with_meta(point()).await.unwrap();
+        assert_eq!(address.city, "کرج");
+        assert!(!meta.from_cache());
+
+        // the new body is cached with its own etag.
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        assert_eq!(client.reverse_geocode(point()).await.unwrap().city, "کرج");
+        assert_eq!(client.cache_stats().unwrap().revalidated, 1);
+    }
+
+    #[derive(Default)]
+    struct SharedStore {
+        // entries as json, the way an external store would hold them.
+        entries: Mutex<std::collections::HashMap<String, (String, Duration)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ResponseCache for SharedStore {
+        async fn get(&self, key: &str) -> Option<CachedEntry> {
+            let entries = self.entries.lock().unwrap();
+            let (json, _) = entries.get(key)?;
+
+            serde_json::from_str(json).ok()
+        }
+
+        async fn put(&self, key: &str, entry: CachedEntry, ttl: Duration) {
+            let json = serde_json::to_string(&entry).unwrap();
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), (json, ttl));
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_cache_store_is_shared() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = Arc::new(SharedStore::default());
+        let build = || {
+            Client::builder("key")
+                .base_url(&server.uri())
+                .cache_store(store.clone())
+                .build()
+                .unwrap()
+        };
+
+        // two instances of a service, the second one is served by the first one's response.
+        let (_, meta) = build().reverse_geocode_with_meta(point()).await.unwrap();
+        assert!(!meta.from_cache());
+        let other = build();
+        let (address, meta) = other.reverse_geocode_with_meta(point()).await.unwrap();
+        assert!(meta.from_cache());
+        assert_eq!(address.city, "تهران");
+        assert_eq!(
+            other.cache_stats(),
+            Some(CacheStats {
+                hits: 1,
+                misses: 0,
+                revalidated: 0,
+                entries: 0,
+            })
+        );
+
+        let entries = store.entries.lock().unwrap();
+        let (json, ttl) = &entries["reverse_geocode?lat=35.731984409609694&lng=51.392684661470156"];
+        assert_eq!(*ttl, Duration::from_secs(600));
+        assert!(json.starts_with(r#"{"body":"{"#));
+    }
+
+    #[tokio::test]
+    async fn errors_are_not_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16))
+            .build()
+            .unwrap();
+
+        client.reverse_geocode(point()).await.unwrap_err();
+        client.reverse_geocode(point()).await.unwrap_err();
+    }
+
+    #[cfg(feature = "disk-cache")]
+    #[tokio::test]
+    async fn disk_cache_survives_restart() {
+        let dir = crate::disk_cache::tests::TempDir::new();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16).persist(&dir.0))
+            .build()
+            .unwrap();
+        client.reverse_geocode(point()).await.unwrap();
+        drop(client);
+
+        // the mock server is gone, only the disk can answer.
+        let base_url = server.uri();
+        drop(server);
+        let client = Client::builder("key")
+            .base_url(&base_url)
+            .cache(CacheConfig::new(16).persist(&dir.0))
+            .build()
+            .unwrap();
+        let postal_address = client.reverse_geocode(point()).await.unwrap();
+
+        assert_eq!(postal_address.city, "تهران");
+        assert_eq!(client.cache_stats().unwrap().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn meta_matches_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .insert_header("X-Request-Id", "abc-123")
+                    .set_delay(Duration::from_millis(50)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .cache(CacheConfig::new(16))
+            .build()
+            .unwrap();
+
+        let (postal_address, meta) = client.reverse_geocode_with_meta(point()).await.unwrap();
+        assert_eq!(postal_address.city, "تهران");
+        assert_eq!(meta.status(), http::StatusCode::OK);
+        assert_eq!(meta.headers()["x-request-id"], "abc-123");
+        assert_eq!(meta.request_id(), Some("abc-123"));
+        assert!(meta.elapsed() >= Duration::from_millis(50));
+        assert_eq!(meta.url().path(), "/v2/reverse");
+        assert_eq!(
+            meta.url().query(),
+            Some("lat=35.731984409609694&lng=51.392684661470156")
+        );
+        assert!(!meta.from_cache());
+
+        let (_, meta) = client.reverse_geocode_with_meta(point()).await.unwrap();
+        assert!(meta.from_cache());
+        assert_eq!(meta.status(), http::StatusCode::OK);
+        assert_eq!(meta.headers()["x-request-id"], "abc-123");
+        assert_eq!(meta.request_id(), Some("abc-123"));
+        assert_eq!(meta.url().path(), "/v2/reverse");
+    }
+
+    #[tokio::test]
+    async fn errors_carry_the_request_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(480)
+                    .set_body_json(serde_json::json!({"code": 480, "message": "Key not found"}))
+                    .insert_header("X-Request-Id", "req-480"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"routes": "none"}))
+                    .insert_header("X-Correlation-Id", "corr-1"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = client(&server, fast_policy());
+
+        let err = client.reverse_geocode(point()).await.unwrap_err();
+        assert_eq!(err.request_id(), Some("req-480"));
+        assert_eq!(
+            err.to_string(),
+            "neshan error 480: Key not found (request id req-480)"
+        );
+
+        let err = client
+            .route_with(
+                crate::Type::Car,
+                point(),
+                point(),
+                &crate::RouteOptions::new(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Decode);
+        assert_eq!(err.request_id(), Some("corr-1"));
+        assert!(err.to_string().ends_with("(request id corr-1)"));
+    }
+
+    #[tokio::test]
+    async fn raw_keeps_unmodeled_fields() {
+        let server = MockServer::start().await;
+        let mut body = postal_address();
+        body["brand_new_field"] = serde_json::json!({ "nested": [1, 2, 3] });
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body.clone()))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, fast_policy());
+
+        assert_eq!(client.reverse_geocode_raw(point()).await.unwrap(), body);
+        assert_eq!(client.reverse_geocode(point()).await.unwrap().city, "تهران");
+    }
+
+    #[tokio::test]
+    async fn detect_error_envelope_with_success_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "status": "ERROR",
+                "code": 480,
+                "message": "Key not found"
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = client(&server, fast_policy());
+
+        for err in [
+            client.reverse_geocode_raw(point()).await.unwrap_err(),
+            client.reverse_geocode(point()).await.unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), ErrorKind::Auth);
+            match err {
+                NeshanError::Api(err) => {
+                    assert_eq!(err.status(), 200);
+                    assert_eq!(err.error().code(), 480);
+                    assert_eq!(err.error().message(), "Key not found");
+                }
+                err => panic!("unexpected error {:?}", err),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn route_summary_skips_geometry() {
+        let steps: Vec<_> = (0..200)
+            .map(|i| {
+                serde_json::json!({
+                    "name": format!("خیابان {}", i),
+                    "instruction": "به سمت شمال بروید",
+                    "polyline": "ctu|Ec`{|H??~@m@??\\mAvBmD",
+                    "start_location": [51.39, 35.73]
+                })
+            })
+            .collect();
+        let body = serde_json::json!({
+            "routes": [{
+                "overview_polyline": { "points": "ctu|Ec`{|H??~@m@??\\mAvBmD".repeat(100) },
+                "legs": [{
+                    "summary": "آزادی - شیخ فضل الله",
+                    "distance": { "value": 5450.0, "text": "۵.۵ کیلومتر" },
+                    "duration": { "value": 688.0, "text": "۱۲ دقیقه" },
+                    "steps": steps
+                }]
+            }]
+        });
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body.clone()))
+            .mount(&server)
+            .await;
+
+        let summaries = client(&server, fast_policy())
+            .route_summary(
+                crate::Type::Car,
+                point(),
+                point(),
+                &crate::RouteOptions::new(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].summary, "آزادی - شیخ فضل الله");
+        assert_eq!(summaries[0].distance.value, 5450.0);
+        assert_eq!(summaries[0].duration.value, 688.0);
+
+        // what is kept is a sliver of what was sent.
+        let kept = serde_json::to_vec(&summaries).unwrap().len();
+        let sent = serde_json::to_vec(&body).unwrap().len();
+        assert!(kept * 50 < sent, "kept {} of {} bytes", kept, sent);
+    }
+
+    fn single_flight_client(server: &MockServer) -> Client {
+        Client::builder("key")
+            .base_url(&server.uri())
+            .single_flight(true)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn single_flight_coalesces_identical_calls() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = single_flight_client(&server);
+        let calls = (0..10).map(|_| client.reverse_geocode(point()));
+
+        for result in futures_util::future::join_all(calls).await {
+            assert_eq!(result.unwrap().city, "تهران");
+        }
+    }
+
+    #[tokio::test]
+    async fn single_flight_shares_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(500).set_delay(Duration::from_millis(200)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = single_flight_client(&server);
+        let calls = (0..5).map(|_| client.reverse_geocode(point()));
+
+        for result in futures_util::future::join_all(calls).await {
+            assert_eq!(result.unwrap_err().status(), Some(500));
+        }
+    }
+
+    #[tokio::test]
+    async fn single_flight_survives_dropped_leader() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = single_flight_client(&server);
+        let leader =
+            tokio::time::timeout(Duration::from_millis(50), client.reverse_geocode(point()));
+        let follower = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            client.reverse_geocode(point()).await
+        };
+
+        let (leader, follower) = tokio::join!(leader, follower);
+
+        assert!(leader.is_err());
+        assert_eq!(follower.unwrap().city, "تهران");
+    }
+
+    #[tokio::test]
+    async fn single_flight_keeps_distinct_calls_apart() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = single_flight_client(&server);
+        let other = Point {
+            latitude: 35.7,
+            longitude: 51.4,
+        };
+
+        let (a, b) = tokio::join!(
+            client.reverse_geocode(point()),
+            client.reverse_geocode(other)
+        );
+        a.unwrap();
+        b.unwrap();
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl RequestObserver for Recorder {
+        fn on_request_start(&self, endpoint: Endpoint, attempt: u32) {
+            let event = format!("start {} {}", endpoint, attempt);
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn on_response(&self, endpoint: Endpoint, status: u16, _: Duration, attempt: u32) {
+            let event = format!("response {} {} {}", endpoint, status, attempt);
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn on_error(&self, endpoint: Endpoint, kind: ErrorKind, attempt: u32) {
+            let event = format!("error {} {:?} {}", endpoint, kind, attempt);
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_sees_every_attempt() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .mount(&server)
+            .await;
+
+        let recorder = Arc::new(Recorder::default());
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .retry(fast_policy())
+            .observer(recorder.clone())
+            .build()
+            .unwrap();
+
+        client.reverse_geocode(point()).await.unwrap();
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                "start reverse_geocode 1",
+                "response reverse_geocode 503 1",
+                "error reverse_geocode Server 1",
+                "start reverse_geocode 2",
+                "response reverse_geocode 200 2",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn counting_observer_labels_endpoints() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(ResponseTemplate::new(480))
+            .mount(&server)
+            .await;
+
+        let counter = Arc::new(CountingObserver::new());
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .observer(counter.clone())
+            .build()
+            .unwrap();
+
+        client.reverse_geocode(point()).await.unwrap();
+        client.reverse_geocode(point()).await.unwrap();
+        client
+            .route_with(
+                crate::Type::Car,
+                point(),
+                point(),
+                &crate::RouteOptions::new(),
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(counter.requests(Endpoint::ReverseGeocode), 2);
+        assert_eq!(counter.responses(Endpoint::ReverseGeocode, 200), 2);
+        assert_eq!(counter.requests(Endpoint::Route), 1);
+        assert_eq!(counter.responses(Endpoint::Route, 480), 1);
+        assert_eq!(counter.errors(Endpoint::Route, ErrorKind::Auth), 1);
+        assert_eq!(counter.errors(Endpoint::ReverseGeocode, ErrorKind::Auth), 0);
+    }
+
+    #[test]
+    fn reject_invalid_rate_limit() {
+        assert!(Client::builder("key").rate_limit(0.0, 1).build().is_err());
+        assert!(Client::builder("key")
+            .rate_limit(f64::NAN, 1)
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn deadline_bounds_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .retry(
+                fast_policy()
+                    .max_attempts(10)
+                    .initial_backoff(Duration::from_millis(100)),
+            )
+            .deadline(Duration::from_millis(250))
+            .build()
+            .unwrap();
+
+        let start = Instant::now();
+        let err = client.reverse_geocode(point()).await.unwrap_err();
+
+        // attempts at 0ms and 100ms, the third one would start at 300ms.
+        match err {
+            NeshanError::DeadlineExceeded { deadline, attempts } => {
+                assert_eq!(deadline, Duration::from_millis(250));
+                assert_eq!(attempts, 2);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn deadline_cuts_slow_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .set_delay(Duration::from_secs(2)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .deadline(Duration::from_millis(100))
+            .build()
+            .unwrap();
+
+        let start = Instant::now();
+        let err = client.reverse_geocode(point()).await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            NeshanError::DeadlineExceeded { attempts: 1, .. }
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    /// holds a guard for as long as its future is alive, failing or hanging forever.
+    struct Hold {
+        guard: Arc<()>,
+        hang: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Middleware for Hold {
+        async fn handle(&self, _: Request, _: Next<'_>) -> Result<Response, NeshanError> {
+            let _guard = self.guard.clone();
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+
+            Ok(Response {
+                status: http::StatusCode::SERVICE_UNAVAILABLE,
+                headers: http::HeaderMap::new(),
+                body: bytes::Bytes::new(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_call_leaves_nothing_behind() {
+        // hanging on the request itself and sleeping between retries.
+        for hang in [true, false] {
+            let guard = Arc::new(());
+            let client = Client::builder("key")
+                .retry(fast_policy().initial_backoff(Duration::from_secs(10)))
+                .rate_limit(100.0, 10)
+                .single_flight(true)
+                .middleware(Hold {
+                    guard: guard.clone(),
+                    hang,
+                })
+                .build()
+                .unwrap();
+
+            let call =
+                tokio::time::timeout(Duration::from_millis(50), client.reverse_geocode(point()));
+            assert!(call.await.is_err());
+
+            assert_eq!(Arc::strong_count(&guard), 2);
+            drop(client);
+            assert_eq!(Arc::strong_count(&guard), 1);
+            assert_eq!(
+                tokio::runtime::Handle::current()
+                    .metrics()
+                    .num_alive_tasks(),
+                0
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker_cycle() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(3)
+            .expect(3)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .circuit_breaker(CircuitBreaker::new(2, Duration::from_millis(100)))
+            .build()
+            .unwrap();
+        let clone = client.clone();
+
+        client.reverse_geocode(point()).await.unwrap_err();
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+        client.reverse_geocode(point()).await.unwrap_err();
+        assert_eq!(clone.circuit_state(), Some(CircuitState::Open));
+
+        // fails fast without reaching neshan.
+        let err = clone.reverse_geocode(point()).await.unwrap_err();
+        assert!(matches!(err, NeshanError::CircuitOpen { .. }));
+        assert_eq!(err.kind(), ErrorKind::CircuitOpen);
+
+        // a failed probe opens the circuit again.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(client.circuit_state(), Some(CircuitState::HalfOpen));
+        client.reverse_geocode(point()).await.unwrap_err();
+        assert_eq!(client.circuit_state(), Some(CircuitState::Open));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(client.circuit_state(), Some(CircuitState::HalfOpen));
+        client.reverse_geocode(point()).await.unwrap();
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+    }
+
+    #[tokio::test]
+    async fn stats_count_calls() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(ResponseTemplate::new(480))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, fast_policy());
+        let clone = client.clone();
+
+        client.reverse_geocode(point()).await.unwrap();
+        clone.reverse_geocode(point()).await.unwrap();
+        client
+            .route_with(
+                crate::Type::Car,
+                point(),
+                point(),
+                &crate::RouteOptions::new(),
+            )
+            .await
+            .unwrap_err();
+
+        let stats = client.stats();
+        let reverse = stats.endpoint(Endpoint::ReverseGeocode);
+        assert_eq!(reverse.requests, 3);
+        assert_eq!(reverse.successes, 2);
+        assert_eq!(reverse.errors.get(&ErrorKind::Server), Some(&1));
+        assert_eq!(
+            reverse.bytes_received,
+            2 * postal_address().to_string().len() as u64
+        );
+        assert!(reverse.latency > Duration::ZERO);
+
+        let route = stats.endpoint(Endpoint::Route);
+        assert_eq!(route.requests, 1);
+        assert_eq!(route.successes, 0);
+        assert_eq!(route.total_errors(), 1);
+        assert_eq!(route.errors.get(&ErrorKind::Auth), Some(&1));
+
+        clone.reset_stats();
+        assert_eq!(
+            client.stats().endpoint(Endpoint::ReverseGeocode),
+            &EndpointStats::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn last_quota_keeps_latest_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .insert_header("X-RateLimit-Limit", "100")
+                    .insert_header("X-RateLimit-Remaining", "41")
+                    .insert_header("X-RateLimit-Reset", "60"),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("X-RateLimit-Limit", "100")
+                    .insert_header("X-RateLimit-Remaining", "0"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        assert_eq!(client.last_quota(), None);
+
+        client.reverse_geocode(point()).await.unwrap();
+        let first = QuotaInfo {
+            limit: Some(100),
+            remaining: Some(41),
+            reset: Some(Duration::from_secs(60)),
+        };
+        assert_eq!(client.last_quota(), Some(first));
+
+        // a response without the headers leaves the last seen value alone.
+        client.reverse_geocode(point()).await.unwrap();
+        assert_eq!(client.last_quota(), Some(first));
+
+        let throttled = QuotaInfo {
+            limit: Some(100),
+            remaining: Some(0),
+            reset: None,
+        };
+        match client.reverse_geocode(point()).await.unwrap_err() {
+            NeshanError::RateLimited(err) => assert_eq!(err.quota(), Some(throttled)),
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(client.last_quota(), Some(throttled));
+    }
+
+    #[tokio::test]
+    async fn tenants_share_the_backend() {
+        let backend = Arc::new(crate::backend::MemoryBackend::default().respond(
+            "/v2/reverse",
+            200,
+            "application/json",
+            postal_address().to_string(),
+        ));
+        let client = Client::builder("main")
+            .backend(backend.clone())
+            .cache(CacheConfig::new(16))
+            .build()
+            .unwrap();
+        let first = client.with_api_key("tenant-1").unwrap();
+        let second = client.with_api_key("tenant-2").unwrap();
+        assert!(Arc::ptr_eq(&first.inner.http, &second.inner.http));
+        assert!(Arc::ptr_eq(&first.inner.http, &client.inner.http));
+
+        first.reverse_geocode(point()).await.unwrap();
+        second.reverse_geocode((35.8, 51.0)).await.unwrap();
+        let keys: Vec<_> = backend
+            .sent()
+            .iter()
+            .map(|req| req.headers["api-key"].clone())
+            .collect();
+        assert_eq!(keys, vec!["tenant-1", "tenant-2"]);
+
+        // the cache is shared, the usage counters are not.
+        second.reverse_geocode(point()).await.unwrap();
+        assert_eq!(backend.sent().len(), 2);
+        assert_eq!(client.cache_stats().unwrap().hits, 1);
+        let requests = |client: &Client| client.stats().endpoint(Endpoint::ReverseGeocode).requests;
+        assert_eq!(
+            (requests(&client), requests(&first), requests(&second)),
+            (0, 1, 1)
+        );
+
+        assert!(matches!(
+            client.with_api_key("bad\nkey"),
+            Err(NeshanError::Config(_))
+        ));
+        assert!(client.with_api_key_and_rate_limit("key", 0.0, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn tenants_have_their_own_budget() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(header("api-key", "busy"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .insert_header("X-RateLimit-Remaining", "0"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(postal_address())
+                    .insert_header("X-RateLimit-Remaining", "90"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("main")
+            .base_url(&server.uri())
+            .rate_limit(1.0, 1)
+            .build()
+            .unwrap();
+        let busy = client.with_api_key("busy").unwrap();
+        let quiet = client.with_api_key("quiet").unwrap();
+        let relaxed = client
+            .with_api_key_and_rate_limit("relaxed", 100.0, 5)
+            .unwrap();
+
+        // the busy tenant empties its bucket without slowing down the others.
+        busy.reverse_geocode(point()).await.unwrap();
+        let start = Instant::now();
+        quiet.reverse_geocode(point()).await.unwrap();
+        for _ in 0..3 {
+            relaxed.reverse_geocode(point()).await.unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        assert_eq!(busy.last_quota().unwrap().remaining, Some(0));
+        assert_eq!(quiet.last_quota().unwrap().remaining, Some(90));
+        assert_eq!(client.last_quota(), None);
+    }
+
+    #[test]
+    fn circuit_breaker_is_opt_in() {
+        assert_eq!(Client::new("key").circuit_state(), None);
+    }
+
+    #[tokio::test]
+    async fn no_retry_without_policy() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let err = client.reverse_geocode(point()).await.unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Server);
+    }
+
+    #[tokio::test]
+    async fn validate_points_before_sending() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let invalid = Point {
+            latitude: 900.0,
+            longitude: f64::NAN,
+        };
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .validate_points(true)
+            .build()
+            .unwrap();
+        let err = client.reverse_geocode(invalid).await.unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidCoordinate(_)));
+        assert_eq!(err.kind(), ErrorKind::InvalidRequest);
+        assert_eq!(
+            err.to_string(),
+            "invalid point: latitude 900 is out of range, expected -90 to 90"
+        );
+        assert_eq!(
+            client.stats().endpoint(Endpoint::ReverseGeocode).requests,
+            0
+        );
+
+        // without validation the point goes out as is.
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        client.reverse_geocode(invalid).await.unwrap();
+    }
+
+    /// every form of coordinates the client takes, compiled as part of the tests so the
+    /// conversions keep working.
+    #[tokio::test]
+    async fn points_from_other_types() {
+        use crate::mock::MockNeshan;
+        use crate::{MapMatchOptions, RouteOptions, Type};
+
+        let neshan = MockNeshan::start().await;
+        let client = neshan.client();
+        let tehran = Point::new_unchecked(35.7, 51.4);
+        let karaj = Point::new_unchecked(35.8, 51.0);
+        let options = RouteOptions::new();
+
+        client
+            .route_with(Type::Car, tehran, karaj, &options)
+            .await
+            .unwrap();
+        client
+            .route_with(Type::Car, &tehran, &karaj, &options)
+            .await
+            .unwrap();
+        client
+            .route_with(Type::Car, (35.7, 51.4), (35.8, 51.0), &options)
+            .await
+            .unwrap();
+        client.reverse_geocode(&tehran).await.unwrap();
+        client.reverse_geocode((35.7, 51.4)).await.unwrap();
+
+        let pairs = vec![(35.7, 51.4), (35.8, 51.0)];
+        client
+            .distance_matrix(Type::Car, &[tehran, karaj], [karaj])
+            .await
+            .unwrap();
+        client
+            .distance_matrix(Type::Car, &pairs, pairs.iter().map(|pair| (pair.0, pair.1)))
+            .await
+            .unwrap();
+        client
+            .distance_matrix(Type::Car, pairs.clone(), vec![tehran])
+            .await
+            .unwrap();
+        client.map_match(&vec![tehran, karaj]).await.unwrap();
+        client
+            .map_match_with(pairs, &MapMatchOptions::default())
+            .await
+            .unwrap();
+
+        #[cfg(feature = "geo")]
+        {
+            let point = geo_types::Point::new(51.4, 35.7);
+            client.reverse_geocode(point).await.unwrap();
+            client
+                .distance_matrix(
+                    Type::Car,
+                    vec![point],
+                    [geo_types::coord! { x: 51.0, y: 35.8 }],
+                )
+                .await
+                .unwrap();
+        }
+
+        for request in neshan.requests_to(Endpoint::Route).await {
+            assert_eq!(request.query("origin"), Some("35.700000,51.400000"));
+            assert_eq!(request.query("destination"), Some("35.800000,51.000000"));
+        }
+        let matrices = neshan.requests_to(Endpoint::DistanceMatrix).await;
+        assert_eq!(
+            matrices[1].query("origins"),
+            Some("35.700000,51.400000|35.800000,51.000000")
+        );
+        assert_eq!(
+            matrices[1].query("destinations"),
+            matrices[1].query("origins")
+        );
+    }
+
+    #[tokio::test]
+    async fn route_direction_versions() {
+        use crate::mock::MockNeshan;
+        use crate::{DirectionVersion, RouteOptions, Type};
+
+        let neshan = MockNeshan::start().await;
+        let client = neshan.client();
+        let (tehran, karaj) = ((35.7, 51.4), (35.8, 51.0));
+        let options = RouteOptions::new().avoid_odd_even_zone(true);
+
+        let v3 = client
+            .route_with(Type::Car, tehran, karaj, &options)
+            .await
+            .unwrap();
+        assert_eq!(v3.routes.len(), 2);
+        let v4 = client
+            .route_with(
+                Type::Car,
+                tehran,
+                karaj,
+                &options.clone().version(DirectionVersion::V4),
+            )
+            .await
+            .unwrap();
+        assert_eq!(v4.routes[0].legs[0].steps[1].exit, Some(2));
+
+        let v3 = neshan.requests_to(Endpoint::Route).await;
+        assert_eq!(v3.len(), 1);
+        assert_eq!(v3[0].query("avoid_odd_even_zone"), Some("true"));
+        let v4 = neshan.requests_to(Endpoint::RouteV4).await;
+        assert_eq!(v4.len(), 1);
+        assert_eq!(v4[0].path, "/v4/direction");
+        assert_eq!(v4[0].query("avoidOddEvenZone"), Some("true"));
+        assert_eq!(v4[0].query("avoid_odd_even_zone"), None);
+        assert_eq!(client.stats().endpoint(Endpoint::RouteV4).requests, 1);
+    }
+
+    #[tokio::test]
+    async fn route_with_and_without_traffic() {
+        use crate::mock::{MockNeshan, MockResponse};
+        use crate::{DirectionVersion, RouteOptions, Type};
+
+        let neshan = MockNeshan::start().await;
+        let free_flow = MockResponse::json(serde_json::json!({
+            "routes": [{
+                "legs": [{
+                    "summary": "بزرگراه تهران کرج",
+                    "distance": { "value": 40512.0, "text": "۴۰.۵ کیلومتر" },
+                    "duration": { "value": 1980.0, "text": "۳۳ دقیقه" },
+                }],
+            }],
+        }));
+        neshan.respond(Endpoint::RouteNoTraffic, free_flow).await;
+        let client = neshan.client();
+        let (tehran, karaj) = ((35.7, 51.4), (35.8, 51.0));
+
+        let live = client
+            .route_with(Type::Car, tehran, karaj, &RouteOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(live.routes[0].legs[0].duration.value, 2874.0);
+        // the version of the options doesn't move the call off the no traffic endpoint.
+        for version in [DirectionVersion::V3, DirectionVersion::V4] {
+            let options = RouteOptions::new().version(version);
+            let routes = client
+                .route_no_traffic(Type::Car, tehran, karaj, &options)
+                .await
+                .unwrap();
+            assert_eq!(routes.routes[0].legs[0].duration.value, 1980.0);
+        }
+
+        let paths: Vec<String> = neshan
+            .requests()
+            .await
+            .into_iter()
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/v3/direction",
+                "/v4/direction/no-traffic",
+                "/v4/direction/no-traffic"
+            ]
+        );
+        let free_flow = neshan.requests_to(Endpoint::RouteNoTraffic).await;
+        assert_eq!(free_flow[0].query("avoidTrafficZone"), Some("false"));
+        assert_eq!(free_flow[0].query("origin"), Some("35.700000,51.400000"));
+    }
+
+    #[tokio::test]
+    async fn route_through_waypoints() {
+        use crate::{RouteOptions, Type};
+        use wiremock::matchers::{query_param, query_param_is_missing};
+
+        let leg = |summary: &str| {
+            serde_json::json!({
+                "summary": summary,
+                "distance": { "value": 1000.0, "text": "۱ کیلومتر" },
+                "duration": { "value": 120.0, "text": "۲ دقیقه" },
+            })
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param(
+                "waypoints",
+                "35.710000,51.380000|35.720000,51.300000",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": [{ "legs": [leg("a"), leg("b"), leg("c")] }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param_is_missing("waypoints"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": [{ "legs": [leg("a")] }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let (pickup, destination) = ((35.7, 51.4), (35.8, 51.0));
+
+        let waypoints = [
+            Point::new_unchecked(35.71, 51.38),
+            Point::new_unchecked(35.72, 51.3),
+        ];
+        let options = RouteOptions::new().waypoints(&waypoints);
+        let routes = client
+            .route_with(Type::Car, pickup, destination, &options)
+            .await
+            .unwrap();
+        assert_eq!(routes.routes[0].legs.len(), waypoints.len() + 1);
+
+        // no waypoints is the call without them.
+        let options = RouteOptions::new().waypoints(&[]);
+        assert_eq!(options, RouteOptions::new());
+        let routes = client
+            .route_with(Type::Car, pickup, destination, &options)
+            .await
+            .unwrap();
+        assert_eq!(routes.routes[0].legs.len(), 1);
+
+        let too_many = vec![waypoints[0]; RouteOptions::MAX_WAYPOINTS + 1];
+        let err = client
+            .route_with(
+                Type::Car,
+                pickup,
+                destination,
+                &RouteOptions::new().waypoints(&too_many),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid request: 11 waypoints are too many, expected at most 10"
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn route_with_bearing() {
+        use crate::{RouteOptions, Type};
+        use wiremock::matchers::{query_param, query_param_is_missing};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("bearing", "270"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": [],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param_is_missing("bearing"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": [],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let (origin, destination) = ((35.7, 51.4), (35.8, 51.0));
+
+        for options in [RouteOptions::new().bearing(270), RouteOptions::new()] {
+            client
+                .route_with(Type::Car, origin, destination, &options)
+                .await
+                .unwrap();
+        }
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[1].url.query(),
+            Some(
+                "type=car&origin=35.700000%2C51.400000&destination=35.800000%2C51.000000\
+                 &avoid_traffic_zone=false&avoid_odd_even_zone=false&alternative=false"
+            )
+        );
+
+        assert!(RouteOptions::new().bearing(359).check().is_ok());
+        for bearing in [360, 720, u16::MAX] {
+            let err = client
+                .route_with(
+                    Type::Car,
+                    origin,
+                    destination,
+                    &RouteOptions::new().bearing(bearing),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "invalid request: bearing {} is out of range, expected 0 to 359 degrees",
+                    bearing
+                )
+            );
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,472 @@
+//! settings of a client from the environment or a config file, see `ClientConfig`.
+
+use crate::client::{Client, ClientBuilder};
+use crate::error::NeshanError;
+use crate::retry::RetryPolicy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use url::Url;
+
+/// settings of a client as plain data, e.g. from the environment with `from_env` or from a
+/// config file with serde. durations are written as text such as `"500ms"`, `"30s"`, `"2m"`
+/// or `"1h"`, a bare number is in seconds.
+///
+/// settings that are left out keep the defaults of `ClientBuilder`. the builder returned by
+/// `ClientConfig::builder` starts from the config, so calls on it override the config, e.g.
+/// an explicit `retry` replaces the policy built from `max_attempts` and `retry_backoff`.
+///
+/// `Debug` writes the api key as `[redacted]`, so a logged config doesn't leak it.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub api_key: String,
+    /// see `ClientBuilder::base_url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// total time of each call, see `ClientBuilder::deadline`.
+    #[serde(with = "duration_text", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+    /// attempts of each call including the first one, retrying as `RetryPolicy::new` does.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// delay before the first retry, see `RetryPolicy::initial_backoff`.
+    #[serde(with = "duration_text", skip_serializing_if = "Option::is_none")]
+    pub retry_backoff: Option<Duration>,
+    /// requests per second, see `ClientBuilder::rate_limit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<f64>,
+    /// burst of the rate limit, one request when left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_burst: Option<u32>,
+}
+
+impl fmt::Debug for ClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientConfig")
+            .field("api_key", &format_args!("[redacted]"))
+            .field("base_url", &self.base_url)
+            .field("timeout", &self.timeout)
+            .field("max_attempts", &self.max_attempts)
+            .field("retry_backoff", &self.retry_backoff)
+            .field("rate_limit", &self.rate_limit)
+            .field("rate_limit_burst", &self.rate_limit_burst)
+            .finish()
+    }
+}
+
+impl ClientConfig {
+    /// read the config from the environment:
+    ///
+    /// | variable                  | setting            | example   |
+    /// | ------------------------- | ------------------ | --------- |
+    /// | `NESHAN_API_KEY`          | `api_key`          | required  |
+    /// | `NESHAN_BASE_URL`         | `base_url`         | `http://localhost:8080` |
+    /// | `NESHAN_TIMEOUT`          | `timeout`          | `10s`     |
+    /// | `NESHAN_MAX_ATTEMPTS`     | `max_attempts`     | `3`       |
+    /// | `NESHAN_RETRY_BACKOFF`    | `retry_backoff`    | `200ms`   |
+    /// | `NESHAN_RATE_LIMIT`       | `rate_limit`       | `5`       |
+    /// | `NESHAN_RATE_LIMIT_BURST` | `rate_limit_burst` | `10`      |
+    ///
+    /// empty variables count as unset. invalid values fail with `NeshanError::Config`
+    /// naming the variable.
+    pub fn from_env() -> Result<ClientConfig, NeshanError> {
+        ClientConfig::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<ClientConfig, NeshanError> {
+        let var = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+        let invalid = |name: &str, value: &str, expected: &str| {
+            NeshanError::Config(format!("{} is {:?}, expected {}", name, value, expected))
+        };
+        let parse = |name: &str| var(name).map(|value| value.trim().to_string());
+        let duration = |name: &str| -> Result<Option<Duration>, NeshanError> {
+            parse(name)
+                .map(|value| {
+                    parse_duration(&value).map_err(|expected| invalid(name, &value, &expected))
+                })
+                .transpose()
+        };
+
+        let api_key = var("NESHAN_API_KEY")
+            .ok_or_else(|| NeshanError::Config("NESHAN_API_KEY is not set".to_string()))?;
+        let base_url = parse("NESHAN_BASE_URL");
+        if let Some(base_url) = &base_url {
+            Url::parse(base_url)
+                .map_err(|err| invalid("NESHAN_BASE_URL", base_url, &format!("a url, {}", err)))?;
+        }
+        let max_attempts = parse("NESHAN_MAX_ATTEMPTS")
+            .map(|value| match value.parse() {
+                Ok(attempts) if attempts > 0 => Ok(attempts),
+                _ => Err(invalid(
+                    "NESHAN_MAX_ATTEMPTS",
+                    &value,
+                    "a positive whole number",
+                )),
+            })
+            .transpose()?;
+        let rate_limit = parse("NESHAN_RATE_LIMIT")
+            .map(|value| match value.parse::<f64>() {
+                Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+                _ => Err(invalid(
+                    "NESHAN_RATE_LIMIT",
+                    &value,
+                    "a positive number of requests per second",
+                )),
+            })
+            .transpose()?;
+        let rate_limit_burst = parse("NESHAN_RATE_LIMIT_BURST")
+            .map(|value| match value.parse() {
+                Ok(burst) if burst > 0 => Ok(burst),
+                _ => Err(invalid(
+                    "NESHAN_RATE_LIMIT_BURST",
+                    &value,
+                    "a positive whole number",
+                )),
+            })
+            .transpose()?;
+
+        Ok(ClientConfig {
+            api_key,
+            base_url,
+            timeout: duration("NESHAN_TIMEOUT")?,
+            max_attempts,
+            retry_backoff: duration("NESHAN_RETRY_BACKOFF")?,
+            rate_limit,
+            rate_limit_burst,
+        })
+    }
+
+    /// a builder with the config applied, for settings the config doesn't cover or for
+    /// overriding some of them.
+    pub fn builder(&self) -> ClientBuilder {
+        let mut builder = Client::builder(&self.api_key);
+        if let Some(base_url) = &self.base_url {
+            builder = builder.base_url(base_url);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.deadline(timeout);
+        }
+        if self.max_attempts.is_some() || self.retry_backoff.is_some() {
+            let mut policy = RetryPolicy::new();
+            if let Some(attempts) = self.max_attempts {
+                policy = policy.max_attempts(attempts);
+            }
+            if let Some(backoff) = self.retry_backoff {
+                policy = policy.initial_backoff(backoff);
+            }
+            builder = builder.retry(policy);
+        }
+        if let Some(rate) = self.rate_limit {
+            builder = builder.rate_limit(rate, self.rate_limit_burst.unwrap_or(1));
+        }
+
+        builder
+    }
+}
+
+impl Client {
+    /// create a client from the config, see `ClientConfig::from_env`.
+    pub fn from_config(config: &ClientConfig) -> Result<Client, NeshanError> {
+        config.builder().build()
+    }
+}
+
+/// duration from text such as `500ms`, `30s`, `2m` or `1h`, seconds without a unit. the
+/// error tells what was expected.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+
+    let scale = match unit.trim() {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => f64::NAN,
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|number| Duration::try_from_secs_f64(number * scale).ok())
+        .ok_or_else(|| "a duration such as 500ms, 30s, 2m or 1h".to_string())
+}
+
+/// `Option<Duration>` as text, see `parse_duration`.
+mod duration_text {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(super) fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) if duration.subsec_millis() == 0 && duration.subsec_nanos() == 0 => {
+                serializer.serialize_str(&format!("{}s", duration.as_secs()))
+            }
+            Some(duration) => serializer.serialize_str(&format!("{}ms", duration.as_millis())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Text {
+            Seconds(f64),
+            Text(String),
+        }
+
+        let text = match Option::<Text>::deserialize(deserializer)? {
+            None => return Ok(None),
+            Some(Text::Seconds(seconds)) => seconds.to_string(),
+            Some(Text::Text(text)) => text,
+        };
+
+        super::parse_duration(&text)
+            .map(Some)
+            .map_err(|expected| serde::de::Error::custom(format!("expected {}", expected)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_duration, ClientConfig};
+    use crate::client::Client;
+    use crate::error::{ErrorKind, NeshanError};
+    use crate::Point;
+    use std::collections::HashMap;
+    use std::sync::{Mutex, MutexGuard};
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// serializes the tests that touch the environment and restores it afterwards.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    struct Env {
+        saved: Vec<(&'static str, Option<String>)>,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Env {
+        const VARS: [&'static str; 7] = [
+            "NESHAN_API_KEY",
+            "NESHAN_BASE_URL",
+            "NESHAN_TIMEOUT",
+            "NESHAN_MAX_ATTEMPTS",
+            "NESHAN_RETRY_BACKOFF",
+            "NESHAN_RATE_LIMIT",
+            "NESHAN_RATE_LIMIT_BURST",
+        ];
+
+        /// set exactly the given neshan variables, unsetting the others.
+        fn set(vars: &[(&str, &str)]) -> Env {
+            let lock = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let saved = Env::VARS
+                .iter()
+                .map(|name| (*name, std::env::var(name).ok()))
+                .collect();
+            for name in Env::VARS {
+                std::env::remove_var(name);
+            }
+            for (name, value) in vars {
+                std::env::set_var(name, value);
+            }
+
+            Env { saved, _lock: lock }
+        }
+    }
+
+    impl Drop for Env {
+        fn drop(&mut self) {
+            for (name, value) in &self.saved {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
+    }
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<ClientConfig, NeshanError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        ClientConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    fn point() -> Point {
+        Point::new_unchecked(35.7, 51.4)
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 1.5 "), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        for invalid in ["", "s", "ten seconds", "5 days", "-1s", "1e400"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn debug_redacts_api_key() {
+        let config = ClientConfig {
+            api_key: "secret-key".to_string(),
+            max_attempts: Some(3),
+            ..ClientConfig::default()
+        };
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("secret-key"), "{}", debug);
+        assert!(debug.contains("api_key: [redacted]"), "{}", debug);
+        assert!(debug.contains("max_attempts: Some(3)"), "{}", debug);
+    }
+
+    #[test]
+    fn invalid_variables() {
+        let error = |vars: &[(&str, &str)]| match from_vars(vars) {
+            Err(NeshanError::Config(message)) => message,
+            other => panic!("expected a config error, got {:?}", other),
+        };
+
+        assert_eq!(error(&[]), "NESHAN_API_KEY is not set");
+        assert_eq!(
+            error(&[("NESHAN_API_KEY", "  ")]),
+            "NESHAN_API_KEY is not set"
+        );
+        assert_eq!(
+            error(&[("NESHAN_API_KEY", "key"), ("NESHAN_TIMEOUT", "soon")]),
+            "NESHAN_TIMEOUT is \"soon\", expected a duration such as 500ms, 30s, 2m or 1h"
+        );
+        assert!(
+            error(&[("NESHAN_API_KEY", "key"), ("NESHAN_BASE_URL", "neshan")])
+                .starts_with("NESHAN_BASE_URL is \"neshan\", expected a url")
+        );
+        assert!(
+            error(&[("NESHAN_API_KEY", "key"), ("NESHAN_MAX_ATTEMPTS", "0")])
+                .starts_with("NESHAN_MAX_ATTEMPTS")
+        );
+        assert!(
+            error(&[("NESHAN_API_KEY", "key"), ("NESHAN_RATE_LIMIT", "fast")])
+                .starts_with("NESHAN_RATE_LIMIT")
+        );
+
+        // empty variables count as unset.
+        let config = from_vars(&[("NESHAN_API_KEY", "key"), ("NESHAN_TIMEOUT", "")]).unwrap();
+        assert_eq!(config.timeout, None);
+    }
+
+    #[test]
+    fn serde() {
+        let config: ClientConfig = serde_json::from_str(
+            r#"{"api_key": "key", "timeout": "1500ms", "retry_backoff": 2, "rate_limit": 5}"#,
+        )
+        .unwrap();
+        assert_eq!(config.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(config.retry_backoff, Some(Duration::from_secs(2)));
+        assert_eq!(config.max_attempts, None);
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "api_key": "key",
+                "timeout": "1500ms",
+                "retry_backoff": "2s",
+                "rate_limit": 5.0
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<ClientConfig>(json).unwrap(),
+            config
+        );
+
+        assert!(serde_json::from_str::<ClientConfig>(r#"{"timeout": "soon"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn client_from_env() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(470))
+            .mount(&server)
+            .await;
+
+        let config = {
+            let _env = Env::set(&[
+                ("NESHAN_API_KEY", "key"),
+                ("NESHAN_BASE_URL", &server.uri()),
+                ("NESHAN_MAX_ATTEMPTS", "2"),
+                ("NESHAN_RETRY_BACKOFF", "10ms"),
+                ("NESHAN_TIMEOUT", "5s"),
+            ]);
+            ClientConfig::from_env().unwrap()
+        };
+        assert_eq!(config.timeout, Some(Duration::from_secs(5)));
+
+        // the 503 is retried once, then the 470 fails for good.
+        let client = Client::from_config(&config).unwrap();
+        let err = client.reverse_geocode(point()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidRequest);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn builder_calls_override_the_config() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(470))
+            .expect(6)
+            .mount(&server)
+            .await;
+
+        let config = ClientConfig {
+            api_key: "key".to_string(),
+            base_url: Some("http://127.0.0.1:9".to_string()),
+            rate_limit: Some(1000.0),
+            rate_limit_burst: Some(100),
+            ..ClientConfig::default()
+        };
+
+        // the config's rate limit stays, its base url is replaced.
+        let client = config.builder().base_url(&server.uri()).build().unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(client.reverse_geocode(point()).await.is_err());
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // an explicit rate limit replaces the config's one.
+        let client = config
+            .builder()
+            .base_url(&server.uri())
+            .rate_limit(10.0, 1)
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(client.reverse_geocode(point()).await.is_err());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+}
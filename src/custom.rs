@@ -0,0 +1,306 @@
+//! calls to endpoints the crate doesn't model yet, see `Client::get_json`.
+
+use crate::client::Client;
+use crate::endpoint::{labeled_key, Endpoint};
+use crate::error::NeshanError;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use url::Url;
+
+/// query parameters of a custom call. `query` must serialize to a map whose values are
+/// scalars or lists of scalars, or to a list of name and value pairs. nulls are left out.
+fn query_pairs(query: &impl Serialize) -> Result<Vec<(String, String)>, NeshanError> {
+    let invalid = |message: String| NeshanError::InvalidRequest(message);
+    let scalar = |name: &str, value: Value| match value {
+        Value::Null => Ok(None),
+        Value::String(value) => Ok(Some(value)),
+        Value::Bool(_) | Value::Number(_) => Ok(Some(value.to_string())),
+        value => Err(invalid(format!(
+            "query parameter {} must be a scalar, got {}",
+            name, value
+        ))),
+    };
+
+    let mut pairs = Vec::new();
+    match serde_json::to_value(query).map_err(|err| invalid(err.to_string()))? {
+        Value::Null => {}
+        Value::Object(map) => {
+            for (name, value) in map {
+                let values = match value {
+                    Value::Array(values) => values,
+                    value => vec![value],
+                };
+                for value in values {
+                    if let Some(value) = scalar(&name, value)? {
+                        pairs.push((name.clone(), value));
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::Array(pair) if pair.len() == 2 => {
+                        let mut pair = pair.into_iter();
+                        let name = match pair.next() {
+                            Some(Value::String(name)) => name,
+                            _ => return Err(invalid("query names must be strings".to_string())),
+                        };
+                        if let Some(value) = scalar(&name, pair.next().unwrap_or_default())? {
+                            pairs.push((name, value));
+                        }
+                    }
+                    item => {
+                        return Err(invalid(format!(
+                            "query pairs must be a name and a value, got {}",
+                            item
+                        )))
+                    }
+                }
+            }
+        }
+        query => {
+            return Err(invalid(format!(
+                "query must be a map or a list of pairs, got {}",
+                query
+            )))
+        }
+    }
+
+    Ok(pairs)
+}
+
+/// url of `path` under the base url, rejecting paths that would leave it.
+fn custom_url(base_url: &str, path: &str, query: &[(String, String)]) -> Result<Url, NeshanError> {
+    let escapes = !path.starts_with('/')
+        || path.starts_with("//")
+        || path.contains(['?', '#', '\\'])
+        || path.split('/').any(|segment| {
+            let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+            segment == "." || segment == ".."
+        });
+    if escapes {
+        return Err(NeshanError::InvalidRequest(format!(
+            "path {:?} must be absolute and stay under the base url, e.g. /v1/search",
+            path
+        )));
+    }
+
+    let mut url = Url::parse(&format!("{}{}", base_url.trim_end_matches('/'), path))
+        .map_err(|err| NeshanError::InvalidRequest(format!("invalid path {:?}: {}", path, err)))?;
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+
+    Ok(url)
+}
+
+impl Client {
+    /// call an endpoint the crate doesn't model yet, decoding its json response into `T`.
+    /// the call goes through the same middlewares, retries, rate limiting and error handling
+    /// as the built-in ones, and counts as `Endpoint::Custom` in stats and observers.
+    ///
+    /// `path` is appended to the base url, e.g. `/v1/search`, and may not leave it. `query`
+    /// is a map of scalars or lists of scalars, e.g. a struct, or a list of name and value
+    /// pairs such as `&[("term", "cafe")]`.
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+    ) -> Result<T, NeshanError> {
+        self.custom(path, query, |body| Ok(serde_json::from_slice(body)?))
+            .await
+    }
+
+    /// same as `get_json`, returning the response body as is.
+    pub async fn get_bytes(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+    ) -> Result<Bytes, NeshanError> {
+        self.custom(path, query, |body| Ok(body.clone())).await
+    }
+
+    async fn custom<T>(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+        decode: impl Fn(&Bytes) -> Result<T, NeshanError>,
+    ) -> Result<T, NeshanError> {
+        let pairs = query_pairs(query)?;
+        let url = custom_url(self.base_url(), path, &pairs)?;
+
+        let borrowed: Vec<(&str, String)> = pairs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        let key = labeled_key(&format!("{}{}", Endpoint::Custom, path), &borrowed);
+        let call = self.fetch(Endpoint::Custom, url, key, decode);
+
+        crate::trace::instrument(Endpoint::Custom, &[], call)
+            .await
+            .map(|(value, _)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{custom_url, query_pairs};
+    use crate::client::Client;
+    use crate::endpoint::Endpoint;
+    use crate::error::{ErrorKind, NeshanError};
+    use serde::{Deserialize, Serialize};
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        count: u32,
+        items: Vec<Item>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        title: String,
+    }
+
+    #[derive(Serialize)]
+    struct SearchQuery<'a> {
+        term: &'a str,
+        lat: f64,
+        lng: f64,
+        category: Option<&'a str>,
+    }
+
+    #[tokio::test]
+    async fn user_defined_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/search"))
+            .and(query_param("term", "کافه"))
+            .and(query_param("lat", "35.7"))
+            .and(header("api-key", "key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": 1,
+                "items": [{"title": "کافه لمیز", "type": "cafe"}]
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/broken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "status": "ERROR",
+                "code": 480,
+                "message": "Key not found"
+            })))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let query = SearchQuery {
+            term: "کافه",
+            lat: 35.7,
+            lng: 51.4,
+            category: None,
+        };
+        let search: Search = client.get_json("/v1/search", &query).await.unwrap();
+        assert_eq!(
+            search,
+            Search {
+                count: 1,
+                items: vec![Item {
+                    title: "کافه لمیز".to_string()
+                }],
+            }
+        );
+
+        let body = client
+            .get_bytes("/v1/search", &[("term", "کافه"), ("lat", "35.7")])
+            .await
+            .unwrap();
+        assert!(body.starts_with(b"{\"count\":1"));
+
+        // error bodies sent with a success status fail as they do for the built-in calls.
+        let err = client
+            .get_json::<Search>("/v1/broken", &())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Auth);
+
+        let custom = client.stats().endpoint(Endpoint::Custom).clone();
+        assert_eq!(custom.requests, 3);
+        assert_eq!(custom.successes, 2);
+    }
+
+    #[test]
+    fn paths_stay_under_the_base_url() {
+        let url = custom_url("https://api.neshan.org/", "/v1/search", &[]).unwrap();
+        assert_eq!(url.as_str(), "https://api.neshan.org/v1/search");
+
+        for path in [
+            "v1/search",
+            "//evil.example/v1",
+            "/v1/../../admin",
+            "/v1/%2E%2e/admin",
+            "/v1/./search",
+            "/v1/search?key=other",
+            "/v1\\search",
+        ] {
+            assert!(
+                matches!(
+                    custom_url("https://api.neshan.org", path, &[]),
+                    Err(NeshanError::InvalidRequest(_))
+                ),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn query_forms() {
+        let pairs = query_pairs(&serde_json::json!({
+            "term": "cafe",
+            "limit": 5,
+            "open": true,
+            "skip": null,
+            "tag": ["a", "b"]
+        }))
+        .unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("limit".to_string(), "5".to_string()),
+                ("open".to_string(), "true".to_string()),
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string()),
+                ("term".to_string(), "cafe".to_string()),
+            ]
+        );
+        assert_eq!(query_pairs(&()).unwrap(), Vec::new());
+        assert_eq!(
+            query_pairs(&[("b", 2), ("a", 1)]).unwrap(),
+            vec![
+                ("b".to_string(), "2".to_string()),
+                ("a".to_string(), "1".to_string()),
+            ]
+        );
+
+        for query in [
+            serde_json::json!({"nested": {"a": 1}}),
+            serde_json::json!("term"),
+            serde_json::json!([["a", 1, 2]]),
+        ] {
+            assert!(matches!(
+                query_pairs(&query),
+                Err(NeshanError::InvalidRequest(_))
+            ));
+        }
+    }
+}
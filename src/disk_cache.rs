@@ -0,0 +1,228 @@
+//! persistent response cache, compiled only with the `disk-cache` feature.
+//!
+//! every response lives in its own json file named after a hash of its request key. files
+//! are small and read or written inline, their modification time serves as the last use for
+//! evicting the least recently used entries. unreadable or corrupt files count as misses.
+
+use crate::cache::CachedEntry;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    /// milliseconds since the unix epoch, so sub-second ttls survive.
+    expires_ms: u64,
+    #[serde(flatten)]
+    entry: CachedEntry,
+}
+
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    capacity: usize,
+}
+
+/// fnv-1a, stable across builds unlike the std hasher.
+fn hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// whole milliseconds of `duration`, rounded up so a short ttl doesn't expire at once.
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos().div_ceil(1_000_000)).unwrap_or(u64::MAX)
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| u64::try_from(now.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
+impl DiskCache {
+    pub(crate) fn open(dir: PathBuf, capacity: usize) -> std::io::Result<DiskCache> {
+        fs::create_dir_all(&dir)?;
+
+        Ok(DiskCache { dir, capacity })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", hash(key)))
+    }
+
+    /// cached entry with its remaining time to live.
+    pub(crate) fn get(&self, key: &str) -> Option<(CachedEntry, Duration)> {
+        let path = self.path(key);
+        let entry = fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<Entry>(&content).ok());
+
+        let entry = match entry {
+            // a colliding key belongs to another request, leave its file alone.
+            Some(entry) if entry.key != key => return None,
+            Some(entry) if entry.expires_ms > unix_now_ms() => entry,
+            _ => {
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+        };
+
+        // refresh the modification time so eviction sees the entry as recently used.
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        let ttl = Duration::from_millis(entry.expires_ms.saturating_sub(unix_now_ms()));
+        Some((entry.entry, ttl))
+    }
+
+    /// store the entry, failures are ignored as the cache is best effort. bodies that aren't
+    /// utf-8 fail to serialize and are skipped.
+    pub(crate) fn put(&self, key: &str, entry: &CachedEntry, ttl: Duration) {
+        let entry = Entry {
+            key: key.to_string(),
+            expires_ms: unix_now_ms().saturating_add(millis(ttl)),
+            entry: entry.clone(),
+        };
+        let content = match serde_json::to_vec(&entry) {
+            Ok(content) => content,
+            Err(_) => return,
+        };
+
+        // write then rename, so a crash never leaves a half written entry behind.
+        let path = self.path(key);
+        let tmp = path.with_extension("tmp");
+        if fs::write(&tmp, content).is_err() || fs::rename(&tmp, &path).is_err() {
+            let _ = fs::remove_file(&tmp);
+            return;
+        }
+
+        self.evict();
+    }
+
+    fn evict(&self) {
+        let mut files: Vec<_> = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+                .map(|entry| {
+                    let used = entry
+                        .metadata()
+                        .and_then(|metadata| metadata.modified())
+                        .unwrap_or(UNIX_EPOCH);
+                    (used, entry.path())
+                })
+                .collect(),
+            Err(_) => return,
+        };
+
+        if files.len() <= self.capacity {
+            return;
+        }
+
+        files.sort();
+        let excess = files.len() - self.capacity;
+        for (_, path) in files.into_iter().take(excess) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::DiskCache;
+    use crate::cache::CachedEntry;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    /// fresh directory under the system temp dir, removed on drop.
+    pub(crate) struct TempDir(pub(crate) PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new() -> TempDir {
+            static COUNTER: AtomicU32 = AtomicU32::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "neshan-rs-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn corrupt_entry_is_a_miss() {
+        let dir = TempDir::new();
+        let cache = DiskCache::open(dir.0.clone(), 10).unwrap();
+
+        cache.put("a", &CachedEntry::new("{}"), Duration::from_secs(60));
+        std::fs::write(cache.path("a"), "{not json").unwrap();
+
+        assert!(cache.get("a").is_none());
+        assert!(!cache.path("a").exists());
+    }
+
+    #[test]
+    fn expired_entry_is_a_miss() {
+        let dir = TempDir::new();
+        let cache = DiskCache::open(dir.0.clone(), 10).unwrap();
+
+        cache.put("a", &CachedEntry::new("{}"), Duration::ZERO);
+
+        assert!(cache.get("a").is_none());
+    }
+
+    #[test]
+    fn sub_second_ttl() {
+        let dir = TempDir::new();
+        let cache = DiskCache::open(dir.0.clone(), 10).unwrap();
+
+        cache.put("a", &CachedEntry::new("{}"), Duration::from_millis(1500));
+        cache.put("b", &CachedEntry::new("{}"), Duration::from_micros(1));
+        cache.put("c", &CachedEntry::new("{}"), Duration::from_millis(200));
+        cache.put("d", &CachedEntry::new("{}"), Duration::MAX);
+
+        let (_, ttl) = cache.get("a").unwrap();
+        assert!(ttl > Duration::from_secs(1), "{:?}", ttl);
+        assert!(cache.get("c").is_some());
+        assert!(cache.get("d").is_some());
+
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_none());
+        assert!(cache.get("a").is_some());
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let dir = TempDir::new();
+        let cache = DiskCache::open(dir.0.clone(), 2).unwrap();
+        let ttl = Duration::from_secs(60);
+
+        cache.put("a", &CachedEntry::new("a"), ttl);
+        std::thread::sleep(Duration::from_millis(20));
+        cache.put("b", &CachedEntry::new("b"), ttl);
+        std::thread::sleep(Duration::from_millis(20));
+        cache.get("a");
+        std::thread::sleep(Duration::from_millis(20));
+        cache.put("c", &CachedEntry::new("c"), ttl);
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+}
@@ -0,0 +1,1789 @@
+use crate::batch::BatchOptions;
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::point::collect_points;
+use crate::{Distance, Duration, Point, Type};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// distances and durations from each origin to each destination of the distance matrix api.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DistanceMatrix {
+    #[serde(default)]
+    pub origin_addresses: Vec<String>,
+    #[serde(default)]
+    pub destination_addresses: Vec<String>,
+    /// a row per origin, with an element per destination.
+    pub rows: Vec<MatrixRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatrixRow {
+    pub elements: Vec<MatrixElement>,
+}
+
+/// trip from one origin to one destination.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatrixElement {
+    pub status: ElementStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<Distance>,
+}
+
+/// status of a `MatrixElement`, stored as the string neshan sent with serde.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
+pub enum ElementStatus {
+    /// `"Ok"`, neshan found a route.
+    Ok,
+    /// `"NOT_FOUND"`, there is no route between the points, e.g. to an island.
+    NoRoute,
+    /// `"ERROR"`, the request of the element failed, see `Client::distance_matrix_chunked`.
+    Failed,
+    /// any other status, verbatim.
+    Other(String),
+}
+
+impl ElementStatus {
+    /// the string of the status in the api, also used by `Display` and serde.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ElementStatus::Ok => "Ok",
+            ElementStatus::NoRoute => "NOT_FOUND",
+            ElementStatus::Failed => "ERROR",
+            ElementStatus::Other(status) => status,
+        }
+    }
+}
+
+impl std::fmt::Display for ElementStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// known statuses match case-insensitively, anything else becomes `ElementStatus::Other`.
+impl From<String> for ElementStatus {
+    fn from(status: String) -> ElementStatus {
+        match [
+            ElementStatus::Ok,
+            ElementStatus::NoRoute,
+            ElementStatus::Failed,
+        ]
+        .iter()
+        .find(|known| known.as_str().eq_ignore_ascii_case(&status))
+        {
+            Some(known) => known.clone(),
+            None => ElementStatus::Other(status),
+        }
+    }
+}
+
+impl From<ElementStatus> for String {
+    fn from(status: ElementStatus) -> String {
+        match status {
+            ElementStatus::Other(status) => status,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl MatrixElement {
+    fn error() -> MatrixElement {
+        MatrixElement {
+            status: ElementStatus::Failed,
+            duration: None,
+            distance: None,
+        }
+    }
+
+    /// whether neshan found a route, i.e. the status is ok and both values are present.
+    pub fn is_routable(&self) -> bool {
+        self.status == ElementStatus::Ok && self.duration.is_some() && self.distance.is_some()
+    }
+}
+
+impl DistanceMatrix {
+    /// the matrix of a distance matrix body, e.g. one stored for planning offline.
+    pub fn from_json(json: &str) -> Result<DistanceMatrix, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// the element from origin `origin` to destination `destination`, `None` when either index
+    /// is out of range or there is no route between them.
+    pub fn get(&self, origin: usize, destination: usize) -> Option<&MatrixElement> {
+        self.rows
+            .get(origin)?
+            .elements
+            .get(destination)
+            .filter(|element| element.is_routable())
+    }
+
+    /// status of the element from origin `origin` to destination `destination`, e.g. to tell
+    /// a missing route from a failed request. `None` when either index is out of range.
+    pub fn status(&self, origin: usize, destination: usize) -> Option<&ElementStatus> {
+        let element = self.rows.get(origin)?.elements.get(destination)?;
+        Some(&element.status)
+    }
+
+    /// rows with the index of their origin.
+    pub fn iter_rows(&self) -> impl Iterator<Item = (usize, &MatrixRow)> + '_ {
+        self.rows.iter().enumerate()
+    }
+
+    /// routable elements of the origin with the index of their destination.
+    pub fn destinations(
+        &self,
+        origin: usize,
+    ) -> impl Iterator<Item = (usize, &MatrixElement)> + '_ {
+        self.rows
+            .get(origin)
+            .into_iter()
+            .flat_map(|row| row.elements.iter().enumerate())
+            .filter(|(_, element)| element.is_routable())
+    }
+
+    /// routable elements of the column of the destination with the index of their origin.
+    pub fn origins(
+        &self,
+        destination: usize,
+    ) -> impl Iterator<Item = (usize, &MatrixElement)> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter_map(move |(origin, row)| Some((origin, row.elements.get(destination)?)))
+            .filter(|(_, element)| element.is_routable())
+    }
+
+    /// the destination the origin reaches first, skipping unroutable ones.
+    pub fn nearest_destination(&self, origin: usize) -> Option<(usize, &MatrixElement)> {
+        self.destinations(origin).min_by(|(_, a), (_, b)| {
+            let (a, b) = (a.duration.as_ref(), b.duration.as_ref());
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+
+    /// durations in seconds, a row per origin. unroutable elements are `None`.
+    pub fn to_duration_grid(&self) -> Vec<Vec<Option<f64>>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.elements
+                    .iter()
+                    .map(|element| {
+                        element
+                            .duration
+                            .as_ref()
+                            .filter(|_| element.is_routable())
+                            .map(|duration| duration.value)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// durations in seconds as csv, with the indices of the origins and destinations as
+    /// labels. unroutable elements are empty fields.
+    pub fn to_csv_durations(&self) -> String {
+        self.to_csv_durations_labeled(&[], &[])
+    }
+
+    /// same as `to_csv_durations` with the given labels, indices are used for missing ones.
+    pub fn to_csv_durations_labeled(&self, origins: &[&str], destinations: &[&str]) -> String {
+        self.csv_string(origins, destinations, MatrixUnit::Seconds)
+    }
+
+    /// distances in meters as csv, see `to_csv_durations`.
+    pub fn to_csv_distances(&self) -> String {
+        self.to_csv_distances_labeled(&[], &[])
+    }
+
+    /// same as `to_csv_distances` with the given labels, indices are used for missing ones.
+    pub fn to_csv_distances_labeled(&self, origins: &[&str], destinations: &[&str]) -> String {
+        self.csv_string(origins, destinations, MatrixUnit::Meters)
+    }
+
+    /// write the durations or the distances of `options` as csv, a header of destination
+    /// labels and then a row per origin. the labels are the addresses neshan sent unless
+    /// `CsvOptions::labels` gives others, and indices when neither has one.
+    pub fn to_csv<W: Write>(&self, mut writer: W, options: &CsvOptions) -> io::Result<()> {
+        fn labels<'a>(given: &'a [String], addresses: &'a [String]) -> Vec<&'a str> {
+            match given.is_empty() {
+                true => addresses.iter().map(String::as_str).collect(),
+                false => given.iter().map(String::as_str).collect(),
+            }
+        }
+
+        self.write_csv(
+            &mut writer,
+            &labels(&options.origins, &self.origin_addresses),
+            &labels(&options.destinations, &self.destination_addresses),
+            options.unit,
+            &options.placeholder,
+        )
+    }
+
+    fn csv_string(&self, origins: &[&str], destinations: &[&str], unit: MatrixUnit) -> String {
+        let mut csv = Vec::new();
+        self.write_csv(&mut csv, origins, destinations, unit, "")
+            .expect("writing to a vec doesn't fail");
+
+        String::from_utf8(csv).expect("the csv is built from strings")
+    }
+
+    fn write_csv(
+        &self,
+        writer: &mut impl Write,
+        origins: &[&str],
+        destinations: &[&str],
+        unit: MatrixUnit,
+        placeholder: &str,
+    ) -> io::Result<()> {
+        let label = |labels: &[&str], index: usize| match labels.get(index) {
+            Some(label) => csv_field(label),
+            None => index.to_string(),
+        };
+        let placeholder = csv_field(placeholder);
+        let columns = self
+            .rows
+            .iter()
+            .map(|row| row.elements.len())
+            .max()
+            .unwrap_or_default();
+
+        for destination in 0..columns {
+            write!(writer, ",{}", label(destinations, destination))?;
+        }
+        writeln!(writer)?;
+
+        for (origin, row) in self.iter_rows() {
+            write!(writer, "{}", label(origins, origin))?;
+            for destination in 0..columns {
+                let value = row
+                    .elements
+                    .get(destination)
+                    .filter(|element| element.is_routable())
+                    .and_then(|element| unit.value(element))
+                    .filter(|value| value.is_finite());
+                match value {
+                    Some(value) => write!(writer, ",{}", value)?,
+                    None => write!(writer, ",{}", placeholder)?,
+                }
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// what the cells of `DistanceMatrix::to_csv` hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixUnit {
+    /// durations in seconds, as neshan sends them.
+    Seconds,
+    Minutes,
+    /// distances in meters, as neshan sends them.
+    Meters,
+    Kilometers,
+}
+
+impl MatrixUnit {
+    fn value(&self, element: &MatrixElement) -> Option<f64> {
+        let duration = || element.duration.as_ref().map(|duration| duration.value);
+        let distance = || element.distance.as_ref().map(|distance| distance.value);
+
+        match self {
+            MatrixUnit::Seconds => duration(),
+            MatrixUnit::Minutes => duration().map(|seconds| seconds / 60.0),
+            MatrixUnit::Meters => distance(),
+            MatrixUnit::Kilometers => distance().map(|meters| meters / 1000.0),
+        }
+    }
+}
+
+/// how `DistanceMatrix::to_csv` writes a matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    unit: MatrixUnit,
+    placeholder: String,
+    origins: Vec<String>,
+    destinations: Vec<String>,
+}
+
+impl CsvOptions {
+    /// cells in `unit`, elements without a route are empty.
+    pub fn new(unit: MatrixUnit) -> CsvOptions {
+        CsvOptions {
+            unit,
+            placeholder: String::new(),
+            origins: Vec::new(),
+            destinations: Vec::new(),
+        }
+    }
+
+    /// the cell of an element without a route, e.g. `-`.
+    pub fn placeholder(mut self, placeholder: &str) -> CsvOptions {
+        self.placeholder = placeholder.to_string();
+        self
+    }
+
+    /// labels of the rows and the columns instead of the addresses, indices are used for
+    /// missing ones.
+    pub fn labels(mut self, origins: &[&str], destinations: &[&str]) -> CsvOptions {
+        self.origins = origins.iter().map(|label| label.to_string()).collect();
+        self.destinations = destinations.iter().map(|label| label.to_string()).collect();
+        self
+    }
+}
+
+/// quote the field when it has a separator, a quote or a line break in it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// how `Client::distance_matrix_chunked` splits its inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLimits {
+    origins: usize,
+    destinations: usize,
+    max_elements: Option<usize>,
+    symmetric: bool,
+    asymmetry_samples: usize,
+}
+
+impl ChunkLimits {
+    /// at most `origins` origins and `destinations` destinations per request.
+    pub fn new(origins: usize, destinations: usize) -> ChunkLimits {
+        ChunkLimits {
+            origins: origins.max(1),
+            destinations: destinations.max(1),
+            max_elements: None,
+            symmetric: false,
+            asymmetry_samples: 0,
+        }
+    }
+
+    /// at most `max` origin and destination pairs per request as well, e.g. when neshan limits
+    /// the elements of a matrix rather than its sides. the destinations of a tile shrink
+    /// first, then its origins.
+    pub fn max_elements(mut self, max: usize) -> ChunkLimits {
+        self.max_elements = Some(max.max(1));
+        self
+    }
+
+    /// origins and destinations of a full tile.
+    fn tile(&self) -> (usize, usize) {
+        match self.max_elements {
+            Some(max) => {
+                let origins = self.origins.min(max);
+                (origins, self.destinations.min(max / origins))
+            }
+            None => (self.origins, self.destinations),
+        }
+    }
+
+    /// assume the trip from a to b takes as long as the one from b to a, which roughly halves
+    /// the requests when origins and destinations are the same points. ignored otherwise.
+    pub fn symmetric(mut self, symmetric: bool) -> ChunkLimits {
+        self.symmetric = symmetric;
+        self
+    }
+
+    /// request this many of the mirrored pairs anyway, one request each, to estimate the
+    /// asymmetry of a symmetric matrix.
+    pub fn asymmetry_samples(mut self, samples: usize) -> ChunkLimits {
+        self.asymmetry_samples = samples;
+        self
+    }
+}
+
+/// a matrix of `Client::distance_matrix_symmetric` with the estimate of its asymmetry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymmetricMatrix {
+    pub matrix: DistanceMatrix,
+    /// mean relative difference of the durations of the two directions between the pairs
+    /// sampled by `ChunkLimits::asymmetry_samples`, `None` without samples or when none of
+    /// them has both durations.
+    pub asymmetry: Option<f64>,
+}
+
+/// an origin of `Client::rank_by_duration` with its trip to the destination.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedOrigin {
+    /// position of the origin in the input.
+    pub index: usize,
+    /// `None` when the origin can't reach the destination.
+    pub duration: Option<Duration>,
+    pub distance: Option<Distance>,
+}
+
+impl RankedOrigin {
+    pub fn is_routable(&self) -> bool {
+        self.duration.is_some() && self.distance.is_some()
+    }
+}
+
+/// durations with and without traffic from each origin to each destination, see
+/// `Client::traffic_delay_matrix`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficMatrix {
+    pub origin_addresses: Vec<String>,
+    pub destination_addresses: Vec<String>,
+    /// a row per origin, with an element per destination.
+    pub rows: Vec<Vec<TrafficElement>>,
+}
+
+impl TrafficMatrix {
+    /// element from the `origin`th origin to the `destination`th destination.
+    pub fn get(&self, origin: usize, destination: usize) -> Option<&TrafficElement> {
+        self.rows.get(origin)?.get(destination)
+    }
+}
+
+/// which of the two calls of `Client::traffic_delay_matrix` found a route for an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Availability {
+    Both,
+    TrafficOnly,
+    NoTrafficOnly,
+    Neither,
+}
+
+/// trip from one origin to one destination, with and without traffic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficElement {
+    /// `None` when the call with traffic found no route.
+    pub duration: Option<Duration>,
+    /// `None` when the call without traffic found no route.
+    pub duration_no_traffic: Option<Duration>,
+    /// distance of the trip with traffic, or without it when only that one was found.
+    pub distance: Option<Distance>,
+}
+
+impl TrafficElement {
+    fn merge(
+        traffic: Option<&MatrixElement>,
+        no_traffic: Option<&MatrixElement>,
+    ) -> TrafficElement {
+        let traffic = traffic.filter(|e| e.is_routable());
+        let no_traffic = no_traffic.filter(|e| e.is_routable());
+
+        TrafficElement {
+            duration: traffic.and_then(|e| e.duration.clone()),
+            duration_no_traffic: no_traffic.and_then(|e| e.duration.clone()),
+            distance: traffic.or(no_traffic).and_then(|e| e.distance.clone()),
+        }
+    }
+
+    pub fn availability(&self) -> Availability {
+        match (&self.duration, &self.duration_no_traffic) {
+            (Some(_), Some(_)) => Availability::Both,
+            (Some(_), None) => Availability::TrafficOnly,
+            (None, Some(_)) => Availability::NoTrafficOnly,
+            (None, None) => Availability::Neither,
+        }
+    }
+
+    /// duration with traffic over the one without it, e.g. 1.5 when traffic adds half of the
+    /// free-flow time. `None` unless both are known and the one without traffic isn't zero.
+    pub fn delay_ratio(&self) -> Option<f64> {
+        let traffic = self.duration.as_ref()?.value;
+        let no_traffic = self.duration_no_traffic.as_ref()?.value;
+        if no_traffic > 0.0 {
+            Some(traffic / no_traffic)
+        } else {
+            None
+        }
+    }
+}
+
+/// part of the matrix requested at once.
+#[derive(Clone)]
+struct Tile {
+    origins: Range<usize>,
+    destinations: Range<usize>,
+}
+
+/// neshan can't answer a matrix without origins or destinations.
+pub(crate) fn check_sizes(origins: &[Point], destinations: &[Point]) -> Result<(), NeshanError> {
+    match (origins.is_empty(), destinations.is_empty()) {
+        (false, false) => Ok(()),
+        (true, _) => Err(NeshanError::InvalidRequest(
+            "the distance matrix has no origins".to_string(),
+        )),
+        (_, true) => Err(NeshanError::InvalidRequest(
+            "the distance matrix has no destinations".to_string(),
+        )),
+    }
+}
+
+impl Client {
+    /// distances and durations from every origin to every destination, failing without
+    /// sending a request when either list is empty.
+    /// https://platform.neshan.org/api/distance-matrix
+    pub async fn distance_matrix(
+        &self,
+        vehicle: Type,
+        origins: impl IntoIterator<Item = impl Into<Point>>,
+        destinations: impl IntoIterator<Item = impl Into<Point>>,
+    ) -> Result<DistanceMatrix, NeshanError> {
+        let (origins, destinations) = (collect_points(origins), collect_points(destinations));
+        self.matrix(Endpoint::DistanceMatrix, vehicle, &origins, &destinations)
+            .await
+    }
+
+    /// same as `distance_matrix` with the durations of free-flowing roads, ignoring the
+    /// current traffic.
+    pub async fn distance_matrix_no_traffic(
+        &self,
+        vehicle: Type,
+        origins: impl IntoIterator<Item = impl Into<Point>>,
+        destinations: impl IntoIterator<Item = impl Into<Point>>,
+    ) -> Result<DistanceMatrix, NeshanError> {
+        let (origins, destinations) = (collect_points(origins), collect_points(destinations));
+        self.matrix(
+            Endpoint::DistanceMatrixNoTraffic,
+            vehicle,
+            &origins,
+            &destinations,
+        )
+        .await
+    }
+
+    /// `distance_matrix` and `distance_matrix_no_traffic` requested concurrently and merged,
+    /// e.g. to find the pairs traffic slows down the most. an element one of the calls found
+    /// no route for, or left out, keeps the duration of the other one, see
+    /// `TrafficElement::availability`. fails when either call fails.
+    pub async fn traffic_delay_matrix(
+        &self,
+        vehicle: Type,
+        origins: impl IntoIterator<Item = impl Into<Point>>,
+        destinations: impl IntoIterator<Item = impl Into<Point>>,
+    ) -> Result<TrafficMatrix, NeshanError> {
+        let (origins, destinations) = (collect_points(origins), collect_points(destinations));
+        let (traffic, no_traffic) = futures_util::future::try_join(
+            self.distance_matrix(vehicle.clone(), &origins, &destinations),
+            self.distance_matrix_no_traffic(vehicle, &origins, &destinations),
+        )
+        .await?;
+
+        let rows = (0..origins.len())
+            .map(|origin| {
+                (0..destinations.len())
+                    .map(|destination| {
+                        TrafficElement::merge(
+                            traffic.get(origin, destination),
+                            no_traffic.get(origin, destination),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(TrafficMatrix {
+            origin_addresses: traffic.origin_addresses,
+            destination_addresses: traffic.destination_addresses,
+            rows,
+        })
+    }
+
+    async fn matrix(
+        &self,
+        endpoint: Endpoint,
+        vehicle: Type,
+        origins: &[Point],
+        destinations: &[Point],
+    ) -> Result<DistanceMatrix, NeshanError> {
+        check_sizes(origins, destinations)?;
+        self.check(origins)?;
+        self.check(destinations)?;
+
+        let query = crate::protocol::distance_matrix_query(vehicle, origins, destinations);
+        let points: Vec<Point> = origins.iter().chain(destinations).copied().collect();
+        let call = self.get(endpoint, &query);
+
+        crate::trace::instrument(endpoint, &points, call)
+            .await
+            .map(|(matrix, _)| matrix)
+    }
+
+    /// origins ordered by how fast they reach the destination, e.g. the drivers closest to a
+    /// pickup. ties are broken by distance, then by index. origins without a route come last
+    /// in their input order.
+    pub async fn rank_by_duration(
+        &self,
+        vehicle: Type,
+        origins: impl IntoIterator<Item = impl Into<Point>>,
+        destination: impl Into<Point>,
+    ) -> Result<Vec<RankedOrigin>, NeshanError> {
+        let origins = collect_points(origins);
+        let matrix = self
+            .distance_matrix(vehicle, &origins, [destination.into()])
+            .await?;
+
+        let mut ranked: Vec<RankedOrigin> = (0..origins.len())
+            .map(|index| match matrix.get(index, 0) {
+                Some(element) => RankedOrigin {
+                    index,
+                    duration: element.duration.clone(),
+                    distance: element.distance.clone(),
+                },
+                None => RankedOrigin {
+                    index,
+                    duration: None,
+                    distance: None,
+                },
+            })
+            .collect();
+
+        let value = |value: Option<f64>| value.unwrap_or(f64::INFINITY);
+        ranked.sort_by(|a, b| {
+            b.is_routable()
+                .cmp(&a.is_routable())
+                .then_with(|| {
+                    value(a.duration.as_ref().map(|duration| duration.value))
+                        .total_cmp(&value(b.duration.as_ref().map(|duration| duration.value)))
+                })
+                .then_with(|| {
+                    value(a.distance.as_ref().map(|distance| distance.value))
+                        .total_cmp(&value(b.distance.as_ref().map(|distance| distance.value)))
+                })
+                .then_with(|| a.index.cmp(&b.index))
+        });
+
+        Ok(ranked)
+    }
+
+    /// same as `distance_matrix` for inputs larger than a single request allows. the inputs
+    /// are split into tiles within `limits`, at most `concurrency` of them are requested at
+    /// the same time and the tiles are put back together in the order of the inputs.
+    ///
+    /// a tile that fails, or whose response doesn't match its size, doesn't fail the others:
+    /// its elements get the `ElementStatus::Failed` status.
+    ///
+    /// with `ChunkLimits::symmetric` and the same origins and destinations, only tiles with
+    /// elements on or above the diagonal are requested and the missing elements below it are
+    /// mirrored from above. see `distance_matrix_symmetric` for the estimate of how much the
+    /// two directions differ.
+    pub async fn distance_matrix_chunked(
+        &self,
+        vehicle: Type,
+        origins: impl IntoIterator<Item = impl Into<Point>>,
+        destinations: impl IntoIterator<Item = impl Into<Point>>,
+        limits: ChunkLimits,
+        concurrency: usize,
+    ) -> DistanceMatrix {
+        let (origins, destinations) = (collect_points(origins), collect_points(destinations));
+        let (matrix, _, _) = self
+            .chunked(vehicle, &origins, &destinations, limits, concurrency)
+            .await;
+
+        matrix
+    }
+
+    /// same as `distance_matrix_chunked` from `points` to themselves with
+    /// `ChunkLimits::symmetric`, along with how much the two directions differ between the
+    /// pairs sampled by `ChunkLimits::asymmetry_samples`.
+    pub async fn distance_matrix_symmetric(
+        &self,
+        vehicle: Type,
+        points: impl IntoIterator<Item = impl Into<Point>>,
+        limits: ChunkLimits,
+        concurrency: usize,
+    ) -> SymmetricMatrix {
+        let points = collect_points(points);
+        let (matrix, asymmetry, _) = self
+            .chunked(
+                vehicle,
+                &points,
+                &points,
+                limits.symmetric(true),
+                concurrency,
+            )
+            .await;
+
+        SymmetricMatrix { matrix, asymmetry }
+    }
+
+    /// same as `distance_matrix_chunked`, failing with the error of the first tile that
+    /// failed, in the order of the inputs, instead of marking its elements. a tile whose
+    /// response doesn't match its size fails with `NeshanError::Decode`. the other tiles are
+    /// still requested.
+    pub async fn distance_matrix_chunked_strict(
+        &self,
+        vehicle: Type,
+        origins: impl IntoIterator<Item = impl Into<Point>>,
+        destinations: impl IntoIterator<Item = impl Into<Point>>,
+        limits: ChunkLimits,
+        concurrency: usize,
+    ) -> Result<DistanceMatrix, NeshanError> {
+        let (origins, destinations) = (collect_points(origins), collect_points(destinations));
+        match self
+            .chunked(vehicle, &origins, &destinations, limits, concurrency)
+            .await
+        {
+            (_, _, Some(err)) => Err(err),
+            (matrix, _, None) => Ok(matrix),
+        }
+    }
+
+    /// the matrix with the failed tiles marked, its asymmetry when symmetric and sampled, and
+    /// the error of the first failed tile.
+    async fn chunked(
+        &self,
+        vehicle: Type,
+        origins: &[Point],
+        destinations: &[Point],
+        limits: ChunkLimits,
+        concurrency: usize,
+    ) -> (DistanceMatrix, Option<f64>, Option<NeshanError>) {
+        let symmetric = limits.symmetric && origins == destinations;
+        let (tile_origins, tile_destinations) = limits.tile();
+
+        let mut tiles: Vec<Tile> = (0..origins.len())
+            .step_by(tile_origins)
+            .flat_map(|origin| {
+                (0..destinations.len())
+                    .step_by(tile_destinations)
+                    .map(move |destination| Tile {
+                        origins: origin..origins.len().min(origin + tile_origins),
+                        destinations: destination
+                            ..destinations.len().min(destination + tile_destinations),
+                    })
+            })
+            // a tile entirely below the diagonal is mirrored from its counterpart above.
+            .filter(|tile| !symmetric || tile.origins.start < tile.destinations.end)
+            .collect();
+
+        let mut requested = vec![vec![false; destinations.len()]; origins.len()];
+        for tile in &tiles {
+            for row in &mut requested[tile.origins.clone()] {
+                row[tile.destinations.clone()].fill(true);
+            }
+        }
+
+        let mut samples = Vec::new();
+        if symmetric && limits.asymmetry_samples > 0 {
+            let mirrored: Vec<(usize, usize)> = (0..origins.len())
+                .flat_map(|origin| (0..origin).map(move |destination| (origin, destination)))
+                .filter(|(origin, destination)| !requested[*origin][*destination])
+                .collect();
+            let count = limits.asymmetry_samples.min(mirrored.len());
+            samples = (0..count)
+                .map(|i| mirrored[i * mirrored.len() / count])
+                .collect();
+        }
+        tiles.extend(samples.iter().map(|(origin, destination)| Tile {
+            origins: *origin..origin + 1,
+            destinations: *destination..destination + 1,
+        }));
+
+        let vehicle = &vehicle;
+        let results = self
+            .run_batch(
+                tiles.iter().cloned(),
+                &BatchOptions::new(concurrency),
+                |client, tile| async move {
+                    client
+                        .distance_matrix(
+                            vehicle.clone(),
+                            &origins[tile.origins],
+                            &destinations[tile.destinations],
+                        )
+                        .await
+                },
+            )
+            .await;
+
+        let mut rows: Vec<MatrixRow> = (0..origins.len())
+            .map(|_| MatrixRow {
+                elements: vec![MatrixElement::error(); destinations.len()],
+            })
+            .collect();
+        let mut origin_addresses = vec![None; origins.len()];
+        let mut destination_addresses = vec![None; destinations.len()];
+        let mut failure = None;
+
+        for (tile, result) in tiles.into_iter().zip(results) {
+            let matrix = match result {
+                Ok(matrix)
+                    if matrix.rows.len() == tile.origins.len()
+                        && matrix
+                            .rows
+                            .iter()
+                            .all(|row| row.elements.len() == tile.destinations.len()) =>
+                {
+                    matrix
+                }
+                Err(err) => {
+                    failure.get_or_insert(err);
+                    continue;
+                }
+                Ok(matrix) => {
+                    let message = format!(
+                        "a tile of {} by {} came back with {} rows",
+                        tile.origins.len(),
+                        tile.destinations.len(),
+                        matrix.rows.len()
+                    );
+                    failure.get_or_insert(NeshanError::Decode {
+                        source: std::sync::Arc::new(serde::de::Error::custom(message)),
+                        request_id: None,
+                    });
+                    continue;
+                }
+            };
+
+            for (i, row) in matrix.rows.into_iter().enumerate() {
+                rows[tile.origins.start + i].elements[tile.destinations.clone()]
+                    .clone_from_slice(&row.elements);
+            }
+            for (i, address) in matrix.origin_addresses.into_iter().enumerate() {
+                if let Some(slot) = origin_addresses.get_mut(tile.origins.start + i) {
+                    *slot = Some(address);
+                }
+            }
+            for (i, address) in matrix.destination_addresses.into_iter().enumerate() {
+                if let Some(slot) = destination_addresses.get_mut(tile.destinations.start + i) {
+                    *slot = Some(address);
+                }
+            }
+        }
+
+        let mut asymmetry = None;
+        if symmetric {
+            for (origin, destination) in &samples {
+                requested[*origin][*destination] = true;
+            }
+            for origin in 0..origins.len() {
+                for destination in 0..origin {
+                    if !requested[origin][destination] {
+                        rows[origin].elements[destination] =
+                            rows[destination].elements[origin].clone();
+                    }
+                }
+            }
+
+            let differences: Vec<f64> = samples
+                .iter()
+                .filter_map(|(origin, destination)| {
+                    let there = rows[*origin].elements[*destination].duration.as_ref()?;
+                    let back = rows[*destination].elements[*origin].duration.as_ref()?;
+                    let longest = there.value.max(back.value);
+                    (longest > 0.0).then(|| (there.value - back.value).abs() / longest)
+                })
+                .collect();
+            if !differences.is_empty() {
+                asymmetry = Some(differences.iter().sum::<f64>() / differences.len() as f64);
+            }
+        }
+
+        // neshan may leave the addresses out, keep them empty rather than full of blanks.
+        let addresses = |addresses: Vec<Option<String>>| {
+            if addresses.iter().all(Option::is_none) {
+                Vec::new()
+            } else {
+                addresses
+                    .into_iter()
+                    .map(Option::unwrap_or_default)
+                    .collect()
+            }
+        };
+
+        let matrix = DistanceMatrix {
+            origin_addresses: addresses(origin_addresses),
+            destination_addresses: addresses(destination_addresses),
+            rows,
+        };
+
+        (matrix, asymmetry, failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Availability, ChunkLimits, CsvOptions, DistanceMatrix, ElementStatus, MatrixElement,
+        MatrixRow, MatrixUnit, SymmetricMatrix,
+    };
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::{Distance, Duration, Point, Type};
+
+    fn element(seconds: f64) -> MatrixElement {
+        MatrixElement {
+            status: ElementStatus::Ok,
+            duration: Some(Duration {
+                value: seconds,
+                text: String::new(),
+            }),
+            distance: Some(Distance {
+                value: seconds * 10.0,
+                text: String::new(),
+            }),
+        }
+    }
+
+    fn unroutable() -> MatrixElement {
+        MatrixElement {
+            status: ElementStatus::NoRoute,
+            duration: None,
+            distance: None,
+        }
+    }
+
+    fn matrix() -> DistanceMatrix {
+        let row = |elements| MatrixRow { elements };
+
+        DistanceMatrix {
+            origin_addresses: Vec::new(),
+            destination_addresses: Vec::new(),
+            rows: vec![
+                row(vec![element(0.0), element(300.0), element(120.0)]),
+                row(vec![element(300.0), unroutable(), element(50.0)]),
+                row(vec![unroutable(), element(80.0), element(0.0)]),
+            ],
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let matrix = matrix();
+        assert_eq!(matrix.get(0, 1), Some(&element(300.0)));
+        assert_eq!(matrix.get(1, 1), None);
+        assert_eq!(matrix.get(3, 0), None);
+        assert_eq!(matrix.get(0, 3), None);
+    }
+
+    #[test]
+    fn nearest_destination() {
+        let matrix = matrix();
+        assert_eq!(matrix.nearest_destination(0).unwrap().0, 0);
+        assert_eq!(matrix.nearest_destination(1).unwrap().0, 2);
+        assert_eq!(matrix.nearest_destination(2).unwrap().0, 2);
+        assert!(matrix.nearest_destination(3).is_none());
+
+        let only_unroutable = DistanceMatrix {
+            origin_addresses: Vec::new(),
+            destination_addresses: Vec::new(),
+            rows: vec![MatrixRow {
+                elements: vec![unroutable()],
+            }],
+        };
+        assert!(only_unroutable.nearest_destination(0).is_none());
+    }
+
+    #[test]
+    fn duration_grid() {
+        assert_eq!(
+            matrix().to_duration_grid(),
+            vec![
+                vec![Some(0.0), Some(300.0), Some(120.0)],
+                vec![Some(300.0), None, Some(50.0)],
+                vec![None, Some(80.0), Some(0.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn iterate_rows() {
+        let matrix = matrix();
+        let sizes: Vec<(usize, usize)> = matrix
+            .iter_rows()
+            .map(|(origin, row)| (origin, row.elements.len()))
+            .collect();
+        assert_eq!(sizes, vec![(0, 3), (1, 3), (2, 3)]);
+
+        let reachable: Vec<usize> = matrix.destinations(1).map(|(i, _)| i).collect();
+        assert_eq!(reachable, vec![0, 2]);
+    }
+
+    #[test]
+    fn csv() {
+        let mut matrix = matrix();
+        matrix.rows[0].elements[1].duration.as_mut().unwrap().value = 12.5;
+
+        assert_eq!(
+            matrix.to_csv_durations(),
+            ",0,1,2\n\
+             0,0,12.5,120\n\
+             1,300,,50\n\
+             2,,80,0\n"
+        );
+        assert_eq!(
+            matrix.to_csv_distances_labeled(&["azadi, tehran", "say \"hi\""], &["a", "b\nc"]),
+            ",a,\"b\nc\",2\n\
+             \"azadi, tehran\",0,3000,1200\n\
+             \"say \"\"hi\"\"\",3000,,500\n\
+             2,,800,0\n"
+        );
+
+        let empty = DistanceMatrix {
+            origin_addresses: Vec::new(),
+            destination_addresses: Vec::new(),
+            rows: Vec::new(),
+        };
+        assert_eq!(empty.to_csv_durations(), "\n");
+    }
+
+    /// fields of the lines of a csv, unquoting quoted ones.
+    fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+        let (mut lines, mut fields, mut field) = (Vec::new(), Vec::new(), String::new());
+        let (mut chars, mut quoted) = (csv.chars().peekable(), false);
+        while let Some(c) = chars.next() {
+            match (c, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                ('"', _) => quoted = !quoted,
+                (',', false) => fields.push(std::mem::take(&mut field)),
+                ('\n', false) => {
+                    fields.push(std::mem::take(&mut field));
+                    lines.push(std::mem::take(&mut fields));
+                }
+                (c, _) => field.push(c),
+            }
+        }
+
+        lines
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let matrix =
+            DistanceMatrix::from_json(include_str!("../fixtures/distance_matrix_mixed.json"))
+                .unwrap();
+
+        for (unit, scale, value) in [
+            (MatrixUnit::Seconds, 1.0, 1805.0),
+            (MatrixUnit::Minutes, 60.0, 1805.0),
+            (MatrixUnit::Meters, 1.0, 12133.0),
+            (MatrixUnit::Kilometers, 1000.0, 12133.0),
+        ] {
+            let mut csv = Vec::new();
+            matrix.to_csv(&mut csv, &CsvOptions::new(unit)).unwrap();
+            let lines = parse_csv(std::str::from_utf8(&csv).unwrap());
+
+            assert_eq!(
+                lines[0],
+                vec!["", "میدان انقلاب", "جزیره کیش", "میدان ونک"],
+                "{:?}",
+                unit
+            );
+            assert_eq!(lines.len(), 3);
+            assert_eq!(lines[2][0], "میدان تجریش");
+            let cell: f64 = lines[2][1].parse().unwrap();
+            assert!((cell * scale - value).abs() < 1e-9, "{:?}", unit);
+            assert_eq!(lines[2][2], "");
+            assert_eq!(lines[2][3], "");
+
+            for (origin, line) in lines[1..].iter().enumerate() {
+                for (destination, cell) in line[1..].iter().enumerate() {
+                    let expected = matrix
+                        .get(origin, destination)
+                        .and_then(|element| unit.value(element));
+                    let parsed: Option<f64> = cell.parse().ok();
+                    assert_eq!(parsed, expected, "{:?} {} {}", unit, origin, destination);
+                }
+            }
+        }
+
+        let options = CsvOptions::new(MatrixUnit::Minutes)
+            .placeholder("-")
+            .labels(&["آزادی، تهران", "پیک \"۲\""], &["a"]);
+        let mut csv = Vec::new();
+        matrix.to_csv(&mut csv, &options).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with(",a,1,2\n"));
+        assert!(csv.contains("\n\"پیک \"\"۲\"\"\","));
+        let lines = parse_csv(&csv);
+        assert_eq!(lines[1][0], "آزادی، تهران");
+        assert_eq!(lines[2][0], "پیک \"۲\"");
+        assert_eq!(lines[1][2], "-");
+        assert_eq!(lines[2][3], "-");
+
+        // a failing writer fails the export.
+        struct Full;
+        impl std::io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WriteZero.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(matrix.to_csv(Full, &options).is_err());
+    }
+
+    #[test]
+    fn decode_response() {
+        let matrix: DistanceMatrix = serde_json::from_value(serde_json::json!({
+            "status": "Ok",
+            "origin_addresses": ["میدان آزادی"],
+            "destination_addresses": ["میدان انقلاب", "دریا"],
+            "rows": [{"elements": [
+                {
+                    "status": "Ok",
+                    "duration": {"value": 754, "text": "۱۳ دقیقه"},
+                    "distance": {"value": 5342, "text": "۵٫۳ کیلومتر"}
+                },
+                {"status": "NOT_FOUND"}
+            ]}]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            matrix.get(0, 0).unwrap().duration.as_ref().unwrap().value,
+            754.0
+        );
+        assert_eq!(matrix.get(0, 1), None);
+        assert_eq!(matrix.to_duration_grid(), vec![vec![Some(754.0), None]]);
+    }
+
+    #[test]
+    fn element_statuses() {
+        let matrix =
+            DistanceMatrix::from_json(include_str!("../fixtures/distance_matrix_mixed.json"))
+                .unwrap();
+
+        assert_eq!(matrix.status(0, 0), Some(&ElementStatus::Ok));
+        assert_eq!(matrix.status(1, 1), Some(&ElementStatus::NoRoute));
+        assert_eq!(
+            matrix.status(1, 2),
+            Some(&ElementStatus::Other("MAX_DISTANCE_EXCEEDED".to_string()))
+        );
+        assert_eq!(matrix.status(2, 0), None);
+        assert_eq!(matrix.status(0, 3), None);
+
+        assert_eq!(
+            matrix.get(1, 0).unwrap().duration.as_ref().unwrap().value,
+            1805.0
+        );
+        assert_eq!(matrix.get(0, 1), None);
+        assert_eq!(matrix.get(1, 2), None);
+        assert_eq!(matrix.get(7, 0), None);
+        assert_eq!(matrix.get(0, 7), None);
+
+        let column: Vec<usize> = matrix.origins(0).map(|(origin, _)| origin).collect();
+        assert_eq!(column, vec![0, 1]);
+        assert_eq!(matrix.origins(1).count(), 0);
+        assert_eq!(matrix.origins(2).count(), 1);
+        assert_eq!(matrix.origins(3).count(), 0);
+        let row: Vec<usize> = matrix
+            .destinations(0)
+            .map(|(destination, _)| destination)
+            .collect();
+        assert_eq!(row, vec![0, 2]);
+
+        for (status, parsed) in [
+            ("Ok", ElementStatus::Ok),
+            ("OK", ElementStatus::Ok),
+            ("not_found", ElementStatus::NoRoute),
+            ("ERROR", ElementStatus::Failed),
+            ("", ElementStatus::Other(String::new())),
+        ] {
+            assert_eq!(ElementStatus::from(status.to_string()), parsed);
+        }
+        let json = serde_json::to_value(&matrix.rows[1]).unwrap();
+        assert_eq!(json["elements"][1]["status"], "NOT_FOUND");
+        assert_eq!(json["elements"][2]["status"], "MAX_DISTANCE_EXCEEDED");
+    }
+
+    fn latitudes(points: &str) -> Vec<f64> {
+        points
+            .split('|')
+            .map(|point| point.split(',').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    /// answers with a duration of `100 * origin + destination` latitudes, failing the tile with
+    /// the origin at latitude 2 and the destination at latitude 10.
+    fn respond(request: &wiremock::Request) -> wiremock::ResponseTemplate {
+        let query = |name: &str| {
+            request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+        let (origins, destinations) = (
+            latitudes(&query("origins")),
+            latitudes(&query("destinations")),
+        );
+        if origins.contains(&2.0) && destinations.contains(&10.0) {
+            return wiremock::ResponseTemplate::new(470);
+        }
+
+        let rows: Vec<serde_json::Value> = origins
+            .iter()
+            .map(|origin| {
+                let elements: Vec<serde_json::Value> = destinations
+                    .iter()
+                    .map(|destination| {
+                        serde_json::json!({
+                            "status": "Ok",
+                            "duration": {"value": origin * 100.0 + destination, "text": ""},
+                            "distance": {"value": 1, "text": ""}
+                        })
+                    })
+                    .collect();
+                serde_json::json!({ "elements": elements })
+            })
+            .collect();
+
+        wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "origin_addresses": origins.iter().map(|o| o.to_string()).collect::<Vec<_>>(),
+            "destination_addresses": destinations.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            "rows": rows
+        }))
+    }
+
+    #[tokio::test]
+    async fn distance_matrix_query() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .and(query_param("type", "car"))
+            .and(query_param(
+                "origins",
+                "0.000000,51.000000|1.000000,51.000000",
+            ))
+            .and(query_param("destinations", "10.000000,52.000000"))
+            .respond_with(respond)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let matrix = client
+            .distance_matrix(
+                Type::Car,
+                &[
+                    Point::new_unchecked(0.0, 51.0),
+                    Point::new_unchecked(1.0, 51.0),
+                ],
+                &[Point::new_unchecked(10.0, 52.0)],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            matrix.to_duration_grid(),
+            vec![vec![Some(10.0)], vec![Some(110.0)]]
+        );
+    }
+
+    #[tokio::test]
+    async fn two_by_three_matrix() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let origins = [(0.0, 51.0), (1.0, 51.0)];
+        let destinations = [(3.0, 52.0), (4.0, 52.0), (5.0, 52.0)];
+        let matrix = client
+            .distance_matrix(Type::Car, origins, destinations)
+            .await
+            .unwrap();
+
+        assert_eq!(matrix.rows.len(), 2);
+        assert!(matrix.rows.iter().all(|row| row.elements.len() == 3));
+        assert_eq!(
+            matrix.get(1, 2).unwrap().duration.as_ref().unwrap().value,
+            105.0
+        );
+        assert_eq!(
+            matrix.get(0, 2).unwrap().distance.as_ref().unwrap().value,
+            1.0
+        );
+        assert_eq!(matrix.get(2, 0), None);
+    }
+
+    #[tokio::test]
+    async fn empty_inputs() {
+        let server = wiremock::MockServer::start().await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let some = [Point::new_unchecked(35.7, 51.4)];
+
+        for (origins, destinations, missing) in [
+            (&[][..], &some[..], "origins"),
+            (&some[..], &[][..], "destinations"),
+            (&[][..], &[][..], "origins"),
+        ] {
+            let err = client
+                .distance_matrix(Type::Car, origins, destinations)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, NeshanError::InvalidRequest(ref message) if message.ends_with(missing)),
+                "{}",
+                err
+            );
+            assert!(crate::protocol::build_distance_matrix_request(
+                "key",
+                Type::Car,
+                origins,
+                destinations
+            )
+            .is_err());
+        }
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn merge_traffic_and_no_traffic() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let ok = |seconds: f64| {
+            serde_json::json!({
+                "status": "Ok",
+                "duration": {"value": seconds, "text": ""},
+                "distance": {"value": seconds * 10.0, "text": ""}
+            })
+        };
+        let not_found = serde_json::json!({"status": "NOT_FOUND"});
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .and(query_param("type", "car"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "origin_addresses": ["a", "b"],
+                "destination_addresses": ["c", "d"],
+                "rows": [
+                    {"elements": [ok(150.0), ok(300.0)]},
+                    {"elements": [not_found.clone(), ok(60.0)]}
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // the second row is cut short, its last element is missing.
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix/no-traffic"))
+            .and(query_param("type", "car"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "rows": [
+                    {"elements": [ok(100.0), not_found]},
+                    {"elements": [ok(40.0)]}
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let points = [
+            Point::new_unchecked(35.70, 51.40),
+            Point::new_unchecked(35.71, 51.41),
+        ];
+        let matrix = client
+            .traffic_delay_matrix(Type::Car, &points, &points)
+            .await
+            .unwrap();
+
+        assert_eq!(matrix.origin_addresses, vec!["a", "b"]);
+        let availability: Vec<Vec<Availability>> = matrix
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|element| element.availability()).collect())
+            .collect();
+        assert_eq!(
+            availability,
+            vec![
+                vec![Availability::Both, Availability::TrafficOnly],
+                vec![Availability::NoTrafficOnly, Availability::TrafficOnly],
+            ]
+        );
+
+        let both = matrix.get(0, 0).unwrap();
+        assert_eq!(both.delay_ratio(), Some(1.5));
+        assert_eq!(both.distance.as_ref().unwrap().value, 1500.0);
+        let no_traffic = matrix.get(1, 0).unwrap();
+        assert_eq!(no_traffic.delay_ratio(), None);
+        assert_eq!(no_traffic.duration_no_traffic.as_ref().unwrap().value, 40.0);
+        assert_eq!(no_traffic.distance.as_ref().unwrap().value, 400.0);
+        assert_eq!(matrix.get(1, 1).unwrap().delay_ratio(), None);
+        assert_eq!(matrix.get(2, 0), None);
+    }
+
+    #[tokio::test]
+    async fn chunked_matrix_is_stitched_back() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let origins: Vec<Point> = (0..5)
+            .map(|i| Point::new_unchecked(f64::from(i), 51.0))
+            .collect();
+        let destinations: Vec<Point> = (0..3)
+            .map(|i| Point::new_unchecked(f64::from(10 + i), 52.0))
+            .collect();
+
+        let matrix = client
+            .distance_matrix_chunked(
+                Type::Car,
+                &origins,
+                &destinations,
+                ChunkLimits::new(2, 2),
+                2,
+            )
+            .await;
+
+        let mut shapes: Vec<(usize, usize)> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let query = |name: &str| {
+                    request
+                        .url
+                        .query_pairs()
+                        .find(|(key, _)| key == name)
+                        .map(|(_, value)| value.into_owned())
+                        .unwrap()
+                };
+                (
+                    latitudes(&query("origins")).len(),
+                    latitudes(&query("destinations")).len(),
+                )
+            })
+            .collect();
+        shapes.sort();
+        assert_eq!(shapes, vec![(1, 1), (1, 2), (2, 1), (2, 1), (2, 2), (2, 2)]);
+
+        assert_eq!(matrix.rows.len(), 5);
+        for (origin, row) in matrix.iter_rows() {
+            assert_eq!(row.elements.len(), 3);
+            for (destination, element) in row.elements.iter().enumerate() {
+                if (2..4).contains(&origin) && destination < 2 {
+                    assert_eq!(element.status, ElementStatus::Failed);
+                    assert!(matrix.get(origin, destination).is_none());
+                } else {
+                    let duration = element.duration.as_ref().unwrap().value;
+                    assert_eq!(duration, origin as f64 * 100.0 + 10.0 + destination as f64);
+                }
+            }
+        }
+        assert_eq!(matrix.origin_addresses, vec!["0", "1", "2", "3", "4"]);
+        assert_eq!(matrix.destination_addresses, vec!["10", "11", "12"]);
+    }
+
+    #[test]
+    fn tile_sizes() {
+        assert_eq!(ChunkLimits::new(10, 10).tile(), (10, 10));
+        assert_eq!(ChunkLimits::new(10, 10).max_elements(30).tile(), (10, 3));
+        assert_eq!(ChunkLimits::new(3, 4).max_elements(6).tile(), (3, 2));
+        assert_eq!(ChunkLimits::new(10, 10).max_elements(4).tile(), (4, 1));
+        assert_eq!(ChunkLimits::new(10, 10).max_elements(0).tile(), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn element_limit_with_uneven_tiles() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        // latitudes 3 to 9, clear of the failing tile of `respond`.
+        let origins: Vec<Point> = (3..10)
+            .map(|i| Point::new_unchecked(f64::from(i), 51.0))
+            .collect();
+        let destinations: Vec<Point> = (0..4)
+            .map(|i| Point::new_unchecked(f64::from(20 + i), 52.0))
+            .collect();
+
+        let limits = ChunkLimits::new(3, 4).max_elements(6);
+        let matrix = client
+            .distance_matrix_chunked_strict(Type::Car, &origins, &destinations, limits, 3)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        // origins in tiles of 3, 3 and 1, destinations in tiles of 2 and 2.
+        assert_eq!(requests.len(), 6);
+        assert!(requests.iter().all(|request| {
+            let query: Vec<_> = request.url.query_pairs().collect();
+            let count = |name: &str| {
+                let (_, points) = query.iter().find(|(key, _)| key == name).unwrap();
+                latitudes(points).len()
+            };
+            count("origins") * count("destinations") <= 6
+        }));
+
+        assert_eq!(matrix.rows.len(), 7);
+        for (origin, row) in matrix.iter_rows() {
+            assert_eq!(row.elements.len(), 4);
+            for (destination, element) in row.elements.iter().enumerate() {
+                let expected = (origin + 3) as f64 * 100.0 + 20.0 + destination as f64;
+                assert_eq!(element.duration.as_ref().unwrap().value, expected);
+            }
+        }
+        assert_eq!(matrix.origin_addresses[6], "9");
+        assert_eq!(matrix.destination_addresses[3], "23");
+    }
+
+    #[tokio::test]
+    async fn strict_matrix_fails_with_its_tile() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let origins: Vec<Point> = (0..7)
+            .map(|i| Point::new_unchecked(f64::from(i), 51.0))
+            .collect();
+        let destinations = [Point::new_unchecked(10.0, 52.0)];
+
+        let err = client
+            .distance_matrix_chunked_strict(
+                Type::Car,
+                &origins,
+                &destinations,
+                ChunkLimits::new(3, 1),
+                2,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::Api(_)), "{}", err);
+
+        // without strict the failed tile is marked and the others are kept.
+        let matrix = client
+            .distance_matrix_chunked(
+                Type::Car,
+                &origins,
+                &destinations,
+                ChunkLimits::new(3, 1),
+                2,
+            )
+            .await;
+        let failed: Vec<bool> = (0..7).map(|i| matrix.get(i, 0).is_none()).collect();
+        assert_eq!(failed, vec![true, true, true, false, false, false, false]);
+    }
+
+    #[tokio::test]
+    async fn symmetric_matrix_requests_the_upper_triangle() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let points: Vec<Point> = (0..4)
+            .map(|i| Point::new_unchecked(f64::from(i) + 0.5, 51.0))
+            .collect();
+
+        let full = client
+            .distance_matrix_chunked(Type::Car, &points, &points, ChunkLimits::new(2, 2), 4)
+            .await;
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+
+        server.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .mount(&server)
+            .await;
+        let symmetric = client
+            .distance_matrix_chunked(
+                Type::Car,
+                &points,
+                &points,
+                ChunkLimits::new(2, 2).symmetric(true),
+                4,
+            )
+            .await;
+
+        // the tile of origins 2 and 3 to destinations 0 and 1 is left out.
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        assert_eq!(symmetric.rows.len(), full.rows.len());
+        let grid = symmetric.to_duration_grid();
+        let full_grid = full.to_duration_grid();
+        for origin in 0..4 {
+            assert_eq!(grid[origin].len(), 4);
+            for destination in 0..4 {
+                if origin >= 2 && destination < 2 {
+                    assert_eq!(grid[origin][destination], full_grid[destination][origin]);
+                } else {
+                    assert_eq!(grid[origin][destination], full_grid[origin][destination]);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sampled_asymmetry() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let points: Vec<Point> = (0..4)
+            .map(|i| Point::new_unchecked(f64::from(i) + 1.0, 51.0))
+            .collect();
+
+        let SymmetricMatrix { matrix, asymmetry } = client
+            .distance_matrix_symmetric(
+                Type::Car,
+                &points,
+                ChunkLimits::new(2, 2).asymmetry_samples(2),
+                4,
+            )
+            .await;
+
+        // three tiles and a request for each sample, (2, 0) and (3, 0).
+        assert_eq!(server.received_requests().await.unwrap().len(), 5);
+        assert_eq!(
+            matrix.get(2, 0).unwrap().duration.as_ref().unwrap().value,
+            301.0
+        );
+        assert_eq!(
+            matrix.get(2, 1).unwrap().duration.as_ref().unwrap().value,
+            203.0
+        );
+
+        // 301 against 103 and 401 against 104.
+        let expected = ((301.0 - 103.0) / 301.0 + (401.0 - 104.0) / 401.0) / 2.0;
+        assert!((asymmetry.unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn rank_origins_by_duration() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let element = |seconds: u32, meters: u32| {
+            serde_json::json!({
+                "status": "Ok",
+                "duration": {"value": seconds, "text": ""},
+                "distance": {"value": meters, "text": ""}
+            })
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .and(query_param("destinations", "35.700000,51.400000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "rows": [
+                    {"elements": [element(600, 5000)]},
+                    {"elements": [{"status": "NOT_FOUND"}]},
+                    {"elements": [element(300, 4000)]},
+                    {"elements": [element(600, 3000)]},
+                    {"elements": [element(300, 4000)]},
+                    {"elements": [{"status": "Ok"}]},
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let origins: Vec<Point> = (0..6)
+            .map(|i| Point::new_unchecked(35.0 + f64::from(i) / 10.0, 51.0))
+            .collect();
+
+        let ranked = client
+            .rank_by_duration(Type::Car, &origins, Point::new_unchecked(35.7, 51.4))
+            .await
+            .unwrap();
+
+        let order: Vec<(usize, bool)> = ranked
+            .iter()
+            .map(|origin| (origin.index, origin.is_routable()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (2, true),
+                (4, true),
+                (3, true),
+                (0, true),
+                (1, false),
+                (5, false)
+            ]
+        );
+        assert_eq!(ranked[2].distance.as_ref().unwrap().value, 3000.0);
+        assert!(ranked[4].duration.is_none());
+    }
+}
@@ -0,0 +1,329 @@
+//! lenient parsing of coordinates the way people write them, see `Point::parse_flexible`.
+
+use crate::point::{Latitude, Longitude, ParsePointError};
+use crate::Point;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Degree,
+    Minute,
+    Second,
+    Hemisphere(char),
+    Sign(f64),
+    Separator,
+}
+
+/// one side of the point before it is assigned to an axis.
+struct Coordinate {
+    degrees: f64,
+    hemisphere: Option<char>,
+}
+
+impl Point {
+    /// parse a point written by hand, e.g. `35.7, 51.4`, `35.731984 N, 51.392685 E` or
+    /// `35°43'55.1"N 51°23'33.7"E`.
+    ///
+    /// persian and arabic digits, decimal separator and comma are accepted as well. each
+    /// coordinate is either decimal degrees, degrees and decimal minutes, or degrees, minutes
+    /// and seconds, where minutes and seconds must carry their marks (`'` `′` `’` and `"` `″`
+    /// `”` `''`). a coordinate may have a sign or a hemisphere, either before or after it.
+    /// with hemispheres the coordinates can come in any order, without them latitude comes first.
+    pub fn parse_flexible(s: &str) -> Result<Point, ParsePointError> {
+        let tokens = tokenize(&normalize(s))?;
+        let mut tokens = tokens.as_slice();
+
+        let first = coordinate(&mut tokens)?;
+        if tokens.is_empty() {
+            return Err(invalid("expected two coordinates, found one"));
+        }
+        let second = coordinate(&mut tokens)?;
+        if !tokens.is_empty() {
+            return Err(invalid("expected two coordinates, found more"));
+        }
+
+        let (latitude, longitude) = match (first.hemisphere, second.hemisphere) {
+            (None, None) => (first.degrees, second.degrees),
+            (Some(a), Some(b)) if is_latitude(a) && !is_latitude(b) => {
+                (first.degrees, second.degrees)
+            }
+            (Some(a), Some(b)) if !is_latitude(a) && is_latitude(b) => {
+                (second.degrees, first.degrees)
+            }
+            (Some(_), Some(_)) => {
+                return Err(ambiguous(
+                    "both coordinates are on the same axis, expected one of N/S and one of E/W",
+                ))
+            }
+            _ => return Err(ambiguous("only one of the coordinates has a hemisphere")),
+        };
+
+        Ok(Point::from_parts(
+            Latitude::new(latitude)?,
+            Longitude::new(longitude)?,
+        ))
+    }
+}
+
+fn invalid(message: &str) -> ParsePointError {
+    ParsePointError::InvalidFormat(message.to_string())
+}
+
+fn ambiguous(message: &str) -> ParsePointError {
+    ParsePointError::Ambiguous(message.to_string())
+}
+
+fn is_latitude(hemisphere: char) -> bool {
+    hemisphere == 'N' || hemisphere == 'S'
+}
+
+/// replace persian and arabic digits and separators with their ascii counterparts.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '۰'..='۹' => char::from(b'0' + (c as u32 - '۰' as u32) as u8),
+            '٠'..='٩' => char::from(b'0' + (c as u32 - '٠' as u32) as u8),
+            '٫' => '.',
+            '،' | '؛' => ',',
+            '−' => '-',
+            c => c,
+        })
+        .collect()
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParsePointError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            '0'..='9' | '.' => {
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.peek().copied() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    end = i + 1;
+                    chars.next();
+                }
+                let number = &s[start..end];
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| invalid(&format!("{:?} is not a number", number)))?,
+                )
+            }
+            '°' | 'º' | '˚' => Token::Degree,
+            '\'' if chars.peek().map(|(_, c)| *c) == Some('\'') => {
+                chars.next();
+                Token::Second
+            }
+            '\'' | '′' | '’' | '‘' => Token::Minute,
+            '"' | '″' | '”' | '“' => Token::Second,
+            'n' | 'N' | 's' | 'S' | 'e' | 'E' | 'w' | 'W' => {
+                Token::Hemisphere(c.to_ascii_uppercase())
+            }
+            '-' => Token::Sign(-1.0),
+            '+' => Token::Sign(1.0),
+            ',' | ';' => Token::Separator,
+            c if c.is_whitespace() => continue,
+            c => return Err(invalid(&format!("unexpected character {:?}", c))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn next_if(tokens: &mut &[Token], expected: Token) -> bool {
+    match tokens.first() {
+        Some(token) if *token == expected => {
+            *tokens = &tokens[1..];
+            true
+        }
+        _ => false,
+    }
+}
+
+/// a number followed by the given mark, e.g. the minutes of a dms coordinate.
+fn marked(tokens: &mut &[Token], mark: Token) -> Option<f64> {
+    match tokens {
+        [Token::Number(value), next, ..] if *next == mark => {
+            *tokens = &tokens[2..];
+            Some(*value)
+        }
+        _ => None,
+    }
+}
+
+fn coordinate(tokens: &mut &[Token]) -> Result<Coordinate, ParsePointError> {
+    let prefix = match tokens.first() {
+        Some(Token::Hemisphere(hemisphere)) => {
+            *tokens = &tokens[1..];
+            Some(*hemisphere)
+        }
+        _ => None,
+    };
+    let sign = match tokens.first() {
+        Some(Token::Sign(sign)) => {
+            *tokens = &tokens[1..];
+            Some(*sign)
+        }
+        _ => None,
+    };
+
+    let mut degrees = match tokens.first() {
+        Some(Token::Number(degrees)) => *degrees,
+        _ => return Err(invalid("expected a number of degrees")),
+    };
+    *tokens = &tokens[1..];
+    next_if(tokens, Token::Degree);
+
+    if let Some(minutes) = marked(tokens, Token::Minute) {
+        if degrees.fract() != 0.0 {
+            return Err(invalid("degrees with minutes must be a whole number"));
+        }
+        if minutes >= 60.0 {
+            return Err(invalid(&format!("{} minutes is out of range", minutes)));
+        }
+        degrees += minutes / 60.0;
+
+        if let Some(seconds) = marked(tokens, Token::Second) {
+            if minutes.fract() != 0.0 {
+                return Err(invalid("minutes with seconds must be a whole number"));
+            }
+            if seconds >= 60.0 {
+                return Err(invalid(&format!("{} seconds is out of range", seconds)));
+            }
+            degrees += seconds / 3600.0;
+        }
+    }
+
+    // a hemisphere right after a coordinate with a prefix starts the next coordinate.
+    let hemisphere = match (prefix, tokens.first()) {
+        (None, Some(Token::Hemisphere(hemisphere))) => {
+            *tokens = &tokens[1..];
+            Some(*hemisphere)
+        }
+        (prefix, _) => prefix,
+    };
+    next_if(tokens, Token::Separator);
+
+    match (sign, hemisphere) {
+        (Some(_), Some(_)) => Err(ambiguous(
+            "a coordinate has both a sign and a hemisphere, use only one of them",
+        )),
+        (Some(sign), None) => Ok(Coordinate {
+            degrees: sign * degrees,
+            hemisphere,
+        }),
+        (None, Some('S' | 'W')) => Ok(Coordinate {
+            degrees: -degrees,
+            hemisphere,
+        }),
+        (None, _) => Ok(Coordinate {
+            degrees,
+            hemisphere,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::point::{Axis, ParsePointError};
+    use crate::Point;
+
+    #[test]
+    fn accepted_formats() {
+        let dms = (
+            35.0 + 43.0 / 60.0 + 55.1 / 3600.0,
+            51.0 + 23.0 / 60.0 + 33.7 / 3600.0,
+        );
+        let cases = [
+            ("35.731984, 51.392685", (35.731984, 51.392685)),
+            ("35.731984 51.392685", (35.731984, 51.392685)),
+            ("35.731984;51.392685", (35.731984, 51.392685)),
+            ("35.731984 N, 51.392685 E", (35.731984, 51.392685)),
+            ("N35.731984 E51.392685", (35.731984, 51.392685)),
+            ("51.392685E 35.731984N", (35.731984, 51.392685)),
+            ("40.7128 n, 74.0060 w", (40.7128, -74.006)),
+            ("-33.8688, +151.2093", (-33.8688, 151.2093)),
+            (r#"35°43'55.1"N 51°23'33.7"E"#, dms),
+            ("35°43′55.1″N, 51°23′33.7″E", dms),
+            ("35º43’55.1”N 51º23’33.7”E", dms),
+            ("35°43'55.1''N 51°23'33.7''E", dms),
+            ("N 35° 43' 55.1\" E 51° 23' 33.7\"", dms),
+            (
+                "35°43.5'N 51°23.25'E",
+                (35.0 + 43.5 / 60.0, 51.0 + 23.25 / 60.0),
+            ),
+            (
+                r#"33°52'7.7"S 151°12'33.5"E"#,
+                (
+                    -(33.0 + 52.0 / 60.0 + 7.7 / 3600.0),
+                    151.0 + 12.0 / 60.0 + 33.5 / 3600.0,
+                ),
+            ),
+            ("۳۵٫۷۳۱۹۸۴، ۵۱٫۳۹۲۶۸۵", (35.731984, 51.392685)),
+            ("۳۵°۴۳'۵۵٫۱\"N ۵۱°۲۳'۳۳٫۷\"E", dms),
+            ("٣٥.٧ , ٥١.٤", (35.7, 51.4)),
+        ];
+
+        for (input, (latitude, longitude)) in cases {
+            let point = Point::parse_flexible(input).unwrap_or_else(|err| {
+                panic!("{:?} was rejected: {}", input, err);
+            });
+            assert!(
+                (point.latitude - latitude).abs() < 1e-9
+                    && (point.longitude - longitude).abs() < 1e-9,
+                "{:?} parsed as {:?}",
+                input,
+                point
+            );
+        }
+    }
+
+    #[test]
+    fn rejected_formats() {
+        let cases = [
+            ("35.7", "expected two coordinates, found one"),
+            ("35.7, 51.4, 12", "expected two coordinates, found more"),
+            ("35.7x 51.4", "unexpected character 'x'"),
+            ("35.7.1, 51.4", "\"35.7.1\" is not a number"),
+            ("N, 51.4", "expected a number of degrees"),
+            ("35°61'N 51°E", "61 minutes is out of range"),
+            (
+                "35.5°30'N 51°E",
+                "degrees with minutes must be a whole number",
+            ),
+            (
+                "35.7N 51.4N",
+                "both coordinates are on the same axis, expected one of N/S and one of E/W",
+            ),
+            (
+                "35.7N, 51.4",
+                "only one of the coordinates has a hemisphere",
+            ),
+            (
+                "-35.7S 51.4E",
+                "a coordinate has both a sign and a hemisphere, use only one of them",
+            ),
+        ];
+
+        for (input, message) in cases {
+            match Point::parse_flexible(input) {
+                Ok(point) => panic!("{:?} was accepted as {:?}", input, point),
+                Err(err) => assert_eq!(err.to_string(), message, "{:?}", input),
+            }
+        }
+
+        assert!(matches!(
+            Point::parse_flexible("35.7N, 51.4").unwrap_err(),
+            ParsePointError::Ambiguous(_)
+        ));
+        match Point::parse_flexible("95N 51E").unwrap_err() {
+            ParsePointError::InvalidCoordinate(err) => assert_eq!(err.axis(), Axis::Latitude),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::protocol::Query;
+use std::fmt;
+
+/// neshan api endpoints that the client talks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Endpoint {
+    /// direction api, used by `Client::route`.
+    Route,
+    /// direction api version 4, used by `Client::route_with` with `DirectionVersion::V4`.
+    RouteV4,
+    /// direction api ignoring traffic, used by `Client::route_no_traffic`.
+    RouteNoTraffic,
+    /// reverse geocoding api, used by `Client::reverse_geocode`.
+    ReverseGeocode,
+    /// reverse geocoding api version 5, used by `Client::reverse_geocode_v5`.
+    ReverseGeocodeV5,
+    /// static map images, used by `Client::static_map_to`.
+    StaticMap,
+    /// distance matrix api, used by `Client::distance_matrix`.
+    DistanceMatrix,
+    /// distance matrix api ignoring traffic, used by `Client::distance_matrix_no_traffic`.
+    DistanceMatrixNoTraffic,
+    /// map matching api, used by `Client::map_match`.
+    MapMatching,
+    /// search api, used by `Client::search`.
+    Search,
+    /// geocoding api, used by `Client::geocode`.
+    Geocode,
+    /// trip api, used by `Client::trip`.
+    Trip,
+    /// raster tiles, used by `Client::tile`.
+    Tile,
+    /// endpoints the crate doesn't model, called with `Client::get_json` and
+    /// `Client::get_bytes`.
+    Custom,
+}
+
+impl Endpoint {
+    pub(crate) const ALL: [Endpoint; 14] = [
+        Endpoint::Route,
+        Endpoint::RouteV4,
+        Endpoint::RouteNoTraffic,
+        Endpoint::ReverseGeocode,
+        Endpoint::ReverseGeocodeV5,
+        Endpoint::StaticMap,
+        Endpoint::DistanceMatrix,
+        Endpoint::DistanceMatrixNoTraffic,
+        Endpoint::MapMatching,
+        Endpoint::Search,
+        Endpoint::Geocode,
+        Endpoint::Trip,
+        Endpoint::Tile,
+        Endpoint::Custom,
+    ];
+
+    /// stable label of the endpoint, suitable for logs and metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Endpoint::Route => "route",
+            Endpoint::RouteV4 => "route_v4",
+            Endpoint::RouteNoTraffic => "route_no_traffic",
+            Endpoint::ReverseGeocode => "reverse_geocode",
+            Endpoint::ReverseGeocodeV5 => "reverse_geocode_v5",
+            Endpoint::StaticMap => "static_map",
+            Endpoint::DistanceMatrix => "distance_matrix",
+            Endpoint::DistanceMatrixNoTraffic => "distance_matrix_no_traffic",
+            Endpoint::MapMatching => "map_matching",
+            Endpoint::Search => "search",
+            Endpoint::Geocode => "geocode",
+            Endpoint::Trip => "trip",
+            Endpoint::Tile => "tile",
+            Endpoint::Custom => "custom",
+        }
+    }
+
+    pub(crate) fn path(&self) -> &'static str {
+        match self {
+            Endpoint::Route => "/v3/direction",
+            Endpoint::RouteV4 => "/v4/direction",
+            Endpoint::RouteNoTraffic => "/v4/direction/no-traffic",
+            Endpoint::ReverseGeocode => "/v2/reverse",
+            Endpoint::ReverseGeocodeV5 => "/v5/reverse",
+            Endpoint::StaticMap => "/v4/static",
+            Endpoint::DistanceMatrix => "/v1/distance-matrix",
+            Endpoint::DistanceMatrixNoTraffic => "/v1/distance-matrix/no-traffic",
+            Endpoint::MapMatching => "/v3/map-matching",
+            Endpoint::Search => "/v1/search",
+            Endpoint::Geocode => "/v4/geocoding",
+            Endpoint::Trip => "/v3/trip",
+            Endpoint::Tile => "/v4/tile",
+            // the path of a custom call comes with the call.
+            Endpoint::Custom => "",
+        }
+    }
+}
+
+/// normalized identity of a request, query parameters are sorted so their order doesn't matter.
+/// they stay url-encoded, unlike the ones of `labeled_key`.
+pub(crate) fn request_key(endpoint: Endpoint, query: &Query) -> String {
+    let mut params: Vec<&str> = query.as_str().split('&').collect();
+    params.sort_unstable();
+
+    let mut key = endpoint.as_str().to_string();
+    if !query.is_empty() {
+        key.push('?');
+        key.push_str(&params.join("&"));
+    }
+
+    key
+}
+
+/// same as `request_key` under any label, e.g. one with the path of a custom call.
+pub(crate) fn labeled_key(label: &str, query: &[(&str, String)]) -> String {
+    let mut params: Vec<_> = query.iter().collect();
+    params.sort();
+
+    let mut key = label.to_string();
+    for (i, (name, value)) in params.into_iter().enumerate() {
+        key.push(if i == 0 { '?' } else { '&' });
+        key.push_str(name);
+        key.push('=');
+        key.push_str(value);
+    }
+
+    key
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
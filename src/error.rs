@@ -0,0 +1,436 @@
+use crate::point::InvalidCoordinate;
+use crate::quota::QuotaInfo;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// error body that neshan sends alongside non-success responses.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Error {
+    code: i32,
+    message: String,
+}
+
+impl Error {
+    pub(crate) fn new(code: i32, message: String) -> Error {
+        Error { code, message }
+    }
+
+    /// neshan specific error code, e.g. 480 for an unknown api key.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// human readable description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "AppError {{ code: {}, message: {} }}",
+            self.code, self.message
+        )
+    }
+}
+
+/// coarse classification of failures, used for deciding on retries and for reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// the request or the response body timed out.
+    Timeout,
+    /// connection to neshan could not be established.
+    Connect,
+    /// neshan responded with a 5xx status.
+    Server,
+    /// the request rate is too high (429 or neshan's 482).
+    RateLimited,
+    /// the plan quota is exhausted (neshan's 481).
+    Quota,
+    /// the api key is missing, unknown or not allowed to use the service.
+    Auth,
+    /// neshan rejected the request parameters.
+    InvalidRequest,
+    /// the requested resource does not exist.
+    NotFound,
+    /// the response body could not be decoded.
+    Decode,
+    /// the circuit breaker is open and the request was not sent.
+    CircuitOpen,
+    /// anything else.
+    Other,
+}
+
+impl ErrorKind {
+    pub(crate) const ALL: [ErrorKind; 11] = [
+        ErrorKind::Timeout,
+        ErrorKind::Connect,
+        ErrorKind::Server,
+        ErrorKind::RateLimited,
+        ErrorKind::Quota,
+        ErrorKind::Auth,
+        ErrorKind::InvalidRequest,
+        ErrorKind::NotFound,
+        ErrorKind::Decode,
+        ErrorKind::CircuitOpen,
+        ErrorKind::Other,
+    ];
+
+    /// stable label of the kind, suitable for logs and metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Connect => "connect",
+            ErrorKind::Server => "server",
+            ErrorKind::RateLimited => "rate_limited",
+            ErrorKind::Quota => "quota",
+            ErrorKind::Auth => "auth",
+            ErrorKind::InvalidRequest => "invalid_request",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Decode => "decode",
+            ErrorKind::CircuitOpen => "circuit_open",
+            ErrorKind::Other => "other",
+        }
+    }
+
+    /// classify a non-success http status. neshan reuses the 4xx range for its own
+    /// error codes (470 for bad coordinates, 48x for key and plan problems).
+    pub(crate) fn from_status(status: u16) -> ErrorKind {
+        match status {
+            401 | 403 | 480 | 483 | 484 | 485 => ErrorKind::Auth,
+            429 | 482 => ErrorKind::RateLimited,
+            481 => ErrorKind::Quota,
+            404 => ErrorKind::NotFound,
+            500..=599 => ErrorKind::Server,
+            400..=499 => ErrorKind::InvalidRequest,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// failed response from neshan with its status code and error body.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    status: u16,
+    error: Error,
+    retry_after: Option<Duration>,
+    quota: Option<QuotaInfo>,
+    // boxed to keep `NeshanError` small.
+    request_id: Option<Box<str>>,
+}
+
+impl ApiError {
+    pub(crate) fn new(
+        status: u16,
+        error: Error,
+        retry_after: Option<Duration>,
+        quota: Option<QuotaInfo>,
+        request_id: Option<Box<str>>,
+    ) -> ApiError {
+        ApiError {
+            status,
+            error,
+            retry_after,
+            quota,
+            request_id,
+        }
+    }
+
+    /// http status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// error body neshan sent, or the raw body text when it wasn't the usual json.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// delay requested by the `Retry-After` header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// neshan sometimes answers 200 with an error body, its code classifies those instead.
+    fn classify(&self) -> ErrorKind {
+        match self.status {
+            200..=299 => u16::try_from(self.error.code)
+                .map(ErrorKind::from_status)
+                .unwrap_or(ErrorKind::Other),
+            status => ErrorKind::from_status(status),
+        }
+    }
+
+    /// quota headers of the response, mostly useful when the request was rate limited.
+    pub fn quota(&self) -> Option<QuotaInfo> {
+        self.quota
+    }
+
+    /// id neshan gave the request, see `NeshanError::request_id`.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+}
+
+/// ` (request id ...)` when there is one, for the end of error messages.
+struct RequestId<'a>(Option<&'a str>);
+
+impl fmt::Display for RequestId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(id) => write!(f, " (request id {})", id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// errors returned by the client.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum NeshanError {
+    /// the request did not produce a response, e.g. connection failures or timeouts.
+    Transport {
+        kind: ErrorKind,
+        source: Arc<dyn std::error::Error + Send + Sync>,
+    },
+    /// neshan answered with a non-success status.
+    Api(ApiError),
+    /// neshan throttled the request.
+    RateLimited(ApiError),
+    /// the response body does not match the expected model.
+    Decode {
+        source: Arc<serde_json::Error>,
+        request_id: Option<String>,
+    },
+    /// the client configuration is invalid.
+    Config(String),
+    /// the call did not finish before its deadline, `attempts` counts the requests that were sent.
+    DeadlineExceeded { deadline: Duration, attempts: u32 },
+    /// the circuit breaker is open, `retry_in` is the remaining cool down.
+    CircuitOpen { retry_in: Duration },
+    /// neshan answered with a content type other than the expected one.
+    UnexpectedContentType {
+        content_type: String,
+        request_id: Option<String>,
+    },
+    /// a streamed download failed after `written` bytes were already written out.
+    Interrupted {
+        written: u64,
+        kind: ErrorKind,
+        source: Arc<dyn std::error::Error + Send + Sync>,
+        request_id: Option<String>,
+    },
+    /// a point was rejected before sending, see `ClientBuilder::validate_points`.
+    InvalidCoordinate(InvalidCoordinate),
+    /// the request was rejected before sending, e.g. a static map over the size limits.
+    InvalidRequest(String),
+}
+
+impl NeshanError {
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn from_reqwest(err: reqwest::Error) -> NeshanError {
+        let kind = if err.is_timeout() {
+            ErrorKind::Timeout
+        } else if err.is_connect() {
+            ErrorKind::Connect
+        } else if err.is_decode() {
+            ErrorKind::Decode
+        } else {
+            ErrorKind::Other
+        };
+
+        NeshanError::Transport {
+            kind,
+            source: Arc::new(err),
+        }
+    }
+
+    pub(crate) fn from_api(err: ApiError) -> NeshanError {
+        match err.classify() {
+            ErrorKind::RateLimited => NeshanError::RateLimited(err),
+            _ => NeshanError::Api(err),
+        }
+    }
+
+    /// classification of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            NeshanError::Transport { kind, .. } => *kind,
+            NeshanError::Api(err) | NeshanError::RateLimited(err) => err.classify(),
+            NeshanError::Decode { .. } => ErrorKind::Decode,
+            NeshanError::Config(_) => ErrorKind::Other,
+            NeshanError::DeadlineExceeded { .. } => ErrorKind::Timeout,
+            NeshanError::CircuitOpen { .. } => ErrorKind::CircuitOpen,
+            NeshanError::UnexpectedContentType { .. } => ErrorKind::Decode,
+            NeshanError::Interrupted { kind, .. } => *kind,
+            NeshanError::InvalidCoordinate(_) | NeshanError::InvalidRequest(_) => {
+                ErrorKind::InvalidRequest
+            }
+        }
+    }
+
+    /// http status code when neshan responded at all.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            NeshanError::Api(err) | NeshanError::RateLimited(err) => Some(err.status),
+            _ => None,
+        }
+    }
+
+    /// delay requested by neshan before trying again.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            NeshanError::Api(err) | NeshanError::RateLimited(err) => err.retry_after,
+            _ => None,
+        }
+    }
+
+    /// id of the request taken from the `x-request-id` style headers of the response, the
+    /// one to give neshan's support. `None` when no response came back, it was served from
+    /// the cache or neshan didn't send one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            NeshanError::Api(err) | NeshanError::RateLimited(err) => err.request_id(),
+            NeshanError::Decode { request_id, .. }
+            | NeshanError::UnexpectedContentType { request_id, .. }
+            | NeshanError::Interrupted { request_id, .. } => request_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// attach the request id of the response the error came from, e.g. to decode errors.
+    pub(crate) fn with_request_id(mut self, id: Option<&str>) -> NeshanError {
+        if let NeshanError::Decode { request_id, .. }
+        | NeshanError::UnexpectedContentType { request_id, .. }
+        | NeshanError::Interrupted { request_id, .. } = &mut self
+        {
+            if request_id.is_none() {
+                *request_id = id.map(str::to_string);
+            }
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for NeshanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NeshanError::Transport { source, .. } => write!(f, "request failed: {}", source),
+            NeshanError::Api(err) => write!(
+                f,
+                "neshan error {}: {}{}",
+                err.status,
+                err.error,
+                RequestId(err.request_id())
+            ),
+            NeshanError::RateLimited(err) => write!(
+                f,
+                "rate limited: {}{}",
+                err.error,
+                RequestId(err.request_id())
+            ),
+            NeshanError::Decode { source, request_id } => write!(
+                f,
+                "invalid response body: {}{}",
+                source,
+                RequestId(request_id.as_deref())
+            ),
+            NeshanError::Config(msg) => write!(f, "invalid configuration: {}", msg),
+            NeshanError::DeadlineExceeded { deadline, attempts } => write!(
+                f,
+                "deadline of {:?} exceeded after {} attempt(s)",
+                deadline, attempts
+            ),
+            NeshanError::CircuitOpen { retry_in } => {
+                write!(f, "circuit breaker is open for another {:?}", retry_in)
+            }
+            NeshanError::UnexpectedContentType {
+                content_type,
+                request_id,
+            } => write!(
+                f,
+                "unexpected content type: {}{}",
+                content_type,
+                RequestId(request_id.as_deref())
+            ),
+            NeshanError::Interrupted {
+                written,
+                source,
+                request_id,
+                ..
+            } => write!(
+                f,
+                "download interrupted after {} bytes: {}{}",
+                written,
+                source,
+                RequestId(request_id.as_deref())
+            ),
+            NeshanError::InvalidCoordinate(err) => write!(f, "invalid point: {}", err),
+            NeshanError::InvalidRequest(msg) => write!(f, "invalid request: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for NeshanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NeshanError::Transport { source, .. } | NeshanError::Interrupted { source, .. } => {
+                Some(source.as_ref())
+            }
+            NeshanError::Api(err) | NeshanError::RateLimited(err) => Some(&err.error),
+            NeshanError::Decode { source, .. } => Some(source.as_ref()),
+            NeshanError::InvalidCoordinate(err) => Some(err),
+            NeshanError::Config(_)
+            | NeshanError::DeadlineExceeded { .. }
+            | NeshanError::CircuitOpen { .. }
+            | NeshanError::UnexpectedContentType { .. }
+            | NeshanError::InvalidRequest(_) => None,
+        }
+    }
+}
+
+impl From<InvalidCoordinate> for NeshanError {
+    fn from(err: InvalidCoordinate) -> NeshanError {
+        NeshanError::InvalidCoordinate(err)
+    }
+}
+
+impl From<serde_json::Error> for NeshanError {
+    fn from(err: serde_json::Error) -> NeshanError {
+        NeshanError::Decode {
+            source: Arc::new(err),
+            request_id: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorKind;
+
+    #[test]
+    fn status_classification() {
+        assert_eq!(ErrorKind::from_status(480), ErrorKind::Auth);
+        assert_eq!(ErrorKind::from_status(403), ErrorKind::Auth);
+        assert_eq!(ErrorKind::from_status(470), ErrorKind::InvalidRequest);
+        assert_eq!(ErrorKind::from_status(481), ErrorKind::Quota);
+        assert_eq!(ErrorKind::from_status(482), ErrorKind::RateLimited);
+        assert_eq!(ErrorKind::from_status(429), ErrorKind::RateLimited);
+        assert_eq!(ErrorKind::from_status(503), ErrorKind::Server);
+    }
+}
@@ -0,0 +1,419 @@
+//! realistic responses for the tests of applications, compiled only with the `test-utils`
+//! feature.
+//!
+//! ```
+//! use neshan_rs::fake;
+//!
+//! let route = fake::route().legs(3).total_km(42.0).seed(7).build();
+//! assert_eq!(route.legs.len(), 3);
+//! assert!((route.total_distance().kilometers() - 42.0).abs() < 1e-6);
+//!
+//! let address = fake::postal_address().city("تهران").build();
+//! assert_eq!(address.city, "تهران");
+//! ```
+//!
+//! values depend only on the options and the seed, the same builder always builds the same
+//! value. legs add up to the total, steps add up to their leg and the geometry follows the
+//! steps, so the values pass the checks of the crate itself.
+
+use crate::polyline::{self, Precision};
+use crate::random::Lcg;
+use crate::{
+    Distance, Duration, EncodedPolyline, Leg, Locale, Point, PostalAddress, Route, Routes, Step,
+};
+
+const ROADS: [&str; 8] = [
+    "خیابان آزادی",
+    "بزرگراه شهید همت",
+    "خیابان ولیعصر",
+    "بزرگراه چمران",
+    "خیابان انقلاب",
+    "بزرگراه نیایش",
+    "خیابان شریعتی",
+    "بزرگراه مدرس",
+];
+
+const TURNS: [&str; 4] = [
+    "به سمت راست بپیچید",
+    "به سمت چپ بپیچید",
+    "مستقیم ادامه دهید",
+    "کمی به راست بپیچید",
+];
+
+const NEIGHBOURHOODS: [&str; 6] = [
+    "قزل قلعه",
+    "یوسف آباد",
+    "ونک",
+    "تجریش",
+    "نارمک",
+    "سعادت آباد",
+];
+
+/// the shared generator with the helpers of the builders.
+struct Random(Lcg);
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        Random(Lcg::new(seed ^ 0x5EED_F4CE))
+    }
+
+    /// number in [0, 1).
+    fn next(&mut self) -> f64 {
+        self.0.next_f64()
+    }
+
+    fn between(&mut self, low: f64, high: f64) -> f64 {
+        low + self.next() * (high - low)
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[(self.next() * items.len() as f64) as usize]
+    }
+
+    /// `total` split into `parts` random shares, which add up to it exactly.
+    fn split(&mut self, total: f64, parts: usize) -> Vec<f64> {
+        let weights: Vec<f64> = (0..parts).map(|_| self.between(0.5, 1.5)).collect();
+        Random::share(total, &weights)
+    }
+
+    /// `total` split in proportion to `weights`, the shares add up to it exactly.
+    fn share(total: f64, weights: &[f64]) -> Vec<f64> {
+        let parts = weights.len();
+        let sum: f64 = weights.iter().sum();
+        let mut shares: Vec<f64> = weights.iter().map(|w| total * w / sum).collect();
+        let rest = total - shares[..parts - 1].iter().sum::<f64>();
+        shares[parts - 1] = rest;
+        shares
+    }
+}
+
+/// a builder of a fake `Route`.
+pub fn route() -> RouteBuilder {
+    RouteBuilder {
+        legs: 1,
+        steps: 3,
+        total_km: None,
+        speed: None,
+        start: Point::new_unchecked(35.6997, 51.338),
+        seed: 0,
+    }
+}
+
+/// a builder of a fake `PostalAddress`.
+pub fn postal_address() -> PostalAddressBuilder {
+    PostalAddressBuilder {
+        city: None,
+        neighbourhood: None,
+        route_name: None,
+        in_traffic_zone: None,
+        in_odd_even_zone: None,
+        seed: 0,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RouteBuilder {
+    legs: usize,
+    steps: usize,
+    total_km: Option<f64>,
+    speed: Option<f64>,
+    start: Point,
+    seed: u64,
+}
+
+impl RouteBuilder {
+    /// number of legs, one by default and at least one.
+    pub fn legs(mut self, legs: usize) -> RouteBuilder {
+        self.legs = legs.max(1);
+        self
+    }
+
+    /// number of steps of each leg, three by default. zero builds legs without steps.
+    pub fn steps(mut self, steps: usize) -> RouteBuilder {
+        self.steps = steps;
+        self
+    }
+
+    /// length of the whole route, random between 2 and 40 km by default.
+    pub fn total_km(mut self, km: f64) -> RouteBuilder {
+        self.total_km = Some(km);
+        self
+    }
+
+    /// mean speed in km/h, every leg gets a random speed around 40 km/h by default.
+    pub fn speed(mut self, kmh: f64) -> RouteBuilder {
+        self.speed = Some(kmh);
+        self
+    }
+
+    /// where the route starts, tehran by default.
+    pub fn start(mut self, start: Point) -> RouteBuilder {
+        self.start = start;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> RouteBuilder {
+        self.seed = seed;
+        self
+    }
+
+    pub fn build(&self) -> Route {
+        let mut random = Random::new(self.seed);
+        let total = match self.total_km {
+            Some(km) => km * 1000.0,
+            None => random.between(2.0, 40.0).round() * 1000.0,
+        };
+
+        let mut position = self.start;
+        let mut bearing = random.between(0.0, 360.0);
+        let mut geometry = vec![position];
+        let mut walk = |meters: f64, random: &mut Random| {
+            bearing = (bearing + random.between(-60.0, 60.0)).rem_euclid(360.0);
+            let from = position;
+            position = position.destination(bearing, meters);
+            geometry.push(position);
+            polyline::encode(&[from, position], Precision::Five)
+        };
+
+        let legs = random
+            .split(total, self.legs)
+            .into_iter()
+            .map(|meters| {
+                let speed = self.speed.unwrap_or_else(|| random.between(25.0, 55.0));
+                let seconds = (meters * 3.6 / speed).round();
+                let name = random.pick(&ROADS);
+
+                let steps = match self.steps {
+                    0 => {
+                        walk(meters, &mut random);
+                        Vec::new()
+                    }
+                    count => {
+                        // steps drive a little faster or slower than the leg as a whole.
+                        let lengths = random.split(meters, count);
+                        let weights: Vec<f64> = lengths
+                            .iter()
+                            .map(|meters| meters * random.between(0.8, 1.25))
+                            .collect();
+                        let durations = Random::share(seconds, &weights);
+                        lengths
+                            .into_iter()
+                            .zip(durations)
+                            .enumerate()
+                            .map(|(index, (meters, seconds))| {
+                                let road = random.pick(&ROADS);
+                                let instruction = match index {
+                                    0 => format!("به سمت {} حرکت کنید", road),
+                                    _ => format!("{} به {}", random.pick(&TURNS), road),
+                                };
+                                Step {
+                                    name: road.to_string(),
+                                    instruction,
+                                    distance: Distance::humanized(meters),
+                                    duration: Duration::humanized(seconds),
+                                    polyline: Some(walk(meters, &mut random)),
+                                    maneuver: None,
+                                    modifier: None,
+                                    exit: None,
+                                    bearing_after: None,
+                                    start_location: None,
+                                }
+                            })
+                            .collect()
+                    }
+                };
+
+                Leg {
+                    summary: format!("{} - {}", name, random.pick(&ROADS)),
+                    duration: Duration::humanized(seconds),
+                    distance: Distance::humanized(meters),
+                    steps,
+                }
+            })
+            .collect();
+
+        Route {
+            legs,
+            overview_polyline: Some(EncodedPolyline {
+                points: polyline::encode(&geometry, Precision::Five),
+            }),
+        }
+    }
+
+    /// `Routes` with the built route as its only one.
+    pub fn build_routes(&self) -> Routes {
+        Routes {
+            routes: vec![self.build()],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PostalAddressBuilder {
+    city: Option<String>,
+    neighbourhood: Option<String>,
+    route_name: Option<String>,
+    in_traffic_zone: Option<bool>,
+    in_odd_even_zone: Option<bool>,
+    seed: u64,
+}
+
+impl PostalAddressBuilder {
+    /// city of the address, tehran by default.
+    pub fn city(mut self, city: &str) -> PostalAddressBuilder {
+        self.city = Some(city.to_string());
+        self
+    }
+
+    pub fn neighbourhood(mut self, neighbourhood: &str) -> PostalAddressBuilder {
+        self.neighbourhood = Some(neighbourhood.to_string());
+        self
+    }
+
+    pub fn route_name(mut self, route_name: &str) -> PostalAddressBuilder {
+        self.route_name = Some(route_name.to_string());
+        self
+    }
+
+    /// random by default, an address in the traffic zone is in the odd even zone as well
+    /// unless set otherwise.
+    pub fn in_traffic_zone(mut self, inside: bool) -> PostalAddressBuilder {
+        self.in_traffic_zone = Some(inside);
+        self
+    }
+
+    pub fn in_odd_even_zone(mut self, inside: bool) -> PostalAddressBuilder {
+        self.in_odd_even_zone = Some(inside);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> PostalAddressBuilder {
+        self.seed = seed;
+        self
+    }
+
+    pub fn build(&self) -> PostalAddress {
+        let mut random = Random::new(self.seed);
+        let city = self.city.clone().unwrap_or_else(|| "تهران".to_string());
+        let neighbourhood = self
+            .neighbourhood
+            .clone()
+            .unwrap_or_else(|| random.pick(&NEIGHBOURHOODS).to_string());
+        let route_name = self
+            .route_name
+            .clone()
+            .unwrap_or_else(|| random.pick(&ROADS).to_string());
+        let zone = (random.between(1.0, 23.0) as u32).to_string();
+        let in_traffic_zone = self.in_traffic_zone.unwrap_or_else(|| random.next() < 0.3);
+        let in_odd_even_zone = self.in_odd_even_zone.unwrap_or(in_traffic_zone);
+
+        PostalAddress {
+            formatted_address: format!(
+                "{}، منطقه {}، {}، {}",
+                city,
+                Locale::Persian.number(zone.clone()),
+                neighbourhood,
+                route_name
+            ),
+            route_name,
+            neighbourhood: Some(neighbourhood),
+            state: format!("استان {}", city),
+            city,
+            place: None,
+            municipality_zone: Some(zone),
+            in_traffic_zone,
+            in_odd_even_zone,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{postal_address, route};
+    use crate::{Point, Route};
+
+    fn length(points: &[Point]) -> f64 {
+        points
+            .windows(2)
+            .map(|pair| pair[0].haversine_distance_to(&pair[1]))
+            .sum()
+    }
+
+    #[test]
+    fn deterministic() {
+        let builder = route().legs(3).total_km(42.0).seed(7);
+        assert_eq!(builder.build(), builder.build());
+        assert_eq!(builder.clone().seed(7).build(), builder.build());
+        assert_ne!(builder.clone().seed(8).build(), builder.build());
+        assert_eq!(route().build(), route().build());
+
+        let address = postal_address().seed(3);
+        assert_eq!(address.build(), address.build());
+    }
+
+    #[test]
+    fn route_invariants() {
+        for seed in 0..20 {
+            let route: Route = route().legs(3).total_km(42.0).seed(seed).build();
+            assert_eq!(route.legs.len(), 3);
+            assert!((route.total_distance().value - 42_000.0).abs() < 1e-6);
+
+            for leg in &route.legs {
+                assert_eq!(leg.steps.len(), 3);
+                let meters: f64 = leg.steps.iter().map(|step| step.distance.value).sum();
+                let seconds: f64 = leg.steps.iter().map(|step| step.duration.value).sum();
+                assert!((meters - leg.distance.value).abs() < 1e-6);
+                assert!((seconds - leg.duration.value).abs() < 1e-6);
+                assert_eq!(
+                    leg.distance.text,
+                    leg.distance.humanize(crate::Locale::Persian)
+                );
+                for step in &leg.steps {
+                    let geometry = crate::polyline::decode(
+                        step.polyline.as_deref().unwrap(),
+                        crate::polyline::Precision::Five,
+                    )
+                    .unwrap();
+                    assert!((length(&geometry) - step.distance.value).abs() < 5.0);
+                }
+            }
+
+            let geometry = route.geometry().unwrap();
+            assert_eq!(geometry.len(), 10);
+            assert!((length(&geometry) - 42_000.0).abs() < 20.0);
+            assert_eq!(route.instructions().len(), 9);
+            assert!(route.speed_profile().iter().all(|segment| {
+                segment
+                    .speed
+                    .is_some_and(|speed| (10.0..100.0).contains(&speed))
+            }));
+        }
+
+        let route = route().steps(0).speed(36.0).total_km(3.6).build();
+        assert!(route.legs[0].steps.is_empty());
+        assert_eq!(route.total_duration().value, 360.0);
+        assert_eq!(route.geometry().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn address() {
+        let address = postal_address()
+            .city("اصفهان")
+            .neighbourhood("جلفا")
+            .build();
+        assert_eq!(address.city, "اصفهان");
+        assert_eq!(address.state, "استان اصفهان");
+        assert!(address.formatted_address.starts_with("اصفهان، منطقه "));
+        assert!(address
+            .formatted_address
+            .ends_with(&format!("جلفا، {}", address.route_name)));
+
+        let address = postal_address().in_traffic_zone(true).build();
+        assert!(address.in_traffic_zone && address.in_odd_even_zone);
+        let address = postal_address()
+            .in_traffic_zone(true)
+            .in_odd_even_zone(false)
+            .build();
+        assert!(!address.in_odd_even_zone);
+    }
+}
@@ -0,0 +1,194 @@
+//! conversions between the crate's types and geo-types, compiled only with the `geo` feature.
+//! geo-types puts longitude on the x axis and latitude on the y axis.
+
+use crate::polyline::{self, Precision};
+use crate::{EncodedPolyline, Isochrone, Point, Route, Routes};
+use geo_types::{LineString, MultiLineString, Polygon};
+
+impl From<geo_types::Point<f64>> for Point {
+    fn from(point: geo_types::Point<f64>) -> Point {
+        Point::from(point.0)
+    }
+}
+
+impl From<Point> for geo_types::Point<f64> {
+    fn from(point: Point) -> geo_types::Point<f64> {
+        geo_types::Point(point.into())
+    }
+}
+
+impl From<geo_types::Coord<f64>> for Point {
+    fn from(coord: geo_types::Coord<f64>) -> Point {
+        Point {
+            longitude: coord.x,
+            latitude: coord.y,
+        }
+    }
+}
+
+impl From<Point> for geo_types::Coord<f64> {
+    fn from(point: Point) -> geo_types::Coord<f64> {
+        geo_types::Coord {
+            x: point.longitude,
+            y: point.latitude,
+        }
+    }
+}
+
+impl Route {
+    /// overview geometry of the route, `None` when neshan didn't send one or it is malformed.
+    pub fn to_line_string(&self) -> Option<LineString<f64>> {
+        let overview = self.overview_polyline.as_ref()?;
+        let points = polyline::decode(&overview.points, Precision::Five).ok()?;
+
+        Some(points.into_iter().map(geo_types::Coord::from).collect())
+    }
+
+    /// route without legs whose overview geometry is the given line, e.g. for tests.
+    pub fn from_line_string(line: &LineString<f64>) -> Route {
+        let points: Vec<Point> = line.coords().copied().map(Point::from).collect();
+
+        Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: polyline::encode(&points, Precision::Five),
+            }),
+        }
+    }
+}
+
+impl Routes {
+    /// overview geometries of every route, `None` when any of them is missing.
+    pub fn to_multi_line_string(&self) -> Option<MultiLineString<f64>> {
+        self.routes
+            .iter()
+            .map(Route::to_line_string)
+            .collect::<Option<Vec<_>>>()
+            .map(MultiLineString::new)
+    }
+}
+
+impl Isochrone {
+    /// the polygon of the area, see `Isochrone::polygon`.
+    pub fn to_polygon(&self) -> Polygon<f64> {
+        let exterior: LineString<f64> = self
+            .polygon()
+            .into_iter()
+            .map(geo_types::Coord::from)
+            .collect();
+
+        Polygon::new(exterior, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Isochrone, IsochroneRay, Point, Route, Routes};
+    use geo_types::{line_string, LineString};
+
+    const TEHRAN: Point = Point {
+        longitude: 51.389,
+        latitude: 35.689,
+    };
+
+    #[test]
+    fn point_round_trip() {
+        let point: geo_types::Point<f64> = TEHRAN.into();
+        assert_eq!(point.x(), 51.389);
+        assert_eq!(point.y(), 35.689);
+
+        assert_eq!(Point::from(point), TEHRAN);
+    }
+
+    #[test]
+    fn coord_round_trip() {
+        let coord: geo_types::Coord<f64> = TEHRAN.into();
+        assert_eq!(coord.x, 51.389);
+        assert_eq!(coord.y, 35.689);
+
+        assert_eq!(Point::from(coord), TEHRAN);
+    }
+
+    fn routes() -> Routes {
+        serde_json::from_value(serde_json::json!({
+            "routes": [{
+                "overview_polyline": { "points": "_p~iF~ps|U_ulLnnqC_mqNvxq`@" },
+                "legs": []
+            }, {
+                "overview_polyline": { "points": "_p~iF~ps|U" },
+                "legs": []
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn line_string_is_longitude_then_latitude() {
+        let line = routes().routes[0].to_line_string().unwrap();
+
+        // neshan writes `lat,lng`, geo-types wants x for longitude and y for latitude.
+        let first = line.0[0];
+        assert_eq!(first.x, -120.2);
+        assert_eq!(first.y, 38.5);
+        assert_eq!(
+            line,
+            line_string![
+                (x: -120.2, y: 38.5),
+                (x: -120.95, y: 40.7),
+                (x: -126.453, y: 43.252),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_line_string() {
+        let lines = routes().to_multi_line_string().unwrap();
+
+        assert_eq!(lines.0.len(), 2);
+        assert_eq!(lines.0[1], line_string![(x: -120.2, y: 38.5)]);
+
+        let mut routes = routes();
+        routes.routes[1].overview_polyline = None;
+        assert!(routes.routes[1].to_line_string().is_none());
+        assert!(routes.to_multi_line_string().is_none());
+    }
+
+    #[test]
+    fn line_string_round_trip() {
+        let line: LineString<f64> = line_string![
+            (x: 51.389, y: 35.689),
+            (x: 51.39071, y: 35.70012),
+            (x: 50.93912, y: 35.84003),
+        ];
+
+        let route = Route::from_line_string(&line);
+        assert!(route.legs.is_empty());
+        assert_eq!(route.to_line_string().unwrap(), line);
+        assert_eq!(
+            route.geometry().unwrap()[0],
+            Point::new_unchecked(35.689, 51.389)
+        );
+    }
+
+    #[test]
+    fn isochrone_polygon_is_closed() {
+        let ray = |bearing, radius| IsochroneRay {
+            bearing,
+            radius,
+            reachable: true,
+            truncated: false,
+        };
+        let isochrone = Isochrone {
+            center: TEHRAN,
+            max_duration: 600.0,
+            rays: vec![ray(0.0, 1000.0), ray(120.0, 2000.0), ray(240.0, 500.0)],
+        };
+
+        let polygon = isochrone.to_polygon();
+        let exterior = polygon.exterior();
+        assert_eq!(exterior.0.len(), 4);
+        assert_eq!(exterior.0[0], exterior.0[3]);
+        assert!(exterior.0[0].y > TEHRAN.latitude);
+        assert!(polygon.interiors().is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::search::xy::{self, Xy};
+use crate::Point;
+use serde::{Deserialize, Serialize};
+
+/// an address resolved by the geocoding api.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeocodeResult {
+    /// `OK` for a resolved address.
+    pub status: String,
+    /// name of the place the address resolved to, when neshan sends one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// sent by neshan as `{ "x": lng, "y": lat }`.
+    #[serde(with = "xy")]
+    pub location: Point,
+}
+
+/// response of the geocoding api, which leaves out the location of an address it couldn't
+/// resolve.
+#[derive(Deserialize)]
+pub(crate) struct Geocoding {
+    status: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    location: Option<Xy>,
+}
+
+impl Geocoding {
+    pub(crate) fn result(self) -> Option<GeocodeResult> {
+        let location = self.location?;
+
+        Some(GeocodeResult {
+            status: self.status,
+            title: self.title,
+            location: location.into(),
+        })
+    }
+}
+
+impl GeocodeResult {
+    /// the result of a `/v4/geocoding` body, `None` when the address wasn't resolved.
+    pub fn from_json(json: &str) -> Result<Option<GeocodeResult>, NeshanError> {
+        Ok(serde_json::from_str::<Geocoding>(json)?.result())
+    }
+}
+
+impl Client {
+    /// coordinates of a free text address, e.g. `تهران، میدان آزادی`. `None` when neshan
+    /// couldn't resolve it.
+    /// https://platform.neshan.org/api/geocoding
+    pub async fn geocode(&self, address: &str) -> Result<Option<GeocodeResult>, NeshanError> {
+        let query = crate::protocol::geocode_query(address);
+        let call = self.get::<Geocoding>(Endpoint::Geocode, &query);
+
+        crate::trace::instrument(Endpoint::Geocode, &[], call)
+            .await
+            .map(|(geocoding, _)| geocoding.result())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GeocodeResult;
+    use crate::client::Client;
+    use crate::error::ErrorKind;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn decode_results() {
+        let azadi = GeocodeResult::from_json(include_str!("../fixtures/geocode.json"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(azadi.status, "OK");
+        assert_eq!(azadi.title.as_deref(), Some("میدان آزادی"));
+
+        let missing = include_str!("../fixtures/geocode_not_found.json");
+        assert_eq!(GeocodeResult::from_json(missing).unwrap(), None);
+        assert!(GeocodeResult::from_json(r#"{"status": "OK", "location": {"x": 51.3}}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn geocode_address() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/geocoding"))
+            .and(query_param("address", "تهران، میدان آزادی"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/geocode.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v4/geocoding"))
+            .and(query_param("address", "ناکجاآباد"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/geocode_not_found.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v4/geocoding"))
+            .and(query_param("address", "خطا"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let azadi = client.geocode("تهران، میدان آزادی").await.unwrap().unwrap();
+        assert!((azadi.location.latitude - 35.6997).abs() < 1e-3);
+        assert!((azadi.location.longitude - 51.3380).abs() < 1e-3);
+
+        assert_eq!(client.geocode("ناکجاآباد").await.unwrap(), None);
+        let err = client.geocode("خطا").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Server);
+    }
+}
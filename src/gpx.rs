@@ -0,0 +1,226 @@
+//! reading gps tracks out of gpx files, compiled only with the `gpx` feature.
+//! <https://www.topografix.com/gpx.asp>
+
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::{MapMatchOptions, MatchedPoint, MatchedTrace, Point};
+use quick_xml::events::Event;
+use quick_xml::{Reader, XmlVersion};
+use std::fmt;
+
+/// a `trkpt` of a gpx track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub point: Point,
+    /// the `time` of the point as written in the file, usually iso 8601 in utc.
+    pub time: Option<String>,
+}
+
+/// a gpx track snapped onto the roads, with the points it was read from. neshan's map
+/// matching only takes positions, the times stay here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedTrack {
+    pub trace: MatchedTrace,
+    /// the points of the file, indexed by `MatchedPoint::original_index`.
+    pub track: Vec<TrackPoint>,
+}
+
+impl MatchedTrack {
+    /// the `time` of the track point a snapped point came from, `None` for points neshan
+    /// added along the road and track points without one.
+    pub fn time(&self, point: &MatchedPoint) -> Option<&str> {
+        self.track.get(point.original_index?)?.time.as_deref()
+    }
+}
+
+/// error of reading a gpx file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpxError {
+    /// line of the file the error is at, starting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for GpxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid gpx at line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for GpxError {}
+
+/// the points of every track segment of a gpx file in order, waypoints and routes are
+/// ignored. a file without track points is an error.
+pub fn parse(xml: &str) -> Result<Vec<TrackPoint>, GpxError> {
+    let line = |position: u64| {
+        1 + xml.as_bytes()[..(position as usize).min(xml.len())]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count()
+    };
+    let error = |position: u64, message: String| GpxError {
+        line: line(position),
+        message,
+    };
+
+    let mut reader = Reader::from_str(xml);
+    let mut points = Vec::new();
+    // the start of the current `trkpt` and whether we are within its `time`.
+    let mut current: Option<TrackPoint> = None;
+    let mut in_time = false;
+
+    loop {
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|err| error(reader.error_position(), err.to_string()))?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) if e.local_name().as_ref() == "trkpt" => {
+                let coordinate = |name: &str| -> Result<f64, GpxError> {
+                    let value = e
+                        .try_get_attribute(name)
+                        .map_err(|err| error(position, err.to_string()))?
+                        .ok_or_else(|| error(position, format!("trkpt without {}", name)))?
+                        .normalized_value(XmlVersion::Implicit1_0)
+                        .map_err(|err| error(position, err.to_string()))?;
+                    value.trim().parse().map_err(|_| {
+                        error(position, format!("{} {:?} is not a number", name, value))
+                    })
+                };
+                let point = Point::new(coordinate("lat")?, coordinate("lon")?)
+                    .map_err(|err| error(position, err.to_string()))?;
+
+                let point = TrackPoint { point, time: None };
+                if matches!(event, Event::Empty(_)) {
+                    points.push(point);
+                } else {
+                    current = Some(point);
+                }
+            }
+            Event::Start(ref e) if e.local_name().as_ref() == "time" && current.is_some() => {
+                in_time = true;
+            }
+            Event::Text(ref e) if in_time => {
+                if let Some(point) = current.as_mut() {
+                    let time = point.time.get_or_insert_with(String::new);
+                    time.push_str(e.xml10_content().trim());
+                }
+            }
+            Event::End(ref e) if e.local_name().as_ref() == "time" => in_time = false,
+            Event::End(ref e) if e.local_name().as_ref() == "trkpt" => {
+                points.extend(current.take());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if points.is_empty() {
+        return Err(error(xml.len() as u64, "no track points".to_string()));
+    }
+
+    Ok(points)
+}
+
+impl Client {
+    /// snap the track of a gpx file onto the roads, with the default `MapMatchOptions`.
+    pub async fn map_match_gpx(&self, xml: &str) -> Result<MatchedTrack, NeshanError> {
+        self.map_match_gpx_with(xml, &MapMatchOptions::default())
+            .await
+    }
+
+    /// same as `map_match_gpx`, thinning out long tracks as the options say. a file that can't
+    /// be read is an `InvalidRequest` error and nothing is sent.
+    pub async fn map_match_gpx_with(
+        &self,
+        xml: &str,
+        options: &MapMatchOptions,
+    ) -> Result<MatchedTrack, NeshanError> {
+        let track = parse(xml).map_err(|err| NeshanError::InvalidRequest(err.to_string()))?;
+        let points: Vec<Point> = track.iter().map(|point| point.point).collect();
+        let trace = self.map_match_with(&points, options).await?;
+
+        Ok(MatchedTrack { trace, track })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::Point;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TRACK: &str = include_str!("../fixtures/track.gpx");
+
+    #[test]
+    fn parse_track() {
+        let points = parse(TRACK).unwrap();
+
+        assert_eq!(points.len(), 5);
+        assert_eq!(points[0].point, Point::new_unchecked(35.6997, 51.338));
+        assert_eq!(points[0].time.as_deref(), Some("2024-05-01T07:30:00Z"));
+        // the second segment follows the first one.
+        assert_eq!(points[3].point, Point::new_unchecked(35.7008, 51.375));
+        assert_eq!(points[3].time, None);
+        assert_eq!(points[4].time.as_deref(), Some("2024-05-01T07:35:40Z"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let missing = "<gpx>\n<trk><trkseg>\n<trkpt lat=\"35.7\"/>\n</trkseg></trk></gpx>";
+        let err = parse(missing).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.to_string(), "invalid gpx at line 3: trkpt without lon");
+
+        let invalid = "<gpx><trk><trkseg>\n\n<trkpt lat=\"north\" lon=\"51.4\"/>";
+        assert_eq!(parse(invalid).unwrap_err().line, 3);
+
+        let outside = "<gpx><trk><trkseg><trkpt lat=\"135.7\" lon=\"51.4\"/></trkseg></trk></gpx>";
+        assert_eq!(parse(outside).unwrap_err().line, 1);
+
+        let empty = "<gpx>\n<wpt lat=\"35.7\" lon=\"51.4\"/>\n</gpx>";
+        assert_eq!(parse(empty).unwrap_err().message, "no track points");
+
+        let malformed = "<gpx>\n<trk>\n</gpx>";
+        assert_eq!(parse(malformed).unwrap_err().line, 3);
+    }
+
+    #[tokio::test]
+    async fn map_match_gpx() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/map-matching"))
+            .and(query_param(
+                "path",
+                "35.699700,51.338000|35.700100,51.350000|35.700400,51.362000|35.700800,51.375000|35.701000,51.391000",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "snappedPoints": [
+                    {"location": {"latitude": 35.6998, "longitude": 51.338}, "originalIndex": 0},
+                    {"location": {"latitude": 35.701, "longitude": 51.391}, "originalIndex": 4}
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let matching = client.map_match_gpx(TRACK).await.unwrap();
+        let snapped = &matching.trace.snapped_points;
+        assert_eq!(snapped.len(), 2);
+        assert_eq!(snapped[1].original_index, Some(4));
+        assert_eq!(matching.time(&snapped[0]), Some("2024-05-01T07:30:00Z"));
+        assert_eq!(matching.time(&snapped[1]), Some("2024-05-01T07:35:40Z"));
+        assert_eq!(matching.track.len(), 5);
+
+        let err = client.map_match_gpx("<gpx></gpx>").await.unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)));
+    }
+}
@@ -0,0 +1,191 @@
+//! short human readable durations and distances, next to the persian `text` neshan sends.
+
+use crate::{Distance, Duration};
+
+/// language of `Duration::humanize` and `Distance::humanize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    /// persian words and digits, e.g. `۱ ساعت و ۲۴ دقیقه`.
+    Persian,
+    /// compact english with ascii digits, e.g. `1 h 24 min`.
+    English,
+}
+
+impl Locale {
+    pub(crate) fn number(self, text: String) -> String {
+        match self {
+            Locale::English => text,
+            Locale::Persian => text
+                .chars()
+                .map(|c| match c {
+                    '0'..='9' => char::from_u32('۰' as u32 + (c as u32 - '0' as u32)).unwrap(),
+                    '.' => '٫',
+                    c => c,
+                })
+                .collect(),
+        }
+    }
+
+    /// `count` of the unit, e.g. `2 days`. persian nouns stay singular after a number.
+    fn quantity(self, count: u64, unit: Unit) -> String {
+        let unit = match (self, unit) {
+            (Locale::English, Unit::Second) => "s",
+            (Locale::English, Unit::Minute) => "min",
+            (Locale::English, Unit::Hour) => "h",
+            (Locale::English, Unit::Day) if count == 1 => "day",
+            (Locale::English, Unit::Day) => "days",
+            (Locale::Persian, Unit::Second) => "ثانیه",
+            (Locale::Persian, Unit::Minute) => "دقیقه",
+            (Locale::Persian, Unit::Hour) => "ساعت",
+            (Locale::Persian, Unit::Day) => "روز",
+        };
+
+        format!("{} {}", self.number(count.to_string()), unit)
+    }
+
+    /// two quantities, the second one left out when it is zero.
+    fn pair(self, first: u64, first_unit: Unit, second: u64, second_unit: Unit) -> String {
+        let first = self.quantity(first, first_unit);
+        if second == 0 {
+            return first;
+        }
+
+        let separator = match self {
+            Locale::English => " ",
+            Locale::Persian => " و ",
+        };
+        format!(
+            "{}{}{}",
+            first,
+            separator,
+            self.quantity(second, second_unit)
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Duration {
+    /// the duration rounded for display: seconds below a minute, minutes below an hour,
+    /// hours and minutes below a day and days and hours beyond. negative and nan values show
+    /// as zero.
+    pub fn humanize(&self, locale: Locale) -> String {
+        let seconds = if self.value > 0.0 {
+            self.value.round() as u64
+        } else {
+            0
+        };
+        if seconds < 60 {
+            return locale.quantity(seconds, Unit::Second);
+        }
+
+        let minutes = (seconds as f64 / 60.0).round() as u64;
+        if minutes < 60 {
+            return locale.quantity(minutes, Unit::Minute);
+        }
+        if minutes < 24 * 60 {
+            return locale.pair(minutes / 60, Unit::Hour, minutes % 60, Unit::Minute);
+        }
+
+        let hours = (minutes as f64 / 60.0).round() as u64;
+        locale.pair(hours / 24, Unit::Day, hours % 24, Unit::Hour)
+    }
+}
+
+impl Distance {
+    /// the distance rounded for display: whole meters below a kilometer, kilometers with one
+    /// decimal below a hundred and whole kilometers beyond. negative and nan values show as zero.
+    pub fn humanize(&self, locale: Locale) -> String {
+        let meters = if self.value > 0.0 {
+            self.value.round()
+        } else {
+            0.0
+        };
+
+        let (number, unit) = if meters < 1000.0 {
+            (format!("{}", meters), ("m", "متر"))
+        } else {
+            let tenths = (meters / 100.0).round() as u64;
+            let number = match tenths {
+                tenths if tenths >= 1000 => ((meters / 1000.0).round() as u64).to_string(),
+                tenths if tenths % 10 == 0 => (tenths / 10).to_string(),
+                tenths => format!("{}.{}", tenths / 10, tenths % 10),
+            };
+            (number, ("km", "کیلومتر"))
+        };
+
+        let unit = match locale {
+            Locale::English => unit.0,
+            Locale::Persian => unit.1,
+        };
+        format!("{} {}", locale.number(number), unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+    use crate::{Distance, Duration};
+
+    #[test]
+    fn durations() {
+        let cases = [
+            (0.0, "0 s", "۰ ثانیه"),
+            (-5.0, "0 s", "۰ ثانیه"),
+            (f64::NAN, "0 s", "۰ ثانیه"),
+            (1.0, "1 s", "۱ ثانیه"),
+            (42.4, "42 s", "۴۲ ثانیه"),
+            (59.6, "1 min", "۱ دقیقه"),
+            (89.0, "1 min", "۱ دقیقه"),
+            (90.0, "2 min", "۲ دقیقه"),
+            (1440.0, "24 min", "۲۴ دقیقه"),
+            (3599.0, "1 h", "۱ ساعت"),
+            (5040.0, "1 h 24 min", "۱ ساعت و ۲۴ دقیقه"),
+            (36_000.0, "10 h", "۱۰ ساعت"),
+            (86_370.0, "1 day", "۱ روز"),
+            (90_000.0, "1 day 1 h", "۱ روز و ۱ ساعت"),
+            (183_600.0, "2 days 3 h", "۲ روز و ۳ ساعت"),
+            (864_000.0, "10 days", "۱۰ روز"),
+        ];
+
+        for (value, english, persian) in cases {
+            let duration = Duration {
+                value,
+                text: String::new(),
+            };
+            assert_eq!(duration.humanize(Locale::English), english, "{}", value);
+            assert_eq!(duration.humanize(Locale::Persian), persian, "{}", value);
+        }
+    }
+
+    #[test]
+    fn distances() {
+        let cases = [
+            (0.0, "0 m", "۰ متر"),
+            (-3.0, "0 m", "۰ متر"),
+            (0.4, "0 m", "۰ متر"),
+            (850.4, "850 m", "۸۵۰ متر"),
+            (999.6, "1 km", "۱ کیلومتر"),
+            (1049.0, "1 km", "۱ کیلومتر"),
+            (1050.0, "1.1 km", "۱٫۱ کیلومتر"),
+            (42_300.0, "42.3 km", "۴۲٫۳ کیلومتر"),
+            (99_960.0, "100 km", "۱۰۰ کیلومتر"),
+            (152_480.0, "152 km", "۱۵۲ کیلومتر"),
+        ];
+
+        for (value, english, persian) in cases {
+            let distance = Distance {
+                value,
+                text: String::new(),
+            };
+            assert_eq!(distance.humanize(Locale::English), english, "{}", value);
+            assert_eq!(distance.humanize(Locale::Persian), persian, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! plain text of the instructions of route steps, see `Step::instruction_plain`.
+
+use crate::{Route, Step};
+
+/// tags that separate blocks of text, replaced by a space rather than dropped.
+const BLOCK_TAGS: [&str; 10] = [
+    "br", "div", "hr", "li", "ol", "p", "td", "tr", "ul", "table",
+];
+
+/// character of an entity such as `amp` or `#x200c`, `None` for unknown ones.
+fn entity(name: &str) -> Option<char> {
+    let number = |digits: &str, radix: u32| u32::from_str_radix(digits, radix).ok();
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        "zwnj" => Some('\u{200c}'),
+        "zwj" => Some('\u{200d}'),
+        "rlm" => Some('\u{200f}'),
+        "lrm" => Some('\u{200e}'),
+        _ => match name.strip_prefix('#') {
+            Some(hex) if hex.starts_with(['x', 'X']) => number(&hex[1..], 16),
+            Some(decimal) => number(decimal, 10),
+            None => None,
+        }
+        .and_then(char::from_u32),
+    }
+}
+
+/// `text` without its tags, a tag being a `<` followed by a letter, `/` or `!` up to the next
+/// `>`. any other `<` is kept as is.
+fn strip_tags(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        plain.push_str(&rest[..start]);
+        let tag = &rest[start + 1..];
+        let is_tag = tag
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        match tag.find('>') {
+            Some(end) if is_tag => {
+                let name: String = tag[..end]
+                    .trim_start_matches('/')
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .collect();
+                if BLOCK_TAGS.contains(&name.to_ascii_lowercase().as_str()) {
+                    plain.push(' ');
+                }
+                rest = &tag[end + 1..];
+            }
+            _ => {
+                plain.push('<');
+                rest = tag;
+            }
+        }
+    }
+    plain.push_str(rest);
+
+    plain
+}
+
+/// `text` with its entities decoded, unknown ones are kept as is.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let decoded_entity = after
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| entity(&after[..end]).map(|c| (c, end)));
+        match decoded_entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = after;
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+impl Step {
+    /// the instruction without markup, e.g. for plain text interfaces or speech. tags are
+    /// dropped, entities decoded and runs of whitespace collapsed into a single space.
+    /// zero width non-joiners of persian words are kept.
+    pub fn instruction_plain(&self) -> String {
+        let text = decode_entities(&strip_tags(&self.instruction));
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// the instruction as neshan sent it, which may contain html.
+    pub fn instruction_html(&self) -> &str {
+        &self.instruction
+    }
+}
+
+impl Route {
+    /// plain instructions of every step in order, see `Step::instruction_plain`. steps
+    /// without an instruction are left out.
+    pub fn instructions(&self) -> Vec<String> {
+        self.legs
+            .iter()
+            .flat_map(|leg| &leg.steps)
+            .map(Step::instruction_plain)
+            .filter(|instruction| !instruction.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Distance, Duration, Leg, Route, Step};
+
+    fn step(instruction: &str) -> Step {
+        Step {
+            name: String::new(),
+            instruction: instruction.to_string(),
+            distance: Distance {
+                value: 100.0,
+                text: String::new(),
+            },
+            duration: Duration {
+                value: 10.0,
+                text: String::new(),
+            },
+            polyline: None,
+            maneuver: None,
+            modifier: None,
+            exit: None,
+            bearing_after: None,
+            start_location: None,
+        }
+    }
+
+    #[test]
+    fn plain_instructions() {
+        let cases = [
+            ("به سمت شمال بروید", "به سمت شمال بروید"),
+            ("به <b>خیابان آزادی</b> بپیچید", "به خیابان آزادی بپیچید"),
+            (
+                "<div class=\"step\">در <b><i>میدان</i> انقلاب</b></div>",
+                "در میدان انقلاب",
+            ),
+            ("ادامه دهید<br/>۲۰۰ متر", "ادامه دهید ۲۰۰ متر"),
+            ("ادامه دهید<hr>سپس بپیچید", "ادامه دهید سپس بپیچید"),
+            ("بزرگراه همت &amp; چمران", "بزرگراه همت & چمران"),
+            ("&lt;b&gt; &quot;نیایش&quot; &#39;", "<b> \"نیایش\" '"),
+            ("می\u{200c}روید", "می\u{200c}روید"),
+            ("می&zwnj;روید", "می\u{200c}روید"),
+            ("می&#x200C;روید&#1740;", "می\u{200c}رویدی"),
+            ("  به\n\tراست&nbsp;&nbsp;بپیچید  ", "به راست بپیچید"),
+            ("فاصله < ۲ کیلومتر", "فاصله < ۲ کیلومتر"),
+            ("R&D &unknown; &", "R&D &unknown; &"),
+            ("<!-- note -->خروجی ۲", "خروجی ۲"),
+            ("", ""),
+        ];
+
+        for (raw, plain) in cases {
+            let step = step(raw);
+            assert_eq!(step.instruction_plain(), plain, "{:?}", raw);
+            assert_eq!(step.instruction_html(), raw);
+        }
+    }
+
+    #[test]
+    fn route_instructions() {
+        let leg = |steps| Leg {
+            summary: String::new(),
+            distance: Distance {
+                value: 200.0,
+                text: String::new(),
+            },
+            duration: Duration {
+                value: 20.0,
+                text: String::new(),
+            },
+            steps,
+        };
+        let route = Route {
+            legs: vec![
+                leg(vec![step("<b>حرکت</b> کنید"), step("")]),
+                leg(vec![step("به مقصد رسیدید")]),
+            ],
+            overview_polyline: None,
+        };
+
+        assert_eq!(route.instructions(), vec!["حرکت کنید", "به مقصد رسیدید"]);
+    }
+}
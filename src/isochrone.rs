@@ -0,0 +1,425 @@
+//! rough areas reachable within a duration, see `Client::isochrone_approx`.
+
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::{ChunkLimits, Point, Type};
+use serde_json::{json, Value};
+
+/// options of `Client::isochrone_approx`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsochroneOptions {
+    limits: ChunkLimits,
+    concurrency: usize,
+    rings: usize,
+    max_speed: f64,
+}
+
+impl IsochroneOptions {
+    /// samples on each ray by default.
+    pub const RINGS: usize = 6;
+    /// fastest assumed speed in kilometers per hour by default.
+    pub const MAX_SPEED: f64 = 60.0;
+
+    /// request the travel times within `limits`, see `Client::distance_matrix_chunked`.
+    pub fn new(limits: ChunkLimits) -> IsochroneOptions {
+        IsochroneOptions {
+            limits,
+            concurrency: 1,
+            rings: IsochroneOptions::RINGS,
+            max_speed: IsochroneOptions::MAX_SPEED,
+        }
+    }
+
+    /// request at most this many chunks of the matrix at the same time.
+    pub fn concurrency(mut self, concurrency: usize) -> IsochroneOptions {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// samples on each ray, evenly spaced up to the farthest distance, at least one. more
+    /// rings give a finer interpolation for more elements of the matrix.
+    pub fn rings(mut self, rings: usize) -> IsochroneOptions {
+        self.rings = rings.max(1);
+        self
+    }
+
+    /// fastest speed in kilometers per hour the vehicle is assumed to travel, which sets the
+    /// farthest sample at `max_speed * max_duration`. it must be positive.
+    pub fn max_speed(mut self, max_speed: f64) -> IsochroneOptions {
+        self.max_speed = max_speed;
+        self
+    }
+}
+
+/// how far the area reaches in one direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsochroneRay {
+    /// degrees clockwise from north.
+    pub bearing: f64,
+    /// meters from the center, interpolated between the last sample within the duration and
+    /// the first one beyond it.
+    pub radius: f64,
+    /// `false` when no sample on the ray could be routed to, the radius is then zero.
+    pub reachable: bool,
+    /// whether every routed sample was within the duration, the area may then reach farther
+    /// than the radius.
+    pub truncated: bool,
+}
+
+/// area approximately reachable from a center within a duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isochrone {
+    pub center: Point,
+    /// the duration in seconds.
+    pub max_duration: f64,
+    /// a ray per bearing, clockwise from north.
+    pub rays: Vec<IsochroneRay>,
+}
+
+impl Isochrone {
+    /// the end of each ray clockwise from north, the ring is not closed.
+    pub fn polygon(&self) -> Vec<Point> {
+        self.rays
+            .iter()
+            .map(|ray| self.center.destination(ray.bearing, ray.radius))
+            .collect()
+    }
+
+    /// rays without any routable sample.
+    pub fn unreachable(&self) -> impl Iterator<Item = &IsochroneRay> + '_ {
+        self.rays.iter().filter(|ray| !ray.reachable)
+    }
+
+    /// the area as a geojson `Feature` with a `Polygon` geometry. the bearings of the
+    /// unreachable rays are in the `unreachable` property.
+    pub fn to_geojson(&self) -> Value {
+        let mut ring: Vec<[f64; 2]> = self
+            .polygon()
+            .iter()
+            .map(|point| [point.longitude, point.latitude])
+            .collect();
+        if let Some(first) = ring.first().copied() {
+            ring.push(first);
+        }
+
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [ring],
+            },
+            "properties": {
+                "center": [self.center.longitude, self.center.latitude],
+                "max_duration": self.max_duration,
+                "unreachable": self.unreachable().map(|ray| ray.bearing).collect::<Vec<_>>(),
+            },
+        })
+    }
+}
+
+/// the ray crossing `max_duration` along the samples, as `(distance, duration)` pairs ordered
+/// by distance with `None` for the unroutable ones.
+fn ray(bearing: f64, samples: &[(f64, Option<f64>)], max_duration: f64) -> IsochroneRay {
+    let mut reachable = false;
+    let mut previous = (0.0, 0.0);
+
+    for (distance, duration) in samples {
+        let duration = match duration {
+            Some(duration) => *duration,
+            None => continue,
+        };
+        reachable = true;
+
+        if duration > max_duration {
+            let (previous_distance, previous_duration) = previous;
+            let ratio = (max_duration - previous_duration) / (duration - previous_duration);
+            return IsochroneRay {
+                bearing,
+                radius: previous_distance + ratio * (distance - previous_distance),
+                reachable,
+                truncated: false,
+            };
+        }
+        previous = (*distance, duration);
+    }
+
+    IsochroneRay {
+        bearing,
+        radius: previous.0,
+        reachable,
+        truncated: reachable,
+    }
+}
+
+impl Client {
+    /// approximate the area reachable from `center` within `max_duration` seconds without an
+    /// isochrone api. points are sampled on `bearings` evenly spread rays, their travel times
+    /// are requested with `distance_matrix_chunked_strict` and the radius of each ray is
+    /// interpolated where the travel time crosses the duration. a failed chunk fails the call
+    /// rather than leaving its rays unreachable.
+    ///
+    /// the approximation can't see around obstacles between the samples, e.g. a highway
+    /// passing between two rings, so it is only a rough outline.
+    pub async fn isochrone_approx(
+        &self,
+        vehicle: Type,
+        center: impl Into<Point>,
+        max_duration: f64,
+        bearings: usize,
+        options: &IsochroneOptions,
+    ) -> Result<Isochrone, NeshanError> {
+        let center = center.into();
+        if bearings < 3 {
+            return Err(NeshanError::InvalidRequest(format!(
+                "an isochrone needs at least 3 bearings, got {}",
+                bearings
+            )));
+        }
+        if !(max_duration.is_finite() && max_duration > 0.0) {
+            return Err(NeshanError::InvalidRequest(format!(
+                "the duration of an isochrone must be positive, got {}",
+                max_duration
+            )));
+        }
+        if !(options.max_speed.is_finite() && options.max_speed > 0.0) {
+            return Err(NeshanError::InvalidRequest(format!(
+                "the speed of an isochrone must be positive, got {}",
+                options.max_speed
+            )));
+        }
+        self.check(&[center])?;
+
+        let farthest = options.max_speed / 3.6 * max_duration;
+        let distances: Vec<f64> = (1..=options.rings)
+            .map(|ring| farthest * ring as f64 / options.rings as f64)
+            .collect();
+        let angles: Vec<f64> = (0..bearings)
+            .map(|i| 360.0 * i as f64 / bearings as f64)
+            .collect();
+        let samples: Vec<Point> = angles
+            .iter()
+            .flat_map(|bearing| {
+                distances
+                    .iter()
+                    .map(move |distance| center.destination(*bearing, *distance))
+            })
+            .collect();
+
+        let matrix = self
+            .distance_matrix_chunked_strict(
+                vehicle,
+                &[center],
+                &samples,
+                options.limits,
+                options.concurrency,
+            )
+            .await?;
+
+        let rays = angles
+            .iter()
+            .enumerate()
+            .map(|(i, bearing)| {
+                let samples: Vec<(f64, Option<f64>)> = distances
+                    .iter()
+                    .enumerate()
+                    .map(|(ring, distance)| {
+                        let duration = matrix
+                            .get(0, i * distances.len() + ring)
+                            .and_then(|element| element.duration.as_ref())
+                            .map(|duration| duration.value);
+                        (*distance, duration)
+                    })
+                    .collect();
+                ray(*bearing, &samples, max_duration)
+            })
+            .collect();
+
+        Ok(Isochrone {
+            center,
+            max_duration,
+            rays,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ray, IsochroneOptions};
+    use crate::client::Client;
+    use crate::error::{ErrorKind, NeshanError};
+    use crate::{ChunkLimits, Point, Type};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const CENTER: Point = Point {
+        latitude: 35.7,
+        longitude: 51.4,
+    };
+
+    #[test]
+    fn interpolation() {
+        let samples = [(1000.0, Some(100.0)), (2000.0, Some(400.0))];
+        let crossing = ray(90.0, &samples, 300.0);
+        assert!((crossing.radius - 1666.666).abs() < 1e-2);
+        assert!(crossing.reachable && !crossing.truncated);
+
+        // from the center when the first sample is already too far.
+        assert_eq!(ray(0.0, &[(1000.0, Some(600.0))], 300.0).radius, 500.0);
+
+        // unroutable samples are skipped.
+        let gaps = [(1000.0, None), (2000.0, Some(200.0)), (3000.0, None)];
+        let gaps = ray(0.0, &gaps, 300.0);
+        assert_eq!(gaps.radius, 2000.0);
+        assert!(gaps.truncated);
+
+        let unreachable = ray(0.0, &[(1000.0, None), (2000.0, None)], 300.0);
+        assert_eq!(unreachable.radius, 0.0);
+        assert!(!unreachable.reachable && !unreachable.truncated);
+    }
+
+    /// answers with the duration of driving straight from the center at 4 meters per second
+    /// to the north, 10 to the east and 30 to the west. nothing to the south is routable.
+    fn respond(request: &wiremock::Request) -> ResponseTemplate {
+        let destinations = request
+            .url
+            .query_pairs()
+            .find(|(key, _)| key == "destinations")
+            .map(|(_, value)| value.into_owned())
+            .unwrap();
+
+        let elements: Vec<serde_json::Value> = destinations
+            .split('|')
+            .map(|point| {
+                let (latitude, longitude) = point.split_once(',').unwrap();
+                let point =
+                    Point::new_unchecked(latitude.parse().unwrap(), longitude.parse().unwrap());
+                let speed = match CENTER.bearing_to(&point).round() as u32 {
+                    0 | 360 => 4.0,
+                    90 => 10.0,
+                    270 => 30.0,
+                    _ => return serde_json::json!({"status": "NOT_FOUND"}),
+                };
+                let seconds = CENTER.haversine_distance_to(&point) / speed;
+                serde_json::json!({
+                    "status": "Ok",
+                    "duration": {"value": seconds, "text": ""},
+                    "distance": {"value": seconds * speed, "text": ""}
+                })
+            })
+            .collect();
+
+        ResponseTemplate::new(200)
+            .set_body_json(serde_json::json!({ "rows": [{ "elements": elements }] }))
+    }
+
+    #[tokio::test]
+    async fn isochrone_approx() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(respond)
+            // 4 rays of 4 samples in chunks of 3 destinations.
+            .expect(6)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        // 72 km/h for 300 seconds puts the farthest samples at 6 km.
+        let options = IsochroneOptions::new(ChunkLimits::new(1, 3))
+            .rings(4)
+            .max_speed(72.0)
+            .concurrency(2);
+        let isochrone = client
+            .isochrone_approx(Type::Car, CENTER, 300.0, 4, &options)
+            .await
+            .unwrap();
+
+        let bearings: Vec<f64> = isochrone.rays.iter().map(|ray| ray.bearing).collect();
+        assert_eq!(bearings, vec![0.0, 90.0, 180.0, 270.0]);
+
+        let radii: Vec<f64> = isochrone.rays.iter().map(|ray| ray.radius).collect();
+        for (radius, expected) in radii.iter().zip([1200.0, 3000.0, 0.0, 6000.0]) {
+            assert!((radius - expected).abs() < 1.0, "{:?}", radii);
+        }
+        assert!(!isochrone.rays[2].reachable);
+        assert!(isochrone.rays[3].truncated);
+        assert_eq!(
+            isochrone
+                .unreachable()
+                .map(|ray| ray.bearing)
+                .collect::<Vec<_>>(),
+            vec![180.0]
+        );
+
+        let polygon = isochrone.polygon();
+        assert_eq!(polygon.len(), 4);
+        assert!(polygon[0].latitude > CENTER.latitude);
+        assert!(polygon[1].longitude > CENTER.longitude);
+        assert!(polygon[2].haversine_distance_to(&CENTER) < 1e-3);
+        assert!(polygon[3].longitude < CENTER.longitude);
+
+        let geojson = isochrone.to_geojson();
+        let ring = geojson["geometry"]["coordinates"][0].as_array().unwrap();
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring[0], ring[4]);
+        assert_eq!(
+            geojson["properties"]["unreachable"],
+            serde_json::json!([180.0])
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_requests() {
+        let client = Client::new("key");
+        let options = IsochroneOptions::new(ChunkLimits::new(1, 10));
+
+        let err = client
+            .isochrone_approx(Type::Car, CENTER, 300.0, 2, &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)));
+
+        let err = client
+            .isochrone_approx(Type::Car, CENTER, f64::NAN, 8, &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)));
+
+        for speed in [0.0, -10.0, f64::INFINITY, f64::NAN] {
+            let err = client
+                .isochrone_approx(Type::Car, CENTER, 300.0, 8, &options.max_speed(speed))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, NeshanError::InvalidRequest(_)), "{}", speed);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_matrix_is_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/distance-matrix"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let err = client
+            .isochrone_approx(
+                Type::Car,
+                CENTER,
+                300.0,
+                4,
+                &IsochroneOptions::new(ChunkLimits::new(1, 3)),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Auth);
+    }
+}
@@ -1,65 +1,540 @@
-use reqwest::header;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt;
 
-/// Neshan client based on its api documentation.
-/// <https://platform.neshan.org/api/getting-started>
-pub struct Client {
-    client: reqwest::Client,
+mod address_v5;
+mod api;
+mod arithmetic;
+mod audit;
+mod backend;
+pub mod batch;
+mod bounding_box;
+mod cache;
+mod cassette;
+mod circuit;
+mod client;
+mod config;
+mod custom;
+#[cfg(feature = "disk-cache")]
+mod disk_cache;
+mod distance_matrix;
+mod dms;
+mod endpoint;
+mod error;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod fake;
+#[cfg(feature = "geo")]
+mod geo;
+mod geocode;
+#[cfg(feature = "gpx")]
+pub mod gpx;
+mod humanize;
+mod instruction;
+mod isochrone;
+pub mod links;
+mod map_matching;
+mod meta;
+pub mod middleware;
+#[cfg(any(test, feature = "test-utils"))]
+mod mock;
+mod observer;
+mod optimize;
+#[cfg(feature = "otel")]
+mod otel;
+mod persist;
+mod place;
+mod point;
+pub mod polyline;
+mod progress;
+pub mod protocol;
+mod quota;
+#[cfg(any(test, feature = "test-utils"))]
+mod random;
+mod rate_limit;
+mod reroute;
+mod retry;
+mod route_addresses;
+mod search;
+mod single_flight;
+mod speed;
+mod static_map;
+mod stats;
+mod tiles;
+mod trace;
+mod trip;
+#[cfg(feature = "uom")]
+mod units;
+#[cfg(feature = "utm")]
+mod utm;
+#[cfg(feature = "zones")]
+pub mod zones;
+
+pub use address_v5::PostalAddressV5;
+pub use api::NeshanApi;
+pub use audit::{AuditEntry, AuditSink, JsonlAudit};
+#[cfg(feature = "reqwest")]
+pub use backend::ReqwestBackend;
+pub use backend::{BodyStream, HttpBackend, StreamingResponse};
+pub use bounding_box::{BoundingBox, BoundingBoxError};
+pub use cache::{CacheConfig, CacheStats, CachedEntry, ResponseCache};
+pub use cassette::{Cassette, CassetteMode};
+pub use circuit::{CircuitBreaker, CircuitState};
+pub use client::{Client, ClientBuilder};
+pub use config::ClientConfig;
+pub use distance_matrix::{
+    Availability, ChunkLimits, CsvOptions, DistanceMatrix, ElementStatus, MatrixElement, MatrixRow,
+    MatrixUnit, RankedOrigin, SymmetricMatrix, TrafficElement, TrafficMatrix,
+};
+pub use endpoint::Endpoint;
+pub use error::{ApiError, Error, ErrorKind, NeshanError};
+pub use geocode::GeocodeResult;
+pub use humanize::Locale;
+pub use isochrone::{Isochrone, IsochroneOptions, IsochroneRay};
+pub use map_matching::{
+    Downsample, MapMatchChunkError, MapMatchOptions, MatchedPoint, MatchedTrace, SnappedPoint,
+};
+pub use meta::ResponseMeta;
+#[cfg(any(test, feature = "test-utils"))]
+pub use mock::{MockNeshan, MockResponse, RecordedRequest};
+pub use observer::{CountingObserver, NoopObserver, RequestObserver};
+pub use optimize::{OptimizedTrip, TripPoint};
+pub use persist::{Persist, SCHEMA_VERSION};
+pub use place::{PlaceDetails, PlaceDetailsOptions};
+pub use point::{
+    Axis, InvalidCoordinate, Latitude, Longitude, ParsePointError, Point, EARTH_RADIUS,
+};
+pub use progress::{RoutePosition, RouteProgress};
+pub use quota::QuotaInfo;
+pub use rate_limit::Priority;
+pub use reroute::Reroute;
+pub use retry::RetryPolicy;
+pub use route_addresses::RouteAddresses;
+pub use search::{SearchItem, SearchResults};
+pub use speed::SpeedSegment;
+pub use static_map::{
+    MapStyle, Marker, PathOverlay, StaticMapBuilder, StaticMapImage, StaticMapRequest,
+};
+pub use stats::{EndpointStats, Stats};
+pub use tiles::{PrefetchedTile, TileCoord, TileError, TileStyle};
+pub use trip::{Segment, Stop, Trip};
+#[cfg(feature = "utm")]
+pub use utm::{Utm, UtmError};
+
+/// vehicle of the direction api, stored as `"car"` or `"motorcycle"` with serde.
+///
+/// other vehicles, e.g. ones of regional deployments, are kept in `Other` and sent to neshan
+/// verbatim. parse unknown strings with `str::parse` rather than building `Other` by hand, so
+/// that e.g. `"Car"` becomes `Type::Car` and a blank vehicle is rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[non_exhaustive]
+pub enum Type {
+    Car,
+    Motorcycle,
+    Other(String),
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Error {
-    code: i32,
-    message: String,
+impl Type {
+    /// the string of the vehicle in the api, also used by `Display` and serde.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Type::Car => "car",
+            Type::Motorcycle => "motorcycle",
+            Type::Other(vehicle) => vehicle,
+        }
+    }
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
+/// known vehicles match case-insensitively, anything else but a blank string becomes
+/// `Type::Other`.
+impl std::str::FromStr for Type {
+    type Err = ParseTypeError;
+
+    fn from_str(s: &str) -> Result<Type, ParseTypeError> {
+        Type::try_from(s.to_string())
     }
 }
 
-impl std::error::Error for Error {}
+/// like `str::parse`.
+impl TryFrom<String> for Type {
+    type Error = ParseTypeError;
+
+    fn try_from(vehicle: String) -> Result<Type, ParseTypeError> {
+        if vehicle.trim().is_empty() {
+            return Err(ParseTypeError { vehicle });
+        }
 
-impl fmt::Debug for Error {
+        Ok(
+            match [Type::Car, Type::Motorcycle]
+                .iter()
+                .find(|known| known.as_str().eq_ignore_ascii_case(&vehicle))
+            {
+                Some(known) => known.clone(),
+                None => Type::Other(vehicle),
+            },
+        )
+    }
+}
+
+/// a blank vehicle, which neshan would get as an empty `type`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTypeError {
+    vehicle: String,
+}
+
+impl fmt::Display for ParseTypeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "AppError {{ code: {}, message: {} }}",
-            self.code, self.message
+            "vehicle {:?} is blank, expected e.g. car or motorcycle",
+            self.vehicle
         )
     }
 }
 
-pub struct Point {
-    pub longitude: f64,
-    pub latitude: f64,
+impl std::error::Error for ParseTypeError {}
+
+impl From<Type> for String {
+    fn from(vehicle: Type) -> String {
+        match vehicle {
+            Type::Other(vehicle) => vehicle,
+            known => known.as_str().to_string(),
+        }
+    }
 }
 
-pub enum Type {
-    Car,
-    Motorcycle,
+/// version of the direction api that `Client::route_with` calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectionVersion {
+    /// `/v3/direction`.
+    #[default]
+    V3,
+    /// `/v4/direction`, which also sends the maneuver of each step, see `Step::maneuver`.
+    V4,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl DirectionVersion {
+    pub(crate) fn endpoint(self) -> Endpoint {
+        match self {
+            DirectionVersion::V3 => Endpoint::Route,
+            DirectionVersion::V4 => Endpoint::RouteV4,
+        }
+    }
+
+    fn is_v3(&self) -> bool {
+        *self == DirectionVersion::V3
+    }
+}
+
+/// options of the direction api.
+///
+/// the serde form is meant for storing the options, e.g. along with a planned trip, and is
+/// independent of the query parameters sent to neshan. missing fields take their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteOptions {
+    avoid_traffic_zone: bool,
+    avoid_odd_even_zone: bool,
+    alternative_paths: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<Priority>,
+    #[serde(skip_serializing_if = "DirectionVersion::is_v3")]
+    version: DirectionVersion,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    waypoints: Vec<Point>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bearing: Option<u16>,
+}
+
+impl RouteOptions {
+    /// most waypoints of a route, more fail before sending.
+    pub const MAX_WAYPOINTS: usize = 10;
+
+    pub fn new() -> RouteOptions {
+        RouteOptions::default()
+    }
+
+    /// stops to pass through in order between the origin and the destination, each starting
+    /// a new leg of the route.
+    pub fn waypoints(mut self, waypoints: &[Point]) -> RouteOptions {
+        self.waypoints = waypoints.to_vec();
+        self
+    }
+
+    /// heading at the origin in degrees clockwise from north, 0 to 359, so that the route
+    /// doesn't start with a u-turn on e.g. a divided highway.
+    pub fn bearing(mut self, bearing: u16) -> RouteOptions {
+        self.bearing = Some(bearing);
+        self
+    }
+
+    /// check the options against the limits of the direction api.
+    pub(crate) fn check(&self) -> Result<(), NeshanError> {
+        if let Some(bearing) = self.bearing.filter(|bearing| *bearing >= 360) {
+            return Err(NeshanError::InvalidRequest(format!(
+                "bearing {} is out of range, expected 0 to 359 degrees",
+                bearing
+            )));
+        }
+        if self.waypoints.len() > RouteOptions::MAX_WAYPOINTS {
+            return Err(NeshanError::InvalidRequest(format!(
+                "{} waypoints are too many, expected at most {}",
+                self.waypoints.len(),
+                RouteOptions::MAX_WAYPOINTS
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// the options of a route between two stops of a trip or a batch, which are its origin
+    /// and destination rather than waypoints of a longer route. the bearing is the heading at
+    /// a single origin and is left out as well.
+    pub(crate) fn between_stops(&self) -> RouteOptions {
+        RouteOptions {
+            waypoints: Vec::new(),
+            bearing: None,
+            ..self.clone()
+        }
+    }
+
+    /// find route(s) that doesn't cross the traffic zone.
+    pub fn avoid_traffic_zone(mut self, avoid: bool) -> RouteOptions {
+        self.avoid_traffic_zone = avoid;
+        self
+    }
+
+    /// find route(s) that doesn't cross the odd even zone.
+    pub fn avoid_odd_even_zone(mut self, avoid: bool) -> RouteOptions {
+        self.avoid_odd_even_zone = avoid;
+        self
+    }
+
+    /// return alternative routes besides the primary route.
+    pub fn alternative_paths(mut self, alternative: bool) -> RouteOptions {
+        self.alternative_paths = alternative;
+        self
+    }
+
+    /// priority of the request when the rate limiter is saturated, overriding the one of the
+    /// client handle, see `Client::with_priority`.
+    pub fn priority(mut self, priority: Priority) -> RouteOptions {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// version of the direction api to call, v3 by default. the query parameters are named
+    /// as the version expects them.
+    pub fn version(mut self, version: DirectionVersion) -> RouteOptions {
+        self.version = version;
+        self
+    }
+}
+
+/// routes of the direction api.
+///
+/// the response models compare field by field, except `Distance` and `Duration` which
+/// compare by their `value` only. values are floats, so a route with a nan value is not
+/// equal to itself and values that went through arithmetic may differ in their last bits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Routes {
     pub routes: Vec<Route>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Routes {
+    /// the routes of a direction body, v3 or v4.
+    pub fn from_json(json: &str) -> Result<Routes, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// number of alternative routes.
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// the alternative at `index`, neshan sends the suggested one first.
+    pub fn get(&self, index: usize) -> Option<&Route> {
+        self.routes.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Route> {
+        self.routes.iter()
+    }
+
+    /// legs of all alternatives in order, each with the index of its route.
+    ///
+    /// ```
+    /// # use neshan_rs::Routes;
+    /// # let routes: Routes = serde_json::from_str(r#"{"routes": []}"#).unwrap();
+    /// for (route, leg) in routes.all_legs() {
+    ///     println!("alternative {}: {}", route, leg.summary);
+    /// }
+    /// ```
+    pub fn all_legs(&self) -> impl Iterator<Item = (usize, &Leg)> + '_ {
+        self.routes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, route)| route.legs.iter().map(move |leg| (index, leg)))
+    }
+}
+
+/// routes by value, the suggested one first.
+///
+/// ```
+/// # use neshan_rs::Routes;
+/// # let routes: Routes = serde_json::from_str(r#"{"routes": []}"#).unwrap();
+/// for route in &routes {
+///     println!("{} legs", route.legs().count());
+/// }
+/// ```
+impl IntoIterator for Routes {
+    type Item = Route;
+    type IntoIter = std::vec::IntoIter<Route>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.routes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Routes {
+    type Item = &'a Route;
+    type IntoIter = std::slice::Iter<'a, Route>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.routes.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Route {
     pub legs: Vec<Leg>,
+    /// simplified geometry of the whole route.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overview_polyline: Option<EncodedPolyline>,
+}
+
+impl Route {
+    pub fn legs(&self) -> std::slice::Iter<'_, Leg> {
+        self.legs.iter()
+    }
+
+    /// points of the overview geometry, empty when neshan didn't send one.
+    pub fn geometry(&self) -> Result<Vec<Point>, polyline::PolylineError> {
+        match &self.overview_polyline {
+            Some(overview) => polyline::decode(&overview.points, polyline::Precision::Five),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// geometry in google's encoded polyline format with five decimals, see `polyline`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedPolyline {
+    pub points: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Leg {
     pub summary: String,
     pub duration: Duration,
     pub distance: Distance,
+    /// maneuvers of the leg in order, empty when neshan didn't send them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+}
+
+/// one maneuver of a leg, e.g. a turn onto the next road.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// name of the road the step follows, empty for unnamed roads.
+    #[serde(default)]
+    pub name: String,
+    /// what to do at the start of the step, in persian.
+    #[serde(default)]
+    pub instruction: String,
+    pub distance: Distance,
+    pub duration: Duration,
+    /// geometry of the step in the format of `EncodedPolyline`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub polyline: Option<String>,
+    /// kind of the maneuver, e.g. `turn` or `roundabout`. only v4 of the direction api sends
+    /// it and the fields after it, see `DirectionVersion`.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub maneuver: Option<String>,
+    /// direction of the maneuver, e.g. `left` or `slight right`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modifier: Option<String>,
+    /// exit to take at a roundabout, counting from one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<u32>,
+    /// heading after the maneuver in degrees clockwise from north.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bearing_after: Option<f64>,
+    /// where the step starts, sent by neshan as `[lng, lat]`.
+    #[serde(
+        default,
+        with = "optimize::lng_lat::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub start_location: Option<Point>,
+}
+
+/// distance, duration and summary of a route, decoded without its geometry and steps.
+/// see `Client::route_summary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteSummary {
+    pub summary: String,
+    pub duration: Duration,
+    pub distance: Distance,
+}
+
+/// decoding target of `Client::route_summary`, only the fields of `RouteSummary` are kept
+/// while serde skips everything else without building it.
+#[derive(Deserialize)]
+struct RouteSummaries {
+    routes: Vec<SummaryRoute>,
+}
+
+#[derive(Deserialize)]
+struct SummaryRoute {
+    legs: Vec<RouteSummary>,
+}
+
+impl From<SummaryRoute> for RouteSummary {
+    /// legs of the route are merged, their values add up and their texts are joined.
+    fn from(route: SummaryRoute) -> RouteSummary {
+        let mut legs = route.legs.into_iter();
+        let mut summary = legs.next().unwrap_or(RouteSummary {
+            summary: String::new(),
+            duration: Duration {
+                value: 0.0,
+                text: String::new(),
+            },
+            distance: Distance {
+                value: 0.0,
+                text: String::new(),
+            },
+        });
+
+        for leg in legs {
+            summary.summary = format!("{}، {}", summary.summary, leg.summary);
+            summary.duration.value += leg.duration.value;
+            summary.duration.text = format!("{}، {}", summary.duration.text, leg.duration.text);
+            summary.distance.value += leg.distance.value;
+            summary.distance.text = format!("{}، {}", summary.distance.text, leg.distance.text);
+        }
+
+        summary
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PostalAddress {
     pub formatted_address: String,
     pub route_name: String,
@@ -72,124 +547,574 @@ pub struct PostalAddress {
     pub in_odd_even_zone: bool,
 }
 
+impl PostalAddress {
+    /// the address of a `/v2/reverse` body, e.g. one logged next to a delivery.
+    pub fn from_json(json: &str) -> Result<PostalAddress, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
 /// distance from origin to destination in persian text form and meter.
-#[derive(Debug, Serialize, Deserialize)]
+/// distances compare by their value, the text is ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Distance {
     pub value: f64,
     pub text: String,
 }
 
+impl Distance {
+    pub fn meters(&self) -> f64 {
+        self.value
+    }
+
+    pub fn kilometers(&self) -> f64 {
+        self.value / 1000.0
+    }
+}
+
+impl PartialEq for Distance {
+    fn eq(&self, other: &Distance) -> bool {
+        self.value == other.value
+    }
+}
+
+impl PartialOrd for Distance {
+    fn partial_cmp(&self, other: &Distance) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
 /// distance from origin to destination in persian text form and seconds.
-#[derive(Debug, Serialize, Deserialize)]
+/// durations compare by their value, the text is ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Duration {
     pub value: f64,
     pub text: String,
 }
 
-impl fmt::Display for Type {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Type::Car => f.write_str("car"),
-            Type::Motorcycle => f.write_str("motorcycle"),
+impl Duration {
+    /// the duration as a `std::time::Duration`. negative and nan values become zero and
+    /// values that don't fit become the maximum, use `TryFrom` for rejecting them instead.
+    pub fn as_std(&self) -> std::time::Duration {
+        match std::time::Duration::try_from(self) {
+            Ok(duration) => duration,
+            Err(_) if self.value > 0.0 => std::time::Duration::MAX,
+            Err(_) => std::time::Duration::ZERO,
         }
     }
+
+    pub fn minutes(&self) -> f64 {
+        self.value / 60.0
+    }
 }
 
-impl Client {
-    /// create client for communicating with neshan.
-    pub fn new(api_key: &str) -> Client {
-        let mut headers = header::HeaderMap::new();
-        headers.insert("Api-Key", header::HeaderValue::from_str(api_key).unwrap());
+impl TryFrom<&Duration> for std::time::Duration {
+    type Error = InvalidDuration;
 
-        let client = reqwest::Client::builder()
-            .user_agent("neshan-rs")
-            .default_headers(headers)
-            .build()
-            .unwrap();
+    fn try_from(duration: &Duration) -> Result<std::time::Duration, InvalidDuration> {
+        std::time::Duration::try_from_secs_f64(duration.value).map_err(|_| InvalidDuration {
+            value: duration.value,
+        })
+    }
+}
+
+impl TryFrom<Duration> for std::time::Duration {
+    type Error = InvalidDuration;
 
-        Client { client }
+    fn try_from(duration: Duration) -> Result<std::time::Duration, InvalidDuration> {
+        std::time::Duration::try_from(&duration)
     }
+}
 
-    /// route finds route(s) from origin to destination.
-    ///
-    /// avoid_traffic_zone finds route(s) that doesn't cross the traffic zone.
-    /// avoid_odd_even_zone finds route(s) that doesn's cross the odd_even_zone.
-    /// alternative_paths returns alternative routes besides the primary route.
-    pub async fn route(
-        &self,
-        vehicle: Type,
-        origin: Point,
-        destination: Point,
-        avoid_traffic_zone: bool,
-        avoid_odd_even_zone: bool,
-        alternative_paths: bool,
-    ) -> Result<Routes, Box<dyn std::error::Error>> {
-        let res = self
-            .client
-            .get("https://api.neshan.org/v3/direction")
-            .query(&[
-                ("type", vehicle.to_string()),
-                (
-                    "origin",
-                    format!("{},{}", origin.latitude, origin.longitude),
-                ),
-                (
-                    "destination",
-                    format!("{},{}", destination.latitude, destination.longitude),
-                ),
-                ("avoid_traffic_zone", avoid_traffic_zone.to_string()),
-                ("avoid_odd_event_zone", avoid_odd_even_zone.to_string()),
-                ("alternative", alternative_paths.to_string()),
-            ])
-            .send()
-            .await?;
-
-        if !res.status().is_success() {
-            let err = res.json::<Error>().await?;
-
-            return Err(Box::new(err));
-        }
-
-        let routes = res.json::<Routes>().await?;
-
-        Ok(routes)
-    }
-
-    /// find postal address for the given point.
-    /// https://platform.neshan.org/api/reverse-geocoding
-    pub async fn reverse_geocode(
-        &self,
-        point: Point,
-    ) -> Result<PostalAddress, Box<dyn std::error::Error>> {
-        let res = self
-            .client
-            .get("https://api.neshan.org/v2/reverse")
-            .query(&[
-                ("lat", point.latitude.to_string()),
-                ("lng", point.longitude.to_string()),
-            ])
-            .send()
-            .await?;
-
-        if !res.status().is_success() {
-            let err = res.json::<Error>().await?;
-
-            return Err(Box::new(err));
-        }
-
-        let postal_address = res.json::<PostalAddress>().await?;
-
-        Ok(postal_address)
+impl PartialEq for Duration {
+    fn eq(&self, other: &Duration) -> bool {
+        self.value == other.value
+    }
+}
+
+impl PartialOrd for Duration {
+    fn partial_cmp(&self, other: &Duration) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+/// `Duration` whose value is negative, nan or too large for a `std::time::Duration`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidDuration {
+    value: f64,
+}
+
+impl InvalidDuration {
+    /// rejected number of seconds.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl fmt::Display for InvalidDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} seconds is not a valid duration, expected a finite non-negative number",
+            self.value
+        )
+    }
+}
+
+impl std::error::Error for InvalidDuration {}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        Distance, DistanceMatrix, Duration, ErrorKind, GeocodeResult, Leg, MatchedTrace,
+        OptimizedTrip, Point, PostalAddress, PostalAddressV5, Priority, Route, RouteOptions,
+        Routes, SearchResults, Type,
+    };
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::convert::TryFrom;
+
+    /// responses in the shape of neshan's, see `fixtures/README.md`.
+    const FIXTURES: [(&str, &str); 19] = [
+        ("route", include_str!("../fixtures/route.json")),
+        (
+            "reverse_geocode",
+            include_str!("../fixtures/reverse_geocode.json"),
+        ),
+        (
+            "reverse_geocode_minimal",
+            include_str!("../fixtures/reverse_geocode_minimal.json"),
+        ),
+        ("route_empty", include_str!("../fixtures/route_empty.json")),
+        (
+            "route_without_geometry",
+            include_str!("../fixtures/route_without_geometry.json"),
+        ),
+        (
+            "distance_matrix",
+            include_str!("../fixtures/distance_matrix.json"),
+        ),
+        (
+            "distance_matrix_unroutable",
+            include_str!("../fixtures/distance_matrix_unroutable.json"),
+        ),
+        (
+            "distance_matrix_mixed",
+            include_str!("../fixtures/distance_matrix_mixed.json"),
+        ),
+        (
+            "map_matching",
+            include_str!("../fixtures/map_matching.json"),
+        ),
+        (
+            "map_matching_detailed",
+            include_str!("../fixtures/map_matching_detailed.json"),
+        ),
+        ("search", include_str!("../fixtures/search.json")),
+        (
+            "search_minimal",
+            include_str!("../fixtures/search_minimal.json"),
+        ),
+        ("geocode", include_str!("../fixtures/geocode.json")),
+        ("trip_round", include_str!("../fixtures/trip_round.json")),
+        ("trip_open", include_str!("../fixtures/trip_open.json")),
+        ("route_steps", include_str!("../fixtures/route_steps.json")),
+        ("route_v4", include_str!("../fixtures/route_v4.json")),
+        (
+            "reverse_geocode_v5",
+            include_str!("../fixtures/reverse_geocode_v5.json"),
+        ),
+        (
+            "reverse_geocode_v5_rural",
+            include_str!("../fixtures/reverse_geocode_v5_rural.json"),
+        ),
+    ];
+
+    /// decode the fixture, then check that encoding the model and decoding it again gives
+    /// back the same model and the same json.
+    fn round_trip<T>(name: &str, fixture: &str) -> T
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Clone + std::fmt::Debug,
+    {
+        let model: T =
+            serde_json::from_str(fixture).unwrap_or_else(|err| panic!("{}: {}", name, err));
+
+        let json = serde_json::to_value(&model).unwrap();
+        let decoded: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(decoded, model, "{}", name);
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), json, "{}", name);
+        assert_eq!(model.clone(), model, "{}", name);
+
+        model
+    }
+
+    #[test]
+    fn fixtures_round_trip() {
+        for (name, fixture) in FIXTURES {
+            if name.starts_with("route") {
+                let routes: Routes = round_trip(name, fixture);
+                assert_eq!(Routes::from_json(fixture).unwrap(), routes, "{}", name);
+            } else if name.starts_with("reverse_geocode_v5") {
+                let address: PostalAddressV5 = round_trip(name, fixture);
+                assert_eq!(PostalAddressV5::from_json(fixture).unwrap(), address);
+                assert!(address.settlement().is_some());
+            } else if name.starts_with("reverse_geocode") {
+                let address: PostalAddress = round_trip(name, fixture);
+                assert_eq!(PostalAddress::from_json(fixture).unwrap(), address);
+                assert!(!address.city.is_empty());
+            } else if name.starts_with("distance_matrix") {
+                let matrix: DistanceMatrix = round_trip(name, fixture);
+                assert_eq!(DistanceMatrix::from_json(fixture).unwrap(), matrix);
+                assert!(!matrix.rows.is_empty());
+            } else if name.starts_with("map_matching") {
+                let trace: MatchedTrace = round_trip(name, fixture);
+                assert_eq!(MatchedTrace::from_json(fixture).unwrap(), trace);
+                assert!(!trace.snapped_points.is_empty());
+            } else if name.starts_with("search") {
+                let results: SearchResults = round_trip(name, fixture);
+                assert_eq!(SearchResults::from_json(fixture).unwrap(), results);
+                assert_eq!(results.items.len(), results.count);
+            } else if name.starts_with("geocode") {
+                let result: GeocodeResult = round_trip(name, fixture);
+                assert_eq!(GeocodeResult::from_json(fixture).unwrap(), Some(result));
+            } else if name.starts_with("trip") {
+                let trip: OptimizedTrip = round_trip(name, fixture);
+                assert_eq!(OptimizedTrip::from_json(fixture).unwrap(), trip);
+                assert_eq!(trip.order()[0], 0);
+            } else {
+                panic!("{} is not decoded by any test", name);
+            }
+        }
+    }
+
+    #[test]
+    fn fixture_shapes() {
+        let routes = Routes::from_json(FIXTURES[0].1).unwrap();
+        assert_eq!(routes.routes.len(), 2);
+        assert_eq!(routes.routes[1].legs.len(), 2);
+        assert!(routes.routes[0].overview_polyline.is_some());
+
+        assert!(Routes::from_json(FIXTURES[3].1).unwrap().is_empty());
+
+        let without_geometry = Routes::from_json(FIXTURES[4].1).unwrap();
+        assert_eq!(without_geometry.routes[0].overview_polyline, None);
+        assert_eq!(without_geometry.routes[0].geometry().unwrap(), Vec::new());
+
+        // v3 steps leave out the maneuvers that v4 sends.
+        let v3 = Routes::from_json(FIXTURES[15].1).unwrap();
+        let steps = &v3.routes[0].legs[0].steps;
+        assert_eq!(steps.len(), 2);
+        assert!(steps
+            .iter()
+            .all(|step| step.maneuver.is_none() && step.start_location.is_none()));
+        let v4 = Routes::from_json(FIXTURES[16].1).unwrap();
+        let steps = &v4.routes[0].legs[0].steps;
+        assert_eq!(steps[0].maneuver.as_deref(), Some("depart"));
+        assert_eq!(
+            steps[0].start_location,
+            Some(Point::new_unchecked(35.6997, 51.338))
+        );
+        assert_eq!(
+            (
+                steps[1].modifier.as_deref(),
+                steps[1].exit,
+                steps[1].bearing_after
+            ),
+            (Some("right"), Some(2), Some(91.0))
+        );
+        assert_eq!(steps[2].polyline, None);
+        assert_eq!(steps[0].name, v3.routes[0].legs[0].steps[0].name);
+
+        let matrix = DistanceMatrix::from_json(FIXTURES[5].1).unwrap();
+        assert_eq!(matrix.origin_addresses.len(), 2);
+        assert_eq!(
+            matrix.get(1, 0).unwrap().duration.as_ref().unwrap().value,
+            960.0
+        );
+
+        let unroutable = DistanceMatrix::from_json(FIXTURES[6].1).unwrap();
+        assert!(unroutable.destination_addresses.is_empty());
+        assert!(unroutable.get(0, 0).is_some());
+        assert!(unroutable.get(0, 1).is_none());
+    }
+
+    #[test]
+    fn from_json_errors_are_decode_errors() {
+        let err = Routes::from_json("{\"routes\": 1}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Decode);
+        assert!(PostalAddress::from_json("").is_err());
+    }
+
+    #[test]
+    fn missing_optional_fields_round_trip_as_null() {
+        let address: PostalAddress = serde_json::from_str(FIXTURES[2].1).unwrap();
+        assert_eq!(address.neighbourhood, None);
+
+        let json = serde_json::to_value(&address).unwrap();
+        assert_eq!(json["neighbourhood"], serde_json::Value::Null);
+        assert_eq!(
+            serde_json::from_value::<PostalAddress>(json).unwrap(),
+            address
+        );
+    }
+
+    fn synthetic_routes() -> Routes {
+        let leg = |summary: &str, meters: f64| Leg {
+            summary: summary.to_string(),
+            duration: Duration {
+                value: meters / 10.0,
+                text: String::new(),
+            },
+            distance: Distance {
+                value: meters,
+                text: String::new(),
+            },
+            steps: Vec::new(),
+        };
+        let route = |legs| Route {
+            legs,
+            overview_polyline: None,
+        };
+
+        Routes {
+            routes: vec![
+                route(vec![leg("a", 100.0), leg("b", 200.0)]),
+                route(Vec::new()),
+                route(vec![leg("c", 300.0)]),
+            ],
+        }
+    }
+
+    #[test]
+    fn iterate_routes() {
+        let routes = synthetic_routes();
+        assert_eq!(routes.len(), 3);
+        assert!(!routes.is_empty());
+        assert_eq!(routes.get(2).unwrap().legs().count(), 1);
+        assert!(routes.get(3).is_none());
+
+        let legs: Vec<usize> = (&routes).into_iter().map(|r| r.legs().count()).collect();
+        assert_eq!(legs, vec![2, 0, 1]);
+
+        let flattened: Vec<(usize, &str)> = routes
+            .all_legs()
+            .map(|(route, leg)| (route, leg.summary.as_str()))
+            .collect();
+        assert_eq!(flattened, vec![(0, "a"), (0, "b"), (2, "c")]);
+
+        let owned: Vec<Route> = routes.clone().into_iter().collect();
+        assert_eq!(owned, routes.routes);
+
+        let empty = Routes { routes: Vec::new() };
+        assert!(empty.is_empty());
+        assert_eq!(empty.all_legs().count(), 0);
+    }
+
+    #[test]
+    fn equality_ignores_text_of_values() {
+        let routes: Routes = serde_json::from_str(FIXTURES[0].1).unwrap();
+
+        let mut other = routes.clone();
+        other.routes[0].legs[0].distance.text = "40.5 km".to_string();
+        assert_eq!(other, routes);
+
+        other.routes[0].legs[0].summary.push('!');
+        assert_ne!(other, routes);
+    }
+
+    fn duration(value: f64) -> Duration {
+        Duration {
+            value,
+            text: String::new(),
+        }
+    }
+
+    fn distance(value: f64) -> Distance {
+        Distance {
+            value,
+            text: String::new(),
+        }
+    }
+
+    #[test]
+    fn duration_conversions() {
+        let fractional = duration(90.25);
+        assert_eq!(
+            fractional.as_std(),
+            std::time::Duration::from_millis(90_250)
+        );
+        assert_eq!(
+            std::time::Duration::try_from(&fractional),
+            Ok(std::time::Duration::from_millis(90_250))
+        );
+        assert_eq!(fractional.minutes(), 1.5041666666666667);
+        assert_eq!(
+            duration(0.000_001).as_std(),
+            std::time::Duration::from_micros(1)
+        );
+    }
+
+    #[test]
+    fn invalid_duration() {
+        for value in [-1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e30] {
+            let err = std::time::Duration::try_from(duration(value)).unwrap_err();
+            assert!(err.value().to_bits() == value.to_bits(), "{}", value);
+        }
+        assert_eq!(
+            std::time::Duration::try_from(duration(-1.5))
+                .unwrap_err()
+                .to_string(),
+            "-1.5 seconds is not a valid duration, expected a finite non-negative number"
+        );
+
+        assert_eq!(duration(-1.0).as_std(), std::time::Duration::ZERO);
+        assert_eq!(duration(f64::NAN).as_std(), std::time::Duration::ZERO);
+        assert_eq!(duration(f64::INFINITY).as_std(), std::time::Duration::MAX);
+    }
+
+    #[test]
+    fn distance_units() {
+        let distance = distance(12_345.0);
+        assert_eq!(distance.meters(), 12_345.0);
+        assert_eq!(distance.kilometers(), 12.345);
+    }
+
+    #[test]
+    fn compare_by_value() {
+        assert!(duration(60.0) < duration(61.5));
+        assert_eq!(
+            Duration {
+                value: 60.0,
+                text: "۱ دقیقه".to_string()
+            },
+            duration(60.0)
+        );
+        assert!(distance(1000.0) > distance(999.0));
+        assert_eq!(duration(f64::NAN).partial_cmp(&duration(1.0)), None);
+    }
+
+    #[test]
+    fn route_options_serde_round_trip() {
+        let options = RouteOptions::new()
+            .avoid_traffic_zone(true)
+            .alternative_paths(true)
+            .priority(Priority::Low);
+
+        let json = serde_json::to_value(&options).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "avoid_traffic_zone": true,
+                "avoid_odd_even_zone": false,
+                "alternative_paths": true,
+                "priority": "low"
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<RouteOptions>(json).unwrap(),
+            options
+        );
+
+        let partial: RouteOptions =
+            serde_json::from_str(r#"{ "avoid_odd_even_zone": true }"#).unwrap();
+        assert_eq!(partial, RouteOptions::new().avoid_odd_even_zone(true));
+    }
+
+    #[test]
+    fn type_serde_round_trip() {
+        let vehicles = [
+            Type::Car,
+            Type::Motorcycle,
+            Type::Other("truck".to_string()),
+        ];
+        for vehicle in vehicles {
+            let json = serde_json::to_string(&vehicle).unwrap();
+            assert_eq!(json, format!("\"{}\"", vehicle));
+            assert_eq!(serde_json::from_str::<Type>(&json).unwrap(), vehicle);
+            assert_eq!(vehicle.to_string().parse::<Type>().unwrap(), vehicle);
+        }
+
+        assert_eq!(Type::Car.to_string(), "car");
+        assert_eq!(Type::Motorcycle.to_string(), "motorcycle");
+        assert_eq!(
+            serde_json::from_str::<Type>("\"Motorcycle\"").unwrap(),
+            Type::Motorcycle
+        );
+    }
+
+    #[test]
+    fn parse_type() {
+        assert_eq!("car".parse::<Type>().unwrap(), Type::Car);
+        assert_eq!("CAR".parse::<Type>().unwrap(), Type::Car);
+        assert_eq!("MotorCycle".parse::<Type>().unwrap(), Type::Motorcycle);
+        // unknown vehicles are kept verbatim.
+        let truck = "Truck".parse::<Type>().unwrap();
+        assert_eq!(truck, Type::Other("Truck".to_string()));
+        assert_eq!(truck.to_string(), "Truck");
+        assert_eq!(truck.as_str(), "Truck");
+
+        // a blank vehicle would be sent as an empty `type`.
+        for blank in ["", " ", "\t\n"] {
+            let err = blank.parse::<Type>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "vehicle {:?} is blank, expected e.g. car or motorcycle",
+                    blank
+                )
+            );
+            let json = serde_json::to_string(blank).unwrap();
+            assert!(serde_json::from_str::<Type>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn other_vehicles_pass_through() {
+        // parse, print and parse again keeps unknown values as they were written.
+        for vehicle in ["Truck", "heavy/truck", "وانت", " bus "] {
+            let parsed = vehicle.parse::<Type>().unwrap();
+            assert_eq!(parsed, Type::Other(vehicle.to_string()));
+            assert_eq!(parsed.to_string(), vehicle);
+            assert_eq!(parsed.to_string().parse::<Type>().unwrap(), parsed);
+        }
+        for (vehicle, known) in [("Car", Type::Car), ("MOTORCYCLE", Type::Motorcycle)] {
+            let parsed = vehicle.parse::<Type>().unwrap();
+            assert_eq!(parsed, known);
+            assert_eq!(parsed.to_string().parse::<Type>().unwrap(), known);
+        }
+
+        // in a config file and in the query sent to neshan.
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Config {
+            vehicle: Type,
+        }
+        let config: Config = serde_json::from_str(r#"{ "vehicle": "Van" }"#).unwrap();
+        assert_eq!(config.vehicle, Type::Other("Van".to_string()));
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"vehicle":"Van"}"#
+        );
+        let query = crate::protocol::route_query(
+            config.vehicle,
+            Point::new_unchecked(35.7, 51.4),
+            Point::new_unchecked(35.8, 51.0),
+            &RouteOptions::new(),
+        );
+        assert!(query.as_str().starts_with("type=Van&"));
+    }
+
     #[tokio::test]
+    #[allow(deprecated)]
     async fn routes() {
-        let api_key = std::env::var("NESHAN_RS_API_KEY").unwrap();
+        let neshan = super::MockNeshan::start().await;
 
-        let client = super::Client::new(&api_key);
+        let client = neshan.client();
         let routes = client
             .route(
                 super::Type::Car,
@@ -208,14 +1133,25 @@ mod tests {
             .await
             .unwrap();
 
-        println!("{:?}", routes);
+        assert_eq!(routes.routes.len(), 2);
+        assert_eq!(routes.routes[0].legs[0].distance.value, 40512.0);
+
+        let requests = neshan.requests().await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/v3/direction");
+        assert_eq!(
+            requests[0].query("origin"),
+            Some("35.731984409609694,51.392684661470156")
+        );
+        assert_eq!(requests[0].query("avoid_traffic_zone"), Some("true"));
+        assert_eq!(requests[0].query("alternative"), Some("false"));
     }
 
     #[tokio::test]
     async fn reverse_geocode() {
-        let api_key = std::env::var("NESHAN_RS_API_KEY").unwrap();
+        let neshan = super::MockNeshan::start().await;
 
-        let client = super::Client::new(&api_key);
+        let client = neshan.client();
         let postal_address = client
             .reverse_geocode(super::Point {
                 latitude: 35.731984409609694,
@@ -228,6 +1164,11 @@ mod tests {
         assert_eq!(postal_address.municipality_zone.as_ref().unwrap(), "6");
         assert_eq!(postal_address.city, "تهران");
 
-        println!("{:?}", postal_address);
+        let requests = neshan.requests().await;
+        assert_eq!(requests[0].query("lat"), Some("35.731984409609694"));
+        assert_eq!(
+            requests[0].header("api-key"),
+            Some(super::MockNeshan::API_KEY)
+        );
     }
 }
@@ -0,0 +1,155 @@
+//! links that open a location, a route or a search on the neshan website, e.g. to share a
+//! result with a user. the neshan app opens these links as well when it is installed.
+//!
+//! the links are built offline with the formats of the website:
+//!
+//! - a point: `https://neshan.org/maps/@35.699700,51.338000,16z`
+//! - a route: `https://neshan.org/maps/routing/car/origin/35.699700,51.338000/destination/35.835500,50.991500`
+//! - a search: `https://neshan.org/maps/search/%DA%A9%D8%A7%D9%81%D9%87`
+//!
+//! coordinates are written with six decimals as in the api calls, terms and vehicles are
+//! percent encoded as a single path segment. neshan doesn't document a uri scheme of its
+//! app, so there are no `neshan://` variants.
+
+use crate::{Point, Type};
+use url::Url;
+
+const MAPS: &str = "https://neshan.org/maps";
+
+/// zoom of the map around a point, about the level of a few streets.
+const POINT_ZOOM: u8 = 16;
+
+fn maps(segments: &[&str]) -> Url {
+    let mut url = Url::parse(MAPS).expect("the maps url is valid");
+    url.path_segments_mut()
+        .expect("the maps url has a path")
+        .extend(segments);
+
+    url
+}
+
+/// the map centered on `point`, e.g. a resolved address.
+pub fn point_url(point: &Point) -> Url {
+    maps(&[&format!("@{},{}z", point, POINT_ZOOM)])
+}
+
+/// the route from `origin` to `destination` with the given vehicle.
+pub fn route_url(origin: &Point, destination: &Point, vehicle: &Type) -> Url {
+    maps(&[
+        "routing",
+        vehicle.as_str(),
+        "origin",
+        &origin.to_string(),
+        "destination",
+        &destination.to_string(),
+    ])
+}
+
+/// places matching `term`, e.g. `کافه`. a term of `.` or `..` can't be a segment of a url
+/// and opens the search without a term.
+pub fn search_url(term: &str) -> Url {
+    maps(&["search", term])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{point_url, route_url, search_url};
+    use crate::{Point, Type};
+
+    const TEHRAN: Point = Point {
+        latitude: 35.6997,
+        longitude: 51.338,
+    };
+    const KARAJ: Point = Point {
+        latitude: 35.8355,
+        longitude: 50.9915,
+    };
+
+    #[test]
+    fn point_and_route() {
+        assert_eq!(
+            point_url(&TEHRAN).as_str(),
+            "https://neshan.org/maps/@35.699700,51.338000,16z"
+        );
+        assert_eq!(
+            point_url(&Point::new_unchecked(-0.5, -179.25)).as_str(),
+            "https://neshan.org/maps/@-0.500000,-179.250000,16z"
+        );
+        assert_eq!(
+            route_url(&TEHRAN, &KARAJ, &Type::Car).as_str(),
+            "https://neshan.org/maps/routing/car/origin/35.699700,51.338000\
+             /destination/35.835500,50.991500"
+        );
+        assert_eq!(
+            route_url(&KARAJ, &TEHRAN, &Type::Motorcycle).path(),
+            "/maps/routing/motorcycle/origin/35.835500,50.991500/destination/35.699700,51.338000"
+        );
+        // unknown vehicles are a single segment like search terms.
+        assert_eq!(
+            route_url(&TEHRAN, &KARAJ, &"heavy/truck".parse().unwrap()).path(),
+            "/maps/routing/heavy%2Ftruck/origin/35.699700,51.338000/destination/35.835500,50.991500"
+        );
+    }
+
+    #[test]
+    fn search_terms_are_encoded() {
+        let cases = [
+            ("cafe", "cafe"),
+            ("کافه", "%DA%A9%D8%A7%D9%81%D9%87"),
+            (
+                "میدان آزادی",
+                "%D9%85%DB%8C%D8%AF%D8%A7%D9%86%20%D8%A2%D8%B2%D8%A7%D8%AF%DB%8C",
+            ),
+            // zero width non-joiner of persian words.
+            ("می\u{200c}رود", "%D9%85%DB%8C%E2%80%8C%D8%B1%D9%88%D8%AF"),
+            ("a b", "a%20b"),
+            ("50%", "50%25"),
+            ("a/b", "a%2Fb"),
+            ("what?", "what%3F"),
+            ("#1", "%231"),
+            ("a+b&c=d", "a+b&c=d"),
+            ("...", "..."),
+            ("", ""),
+        ];
+
+        for (term, encoded) in cases {
+            let url = search_url(term);
+            assert_eq!(
+                url.as_str(),
+                format!("https://neshan.org/maps/search/{}", encoded),
+                "{:?}",
+                term
+            );
+            assert_eq!(url.query(), None);
+            assert_eq!(url.fragment(), None);
+
+            let segment = url.path_segments().unwrap().next_back().unwrap();
+            let decoded = percent_decode(segment);
+            assert_eq!(decoded, term);
+        }
+
+        for term in [".", ".."] {
+            assert_eq!(search_url(term).as_str(), "https://neshan.org/maps/search");
+        }
+    }
+
+    fn percent_decode(segment: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut rest = segment.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            match byte {
+                b'%' => {
+                    let hex = std::str::from_utf8(&tail[..2]).unwrap();
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                    rest = &tail[2..];
+                }
+                _ => {
+                    bytes.push(byte);
+                    rest = tail;
+                }
+            }
+        }
+
+        String::from_utf8(bytes).unwrap()
+    }
+}
@@ -0,0 +1,747 @@
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::point::collect_points;
+use crate::polyline::{self, Precision};
+use crate::Point;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+
+/// a recorded trace snapped onto the road network.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchedTrace {
+    #[serde(rename = "snappedPoints")]
+    pub snapped_points: Vec<MatchedPoint>,
+    /// encoded polyline of the matched path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchedPoint {
+    /// the snapped coordinate on the road.
+    pub location: Point,
+    /// index of the point in the input of `Client::map_match`, `None` for points neshan added
+    /// along the road.
+    #[serde(
+        rename = "originalIndex",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub original_index: Option<usize>,
+    /// meters between the input point and `location`, when neshan reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    /// index of the segment of `geometry` the point is on, when neshan reports it.
+    #[serde(
+        rename = "segmentIndex",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub segment_index: Option<usize>,
+}
+
+impl MatchedTrace {
+    /// the snapped trace of a map matching body, e.g. one kept to replay a trip.
+    pub fn from_json(json: &str) -> Result<MatchedTrace, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// the farthest any point was moved to snap it onto the road, `None` when neshan reported
+    /// no snap distances. a large value hints at a trace that doesn't follow the roads.
+    pub fn max_snap_distance(&self) -> Option<f64> {
+        self.snapped_points
+            .iter()
+            .filter_map(|point| point.distance)
+            .filter(|distance| !distance.is_nan())
+            .fold(None, |max, distance| match max {
+                Some(max) if max >= distance => Some(max),
+                _ => Some(distance),
+            })
+    }
+
+    /// locations of the snapped points in order, e.g. for `BoundingBox::from_points`.
+    pub fn points(&self) -> Vec<Point> {
+        self.snapped_points
+            .iter()
+            .map(|point| point.location)
+            .collect()
+    }
+}
+
+/// a trace of a single point has no road to follow.
+pub(crate) fn check_trace(points: &[Point]) -> Result<(), NeshanError> {
+    match points.len() {
+        0 | 1 => Err(NeshanError::InvalidRequest(format!(
+            "map matching needs at least two points, the trace has {}",
+            points.len()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// meters to the nearest road beyond which `Client::snap_to_road` finds no road nearby.
+const SNAP_RADIUS: f64 = 500.0;
+
+/// a point moved onto the nearest road by `Client::snap_to_road`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnappedPoint {
+    /// the point on the road.
+    pub point: Point,
+    /// meters between the input point and `point`.
+    pub distance: f64,
+}
+
+/// a chunk of `Client::map_match_chunked` that failed, with the indices of its points in the
+/// input so that just that slice can be retried.
+#[derive(Debug)]
+pub struct MapMatchChunkError {
+    pub points: Range<usize>,
+    pub error: NeshanError,
+}
+
+impl fmt::Display for MapMatchChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "map matching points {} to {} failed: {}",
+            self.points.start, self.points.end, self.error
+        )
+    }
+}
+
+impl std::error::Error for MapMatchChunkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// windows of `chunk_size` points, each starting `overlap` points before the end of the
+/// previous one. the last window has at least two points as well.
+fn chunks(len: usize, chunk_size: usize, overlap: usize) -> Vec<Range<usize>> {
+    let chunk_size = chunk_size.max(2);
+    let step = chunk_size - overlap.min(chunk_size - 1);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = len.min(start + chunk_size);
+        chunks.push(start.min(len.saturating_sub(2))..end);
+        if end == len {
+            return chunks;
+        }
+        start += step;
+    }
+}
+
+/// position of the first snapped point of input point `cut` or later, points neshan added go
+/// with the input point before them.
+fn seam(points: &[MatchedPoint], cut: usize) -> usize {
+    points
+        .iter()
+        .position(|point| point.original_index.is_some_and(|index| index >= cut))
+        .unwrap_or(points.len())
+}
+
+/// how traces longer than `MapMatchOptions::max_points` are thinned out. the first and last
+/// points are always kept, and when the strategy still leaves too many points they are
+/// thinned out evenly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Downsample {
+    /// keep every nth point.
+    EveryNth(usize),
+    /// drop points closer than this many meters to the last kept one.
+    MinSpacing(f64),
+}
+
+/// options of `Client::map_match_with`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapMatchOptions {
+    max_points: usize,
+    downsample: Downsample,
+}
+
+impl Default for MapMatchOptions {
+    fn default() -> MapMatchOptions {
+        MapMatchOptions {
+            max_points: MapMatchOptions::MAX_POINTS,
+            downsample: Downsample::MinSpacing(10.0),
+        }
+    }
+}
+
+impl MapMatchOptions {
+    /// most points sent in one request by default.
+    pub const MAX_POINTS: usize = 500;
+
+    pub fn new() -> MapMatchOptions {
+        MapMatchOptions::default()
+    }
+
+    /// most points sent in one request, at least two.
+    pub fn max_points(mut self, max_points: usize) -> MapMatchOptions {
+        self.max_points = max_points.max(2);
+        self
+    }
+
+    pub fn downsample(mut self, downsample: Downsample) -> MapMatchOptions {
+        self.downsample = downsample;
+        self
+    }
+
+    /// indices of the points that are sent, all of them when they are within the limit.
+    pub(crate) fn keep(&self, points: &[Point]) -> Vec<usize> {
+        let all: Vec<usize> = (0..points.len()).collect();
+        if points.len() <= self.max_points {
+            return all;
+        }
+
+        let last = points.len() - 1;
+        let mut kept: Vec<usize> = match self.downsample {
+            Downsample::EveryNth(n) => all.into_iter().step_by(n.max(1)).collect(),
+            Downsample::MinSpacing(meters) => {
+                let mut kept = vec![0];
+                for (i, point) in points.iter().enumerate().skip(1) {
+                    if point.haversine_distance_to(&points[kept[kept.len() - 1]]) >= meters {
+                        kept.push(i);
+                    }
+                }
+                kept
+            }
+        };
+        if kept.last() != Some(&last) {
+            kept.push(last);
+        }
+
+        if kept.len() > self.max_points {
+            let step = (kept.len() - 1) as f64 / (self.max_points - 1) as f64;
+            kept = (0..self.max_points)
+                .map(|i| kept[(i as f64 * step).round() as usize])
+                .collect();
+        }
+
+        kept
+    }
+}
+
+impl Client {
+    /// snap a recorded trace onto the roads, with the default `MapMatchOptions`.
+    /// https://platform.neshan.org/api/map-matching
+    pub async fn map_match(
+        &self,
+        points: impl IntoIterator<Item = impl Into<Point>>,
+    ) -> Result<MatchedTrace, NeshanError> {
+        self.map_match_with(points, &MapMatchOptions::default())
+            .await
+    }
+
+    /// same as `map_match`, thinning out long traces as the options say. the original indices
+    /// of the snapped points refer to `points`, not to the points that were sent. fails
+    /// without sending a request when there are fewer than two points.
+    pub async fn map_match_with(
+        &self,
+        points: impl IntoIterator<Item = impl Into<Point>>,
+        options: &MapMatchOptions,
+    ) -> Result<MatchedTrace, NeshanError> {
+        let points = collect_points(points);
+        check_trace(&points)?;
+        let kept = options.keep(&points);
+        let sent: Vec<Point> = kept.iter().map(|i| points[*i]).collect();
+        self.check(&sent)?;
+
+        let query = crate::protocol::map_match_query(&sent);
+        let call = self.get(Endpoint::MapMatching, &query);
+        let (mut matching, _): (MatchedTrace, _) =
+            crate::trace::instrument(Endpoint::MapMatching, &sent, call).await?;
+
+        for point in &mut matching.snapped_points {
+            point.original_index = point
+                .original_index
+                .and_then(|index| kept.get(index).copied());
+        }
+
+        Ok(matching)
+    }
+
+    /// the nearest point on a road to `point`, e.g. a position reported from inside a
+    /// building, to start a route from. `None` when neshan snapped it more than 500 meters
+    /// away or not at all, see `Client::snap_to_road_within`. the point is sent to the map
+    /// matching api as a trace of two fixes at the same spot.
+    pub async fn snap_to_road(
+        &self,
+        point: impl Into<Point>,
+    ) -> Result<Option<SnappedPoint>, NeshanError> {
+        self.snap_to_road_within(point, SNAP_RADIUS).await
+    }
+
+    /// same as `Client::snap_to_road`, finding no road beyond `radius` meters.
+    pub async fn snap_to_road_within(
+        &self,
+        point: impl Into<Point>,
+        radius: f64,
+    ) -> Result<Option<SnappedPoint>, NeshanError> {
+        let point = point.into();
+        let matching = self.map_match([point, point]).await?;
+
+        let snapped = matching
+            .snapped_points
+            .iter()
+            .find(|snapped| snapped.original_index.is_some())
+            .or_else(|| matching.snapped_points.first())
+            .map(|snapped| SnappedPoint {
+                point: snapped.location,
+                distance: point.haversine_distance_to(&snapped.location),
+            });
+        Ok(snapped.filter(|snapped| snapped.distance <= radius))
+    }
+
+    /// same as `map_match` for traces longer than a request allows. the trace is matched in
+    /// chunks of `chunk_size` points, one after the other, each one repeating the last
+    /// `overlap` points of the previous chunk. at a seam the points before the middle of the
+    /// overlap come from the earlier chunk and the rest from the later one, so the snapped
+    /// points have no repeats or jumps where the chunks meet.
+    ///
+    /// the original indices refer to `points`. the segment indices of a chunk refer to its own
+    /// geometry and are left out, `geometry` is the polyline of the snapped points instead.
+    /// stops at the first chunk that fails.
+    pub async fn map_match_chunked(
+        &self,
+        points: impl IntoIterator<Item = impl Into<Point>>,
+        chunk_size: usize,
+        overlap: usize,
+    ) -> Result<MatchedTrace, MapMatchChunkError> {
+        let points = collect_points(points);
+        let options = MapMatchOptions::new().max_points(chunk_size);
+
+        let mut stitched: Vec<MatchedPoint> = Vec::new();
+        // points of the last chunk from its seam with the previous one on.
+        let mut pending: Vec<MatchedPoint> = Vec::new();
+        let mut previous_end: Option<usize> = None;
+        for range in chunks(points.len(), chunk_size, overlap) {
+            let chunk = self
+                .map_match_with(&points[range.clone()], &options)
+                .await
+                .map_err(|error| MapMatchChunkError {
+                    points: range.clone(),
+                    error,
+                })?;
+            let mut snapped = chunk.snapped_points;
+            for point in &mut snapped {
+                point.original_index = point.original_index.map(|index| index + range.start);
+                point.segment_index = None;
+            }
+
+            let from = match previous_end {
+                Some(end) => {
+                    let cut = range.start + (end - range.start) / 2;
+                    let at = seam(&pending, cut);
+                    stitched.extend(pending.drain(..at));
+                    seam(&snapped, cut)
+                }
+                None => 0,
+            };
+            pending = snapped.split_off(from);
+            // both chunks may have snapped onto the same spot at the seam.
+            if stitched.last().map(|point| point.location)
+                == pending.first().map(|point| point.location)
+            {
+                pending.remove(0);
+            }
+            previous_end = Some(range.end);
+        }
+        stitched.append(&mut pending);
+
+        let geometry = match stitched.is_empty() {
+            true => None,
+            false => {
+                let points: Vec<Point> = stitched.iter().map(|point| point.location).collect();
+                Some(polyline::encode(&points, Precision::Five))
+            }
+        };
+        Ok(MatchedTrace {
+            snapped_points: stitched,
+            geometry,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks, Downsample, MapMatchOptions, MatchedPoint, MatchedTrace};
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::Point;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// points 1.1 meters apart along a meridian.
+    fn trace(len: usize) -> Vec<Point> {
+        (0..len)
+            .map(|i| Point::new_unchecked((3_570_000 + i) as f64 / 1e5, 51.4))
+            .collect()
+    }
+
+    #[test]
+    fn short_traces_are_sent_whole() {
+        let options = MapMatchOptions::new().max_points(10);
+        assert_eq!(options.keep(&trace(10)), (0..10).collect::<Vec<_>>());
+        assert!(options.keep(&[]).is_empty());
+    }
+
+    #[test]
+    fn every_nth() {
+        let options = MapMatchOptions::new()
+            .max_points(5)
+            .downsample(Downsample::EveryNth(3));
+        assert_eq!(options.keep(&trace(10)), vec![0, 3, 6, 9]);
+        assert_eq!(options.keep(&trace(12)), vec![0, 3, 6, 9, 11]);
+        // still too many, thinned out evenly.
+        assert_eq!(options.keep(&trace(20)), vec![0, 6, 12, 15, 19]);
+    }
+
+    #[test]
+    fn min_spacing() {
+        let options = MapMatchOptions::new()
+            .max_points(5)
+            .downsample(Downsample::MinSpacing(3.0));
+        // a point every 1.1 meters, every third one is far enough from the last kept.
+        assert_eq!(options.keep(&trace(8)), vec![0, 3, 6, 7]);
+
+        let mut stops = trace(3);
+        stops.extend(vec![Point::new_unchecked(35.7, 51.41); 10]);
+        assert_eq!(options.keep(&stops), vec![0, 3, 12]);
+    }
+
+    #[test]
+    fn fixtures() {
+        let plain: MatchedTrace =
+            serde_json::from_str(include_str!("../fixtures/map_matching.json")).unwrap();
+        assert_eq!(plain.snapped_points.len(), 4);
+        assert_eq!(plain.snapped_points[2].original_index, None);
+        assert!(plain
+            .snapped_points
+            .iter()
+            .all(|point| point.distance.is_none() && point.segment_index.is_none()));
+        assert_eq!(plain.max_snap_distance(), None);
+        assert!(!serde_json::to_string(&plain)
+            .unwrap()
+            .contains("segmentIndex"));
+
+        let detailed: MatchedTrace =
+            serde_json::from_str(include_str!("../fixtures/map_matching_detailed.json")).unwrap();
+        assert_eq!(detailed.snapped_points[1].distance, Some(0.8));
+        assert_eq!(detailed.snapped_points[2].distance, None);
+        assert_eq!(detailed.snapped_points[3].segment_index, Some(5));
+        assert_eq!(detailed.max_snap_distance(), Some(14.6));
+        assert_eq!(
+            serde_json::from_value::<MatchedTrace>(serde_json::to_value(&detailed).unwrap())
+                .unwrap(),
+            detailed
+        );
+    }
+
+    #[test]
+    fn max_snap_distance() {
+        let trace = |distances: &[Option<f64>]| MatchedTrace {
+            snapped_points: distances
+                .iter()
+                .map(|distance| MatchedPoint {
+                    location: Point::new_unchecked(35.7, 51.4),
+                    original_index: None,
+                    distance: *distance,
+                    segment_index: None,
+                })
+                .collect(),
+            geometry: None,
+        };
+
+        assert_eq!(trace(&[]).max_snap_distance(), None);
+        assert_eq!(trace(&[None, None]).max_snap_distance(), None);
+        assert_eq!(
+            trace(&[Some(3.0), None, Some(12.5), Some(0.0)]).max_snap_distance(),
+            Some(12.5)
+        );
+        assert_eq!(
+            trace(&[Some(f64::NAN), Some(1.5)]).max_snap_distance(),
+            Some(1.5)
+        );
+        assert_eq!(trace(&[Some(0.0)]).max_snap_distance(), Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn snap_a_recorded_trace() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/map-matching"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/map_matching.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        // pings of a motorcycle on azadi street, a few meters off the road.
+        let raw = [
+            Point::new_unchecked(35.69985, 51.33795),
+            Point::new_unchecked(35.70021, 51.35012),
+            Point::new_unchecked(35.70052, 51.36189),
+        ];
+        let matching = client.map_match(&raw).await.unwrap();
+
+        let points = matching.points();
+        assert_eq!(points.len(), 4);
+        for point in &matching.snapped_points {
+            if let Some(index) = point.original_index {
+                let moved = raw[index].haversine_distance_to(&point.location);
+                assert!(moved > 1.0 && moved < 50.0, "{}: {}", index, moved);
+            }
+        }
+        assert_eq!(points[2], matching.snapped_points[2].location);
+
+        for trace in [&raw[..0], &raw[..1]] {
+            let err = client.map_match(trace).await.unwrap_err();
+            assert!(
+                matches!(err, NeshanError::InvalidRequest(ref message) if message.contains("two points")),
+                "{}",
+                err
+            );
+            assert!(crate::protocol::build_map_match_request("key", trace).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn snap_points_to_the_road() {
+        // the middle of laleh park, and a point on kargar street.
+        let park = Point::new_unchecked(35.7115, 51.393);
+        let kargar = Point::new_unchecked(35.7115, 51.3897);
+
+        let server = MockServer::start().await;
+        let snapped = |latitude: f64, longitude: f64| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "snappedPoints": [
+                    {"location": {"latitude": latitude, "longitude": longitude}, "originalIndex": 0},
+                    {"location": {"latitude": latitude, "longitude": longitude}, "originalIndex": 1}
+                ]
+            }))
+        };
+        Mock::given(path("/v3/map-matching"))
+            .and(query_param(
+                "path",
+                "35.711500,51.393000|35.711500,51.393000",
+            ))
+            .respond_with(snapped(35.7115, 51.3897))
+            .mount(&server)
+            .await;
+        Mock::given(path("/v3/map-matching"))
+            .and(query_param(
+                "path",
+                "35.711500,51.389700|35.711500,51.389700",
+            ))
+            .respond_with(snapped(35.71152, 51.3897))
+            .mount(&server)
+            .await;
+        Mock::given(path("/v3/map-matching"))
+            .and(query_param(
+                "path",
+                "35.000000,51.000000|35.000000,51.000000",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "snappedPoints": []
+            })))
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+
+        let from_park = client.snap_to_road(park).await.unwrap().unwrap();
+        assert_eq!(from_park.point, kargar);
+        assert!(
+            (from_park.distance - 300.0).abs() < 10.0,
+            "{}",
+            from_park.distance
+        );
+
+        let on_street = client.snap_to_road(kargar).await.unwrap().unwrap();
+        assert!(on_street.distance < 5.0, "{}", on_street.distance);
+
+        assert_eq!(client.snap_to_road_within(park, 100.0).await.unwrap(), None);
+        assert_eq!(client.snap_to_road((35.0, 51.0)).await.unwrap(), None);
+    }
+
+    #[test]
+    fn chunk_windows() {
+        assert_eq!(chunks(10, 4, 1), vec![0..4, 3..7, 6..10]);
+        // the last window would have a single point.
+        assert_eq!(chunks(9, 4, 0), vec![0..4, 4..8, 7..9]);
+        assert_eq!(chunks(3, 10, 2), vec![0..3]);
+        assert_eq!(chunks(4, 2, 5), vec![0..2, 1..3, 2..4]);
+        assert_eq!(chunks(0, 4, 1), vec![0..0]);
+    }
+
+    /// snaps every point of the path 4 meters east and adds a point between each two, failing
+    /// a path that starts at `fail`.
+    fn snap(fail: Option<f64>) -> impl Fn(&wiremock::Request) -> ResponseTemplate {
+        move |request: &wiremock::Request| {
+            let (_, path) = request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == "path")
+                .unwrap();
+            let points: Vec<(f64, f64)> = path
+                .split('|')
+                .map(|point| {
+                    let (lat, lng) = point.split_once(',').unwrap();
+                    (lat.parse().unwrap(), lng.parse::<f64>().unwrap() + 0.00004)
+                })
+                .collect();
+            if fail == Some(points[0].0) {
+                return ResponseTemplate::new(500);
+            }
+
+            let mut snapped = Vec::new();
+            for (i, (lat, lng)) in points.iter().enumerate() {
+                if i > 0 {
+                    let between = (lat + points[i - 1].0) / 2.0;
+                    snapped.push(serde_json::json!({
+                        "location": {"latitude": between, "longitude": lng}
+                    }));
+                }
+                snapped.push(serde_json::json!({
+                    "location": {"latitude": lat, "longitude": lng},
+                    "originalIndex": i,
+                    "segmentIndex": i,
+                }));
+            }
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "snappedPoints": snapped,
+                "geometry": "_p~iF~ps|U"
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn chunks_are_stitched_at_the_seams() {
+        let server = MockServer::start().await;
+        Mock::given(path("/v3/map-matching"))
+            .respond_with(snap(None))
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+
+        // 20 points 11 meters apart, in chunks of 0..6, 4..10, 8..14, 12..18 and 16..20.
+        let raw: Vec<Point> = (0..20)
+            .map(|i| Point::new_unchecked(35.7 + f64::from(i) * 1e-4, 51.4))
+            .collect();
+        let matching = client.map_match_chunked(&raw, 6, 2).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 5);
+
+        // every input point once and every added point once.
+        assert_eq!(matching.snapped_points.len(), 39);
+        let indices: Vec<usize> = matching
+            .snapped_points
+            .iter()
+            .filter_map(|point| point.original_index)
+            .collect();
+        assert_eq!(indices, (0..20).collect::<Vec<_>>());
+        for pair in matching.points().windows(2) {
+            let gap = pair[0].haversine_distance_to(&pair[1]);
+            assert!(gap > 1.0 && gap < 8.0, "{} {} {}", pair[0], pair[1], gap);
+        }
+        assert!(matching
+            .snapped_points
+            .iter()
+            .all(|point| point.segment_index.is_none()));
+        let geometry = crate::polyline::decode(
+            matching.geometry.as_deref().unwrap(),
+            crate::polyline::Precision::Five,
+        )
+        .unwrap();
+        assert_eq!(geometry.len(), 39);
+
+        // a trace within a chunk is matched as it is.
+        let short = client.map_match_chunked(&raw[..5], 6, 2).await.unwrap();
+        assert_eq!(short.snapped_points.len(), 9);
+    }
+
+    #[tokio::test]
+    async fn failed_chunk_has_its_range() {
+        let server = MockServer::start().await;
+        let raw: Vec<Point> = (0..20)
+            .map(|i| Point::new_unchecked(35.7 + f64::from(i) * 1e-4, 51.4))
+            .collect();
+        Mock::given(path("/v3/map-matching"))
+            .respond_with(snap(Some(raw[8].latitude)))
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .retry(crate::RetryPolicy::new().max_attempts(1))
+            .build()
+            .unwrap();
+
+        let err = client.map_match_chunked(&raw, 6, 2).await.unwrap_err();
+        assert_eq!(err.points, 8..14);
+        assert!(matches!(err.error, NeshanError::Api(_)));
+        assert!(err
+            .to_string()
+            .starts_with("map matching points 8 to 14 failed"));
+        // the chunks after the failed one aren't requested.
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        let err = client.map_match_chunked(&raw[..1], 6, 2).await.unwrap_err();
+        assert_eq!(err.points, 0..1);
+        assert!(matches!(err.error, NeshanError::InvalidRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn original_indices_refer_to_the_input() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/map-matching"))
+            .and(query_param(
+                "path",
+                "35.700000,51.400000|35.700030,51.400000|35.700060,51.400000|35.700070,51.400000",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "snappedPoints": [
+                    {"location": {"latitude": 35.7, "longitude": 51.4001}, "originalIndex": 0},
+                    {"location": {"latitude": 35.70002, "longitude": 51.4001}},
+                    {"location": {"latitude": 35.70003, "longitude": 51.4001}, "originalIndex": 1},
+                    {"location": {"latitude": 35.70007, "longitude": 51.4001}, "originalIndex": 3}
+                ],
+                "geometry": "_p~iF~ps|U"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let options = MapMatchOptions::new()
+            .max_points(5)
+            .downsample(Downsample::MinSpacing(3.0));
+        let matching = client.map_match_with(&trace(8), &options).await.unwrap();
+
+        let indices: Vec<Option<usize>> = matching
+            .snapped_points
+            .iter()
+            .map(|point| point.original_index)
+            .collect();
+        assert_eq!(indices, vec![Some(0), None, Some(3), Some(7)]);
+        assert_eq!(matching.geometry.as_deref(), Some("_p~iF~ps|U"));
+    }
+}
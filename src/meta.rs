@@ -0,0 +1,48 @@
+use http::{HeaderMap, StatusCode};
+use std::time::Duration;
+use url::Url;
+
+/// details of the http exchange behind a result, returned by the `*_with_meta` methods.
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) elapsed: Duration,
+    pub(crate) url: Url,
+    pub(crate) cached: bool,
+}
+
+impl ResponseMeta {
+    /// http status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// headers of the response, the stored ones when it was served from the cache.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// wall time of the whole call, retries and waits included.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// url the client requested, before any middleware changes, also for a response served
+    /// from the cache. the api key is never part of it.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// id neshan gave the request, from the `x-request-id` style headers. for a response
+    /// served from the cache, the id of the request that filled it.
+    pub fn request_id(&self) -> Option<&str> {
+        crate::protocol::request_id(&self.headers)
+    }
+
+    /// whether the response came from the cache instead of neshan. also true when neshan
+    /// answered a revalidation with `304 Not Modified`, `status` is 304 then.
+    pub fn from_cache(&self) -> bool {
+        self.cached
+    }
+}
@@ -0,0 +1,161 @@
+//! hooks for changing requests and responses on their way to and from neshan.
+//!
+//! every http call of the client, retries included, goes through the registered middlewares
+//! in the order they were added to the builder. a middleware may change the request before
+//! calling `next`, change the response afterwards, or answer by itself without calling
+//! `next` at all.
+
+use crate::backend::HttpBackend;
+use crate::error::NeshanError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderMap, Method, StatusCode};
+use std::sync::Arc;
+use url::Url;
+
+/// outgoing http request, the api key is already among its headers.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+}
+
+/// http response with its whole body.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result<Response, NeshanError>;
+}
+
+/// rest of the middleware chain, ending with the http backend.
+pub struct Next<'a> {
+    http: &'a dyn HttpBackend,
+    middlewares: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        http: &'a dyn HttpBackend,
+        middlewares: &'a [Arc<dyn Middleware>],
+    ) -> Next<'a> {
+        Next { http, middlewares }
+    }
+
+    /// pass the request to the next middleware, or send it when this is the last one.
+    pub async fn run(self, req: Request) -> Result<Response, NeshanError> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(req, Next::new(self.http, rest)).await,
+            None => self.http.send(req).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Middleware, Next, Request, Response};
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::Point;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue, StatusCode};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn point() -> Point {
+        Point {
+            latitude: 35.731984409609694,
+            longitude: 51.392684661470156,
+        }
+    }
+
+    fn postal_address() -> serde_json::Value {
+        serde_json::json!({
+            "formatted_address": "تهران، خیابان آزادی",
+            "route_name": "خیابان آزادی",
+            "city": "تهران",
+            "state": "استان تهران",
+            "in_traffic_zone": false,
+            "in_odd_even_zone": false
+        })
+    }
+
+    /// appends its tag to the x-signature header.
+    struct Sign(&'static str);
+
+    #[async_trait]
+    impl Middleware for Sign {
+        async fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response, NeshanError> {
+            let signature = match req.headers.get("x-signature") {
+                Some(value) => format!("{}-{}", value.to_str().unwrap(), self.0),
+                None => self.0.to_string(),
+            };
+            req.headers
+                .insert("x-signature", HeaderValue::from_str(&signature).unwrap());
+
+            next.run(req).await
+        }
+    }
+
+    /// answers every request without touching the network.
+    struct Canned;
+
+    #[async_trait]
+    impl Middleware for Canned {
+        async fn handle(&self, _: Request, _: Next<'_>) -> Result<Response, NeshanError> {
+            Ok(Response {
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: Bytes::from(postal_address().to_string()),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn middlewares_compose_in_order() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .and(header("x-signature", "first-second"))
+            .and(header("api-key", "key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(postal_address()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .middleware(Sign("first"))
+            .middleware(Sign("second"))
+            .build()
+            .unwrap();
+
+        client.reverse_geocode(point()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn middleware_short_circuits() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .middleware(Canned)
+            .build()
+            .unwrap();
+
+        let postal_address = client.reverse_geocode(point()).await.unwrap();
+        assert_eq!(postal_address.city, "تهران");
+    }
+}
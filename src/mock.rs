@@ -0,0 +1,340 @@
+//! a local stand-in for neshan, compiled only with the `test-utils` feature.
+
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// the smallest valid png, a single transparent pixel.
+const PIXEL: [u8; 67] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// response of a `MockNeshan` endpoint.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    body: Vec<u8>,
+    content_type: String,
+    headers: Vec<(String, String)>,
+    delay: Option<Duration>,
+}
+
+impl MockResponse {
+    /// success with the given json body.
+    pub fn json(body: serde_json::Value) -> MockResponse {
+        MockResponse {
+            status: 200,
+            body: body.to_string().into_bytes(),
+            content_type: "application/json".to_string(),
+            headers: Vec::new(),
+            delay: None,
+        }
+    }
+
+    /// failure with neshan's error body, e.g. `MockResponse::error(470, 480, "Key not found")`.
+    pub fn error(status: u16, code: i32, message: &str) -> MockResponse {
+        MockResponse::json(serde_json::json!({
+            "status": "ERROR",
+            "code": code,
+            "message": message,
+        }))
+        .status(status)
+    }
+
+    /// the canned response of the endpoint, one of the hand-written `fixtures/`. custom
+    /// endpoints get an empty object.
+    pub fn canned(endpoint: Endpoint) -> MockResponse {
+        let json = |body: &str| MockResponse::json(serde_json::from_str(body).unwrap());
+
+        match endpoint {
+            Endpoint::Route | Endpoint::RouteNoTraffic => {
+                json(include_str!("../fixtures/route.json"))
+            }
+            Endpoint::RouteV4 => json(include_str!("../fixtures/route_v4.json")),
+            Endpoint::ReverseGeocode => json(include_str!("../fixtures/reverse_geocode.json")),
+            Endpoint::ReverseGeocodeV5 => json(include_str!("../fixtures/reverse_geocode_v5.json")),
+            Endpoint::StaticMap | Endpoint::Tile => MockResponse {
+                body: PIXEL.to_vec(),
+                content_type: "image/png".to_string(),
+                ..MockResponse::json(serde_json::Value::Null)
+            },
+            Endpoint::DistanceMatrix | Endpoint::DistanceMatrixNoTraffic => {
+                json(include_str!("../fixtures/distance_matrix.json"))
+            }
+            Endpoint::MapMatching => json(include_str!("../fixtures/map_matching.json")),
+            Endpoint::Search => json(include_str!("../fixtures/search.json")),
+            Endpoint::Geocode => json(include_str!("../fixtures/geocode.json")),
+            Endpoint::Trip => json(include_str!("../fixtures/trip_round.json")),
+            // custom calls have no path of their own to mount it on.
+            Endpoint::Custom => json("{}"),
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> MockResponse {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> MockResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// wait this long before answering, e.g. to trigger timeouts.
+    pub fn delay(mut self, delay: Duration) -> MockResponse {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn template(&self) -> ResponseTemplate {
+        let mut template =
+            ResponseTemplate::new(self.status).set_body_raw(self.body.clone(), &self.content_type);
+        for (name, value) in &self.headers {
+            template = template.insert_header(name.as_str(), value.as_str());
+        }
+        if let Some(delay) = self.delay {
+            template = template.set_delay(delay);
+        }
+
+        template
+    }
+}
+
+/// a request `MockNeshan` received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// headers with lowercase names, in the order they were received.
+    pub headers: Vec<(String, String)>,
+}
+
+impl RecordedRequest {
+    /// the first value of the query parameter.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// the first value of the header, the name is case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// local http server answering every endpoint of neshan with a canned response, for testing
+/// code that uses the client without a network or an api key.
+///
+/// ```
+/// # #[cfg(feature = "test-utils")]
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// use neshan_rs::{Endpoint, MockNeshan, MockResponse, Point};
+///
+/// let neshan = MockNeshan::start().await;
+/// neshan
+///     .respond(Endpoint::ReverseGeocode, MockResponse::error(470, 480, "Key not found"))
+///     .await;
+///
+/// let client = neshan.client();
+/// assert!(client.reverse_geocode(Point::new_unchecked(35.7, 51.4)).await.is_err());
+/// assert_eq!(neshan.requests().await[0].query("lat"), Some("35.700000"));
+/// # });
+/// ```
+pub struct MockNeshan {
+    server: MockServer,
+    /// wiremock picks the mock with the lowest priority number, so every override gets a
+    /// lower number than the ones before it.
+    priority: AtomicU8,
+}
+
+impl MockNeshan {
+    /// api key of the clients of `MockNeshan::client`.
+    pub const API_KEY: &'static str = "mock-neshan";
+
+    /// start the server with the canned response of every endpoint.
+    pub async fn start() -> MockNeshan {
+        let server = MockServer::start().await;
+        for endpoint in Endpoint::ALL {
+            if endpoint == Endpoint::Custom {
+                continue;
+            }
+            Mock::given(method("GET"))
+                .and(path(endpoint.path()))
+                .respond_with(MockResponse::canned(endpoint).template())
+                .with_priority(u8::MAX)
+                .mount(&server)
+                .await;
+        }
+
+        MockNeshan {
+            server,
+            priority: AtomicU8::new(u8::MAX - 1),
+        }
+    }
+
+    /// base url to point a `ClientBuilder` at, for clients with other settings than the ones
+    /// of `MockNeshan::client`.
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// a client talking to this server with `MockNeshan::API_KEY`.
+    pub fn client(&self) -> Client {
+        Client::builder(MockNeshan::API_KEY)
+            .base_url(&self.uri())
+            .build()
+            .expect("the mock server has a valid url")
+    }
+
+    /// answer the endpoint with `response` from now on, replacing earlier responses of it.
+    pub async fn respond(&self, endpoint: Endpoint, response: MockResponse) {
+        let priority = self
+            .priority
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |priority| {
+                Some(priority.saturating_sub(1).max(1))
+            })
+            .expect("the update always succeeds");
+
+        Mock::given(method("GET"))
+            .and(path(endpoint.path()))
+            .respond_with(response.template())
+            .with_priority(priority)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// every request received so far, in order.
+    pub async fn requests(&self) -> Vec<RecordedRequest> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|request| RecordedRequest {
+                method: request.method.to_string(),
+                path: request.url.path().to_string(),
+                query: request.url.query_pairs().into_owned().collect(),
+                headers: request
+                    .headers
+                    .iter()
+                    .map(|(name, value)| {
+                        (
+                            name.as_str().to_string(),
+                            String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                        )
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// the requests received so far for the endpoint.
+    pub async fn requests_to(&self, endpoint: Endpoint) -> Vec<RecordedRequest> {
+        self.requests()
+            .await
+            .into_iter()
+            .filter(|request| request.path == endpoint.path())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockNeshan, MockResponse};
+    use crate::error::NeshanError;
+    use crate::{Endpoint, MapMatchOptions, Point, RouteOptions, StaticMapRequest, Type};
+    use std::time::Duration;
+
+    const TEHRAN: Point = Point {
+        latitude: 35.6997,
+        longitude: 51.338,
+    };
+
+    #[tokio::test]
+    async fn canned_responses() {
+        let neshan = MockNeshan::start().await;
+        let client = neshan.client();
+
+        let routes = client
+            .route_with(Type::Car, TEHRAN, TEHRAN, &RouteOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(routes.len(), 2);
+        assert!(!client
+            .reverse_geocode(TEHRAN)
+            .await
+            .unwrap()
+            .city
+            .is_empty());
+        let matrix = client
+            .distance_matrix(Type::Car, &[TEHRAN, TEHRAN], &[TEHRAN, TEHRAN])
+            .await
+            .unwrap();
+        assert_eq!(matrix.rows.len(), 2);
+        let trace = client
+            .map_match_with(&[TEHRAN, TEHRAN], &MapMatchOptions::new())
+            .await
+            .unwrap();
+        assert!(!trace.snapped_points.is_empty());
+
+        let mut image = Vec::new();
+        client
+            .static_map_to(&StaticMapRequest::new(TEHRAN, 14, 64, 64), &mut image)
+            .await
+            .unwrap();
+        assert!(image.starts_with(b"\x89PNG"));
+
+        let requests = neshan.requests().await;
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/v3/direction");
+        assert_eq!(requests[0].query("type"), Some("car"));
+        assert_eq!(requests[0].header("api-key"), Some(MockNeshan::API_KEY));
+        assert_eq!(neshan.requests_to(Endpoint::ReverseGeocode).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn overrides() {
+        let neshan = MockNeshan::start().await;
+        let client = neshan.client();
+
+        neshan
+            .respond(
+                Endpoint::ReverseGeocode,
+                MockResponse::error(470, 480, "Key not found"),
+            )
+            .await;
+        let err = client.reverse_geocode(TEHRAN).await.unwrap_err();
+        assert!(matches!(err, NeshanError::Api(ref api) if api.error().code() == 480));
+
+        // the latest override wins.
+        neshan
+            .respond(
+                Endpoint::ReverseGeocode,
+                MockResponse::canned(Endpoint::ReverseGeocode)
+                    .delay(Duration::from_millis(50))
+                    .header("x-served-by", "mock"),
+            )
+            .await;
+        assert!(client.reverse_geocode(TEHRAN).await.is_ok());
+
+        // other endpoints keep their canned responses.
+        assert!(client
+            .route_with(Type::Car, TEHRAN, TEHRAN, &RouteOptions::new())
+            .await
+            .is_ok());
+    }
+}
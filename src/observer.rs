@@ -0,0 +1,82 @@
+use crate::endpoint::Endpoint;
+use crate::error::ErrorKind;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// hooks around every http call the client makes, retries included.
+///
+/// `attempt` starts from 1 for the first try of a call. `on_response` is called whenever
+/// neshan answered, even with an error status, and `on_error` whenever the attempt failed,
+/// so a 503 triggers both of them.
+pub trait RequestObserver: Send + Sync {
+    fn on_request_start(&self, _endpoint: Endpoint, _attempt: u32) {}
+
+    fn on_response(&self, _endpoint: Endpoint, _status: u16, _duration: Duration, _attempt: u32) {}
+
+    fn on_error(&self, _endpoint: Endpoint, _kind: ErrorKind, _attempt: u32) {}
+}
+
+/// observer that ignores everything, used by default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopObserver;
+
+impl RequestObserver for NoopObserver {}
+
+/// observer that counts requests, response statuses and errors of each endpoint.
+#[derive(Debug, Default)]
+pub struct CountingObserver {
+    counts: Mutex<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    requests: HashMap<Endpoint, u64>,
+    responses: HashMap<(Endpoint, u16), u64>,
+    errors: HashMap<(Endpoint, ErrorKind), u64>,
+}
+
+impl CountingObserver {
+    pub fn new() -> CountingObserver {
+        CountingObserver::default()
+    }
+
+    /// number of http calls sent to the endpoint.
+    pub fn requests(&self, endpoint: Endpoint) -> u64 {
+        let counts = self.counts.lock().unwrap();
+        counts.requests.get(&endpoint).copied().unwrap_or(0)
+    }
+
+    /// number of responses with the given status received from the endpoint.
+    pub fn responses(&self, endpoint: Endpoint, status: u16) -> u64 {
+        let counts = self.counts.lock().unwrap();
+        counts
+            .responses
+            .get(&(endpoint, status))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// number of failed attempts of the given kind on the endpoint.
+    pub fn errors(&self, endpoint: Endpoint, kind: ErrorKind) -> u64 {
+        let counts = self.counts.lock().unwrap();
+        counts.errors.get(&(endpoint, kind)).copied().unwrap_or(0)
+    }
+}
+
+impl RequestObserver for CountingObserver {
+    fn on_request_start(&self, endpoint: Endpoint, _attempt: u32) {
+        let mut counts = self.counts.lock().unwrap();
+        *counts.requests.entry(endpoint).or_default() += 1;
+    }
+
+    fn on_response(&self, endpoint: Endpoint, status: u16, _duration: Duration, _attempt: u32) {
+        let mut counts = self.counts.lock().unwrap();
+        *counts.responses.entry((endpoint, status)).or_default() += 1;
+    }
+
+    fn on_error(&self, endpoint: Endpoint, kind: ErrorKind, _attempt: u32) {
+        let mut counts = self.counts.lock().unwrap();
+        *counts.errors.entry((endpoint, kind)).or_default() += 1;
+    }
+}
@@ -0,0 +1,248 @@
+//! the visiting order of several stops, see `Client::trip`.
+
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::point::collect_points;
+use crate::{Distance, Duration, Leg, Point, Route, Type};
+use serde::{Deserialize, Serialize};
+
+/// stops of the trip api in the order neshan suggests visiting them, with the route through
+/// them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptimizedTrip {
+    /// the waypoints in visiting order.
+    pub points: Vec<TripPoint>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+}
+
+/// a waypoint of an `OptimizedTrip`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TripPoint {
+    /// name of the road the waypoint is on, empty when neshan doesn't send one.
+    #[serde(default)]
+    pub name: String,
+    /// sent by neshan as `[lng, lat]`.
+    #[serde(with = "lng_lat")]
+    pub location: Point,
+    /// position of the waypoint in the input of `Client::trip`.
+    pub index: usize,
+}
+
+/// points in the `[lng, lat]` form of the trip api and the steps of the direction api.
+pub(crate) mod lng_lat {
+    use crate::Point;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        point: &Point,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        [point.longitude, point.latitude].serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Point, D::Error> {
+        let [longitude, latitude] = <[f64; 2]>::deserialize(deserializer)?;
+        Ok(Point::new_unchecked(latitude, longitude))
+    }
+
+    /// the same for fields that neshan may leave out.
+    pub(crate) mod option {
+        use crate::Point;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub(crate) fn serialize<S: Serializer>(
+            point: &Option<Point>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match point {
+                Some(point) => super::serialize(point, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Point>, D::Error> {
+            let point = Option::<[f64; 2]>::deserialize(deserializer)?;
+            Ok(point.map(|[longitude, latitude]| Point::new_unchecked(latitude, longitude)))
+        }
+    }
+}
+
+impl OptimizedTrip {
+    /// the visiting order and route of a trip body.
+    pub fn from_json(json: &str) -> Result<OptimizedTrip, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// indices of the input waypoints in visiting order.
+    pub fn order(&self) -> Vec<usize> {
+        self.points.iter().map(|point| point.index).collect()
+    }
+
+    /// `items` in visiting order, e.g. the orders of the stops given to `Client::trip`.
+    /// waypoints past the end of `items` are skipped.
+    pub fn reorder<'a, T>(&self, items: &'a [T]) -> Vec<&'a T> {
+        self.points
+            .iter()
+            .filter_map(|point| items.get(point.index))
+            .collect()
+    }
+
+    /// legs of the suggested route between consecutive stops, and back to the first one on a
+    /// round trip.
+    pub fn legs(&self) -> &[Leg] {
+        match self.routes.first() {
+            Some(route) => &route.legs,
+            None => &[],
+        }
+    }
+
+    /// distance of all the legs, the text is in persian like the one of neshan.
+    pub fn total_distance(&self) -> Distance {
+        self.legs().iter().map(|leg| leg.distance.clone()).sum()
+    }
+
+    /// duration of all the legs, the text is in persian like the one of neshan.
+    pub fn total_duration(&self) -> Duration {
+        self.legs().iter().map(|leg| leg.duration.clone()).sum()
+    }
+}
+
+/// neshan needs at least two waypoints to order them.
+pub(crate) fn check_waypoints(waypoints: &[Point]) -> Result<(), NeshanError> {
+    match waypoints.len() {
+        0 | 1 => Err(NeshanError::InvalidRequest(format!(
+            "a trip needs at least two waypoints, got {}",
+            waypoints.len()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+impl Client {
+    /// the order to visit `waypoints` in, starting from the first one. a round trip returns to
+    /// the first waypoint, an open one ends wherever is best. fails without sending a request
+    /// when there are fewer than two waypoints.
+    /// https://platform.neshan.org/api/trip
+    pub async fn trip(
+        &self,
+        vehicle: Type,
+        waypoints: impl IntoIterator<Item = impl Into<Point>>,
+        round_trip: bool,
+    ) -> Result<OptimizedTrip, NeshanError> {
+        let waypoints = collect_points(waypoints);
+        check_waypoints(&waypoints)?;
+        self.check(&waypoints)?;
+
+        let query = crate::protocol::trip_query(vehicle, &waypoints, round_trip);
+        let call = self.get(Endpoint::Trip, &query);
+
+        crate::trace::instrument(Endpoint::Trip, &waypoints, call)
+            .await
+            .map(|(trip, _)| trip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptimizedTrip;
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::{Point, Type};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn waypoints() -> Vec<Point> {
+        vec![
+            Point::new_unchecked(35.6997, 51.338),
+            Point::new_unchecked(35.7172, 51.3325),
+            Point::new_unchecked(35.7009, 51.3914),
+            Point::new_unchecked(35.7575, 51.4109),
+        ]
+    }
+
+    #[test]
+    fn reorder_items() {
+        let trip = OptimizedTrip::from_json(include_str!("../fixtures/trip_round.json")).unwrap();
+        assert_eq!(trip.order(), vec![0, 2, 3, 1]);
+        assert_eq!(
+            trip.reorder(&["a", "b", "c", "d"]),
+            vec![&"a", &"c", &"d", &"b"]
+        );
+        assert_eq!(trip.reorder(&["a", "b"]), vec![&"a", &"b"]);
+        assert_eq!(
+            trip.points[1].location,
+            Point::new_unchecked(35.7009, 51.3914)
+        );
+        assert_eq!(trip.legs().len(), 4);
+        assert_eq!(trip.total_distance().value, 24722.5);
+        assert_eq!(trip.total_duration().value, 3444.0);
+
+        let empty = OptimizedTrip::from_json(r#"{"points": []}"#).unwrap();
+        assert!(empty.legs().is_empty() && empty.order().is_empty());
+        assert_eq!(empty.total_distance().value, 0.0);
+    }
+
+    #[tokio::test]
+    async fn round_and_open_trips() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/trip"))
+            .and(query_param("type", "car"))
+            .and(query_param(
+                "waypoints",
+                "35.699700,51.338000|35.717200,51.332500|35.700900,51.391400|35.757500,51.410900",
+            ))
+            .and(query_param("roundTrip", "true"))
+            .and(query_param("sourceIsAnyPoint", "false"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/trip_round.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/trip"))
+            .and(query_param("type", "motorcycle"))
+            .and(query_param("roundTrip", "false"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/trip_open.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let stops = waypoints();
+        let round = client.trip(Type::Car, &stops, true).await.unwrap();
+        // back to the first stop, a leg per stop.
+        assert_eq!(round.legs().len(), stops.len());
+        assert_eq!(round.reorder(&stops)[1], &stops[2]);
+
+        let open = client
+            .trip(Type::Motorcycle, &stops[..3], false)
+            .await
+            .unwrap();
+        assert_eq!(open.order(), vec![0, 2, 1]);
+        assert_eq!(open.legs().len(), 2);
+        assert_eq!(open.legs()[1].summary, "بزرگراه همت");
+
+        for few in [&stops[..0], &stops[..1]] {
+            let err = client.trip(Type::Car, few, true).await.unwrap_err();
+            assert!(matches!(err, NeshanError::InvalidRequest(_)), "{}", err);
+            assert!(crate::protocol::build_trip_request("key", Type::Car, few, true).is_err());
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! opentelemetry metrics, compiled only with the `otel` feature.
+//!
+//! the metrics are recorded from the same hooks as `RequestObserver`, so they always agree
+//! with what a custom observer sees:
+//!
+//! - `neshan.requests`: http calls sent, retries included, by `endpoint`.
+//! - `neshan.errors`: failed attempts by `endpoint` and `error.type`.
+//! - `neshan.request.duration`: seconds until neshan answered, by `endpoint` and `http.status`.
+
+use crate::endpoint::Endpoint;
+use crate::error::ErrorKind;
+use crate::observer::RequestObserver;
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+use std::sync::Arc;
+use std::time::Duration;
+
+struct Metrics {
+    requests: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+/// records the metrics, then passes every hook to the configured observer.
+pub(crate) struct MetricsObserver {
+    metrics: Metrics,
+    observer: Arc<dyn RequestObserver>,
+}
+
+impl MetricsObserver {
+    pub(crate) fn new(meter: &Meter, observer: Arc<dyn RequestObserver>) -> MetricsObserver {
+        MetricsObserver {
+            metrics: Metrics {
+                requests: meter
+                    .u64_counter("neshan.requests")
+                    .with_description("http calls sent to neshan")
+                    .build(),
+                errors: meter
+                    .u64_counter("neshan.errors")
+                    .with_description("failed http calls to neshan")
+                    .build(),
+                duration: meter
+                    .f64_histogram("neshan.request.duration")
+                    .with_description("time until neshan answered")
+                    .with_unit("s")
+                    .build(),
+            },
+            observer,
+        }
+    }
+}
+
+impl RequestObserver for MetricsObserver {
+    fn on_request_start(&self, endpoint: Endpoint, attempt: u32) {
+        self.metrics
+            .requests
+            .add(1, &[KeyValue::new("endpoint", endpoint.as_str())]);
+        self.observer.on_request_start(endpoint, attempt);
+    }
+
+    fn on_response(&self, endpoint: Endpoint, status: u16, duration: Duration, attempt: u32) {
+        self.metrics.duration.record(
+            duration.as_secs_f64(),
+            &[
+                KeyValue::new("endpoint", endpoint.as_str()),
+                KeyValue::new("http.status", i64::from(status)),
+            ],
+        );
+        self.observer
+            .on_response(endpoint, status, duration, attempt);
+    }
+
+    fn on_error(&self, endpoint: Endpoint, kind: ErrorKind, attempt: u32) {
+        self.metrics.errors.add(
+            1,
+            &[
+                KeyValue::new("endpoint", endpoint.as_str()),
+                KeyValue::new("error.type", kind.as_str()),
+            ],
+        );
+        self.observer.on_error(endpoint, kind, attempt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Client;
+    use crate::retry::RetryPolicy;
+    use crate::Point;
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, Metric, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn find<'a>(metrics: &[&'a Metric], name: &str) -> &'a Metric {
+        metrics
+            .iter()
+            .find(|metric| metric.name() == name)
+            .expect(name)
+    }
+
+    /// data points of a u64 counter as their sorted attributes and value.
+    fn sums(metric: &Metric) -> Vec<(Vec<KeyValue>, u64)> {
+        let sum = match metric.data() {
+            AggregatedMetrics::U64(MetricData::Sum(sum)) => sum,
+            data => panic!("unexpected data {:?}", data),
+        };
+        let mut points: Vec<_> = sum
+            .data_points()
+            .map(|point| {
+                let mut attributes: Vec<_> = point.attributes().cloned().collect();
+                attributes.sort_by(|a, b| a.key.cmp(&b.key));
+                (attributes, point.value())
+            })
+            .collect();
+        points.sort_by_key(|(attributes, _)| format!("{:?}", attributes));
+
+        points
+    }
+
+    #[tokio::test]
+    async fn record_requests_errors_and_latency() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "formatted_address": "تهران، خیابان آزادی",
+                "route_name": "خیابان آزادی",
+                "city": "تهران",
+                "state": "استان تهران",
+                "in_traffic_zone": false,
+                "in_odd_even_zone": false
+            })))
+            .mount(&server)
+            .await;
+
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .retry(
+                RetryPolicy::new()
+                    .initial_backoff(Duration::from_millis(10))
+                    .jitter(false),
+            )
+            .meter_provider(&provider)
+            .build()
+            .unwrap();
+        client
+            .reverse_geocode(Point {
+                latitude: 35.731984409609694,
+                longitude: 51.392684661470156,
+            })
+            .await
+            .unwrap();
+
+        provider.force_flush().unwrap();
+        let exported = exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<_> = exported
+            .last()
+            .unwrap()
+            .scope_metrics()
+            .flat_map(|scope| scope.metrics())
+            .collect();
+
+        assert_eq!(
+            sums(find(&metrics, "neshan.requests")),
+            vec![(vec![KeyValue::new("endpoint", "reverse_geocode")], 2)]
+        );
+        assert_eq!(
+            sums(find(&metrics, "neshan.errors")),
+            vec![(
+                vec![
+                    KeyValue::new("endpoint", "reverse_geocode"),
+                    KeyValue::new("error.type", "server"),
+                ],
+                1
+            )]
+        );
+
+        let histogram = match find(&metrics, "neshan.request.duration").data() {
+            AggregatedMetrics::F64(MetricData::Histogram(histogram)) => histogram,
+            data => panic!("unexpected data {:?}", data),
+        };
+        let mut statuses: Vec<_> = histogram
+            .data_points()
+            .map(|point| {
+                let status = point
+                    .attributes()
+                    .find(|attribute| attribute.key.as_str() == "http.status")
+                    .map(|attribute| attribute.value.to_string());
+                (status, point.count())
+            })
+            .collect();
+        statuses.sort();
+        assert_eq!(
+            statuses,
+            vec![(Some("200".to_string()), 1), (Some("503".to_string()), 1)]
+        );
+    }
+}
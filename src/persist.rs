@@ -0,0 +1,187 @@
+//! saving responses to json files and loading them in later runs, see `Persist`.
+
+use crate::error::NeshanError;
+use crate::{DistanceMatrix, MatchedTrace, PostalAddress, Routes};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// version of the layout of saved files, bumped whenever a change of the response models
+/// would make older files load wrongly.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Saved<'a, T> {
+    schema_version: u32,
+    crate_version: &'a str,
+    kind: &'a str,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Loaded {
+    schema_version: u32,
+    #[serde(default)]
+    crate_version: String,
+    kind: String,
+    data: serde_json::Value,
+}
+
+/// a response that can be saved to a json file and loaded back, e.g. a big `DistanceMatrix`
+/// computed once and reused by later runs.
+///
+/// files carry the `SCHEMA_VERSION` and the crate version that wrote them, loading a file
+/// of another schema version or of another kind of response fails instead of misreading it.
+pub trait Persist: Serialize + DeserializeOwned {
+    /// name of the kind of response in saved files.
+    const KIND: &'static str;
+
+    /// write to `path`, replacing any file there. the file is written next to it then
+    /// renamed, so a crash never leaves a half written file behind.
+    fn save_json(&self, path: impl AsRef<Path>) -> Result<(), NeshanError> {
+        let path = path.as_ref();
+        let saved = Saved {
+            schema_version: SCHEMA_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION"),
+            kind: Self::KIND,
+            data: self,
+        };
+        let json = serde_json::to_vec(&saved)?;
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|err| {
+                let _ = fs::remove_file(&tmp);
+                NeshanError::Config(format!("cannot write {}: {}", path.display(), err))
+            })
+    }
+
+    /// read a file written by `save_json`.
+    fn load_json(path: impl AsRef<Path>) -> Result<Self, NeshanError> {
+        let path = path.as_ref();
+        let invalid =
+            |reason: String| NeshanError::Config(format!("invalid {}: {}", path.display(), reason));
+
+        let json = fs::read(path).map_err(|err| {
+            NeshanError::Config(format!("cannot read {}: {}", path.display(), err))
+        })?;
+        let loaded: Loaded =
+            serde_json::from_slice(&json).map_err(|err| invalid(err.to_string()))?;
+
+        if loaded.schema_version != SCHEMA_VERSION {
+            return Err(invalid(format!(
+                "schema version {} written by neshan-rs {}, this is neshan-rs {} reading \
+                 schema version {}",
+                loaded.schema_version,
+                loaded.crate_version,
+                env!("CARGO_PKG_VERSION"),
+                SCHEMA_VERSION
+            )));
+        }
+        if loaded.kind != Self::KIND {
+            return Err(invalid(format!(
+                "holds a {}, not a {}",
+                loaded.kind,
+                Self::KIND
+            )));
+        }
+
+        serde_json::from_value(loaded.data).map_err(|err| invalid(err.to_string()))
+    }
+}
+
+impl Persist for Routes {
+    const KIND: &'static str = "routes";
+}
+
+impl Persist for DistanceMatrix {
+    const KIND: &'static str = "distance_matrix";
+}
+
+impl Persist for PostalAddress {
+    const KIND: &'static str = "postal_address";
+}
+
+impl Persist for MatchedTrace {
+    const KIND: &'static str = "matched_trace";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Persist, SCHEMA_VERSION};
+    use crate::error::NeshanError;
+    use crate::{DistanceMatrix, PostalAddress, Routes};
+    use std::path::PathBuf;
+
+    fn saved_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("neshan-rs-{}-{}.json", name, std::process::id()))
+    }
+
+    fn config_error(err: NeshanError) -> String {
+        match err {
+            NeshanError::Config(message) => message,
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = saved_path("persist-routes");
+        let routes = Routes::from_json(include_str!("../fixtures/route.json")).unwrap();
+        routes.save_json(&path).unwrap();
+        assert_eq!(Routes::load_json(&path).unwrap(), routes);
+
+        let matrix =
+            DistanceMatrix::from_json(include_str!("../fixtures/distance_matrix.json")).unwrap();
+        matrix.save_json(&path).unwrap();
+        assert_eq!(DistanceMatrix::load_json(&path).unwrap(), matrix);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["crate_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["kind"], "distance_matrix");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reject_incompatible_files() {
+        let path = saved_path("persist-incompatible");
+        let matrix =
+            DistanceMatrix::from_json(include_str!("../fixtures/distance_matrix.json")).unwrap();
+        matrix.save_json(&path).unwrap();
+
+        let message = config_error(Routes::load_json(&path).unwrap_err());
+        assert!(
+            message.ends_with("holds a distance_matrix, not a routes"),
+            "{}",
+            message
+        );
+
+        let mut json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        json["schema_version"] = serde_json::json!(SCHEMA_VERSION + 1);
+        json["crate_version"] = serde_json::json!("9.0.0");
+        std::fs::write(&path, json.to_string()).unwrap();
+        let message = config_error(DistanceMatrix::load_json(&path).unwrap_err());
+        assert!(
+            message.contains(&format!(
+                "schema version {} written by neshan-rs 9.0.0",
+                SCHEMA_VERSION + 1
+            )),
+            "{}",
+            message
+        );
+
+        std::fs::write(&path, "{\"routes\": []}").unwrap();
+        config_error(Routes::load_json(&path).unwrap_err());
+        let _ = std::fs::remove_file(&path);
+
+        config_error(PostalAddress::load_json(&path).unwrap_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! what there is to know about a single point, see `Client::place_details`.
+
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::{Marker, Point, PostalAddress, SearchItem, StaticMapRequest};
+
+/// which sections `Client::place_details` fetches. only the address by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceDetailsOptions {
+    address: bool,
+    nearby: Option<(String, usize)>,
+    thumbnail: Option<(u8, u32, u32)>,
+}
+
+impl Default for PlaceDetailsOptions {
+    fn default() -> PlaceDetailsOptions {
+        PlaceDetailsOptions {
+            address: true,
+            nearby: None,
+            thumbnail: None,
+        }
+    }
+}
+
+impl PlaceDetailsOptions {
+    pub fn new() -> PlaceDetailsOptions {
+        PlaceDetailsOptions::default()
+    }
+
+    /// whether to reverse geocode the point.
+    pub fn address(mut self, address: bool) -> PlaceDetailsOptions {
+        self.address = address;
+        self
+    }
+
+    /// search for `term` around the point, keeping the `limit` closest places. neshan's
+    /// search needs a term, e.g. `کافه`.
+    pub fn nearby(mut self, term: &str, limit: usize) -> PlaceDetailsOptions {
+        self.nearby = Some((term.to_string(), limit));
+        self
+    }
+
+    /// a map of `width` by `height` pixels centered on the point with a marker on it.
+    pub fn thumbnail(mut self, zoom: u8, width: u32, height: u32) -> PlaceDetailsOptions {
+        self.thumbnail = Some((zoom, width, height));
+        self
+    }
+}
+
+/// sections of `Client::place_details`. each one is `None` when it wasn't asked for and holds
+/// its own error when its call failed, without failing the others.
+#[derive(Debug)]
+pub struct PlaceDetails {
+    pub point: Point,
+    pub address: Option<Result<PostalAddress, NeshanError>>,
+    /// closest places first.
+    pub nearby: Option<Result<Vec<SearchItem>, NeshanError>>,
+    /// the image as neshan sent it, e.g. a png.
+    pub thumbnail: Option<Result<Vec<u8>, NeshanError>>,
+}
+
+impl Client {
+    /// the postal address, the nearby places and a thumbnail map of `point` for e.g. a place
+    /// card, as `options` asks. the calls are made concurrently.
+    pub async fn place_details(
+        &self,
+        point: impl Into<Point>,
+        options: &PlaceDetailsOptions,
+    ) -> PlaceDetails {
+        let point = point.into();
+
+        let address = async {
+            match options.address {
+                true => Some(self.reverse_geocode(point).await),
+                false => None,
+            }
+        };
+        let nearby = async {
+            let (term, limit) = options.nearby.as_ref()?;
+            let results = self.search(term, point).await;
+            Some(results.map(|results| {
+                results
+                    .nearest(point, *limit)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }))
+        };
+        let thumbnail = async {
+            let (zoom, width, height) = options.thumbnail?;
+            let request =
+                StaticMapRequest::new(point, zoom, width, height).marker(Marker::new(point));
+            let mut image = Vec::new();
+            Some(
+                self.static_map_to(&request, &mut image)
+                    .await
+                    .map(|_| image),
+            )
+        };
+
+        let (address, nearby, thumbnail) =
+            futures_util::future::join3(address, nearby, thumbnail).await;
+        PlaceDetails {
+            point,
+            address,
+            nearby,
+            thumbnail,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlaceDetailsOptions;
+    use crate::endpoint::Endpoint;
+    use crate::error::ErrorKind;
+    use crate::mock::{MockNeshan, MockResponse};
+    use crate::Point;
+    use std::time::Duration;
+
+    const POINT: Point = Point {
+        latitude: 35.6997,
+        longitude: 51.338,
+    };
+
+    #[tokio::test]
+    async fn every_section() {
+        let neshan = MockNeshan::start().await;
+        // each call takes a while, one after the other they would take three times as long.
+        for endpoint in [
+            Endpoint::ReverseGeocode,
+            Endpoint::Search,
+            Endpoint::StaticMap,
+        ] {
+            let response = MockResponse::canned(endpoint).delay(Duration::from_millis(300));
+            neshan.respond(endpoint, response).await;
+        }
+
+        let options = PlaceDetailsOptions::new()
+            .nearby("کافه", 2)
+            .thumbnail(15, 200, 100);
+        let started = std::time::Instant::now();
+        let details = neshan.client().place_details(POINT, &options).await;
+        assert!(started.elapsed() < Duration::from_millis(800));
+
+        assert_eq!(details.point, POINT);
+        assert_eq!(details.address.unwrap().unwrap().city, "تهران");
+        let nearby = details.nearby.unwrap().unwrap();
+        assert_eq!(nearby.len(), 2);
+        assert_eq!(nearby[0].title, "کافه رستوران آزادی");
+        assert!(details.thumbnail.unwrap().unwrap().starts_with(b"\x89PNG"));
+
+        let requests = neshan.requests().await;
+        assert_eq!(requests.len(), 3);
+        let search = neshan.requests_to(Endpoint::Search).await;
+        assert_eq!(search[0].query("term"), Some("کافه"));
+        let map = neshan.requests_to(Endpoint::StaticMap).await;
+        assert_eq!(map[0].query("markers"), Some("35.699700,51.338000"));
+        assert_eq!(map[0].query("width"), Some("200"));
+    }
+
+    #[tokio::test]
+    async fn partial_failures() {
+        let neshan = MockNeshan::start().await;
+        neshan
+            .respond(Endpoint::Search, MockResponse::error(404, 404, "Not found"))
+            .await;
+
+        let options = PlaceDetailsOptions::new().nearby("کافه", 5);
+        let details = neshan.client().place_details(POINT, &options).await;
+        assert!(details.address.unwrap().is_ok());
+        assert_eq!(
+            details.nearby.unwrap().unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert!(details.thumbnail.is_none());
+
+        let details = neshan
+            .client()
+            .place_details(POINT, &PlaceDetailsOptions::new().address(false))
+            .await;
+        assert!(details.address.is_none() && details.nearby.is_none());
+        assert_eq!(neshan.requests().await.len(), 2);
+    }
+}
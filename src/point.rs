@@ -0,0 +1,758 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// fewest decimals a coordinate is written with, about 10cm of precision.
+const DECIMALS: usize = 6;
+
+/// mean radius of the earth in meters (IUGG), used by the spherical helpers of `Point`.
+/// treating the earth as a sphere is off by up to about 0.5% compared to the ellipsoid.
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// geographic point in wgs84 degrees.
+///
+/// the fields are public, so a point built as a struct literal is not checked. use
+/// `Point::new` for untrusted input or `ClientBuilder::validate_points` for checking every
+/// point a client sends. `Point::from_parts` takes `Latitude` and `Longitude`, which can't be
+/// swapped by accident like two `f64`s.
+///
+/// with serde a point is stored as `{ "latitude": .., "longitude": .. }`, `lat` and `lng`
+/// are accepted as well when reading it back.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    #[serde(alias = "lat")]
+    pub latitude: f64,
+    #[serde(alias = "lng")]
+    pub longitude: f64,
+}
+
+impl Point {
+    /// point at the given coordinates, failing when either of them is not a finite number
+    /// or is out of range.
+    pub fn new(latitude: f64, longitude: f64) -> Result<Point, InvalidCoordinate> {
+        let point = Point::new_unchecked(latitude, longitude);
+        point.validate()?;
+
+        Ok(point)
+    }
+
+    /// point at the given coordinates without checking them, for input that is known to be valid.
+    pub const fn new_unchecked(latitude: f64, longitude: f64) -> Point {
+        Point {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// point from typed coordinates, which can't be passed the wrong way around.
+    pub const fn from_parts(latitude: Latitude, longitude: Longitude) -> Point {
+        Point::new_unchecked(latitude.0, longitude.0)
+    }
+
+    /// typed coordinates of the point, without checking them.
+    pub fn parts(&self) -> (Latitude, Longitude) {
+        (Latitude(self.latitude), Longitude(self.longitude))
+    }
+
+    /// check that latitude is within ±90 and longitude within ±180 degrees.
+    pub fn validate(&self) -> Result<(), InvalidCoordinate> {
+        Axis::Latitude.check(self.latitude)?;
+        Axis::Longitude.check(self.longitude)
+    }
+
+    /// great circle distance to `other` in meters, e.g. for filtering candidates before
+    /// asking neshan for an actual route.
+    pub fn haversine_distance_to(&self, other: &Point) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (other.longitude - self.longitude).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+
+        // rounding can push `a` slightly above one for antipodal points.
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    /// initial bearing towards `other` in degrees clockwise from north, in `[0, 360)`.
+    /// the bearing of a point to itself is 0.
+    pub fn bearing_to(&self, other: &Point) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lng = (other.longitude - self.longitude).to_radians();
+
+        let y = d_lng.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lng.cos();
+
+        // a bearing a hair west of north wraps around to exactly 360.
+        let bearing = y.atan2(x).to_degrees().rem_euclid(360.0);
+        if bearing >= 360.0 {
+            0.0
+        } else {
+            bearing
+        }
+    }
+
+    /// point reached by travelling `distance` meters along the great circle that leaves this
+    /// point with `bearing` degrees clockwise from north.
+    pub fn destination(&self, bearing: f64, distance: f64) -> Point {
+        let lat1 = self.latitude.to_radians();
+        let lng1 = self.longitude.to_radians();
+        let bearing = bearing.to_radians();
+        let angle = distance / EARTH_RADIUS;
+
+        let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+        let lng2 = lng1
+            + (bearing.sin() * angle.sin() * lat1.cos())
+                .atan2(angle.cos() - lat1.sin() * lat2.sin());
+
+        Point {
+            latitude: lat2.to_degrees(),
+            longitude: (lng2.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+        }
+    }
+}
+
+/// written as `lat,lng`, the form neshan expects in query strings. coordinates keep every
+/// significant digit and have at least six decimals, e.g. `35.700000,51.391234567`.
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut text = String::with_capacity(24);
+        push_point(&mut text, *self);
+        f.write_str(&text)
+    }
+}
+
+/// append the point as it is written by its `Display`, for building query strings without a
+/// string per point.
+pub(crate) fn push_point(out: &mut String, point: Point) {
+    let (latitude, longitude) = point.parts();
+    push_lat_lng(out, latitude, longitude);
+}
+
+/// same as `push_point` with the comma url-encoded, for writing straight into a query string.
+pub(crate) fn push_encoded_point(out: &mut String, point: Point) {
+    let (latitude, longitude) = point.parts();
+    push_coordinate(out, latitude.0);
+    out.push_str("%2C");
+    push_coordinate(out, longitude.0);
+}
+
+/// the `lat,lng` pair of query strings, typed so the axes can't be swapped.
+fn push_lat_lng(out: &mut String, latitude: Latitude, longitude: Longitude) {
+    push_coordinate(out, latitude.0);
+    out.push(',');
+    push_coordinate(out, longitude.0);
+}
+
+/// parses `lat,lng`, with optional whitespace around each coordinate.
+impl FromStr for Point {
+    type Err = ParsePointError;
+
+    fn from_str(s: &str) -> Result<Point, ParsePointError> {
+        let mut parts = s.split(',');
+        let latitude = parts.next().unwrap_or_default();
+        let longitude = parts.next().ok_or(ParsePointError::MissingComma)?;
+        if parts.next().is_some() {
+            return Err(ParsePointError::TooManyComponents(s.split(',').count()));
+        }
+
+        let parse = |axis, value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| ParsePointError::InvalidNumber {
+                    axis,
+                    value: value.trim().to_string(),
+                })
+        };
+        let latitude = Latitude::new(parse(Axis::Latitude, latitude)?)?;
+        let longitude = Longitude::new(parse(Axis::Longitude, longitude)?)?;
+
+        Ok(Point::from_parts(latitude, longitude))
+    }
+}
+
+/// append a single coordinate as it is written by `Point`'s `Display`: the shortest digits
+/// that read back as the same value, padded to `DECIMALS`.
+///
+/// degrees are written by ryu, which is faster than std. beyond them ryu may pick another
+/// last digit than std when two are as short, and it writes tiny values with an exponent,
+/// so anything else is written by std.
+pub(crate) fn push_coordinate(out: &mut String, value: f64) {
+    let start = out.len();
+    let mut buffer = ryu::Buffer::new();
+    match (value.abs() < 1000.0).then(|| buffer.format_finite(value)) {
+        Some(digits) if !digits.contains('e') => out.push_str(digits),
+        _ => {
+            use fmt::Write;
+            let _ = write!(out, "{}", value);
+        }
+    }
+    if !value.is_finite() {
+        return;
+    }
+
+    let decimals = match out[start..].find('.') {
+        Some(dot) => out.len() - start - dot - 1,
+        None => {
+            out.push('.');
+            0
+        }
+    };
+    for _ in decimals..DECIMALS {
+        out.push('0');
+    }
+}
+
+/// a single coordinate as it is written by `Point`'s `Display`.
+pub(crate) fn coordinate(value: f64) -> String {
+    let mut text = String::with_capacity(12);
+    push_coordinate(&mut text, value);
+    text
+}
+
+/// point from a `(latitude, longitude)` pair, the order neshan uses in its apis.
+impl From<(f64, f64)> for Point {
+    fn from((latitude, longitude): (f64, f64)) -> Point {
+        Point::new_unchecked(latitude, longitude)
+    }
+}
+
+impl From<&(f64, f64)> for Point {
+    fn from(pair: &(f64, f64)) -> Point {
+        Point::from(*pair)
+    }
+}
+
+/// copy of a borrowed point, so lists such as `&[Point]` can be passed where the client takes
+/// any points.
+impl From<&Point> for Point {
+    fn from(point: &Point) -> Point {
+        *point
+    }
+}
+
+/// points of a list argument of the client, e.g. `&[Point]` or `Vec<(f64, f64)>`.
+pub(crate) fn collect_points(points: impl IntoIterator<Item = impl Into<Point>>) -> Vec<Point> {
+    points.into_iter().map(Into::into).collect()
+}
+
+/// `(latitude, longitude)` pair of the point.
+impl From<Point> for (f64, f64) {
+    fn from(point: Point) -> (f64, f64) {
+        (point.latitude, point.longitude)
+    }
+}
+
+/// latitude in degrees, within ±90 when built with `Latitude::new`.
+///
+/// written with at least six decimals, the same as in `Point`'s `Display`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Latitude(f64);
+
+impl Latitude {
+    /// latitude of the given degrees, failing when it is not a finite number within ±90.
+    pub fn new(degrees: f64) -> Result<Latitude, InvalidCoordinate> {
+        Axis::Latitude.check(degrees)?;
+        Ok(Latitude(degrees))
+    }
+
+    /// latitude of the given degrees without checking them, for input that is known to be valid.
+    pub const fn new_unchecked(degrees: f64) -> Latitude {
+        Latitude(degrees)
+    }
+
+    pub fn degrees(&self) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Latitude {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&coordinate(self.0))
+    }
+}
+
+/// longitude in degrees, within ±180 when built with `Longitude::new`.
+///
+/// written with at least six decimals, the same as in `Point`'s `Display`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Longitude(f64);
+
+impl Longitude {
+    /// longitude of the given degrees, failing when it is not a finite number within ±180.
+    pub fn new(degrees: f64) -> Result<Longitude, InvalidCoordinate> {
+        Axis::Longitude.check(degrees)?;
+        Ok(Longitude(degrees))
+    }
+
+    /// longitude of the given degrees without checking them, for input that is known to be
+    /// valid.
+    pub const fn new_unchecked(degrees: f64) -> Longitude {
+        Longitude(degrees)
+    }
+
+    pub fn degrees(&self) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Longitude {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&coordinate(self.0))
+    }
+}
+
+/// coordinate axis of a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Latitude,
+    Longitude,
+}
+
+impl Axis {
+    fn limit(self) -> f64 {
+        match self {
+            Axis::Latitude => 90.0,
+            Axis::Longitude => 180.0,
+        }
+    }
+
+    fn check(self, value: f64) -> Result<(), InvalidCoordinate> {
+        if value.is_finite() && value.abs() <= self.limit() {
+            Ok(())
+        } else {
+            Err(InvalidCoordinate { axis: self, value })
+        }
+    }
+}
+
+impl fmt::Display for Axis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Axis::Latitude => f.write_str("latitude"),
+            Axis::Longitude => f.write_str("longitude"),
+        }
+    }
+}
+
+/// coordinate rejected by `Point::new`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidCoordinate {
+    axis: Axis,
+    value: f64,
+}
+
+impl InvalidCoordinate {
+    /// axis of the rejected coordinate.
+    pub fn axis(&self) -> Axis {
+        self.axis
+    }
+
+    /// rejected value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl fmt::Display for InvalidCoordinate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.value.is_finite() {
+            write!(
+                f,
+                "{} {} is out of range, expected -{limit} to {limit}",
+                self.axis,
+                self.value,
+                limit = self.axis.limit()
+            )
+        } else {
+            write!(f, "{} {} is not a finite number", self.axis, self.value)
+        }
+    }
+}
+
+impl std::error::Error for InvalidCoordinate {}
+
+/// error of parsing a `Point` from text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsePointError {
+    /// the text has no comma between latitude and longitude.
+    MissingComma,
+    /// the text has more than two comma separated components.
+    TooManyComponents(usize),
+    /// a component is not a number.
+    InvalidNumber { axis: Axis, value: String },
+    /// a coordinate is out of range.
+    InvalidCoordinate(InvalidCoordinate),
+    /// the text doesn't follow the grammar of `Point::parse_flexible`.
+    InvalidFormat(String),
+    /// the text could be read in more than one way, e.g. when only one coordinate has a
+    /// hemisphere.
+    Ambiguous(String),
+}
+
+impl From<InvalidCoordinate> for ParsePointError {
+    fn from(err: InvalidCoordinate) -> ParsePointError {
+        ParsePointError::InvalidCoordinate(err)
+    }
+}
+
+impl fmt::Display for ParsePointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParsePointError::MissingComma => {
+                f.write_str("expected `lat,lng`, latitude and longitude separated by a comma")
+            }
+            ParsePointError::TooManyComponents(count) => {
+                write!(f, "expected `lat,lng`, got {} components", count)
+            }
+            ParsePointError::InvalidNumber { axis, value } => {
+                write!(f, "{} {:?} is not a number", axis, value)
+            }
+            ParsePointError::InvalidCoordinate(err) => err.fmt(f),
+            ParsePointError::InvalidFormat(message) | ParsePointError::Ambiguous(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParsePointError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParsePointError::InvalidCoordinate(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_lat_lng, Axis, Latitude, Longitude, ParsePointError, Point, EARTH_RADIUS};
+
+    #[test]
+    fn tuple_is_latitude_then_longitude() {
+        let point = Point::from((35.7, 51.4));
+        assert_eq!(point.latitude, 35.7);
+        assert_eq!(point.longitude, 51.4);
+
+        assert_eq!(<(f64, f64)>::from(point), (35.7, 51.4));
+    }
+
+    #[test]
+    fn typed_coordinates() {
+        let err = Latitude::new(90.5).unwrap_err();
+        assert_eq!(err.axis(), Axis::Latitude);
+        assert_eq!(err.value(), 90.5);
+        assert!(Latitude::new(-90.5).is_err());
+        assert!(Latitude::new(f64::NAN).is_err());
+        assert!(Longitude::new(180.5).is_err());
+
+        // 120 is a valid longitude but not a valid latitude.
+        assert!(Latitude::new(120.0).is_err());
+        let longitude = Longitude::new(120.0).unwrap();
+        assert_eq!(longitude.degrees(), 120.0);
+
+        let point = Point::from_parts(Latitude::new(35.7).unwrap(), longitude);
+        assert_eq!(point, Point::new_unchecked(35.7, 120.0));
+        assert_eq!(point.parts(), (Latitude::new_unchecked(35.7), longitude));
+    }
+
+    #[test]
+    fn query_pair_is_latitude_first() {
+        let point = Point {
+            longitude: 51.4,
+            latitude: 35.7,
+        };
+        let (latitude, longitude) = point.parts();
+
+        assert_eq!(latitude.to_string(), "35.700000");
+        assert_eq!(longitude.to_string(), "51.400000");
+        let mut text = String::from("origin=");
+        push_lat_lng(&mut text, latitude, longitude);
+        assert_eq!(text, "origin=35.700000,51.400000");
+        assert_eq!(point.to_string(), text["origin=".len()..]);
+    }
+
+    #[test]
+    fn accept_boundaries() {
+        for (latitude, longitude) in [(90.0, 180.0), (-90.0, -180.0), (0.0, 0.0), (-0.0, 180.0)] {
+            let point = Point::new(latitude, longitude).unwrap();
+            assert_eq!(point, Point::new_unchecked(latitude, longitude));
+        }
+    }
+
+    #[test]
+    fn reject_out_of_range() {
+        let err = Point::new(90.000001, 51.4).unwrap_err();
+        assert_eq!(err.axis(), Axis::Latitude);
+        assert_eq!(err.value(), 90.000001);
+        assert_eq!(
+            err.to_string(),
+            "latitude 90.000001 is out of range, expected -90 to 90"
+        );
+
+        let err = Point::new(35.7, -180.5).unwrap_err();
+        assert_eq!(err.axis(), Axis::Longitude);
+        assert_eq!(
+            err.to_string(),
+            "longitude -180.5 is out of range, expected -180 to 180"
+        );
+    }
+
+    #[test]
+    fn reject_non_finite() {
+        let err = Point::new(f64::NAN, 51.4).unwrap_err();
+        assert_eq!(err.axis(), Axis::Latitude);
+        assert!(err.value().is_nan());
+        assert_eq!(err.to_string(), "latitude NaN is not a finite number");
+
+        let err = Point::new(35.7, f64::INFINITY).unwrap_err();
+        assert_eq!(err.axis(), Axis::Longitude);
+        assert_eq!(err.to_string(), "longitude inf is not a finite number");
+
+        let err = Point::new(f64::NEG_INFINITY, f64::NAN).unwrap_err();
+        assert_eq!(err.axis(), Axis::Latitude);
+    }
+
+    #[test]
+    fn display_lat_lng() {
+        assert_eq!(
+            Point::new_unchecked(35.7, 51.4).to_string(),
+            "35.700000,51.400000"
+        );
+        assert_eq!(
+            Point::new_unchecked(35.731984409609694, -51.0).to_string(),
+            "35.731984409609694,-51.000000"
+        );
+        assert_eq!(
+            Point::new_unchecked(1e-7, 90.0).to_string(),
+            "0.0000001,90.000000"
+        );
+    }
+
+    #[test]
+    fn parse_with_whitespace() {
+        assert_eq!(
+            " 35.7 ,\t51.4 ".parse::<Point>(),
+            Ok(Point::new_unchecked(35.7, 51.4))
+        );
+        assert_eq!(
+            "-90,180".parse::<Point>(),
+            Ok(Point::new_unchecked(-90.0, 180.0))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("35.7".parse::<Point>(), Err(ParsePointError::MissingComma));
+        assert_eq!(
+            "35.7,51.4,0".parse::<Point>(),
+            Err(ParsePointError::TooManyComponents(3))
+        );
+
+        let err = "35.7,east".parse::<Point>().unwrap_err();
+        assert_eq!(err.to_string(), "longitude \"east\" is not a number");
+        let err = ",51.4".parse::<Point>().unwrap_err();
+        assert_eq!(err.to_string(), "latitude \"\" is not a number");
+
+        let err = "91,51.4".parse::<Point>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "latitude 91 is out of range, expected -90 to 90"
+        );
+        assert!(matches!(
+            "35.7,NaN".parse::<Point>(),
+            Err(ParsePointError::InvalidCoordinate(_))
+        ));
+    }
+
+    #[test]
+    fn display_parse_round_trip() {
+        // a fixed linear congruential sequence spreads the points over the whole range
+        // along with a few values that are awkward to print.
+        let mut random = crate::random::Lcg::new(0x5EED);
+        let mut next = || random.next_f64();
+
+        let mut points: Vec<Point> = (0..1000)
+            .map(|_| Point::new_unchecked(next() * 180.0 - 90.0, next() * 360.0 - 180.0))
+            .collect();
+        points.extend([
+            Point::new_unchecked(0.0, -0.0),
+            Point::new_unchecked(90.0, -180.0),
+            Point::new_unchecked(1e-12, 179.99999999999997),
+            Point::new_unchecked(35.731984409609694, 51.392684661470156),
+        ]);
+
+        for point in points {
+            let text = point.to_string();
+            assert!(!text.contains('e'), "{}", text);
+            assert_eq!(text.parse::<Point>(), Ok(point), "{}", text);
+        }
+    }
+
+    /// `push_coordinate` must match the std formatting it replaced byte for byte, or cached
+    /// keys and recorded cassettes would stop matching.
+    #[test]
+    fn coordinates_as_std_writes_them() {
+        fn reference(value: f64) -> String {
+            let mut text = value.to_string();
+            if !value.is_finite() {
+                return text;
+            }
+            let decimals = match text.find('.') {
+                Some(dot) => text.len() - dot - 1,
+                None => {
+                    text.push('.');
+                    0
+                }
+            };
+            for _ in decimals..super::DECIMALS {
+                text.push('0');
+            }
+            text
+        }
+
+        let mut random = crate::random::Lcg::new(0xC00D);
+        let mut values = vec![
+            0.0,
+            -0.0,
+            1.0,
+            -180.0,
+            35.7,
+            1e-5,
+            9.99e-6,
+            1e-7,
+            1e15,
+            1e16,
+            1.5e300,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            f64::EPSILON,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        for _ in 0..100_000 {
+            let bits = random.next_u64();
+            values.push(f64::from_bits(bits));
+            values.push((bits >> 11) as f64 / (1u64 << 53) as f64 * 2000.0 - 1000.0);
+            values.push((bits >> 11) as f64 / (1u64 << 53) as f64 * 1e-3);
+            values.push(((bits >> 11) % 360_000_000) as f64 / 1e6 - 180.0);
+        }
+
+        for value in values {
+            assert_eq!(super::coordinate(value), reference(value), "{:e}", value);
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let point = Point::new_unchecked(35.731984409609694, 51.392684661470156);
+
+        let json = serde_json::to_value(point).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "latitude": 35.731984409609694,
+                "longitude": 51.392684661470156
+            })
+        );
+        assert_eq!(serde_json::from_value::<Point>(json).unwrap(), point);
+    }
+
+    #[test]
+    fn deserialize_lat_lng_alias() {
+        let point: Point = serde_json::from_str(r#"{ "lat": 35.7, "lng": 51.4 }"#).unwrap();
+
+        assert_eq!(point, Point::new_unchecked(35.7, 51.4));
+    }
+
+    const TEHRAN: Point = Point::new_unchecked(35.6892, 51.3890);
+    const KARAJ: Point = Point::new_unchecked(35.8400, 50.9391);
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn haversine_reference_pairs() {
+        // tehran to karaj is about 44km in a straight line.
+        assert_close(TEHRAN.haversine_distance_to(&KARAJ), 43_920.0, 5.0);
+        assert_close(KARAJ.haversine_distance_to(&TEHRAN), 43_920.0, 5.0);
+
+        // big ben to the statue of liberty.
+        let london = Point::new_unchecked(51.5007, -0.1246);
+        let new_york = Point::new_unchecked(40.6892, -74.0445);
+        assert_close(london.haversine_distance_to(&new_york), 5_574_850.0, 500.0);
+    }
+
+    #[test]
+    fn haversine_edge_cases() {
+        assert_eq!(TEHRAN.haversine_distance_to(&TEHRAN), 0.0);
+
+        let half = std::f64::consts::PI * EARTH_RADIUS;
+        let antipode = Point::new_unchecked(-35.6892, 51.3890 - 180.0);
+        assert_close(TEHRAN.haversine_distance_to(&antipode), half, 1e-3);
+        assert_close(
+            Point::new_unchecked(0.0, 0.0).haversine_distance_to(&Point::new_unchecked(0.0, 179.9)),
+            20_003_995.0,
+            1.0,
+        );
+    }
+
+    #[test]
+    fn bearing() {
+        assert_close(TEHRAN.bearing_to(&KARAJ), 292.576, 1e-3);
+        assert_close(KARAJ.bearing_to(&TEHRAN), 112.313, 1e-3);
+
+        let origin = Point::new_unchecked(0.0, 0.0);
+        assert_close(
+            origin.bearing_to(&Point::new_unchecked(1.0, 0.0)),
+            0.0,
+            1e-9,
+        );
+        assert_close(
+            origin.bearing_to(&Point::new_unchecked(0.0, 1.0)),
+            90.0,
+            1e-9,
+        );
+        assert_close(
+            origin.bearing_to(&Point::new_unchecked(-1.0, 0.0)),
+            180.0,
+            1e-9,
+        );
+        assert_close(
+            origin.bearing_to(&Point::new_unchecked(0.0, -1.0)),
+            270.0,
+            1e-9,
+        );
+        assert_eq!(TEHRAN.bearing_to(&TEHRAN), 0.0);
+
+        // due north but for a sliver to the west.
+        let north = Point::new_unchecked(1.0, -1e-300);
+        assert_eq!(origin.bearing_to(&north), 0.0);
+    }
+
+    #[test]
+    fn destination_inverts_distance_and_bearing() {
+        let distance = TEHRAN.haversine_distance_to(&KARAJ);
+        let reached = TEHRAN.destination(TEHRAN.bearing_to(&KARAJ), distance);
+        assert_close(reached.latitude, KARAJ.latitude, 1e-9);
+        assert_close(reached.longitude, KARAJ.longitude, 1e-9);
+
+        let reached = TEHRAN.destination(123.0, 0.0);
+        assert_close(reached.latitude, TEHRAN.latitude, 1e-9);
+        assert_close(reached.longitude, TEHRAN.longitude, 1e-9);
+
+        // crossing the antimeridian wraps the longitude.
+        let reached = Point::new_unchecked(0.0, 179.5).destination(90.0, 111_195.0);
+        assert_close(reached.latitude, 0.0, 1e-9);
+        assert_close(reached.longitude, -179.5, 1e-4);
+    }
+}
@@ -0,0 +1,273 @@
+//! google's encoded polyline format, which neshan uses for route geometries.
+//! <https://developers.google.com/maps/documentation/utilities/polylinealgorithm>
+//!
+//! each point is written as the difference to the previous one, latitude first, with its
+//! coordinates scaled by the precision and rounded to an integer.
+
+use crate::Point;
+use std::fmt;
+
+/// number of decimals kept by the encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    /// five decimals, about a meter. the precision of google and neshan.
+    Five,
+    /// six decimals, about 10cm. the precision of osrm and valhalla.
+    Six,
+}
+
+impl Precision {
+    fn factor(self) -> f64 {
+        match self {
+            Precision::Five => 1e5,
+            Precision::Six => 1e6,
+        }
+    }
+}
+
+/// malformed polyline, `offset` is the position of the offending byte in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolylineError {
+    /// the byte is outside the range of the encoding.
+    InvalidByte { offset: usize, byte: u8 },
+    /// the input ends in the middle of a point, either inside a value or after a latitude.
+    Truncated { offset: usize },
+    /// the value starting at `offset` is too long to be a coordinate, or adds up to one out of
+    /// range.
+    Overflow { offset: usize },
+}
+
+impl PolylineError {
+    /// byte offset in the input where the error was found.
+    pub fn offset(&self) -> usize {
+        match self {
+            PolylineError::InvalidByte { offset, .. }
+            | PolylineError::Truncated { offset }
+            | PolylineError::Overflow { offset } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for PolylineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolylineError::InvalidByte { offset, byte } => {
+                write!(f, "invalid byte {:#04x} at offset {}", byte, offset)
+            }
+            PolylineError::Truncated { offset } => {
+                write!(
+                    f,
+                    "polyline ends in the middle of a point at offset {}",
+                    offset
+                )
+            }
+            PolylineError::Overflow { offset } => {
+                write!(f, "value at offset {} is out of range", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolylineError {}
+
+/// decode a polyline into its points.
+pub fn decode(polyline: &str, precision: Precision) -> Result<Vec<Point>, PolylineError> {
+    let bytes = polyline.as_bytes();
+    let factor = precision.factor();
+
+    let mut points = Vec::new();
+    let (mut latitude, mut longitude) = (0i64, 0i64);
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        latitude = add(latitude, bytes, &mut offset)?;
+        if offset == bytes.len() {
+            return Err(PolylineError::Truncated { offset });
+        }
+        longitude = add(longitude, bytes, &mut offset)?;
+
+        points.push(Point {
+            latitude: latitude as f64 / factor,
+            longitude: longitude as f64 / factor,
+        });
+    }
+
+    Ok(points)
+}
+
+/// add the value starting at `offset` to `sum`, the delta encoded coordinate before it.
+fn add(sum: i64, bytes: &[u8], offset: &mut usize) -> Result<i64, PolylineError> {
+    let start = *offset;
+    sum.checked_add(value(bytes, offset)?)
+        .ok_or(PolylineError::Overflow { offset: start })
+}
+
+/// read one value starting at `offset`, moving `offset` past it.
+fn value(bytes: &[u8], offset: &mut usize) -> Result<i64, PolylineError> {
+    let start = *offset;
+    let mut result = 0i64;
+    let mut shift = 0;
+
+    loop {
+        let byte = match bytes.get(*offset) {
+            Some(byte) => *byte,
+            None => return Err(PolylineError::Truncated { offset: *offset }),
+        };
+        if !(63..=126).contains(&byte) {
+            return Err(PolylineError::InvalidByte {
+                offset: *offset,
+                byte,
+            });
+        }
+        // 12 chunks of 5 bits are far more than any coordinate needs.
+        if shift > 55 {
+            return Err(PolylineError::Overflow { offset: start });
+        }
+
+        let chunk = i64::from(byte - 63);
+        result |= (chunk & 0x1f) << shift;
+        shift += 5;
+        *offset += 1;
+
+        if chunk < 0x20 {
+            break;
+        }
+    }
+
+    Ok(if result & 1 == 1 {
+        !(result >> 1)
+    } else {
+        result >> 1
+    })
+}
+
+/// encode the points as a polyline.
+pub fn encode(points: &[Point], precision: Precision) -> String {
+    let factor = precision.factor();
+
+    let mut polyline = String::new();
+    let (mut latitude, mut longitude) = (0i64, 0i64);
+
+    for point in points {
+        let next_latitude = (point.latitude * factor).round() as i64;
+        let next_longitude = (point.longitude * factor).round() as i64;
+
+        push(&mut polyline, next_latitude - latitude);
+        push(&mut polyline, next_longitude - longitude);
+
+        latitude = next_latitude;
+        longitude = next_longitude;
+    }
+
+    polyline
+}
+
+fn push(polyline: &mut String, value: i64) {
+    let mut value = if value < 0 { !(value << 1) } else { value << 1 };
+
+    while value >= 0x20 {
+        polyline.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+        value >>= 5;
+    }
+    polyline.push(char::from(value as u8 + 63));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, PolylineError, Precision};
+    use crate::Point;
+
+    fn reference() -> Vec<Point> {
+        vec![
+            Point::new_unchecked(38.5, -120.2),
+            Point::new_unchecked(40.7, -120.95),
+            Point::new_unchecked(43.252, -126.453),
+        ]
+    }
+
+    fn assert_points(actual: &[Point], expected: &[Point], tolerance: f64) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual.latitude - expected.latitude).abs() <= tolerance
+                    && (actual.longitude - expected.longitude).abs() <= tolerance,
+                "{:?} is not within {} of {:?}",
+                actual,
+                tolerance,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn reference_vectors() {
+        let five = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+        let six = "_izlhA~rlgdF_{geC~ywl@_kwzCn`{nI";
+
+        assert_eq!(encode(&reference(), Precision::Five), five);
+        assert_eq!(encode(&reference(), Precision::Six), six);
+        assert_points(&decode(five, Precision::Five).unwrap(), &reference(), 1e-9);
+        assert_points(&decode(six, Precision::Six).unwrap(), &reference(), 1e-9);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(encode(&[], Precision::Five), "");
+        assert_eq!(decode("", Precision::Five), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(
+            decode("_p~iF~ps|U _ulL", Precision::Five),
+            Err(PolylineError::InvalidByte {
+                offset: 10,
+                byte: b' '
+            })
+        );
+        // the latitude of the second point is cut in the middle.
+        assert_eq!(
+            decode("_p~iF~ps|U_ul", Precision::Five),
+            Err(PolylineError::Truncated { offset: 13 })
+        );
+        // a latitude without its longitude.
+        assert_eq!(
+            decode("_p~iF~ps|U_ulL", Precision::Five),
+            Err(PolylineError::Truncated { offset: 14 })
+        );
+        assert_eq!(
+            decode("_p~iF~~~~~~~~~~~~~~~?", Precision::Five),
+            Err(PolylineError::Overflow { offset: 5 })
+        );
+        // values of the longest length add up past the range of a coordinate.
+        assert_eq!(
+            decode(&"~~~~~~~~~~~^".repeat(34), Precision::Five),
+            Err(PolylineError::Overflow { offset: 384 })
+        );
+        assert_eq!(
+            decode("_p~iFé", Precision::Five).unwrap_err().to_string(),
+            "invalid byte 0xc3 at offset 5"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut random = crate::random::Lcg::new(0x9017);
+        let mut next = || random.next_f64();
+
+        for (precision, tolerance) in [(Precision::Five, 0.5e-5), (Precision::Six, 0.5e-6)] {
+            for len in [1, 2, 10, 200] {
+                let points: Vec<Point> = (0..len)
+                    .map(|_| Point::new_unchecked(next() * 180.0 - 90.0, next() * 360.0 - 180.0))
+                    .collect();
+
+                let polyline = encode(&points, precision);
+                let decoded = decode(&polyline, precision).unwrap();
+                assert_points(&decoded, &points, tolerance + 1e-12);
+
+                // decoded points are exact at the precision, encoding them again is lossless.
+                assert_eq!(encode(&decoded, precision), polyline);
+            }
+        }
+    }
+}
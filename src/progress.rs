@@ -0,0 +1,408 @@
+//! where a live position is along a route, see `Route::progress`.
+
+use crate::polyline::{self, Precision};
+use crate::{Distance, Duration, Point, Route, EARTH_RADIUS};
+
+/// meters from the route beyond which `Route::progress` takes a position as off the route.
+const OFF_ROUTE: f64 = 50.0;
+
+/// the point of a line nearest to a position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Projection {
+    pub(crate) nearest: Point,
+    /// meters along the line to `nearest`.
+    pub(crate) along: f64,
+    /// meters between the position and `nearest`.
+    pub(crate) offset: f64,
+}
+
+/// the point of `line` nearest to `point`, `None` for an empty line.
+///
+/// segments are flattened around their start, which is precise enough for the few hundred
+/// meters between the points of a route geometry.
+pub(crate) fn project(line: &[Point], point: Point) -> Option<Projection> {
+    let first = *line.first()?;
+    let mut best = Projection {
+        nearest: first,
+        along: 0.0,
+        offset: first.haversine_distance_to(&point),
+    };
+
+    let mut travelled = 0.0;
+    for pair in line.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let scale = start.latitude.to_radians().cos();
+        let flatten = |p: Point| {
+            (
+                (p.longitude - start.longitude).to_radians() * scale * EARTH_RADIUS,
+                (p.latitude - start.latitude).to_radians() * EARTH_RADIUS,
+            )
+        };
+
+        let (ex, ey) = flatten(end);
+        let (px, py) = flatten(point);
+        let squared = ex * ex + ey * ey;
+        let t = if squared > 0.0 {
+            ((px * ex + py * ey) / squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let offset = (px - t * ex).hypot(py - t * ey);
+
+        let length = start.haversine_distance_to(&end);
+        if offset < best.offset {
+            best = Projection {
+                nearest: Point {
+                    latitude: start.latitude + t * (end.latitude - start.latitude),
+                    longitude: start.longitude + t * (end.longitude - start.longitude),
+                },
+                along: travelled + t * length,
+                offset,
+            };
+        }
+        travelled += length;
+    }
+
+    Some(best)
+}
+
+/// outcome of `Route::progress`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteProgress {
+    OnRoute(RoutePosition),
+    /// the position is too far from the route to follow it, e.g. time to reroute.
+    OffRoute {
+        /// point of the route nearest to the position.
+        nearest: Point,
+        /// meters between the position and `nearest`.
+        offset: f64,
+    },
+}
+
+/// a position matched onto a route. distances follow the ones neshan sent for the steps,
+/// the texts are in persian like the ones of neshan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePosition {
+    /// point of the route nearest to the position.
+    pub nearest: Point,
+    /// meters between the position and `nearest`.
+    pub offset: f64,
+    /// index of the current leg.
+    pub leg: usize,
+    /// index of the current step within its leg, the upcoming instruction is the one of the
+    /// step after it.
+    pub step: usize,
+    pub covered: Distance,
+    pub remaining_distance: Distance,
+    /// prorated from the durations of the steps by their remaining distance.
+    pub remaining_duration: Duration,
+}
+
+impl Route {
+    /// where `position`, e.g. a gps fix, is along the route. off the route when it is more
+    /// than 50 meters away, see `Route::progress_within`.
+    ///
+    /// the geometry of the steps is used when every step has one, otherwise the overview
+    /// geometry with the steps spread over it by their distance. `None` without either
+    /// geometry or without steps.
+    pub fn progress(&self, position: &Point) -> Option<RouteProgress> {
+        self.progress_within(position, OFF_ROUTE)
+    }
+
+    /// same as `Route::progress`, off the route beyond `off_route` meters.
+    pub fn progress_within(&self, position: &Point, off_route: f64) -> Option<RouteProgress> {
+        let steps: Vec<_> = self
+            .legs
+            .iter()
+            .enumerate()
+            .flat_map(|(leg, l)| l.steps.iter().enumerate().map(move |(i, s)| (leg, i, s)))
+            .collect();
+        if steps.is_empty() {
+            return None;
+        }
+
+        let (line, ends) = self.step_geometry().or_else(|| self.spread_steps())?;
+        let projection = project(&line, *position)?;
+        if projection.offset > off_route {
+            return Some(RouteProgress::OffRoute {
+                nearest: projection.nearest,
+                offset: projection.offset,
+            });
+        }
+
+        // the first step that ends at or after the projection, the last one beyond the end.
+        let current = ends
+            .iter()
+            .position(|end| *end >= projection.along)
+            .unwrap_or(ends.len() - 1);
+        let start = current
+            .checked_sub(1)
+            .map_or(0.0, |previous| ends[previous]);
+        let length = ends[current] - start;
+        let done = match length > 0.0 {
+            true => ((projection.along - start) / length).clamp(0.0, 1.0),
+            false => 1.0,
+        };
+
+        let (leg, step, current_step) = steps[current];
+        let before: f64 = steps[..current].iter().map(|s| s.2.distance.value).sum();
+        let covered = before + done * current_step.distance.value;
+        let total: f64 = steps.iter().map(|s| s.2.distance.value).sum();
+        let after: f64 = steps[current + 1..]
+            .iter()
+            .map(|s| s.2.duration.value)
+            .sum();
+
+        Some(RouteProgress::OnRoute(RoutePosition {
+            nearest: projection.nearest,
+            offset: projection.offset,
+            leg,
+            step,
+            covered: Distance::humanized(covered),
+            remaining_distance: Distance::humanized((total - covered).max(0.0)),
+            remaining_duration: Duration::humanized(
+                (1.0 - done) * current_step.duration.value + after,
+            ),
+        }))
+    }
+
+    /// the geometries of all steps in one line, with the meters along it to the end of each
+    /// step. `None` when a step has no geometry.
+    fn step_geometry(&self) -> Option<(Vec<Point>, Vec<f64>)> {
+        let mut line: Vec<Point> = Vec::new();
+        let mut ends = Vec::new();
+        let mut travelled = 0.0;
+        for step in self.legs.iter().flat_map(|leg| &leg.steps) {
+            let points = polyline::decode(step.polyline.as_deref()?, Precision::Five).ok()?;
+            for point in points {
+                if let Some(last) = line.last() {
+                    travelled += last.haversine_distance_to(&point);
+                }
+                line.push(point);
+            }
+            ends.push(travelled);
+        }
+
+        Some((line, ends))
+    }
+
+    /// the overview geometry, with the ends of the steps placed by their share of the
+    /// distance.
+    fn spread_steps(&self) -> Option<(Vec<Point>, Vec<f64>)> {
+        let line = self.geometry().ok().filter(|line| !line.is_empty())?;
+        let length: f64 = line
+            .windows(2)
+            .map(|pair| pair[0].haversine_distance_to(&pair[1]))
+            .sum();
+
+        let distances: Vec<f64> = self
+            .legs
+            .iter()
+            .flat_map(|leg| &leg.steps)
+            .map(|step| step.distance.value)
+            .collect();
+        let total: f64 = distances.iter().sum();
+        let mut covered = 0.0;
+        let ends = distances
+            .iter()
+            .map(|distance| {
+                covered += distance;
+                match total > 0.0 {
+                    true => covered / total * length,
+                    false => length,
+                }
+            })
+            .collect();
+
+        Some((line, ends))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{project, RoutePosition, RouteProgress};
+    use crate::polyline::{self, Precision};
+    use crate::{Distance, Duration, EncodedPolyline, Leg, Point, Route, Step};
+
+    const WEST: Point = Point {
+        latitude: 35.7,
+        longitude: 51.3,
+    };
+    const MIDDLE: Point = Point {
+        latitude: 35.7,
+        longitude: 51.35,
+    };
+    const EAST: Point = Point {
+        latitude: 35.7,
+        longitude: 51.4,
+    };
+
+    fn step(points: &[Point], seconds: f64) -> Step {
+        let meters = points
+            .windows(2)
+            .map(|pair| pair[0].haversine_distance_to(&pair[1]))
+            .sum();
+        Step {
+            name: String::new(),
+            instruction: String::new(),
+            distance: Distance::humanized(meters),
+            duration: Duration::humanized(seconds),
+            polyline: Some(polyline::encode(points, Precision::Five)),
+            maneuver: None,
+            modifier: None,
+            exit: None,
+            bearing_after: None,
+            start_location: None,
+        }
+    }
+
+    /// a straight road heading east along 35.7 in two steps of 300 and 600 seconds, then
+    /// the arrival.
+    fn route() -> Route {
+        let steps = vec![
+            step(&[WEST, MIDDLE], 300.0),
+            step(&[MIDDLE, EAST], 600.0),
+            step(&[EAST], 0.0),
+        ];
+        Route {
+            legs: vec![Leg {
+                summary: String::new(),
+                distance: Distance::humanized(steps.iter().map(|s| s.distance.value).sum()),
+                duration: Duration::humanized(900.0),
+                steps,
+            }],
+            overview_polyline: Some(EncodedPolyline {
+                points: polyline::encode(&[WEST, EAST], Precision::Five),
+            }),
+        }
+    }
+
+    fn on_route(progress: Option<RouteProgress>) -> RoutePosition {
+        match progress {
+            Some(RouteProgress::OnRoute(position)) => position,
+            progress => panic!("expected a position on the route, got {:?}", progress),
+        }
+    }
+
+    fn close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn project_onto_line() {
+        let half = WEST.haversine_distance_to(&MIDDLE);
+
+        assert_eq!(project(&[], WEST), None);
+        let single = project(&[WEST], MIDDLE).unwrap();
+        assert_eq!((single.nearest, single.along), (WEST, 0.0));
+        close(single.offset, half, 1e-6);
+
+        let north = project(&[WEST, EAST], Point::new_unchecked(35.701, 51.35)).unwrap();
+        close(north.nearest.latitude, 35.7, 1e-9);
+        close(north.nearest.longitude, 51.35, 1e-9);
+        close(north.along, half, 1.0);
+        close(north.offset, 111.2, 0.5);
+
+        let before = project(&[WEST, EAST], Point::new_unchecked(35.7, 51.29)).unwrap();
+        assert_eq!(before.nearest, WEST);
+        assert_eq!(before.along, 0.0);
+    }
+
+    #[test]
+    fn progress_on_the_line() {
+        let route = route();
+        let half = WEST.haversine_distance_to(&MIDDLE);
+        let total = route.total_distance().value;
+
+        let start = on_route(route.progress(&WEST));
+        assert_eq!((start.leg, start.step), (0, 0));
+        assert_eq!(start.covered.value, 0.0);
+        close(start.remaining_distance.value, total, 1e-6);
+        close(start.remaining_duration.value, 900.0, 1e-6);
+
+        // a quarter of the way, halfway through the first step.
+        let quarter = on_route(route.progress(&Point::new_unchecked(35.7, 51.325)));
+        assert_eq!((quarter.leg, quarter.step), (0, 0));
+        close(quarter.offset, 0.0, 0.01);
+        close(quarter.covered.value, half / 2.0, 1.0);
+        close(quarter.remaining_duration.value, 150.0 + 600.0, 1.0);
+
+        let arrived = on_route(route.progress(&EAST));
+        assert_eq!(arrived.step, 1);
+        close(arrived.remaining_distance.value, 0.0, 1e-6);
+        close(arrived.remaining_duration.value, 0.0, 1e-6);
+        assert_eq!(arrived.remaining_duration.text, "۰ ثانیه");
+    }
+
+    #[test]
+    fn progress_near_the_line() {
+        let route = route();
+        let half = WEST.haversine_distance_to(&MIDDLE);
+
+        // about 22 meters north of three quarters of the way.
+        let near = Point::new_unchecked(35.7002, 51.375);
+        let position = on_route(route.progress(&near));
+        assert_eq!(position.step, 1);
+        close(position.nearest.latitude, 35.7, 1e-9);
+        close(position.nearest.longitude, 51.375, 1e-9);
+        close(position.offset, 22.2, 0.5);
+        close(position.covered.value, half * 1.5, 1.0);
+        close(position.remaining_duration.value, 300.0, 1.0);
+
+        // the same position with a tighter threshold.
+        match route.progress_within(&near, 10.0) {
+            Some(RouteProgress::OffRoute { nearest, offset }) => {
+                assert_eq!(nearest, position.nearest);
+                assert_eq!(offset, position.offset);
+            }
+            progress => panic!("expected off the route, got {:?}", progress),
+        }
+    }
+
+    #[test]
+    fn progress_far_from_the_line() {
+        let route = route();
+
+        match route.progress(&Point::new_unchecked(35.705, 51.35)) {
+            Some(RouteProgress::OffRoute { nearest, offset }) => {
+                close(nearest.longitude, 51.35, 1e-9);
+                close(offset, 556.0, 1.0);
+            }
+            progress => panic!("expected off the route, got {:?}", progress),
+        }
+
+        // before the start, only on the route with a loose threshold.
+        let before = Point::new_unchecked(35.7, 51.29);
+        assert!(matches!(
+            route.progress(&before),
+            Some(RouteProgress::OffRoute { .. })
+        ));
+        let position = on_route(route.progress_within(&before, 1000.0));
+        assert_eq!(position.nearest, WEST);
+        assert_eq!(position.covered.value, 0.0);
+    }
+
+    #[test]
+    fn progress_over_the_overview() {
+        let mut route = route();
+        for step in &mut route.legs[0].steps {
+            step.polyline = None;
+        }
+
+        let position = on_route(route.progress(&Point::new_unchecked(35.7, 51.375)));
+        assert_eq!(position.step, 1);
+        close(position.remaining_duration.value, 300.0, 1.0);
+
+        route.overview_polyline = None;
+        assert_eq!(route.progress(&WEST), None);
+
+        let mut without_steps = self::route();
+        without_steps.legs[0].steps.clear();
+        assert_eq!(without_steps.progress(&WEST), None);
+    }
+}
@@ -0,0 +1,890 @@
+//! building requests to neshan and reading its responses without any io, for services that
+//! send their http requests with a stack of their own. `Client` is built on the same
+//! functions, adding retries, caching and the rest around them.
+//!
+//! ```
+//! use neshan_rs::{protocol, Point, RouteOptions, Type};
+//!
+//! let request = protocol::build_route_request(
+//!     "api-key",
+//!     Type::Car,
+//!     Point::new_unchecked(35.73, 51.39),
+//!     Point::new_unchecked(35.72, 50.95),
+//!     &RouteOptions::new(),
+//! )
+//! .unwrap();
+//! assert_eq!(request.uri().path(), "/v3/direction");
+//!
+//! // send the request with any http client, then read what came back.
+//! let body = br#"{"routes": []}"#;
+//! let routes = protocol::parse_route_response(
+//!     http::StatusCode::OK,
+//!     &http::HeaderMap::new(),
+//!     body,
+//! )
+//! .unwrap();
+//! assert!(routes.is_empty());
+//! ```
+//!
+//! the points of a request are sent as they are, check them with `Point::validate` first
+//! when they come from outside.
+
+use crate::endpoint::Endpoint;
+use crate::error::{ApiError, Error, NeshanError};
+use crate::geocode::Geocoding;
+use crate::quota::QuotaInfo;
+use crate::{
+    DirectionVersion, DistanceMatrix, GeocodeResult, MatchedTrace, OptimizedTrip, Point,
+    PostalAddress, PostalAddressV5, RouteOptions, Routes, SearchResults, StaticMapRequest,
+    TileCoord, TileStyle, Type,
+};
+use http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+use url::Url;
+
+/// where neshan is, and where the requests of this module go.
+pub const BASE_URL: &str = "https://api.neshan.org";
+
+/// query parameters of a request, url-encoded into a single string in the order they are
+/// added, so coordinates are written straight into it without a string per value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Query {
+    encoded: String,
+}
+
+impl Query {
+    pub(crate) fn new() -> Query {
+        Query::default()
+    }
+
+    /// same as pushing each of the pairs, e.g. the ones of `StaticMapRequest::query`.
+    pub(crate) fn from_pairs<'a>(pairs: impl IntoIterator<Item = &'a (&'a str, String)>) -> Query {
+        let mut query = Query::new();
+        for (name, value) in pairs {
+            query.push(name, value);
+        }
+
+        query
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.encoded
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.encoded.is_empty()
+    }
+
+    /// `&name=`, leaving the value to the caller.
+    fn name(&mut self, name: &str) -> &mut String {
+        if !self.encoded.is_empty() {
+            self.encoded.push('&');
+        }
+        self.encoded
+            .extend(url::form_urlencoded::byte_serialize(name.as_bytes()));
+        self.encoded.push('=');
+
+        &mut self.encoded
+    }
+
+    pub(crate) fn push(&mut self, name: &str, value: &str) -> &mut Query {
+        self.name(name)
+            .extend(url::form_urlencoded::byte_serialize(value.as_bytes()));
+        self
+    }
+
+    pub(crate) fn push_bool(&mut self, name: &str, value: bool) -> &mut Query {
+        self.name(name)
+            .push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    /// digits need no encoding, so they are written as they are.
+    pub(crate) fn push_integer(&mut self, name: &str, value: impl Into<u64>) -> &mut Query {
+        use std::fmt::Write;
+
+        let _ = write!(self.name(name), "{}", value.into());
+        self
+    }
+
+    /// a single coordinate, written by `Latitude` and `Longitude` as well.
+    pub(crate) fn push_coordinate(&mut self, name: &str, value: f64) -> &mut Query {
+        crate::point::push_coordinate(self.name(name), value);
+        self
+    }
+
+    /// the points as `lat,lng|lat,lng`, with the separators encoded. coordinates are made of
+    /// digits, `.`, `-` and the letters of `NaN` and `inf`, none of which need encoding.
+    pub(crate) fn push_points(&mut self, name: &str, points: &[Point]) -> &mut Query {
+        let text = self.name(name);
+        text.reserve(points.len() * 26);
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                text.push_str("%7C");
+            }
+            crate::point::push_encoded_point(text, *point);
+        }
+
+        self
+    }
+}
+
+/// neshan reports some failures with a success status and an error body.
+#[derive(Deserialize)]
+struct Envelope {
+    status: Option<String>,
+}
+
+fn is_error_envelope(body: &[u8]) -> bool {
+    serde_json::from_slice::<Envelope>(body)
+        .map(|envelope| envelope.status.as_deref() == Some("ERROR"))
+        .unwrap_or(false)
+}
+
+pub(crate) fn route_query(
+    vehicle: Type,
+    origin: Point,
+    destination: Point,
+    options: &RouteOptions,
+) -> Query {
+    // v3 names the parameters in snake case, v4 in camel case.
+    let (traffic_zone, odd_even_zone) = match options.version {
+        DirectionVersion::V3 => ("avoid_traffic_zone", "avoid_odd_even_zone"),
+        DirectionVersion::V4 => ("avoidTrafficZone", "avoidOddEvenZone"),
+    };
+
+    let mut query = Query::new();
+    query
+        .push("type", vehicle.as_str())
+        .push_points("origin", &[origin])
+        .push_points("destination", &[destination])
+        .push_bool(traffic_zone, options.avoid_traffic_zone)
+        .push_bool(odd_even_zone, options.avoid_odd_even_zone)
+        .push_bool("alternative", options.alternative_paths);
+    if !options.waypoints.is_empty() {
+        query.push_points("waypoints", &options.waypoints);
+    }
+    if let Some(bearing) = options.bearing {
+        query.push_integer("bearing", bearing);
+    }
+
+    query
+}
+
+pub(crate) fn reverse_geocode_query(point: Point) -> Query {
+    let mut query = Query::new();
+    query
+        .push_coordinate("lat", point.latitude)
+        .push_coordinate("lng", point.longitude);
+    query
+}
+
+pub(crate) fn distance_matrix_query(
+    vehicle: Type,
+    origins: &[Point],
+    destinations: &[Point],
+) -> Query {
+    let mut query = Query::new();
+    query
+        .push("type", vehicle.as_str())
+        .push_points("origins", origins)
+        .push_points("destinations", destinations);
+    query
+}
+
+pub(crate) fn map_match_query(points: &[Point]) -> Query {
+    let mut query = Query::new();
+    query.push_points("path", points);
+    query
+}
+
+pub(crate) fn search_query(term: &str, near: Point) -> Query {
+    let mut query = Query::new();
+    query
+        .push("term", term)
+        .push_coordinate("lat", near.latitude)
+        .push_coordinate("lng", near.longitude);
+    query
+}
+
+/// query of a page after the first one of `Client::search_all`, counted from 1.
+pub(crate) fn search_page_query(term: &str, near: Point, page: u32) -> Query {
+    let mut query = search_query(term, near);
+    query.push_integer("page", page);
+    query
+}
+
+pub(crate) fn geocode_query(address: &str) -> Query {
+    let mut query = Query::new();
+    query.push("address", address);
+    query
+}
+
+/// the first waypoint is where the trip starts.
+pub(crate) fn trip_query(vehicle: Type, waypoints: &[Point], round_trip: bool) -> Query {
+    let mut query = Query::new();
+    query
+        .push("type", vehicle.as_str())
+        .push_points("waypoints", waypoints)
+        .push_bool("roundTrip", round_trip)
+        .push_bool("sourceIsAnyPoint", false);
+    query
+}
+
+pub(crate) fn tile_query(coord: TileCoord, style: TileStyle) -> Query {
+    let mut query = Query::new();
+    query
+        .push("type", style.as_str())
+        .push_integer("z", coord.z)
+        .push_integer("x", coord.x)
+        .push_integer("y", coord.y);
+    query
+}
+
+/// url of the endpoint under `base_url` with the query parameters.
+pub(crate) fn url(base_url: &str, endpoint: Endpoint, query: &Query) -> Result<Url, NeshanError> {
+    let mut url = Url::parse(&format!("{}{}", base_url, endpoint.path()))
+        .map_err(|err| NeshanError::Config(format!("invalid url: {}", err)))?;
+    if !query.is_empty() {
+        url.set_query(Some(query.as_str()));
+    }
+
+    Ok(url)
+}
+
+/// the api key as a header value, kept out of debug output.
+pub(crate) fn api_key_header(api_key: &str) -> Result<HeaderValue, NeshanError> {
+    let mut value = HeaderValue::from_str(api_key)
+        .map_err(|_| NeshanError::Config("api key is not a valid header value".to_string()))?;
+    value.set_sensitive(true);
+
+    Ok(value)
+}
+
+fn build(
+    api_key: &str,
+    endpoint: Endpoint,
+    query: &Query,
+) -> Result<http::Request<()>, NeshanError> {
+    let url = url(BASE_URL, endpoint, query)?;
+
+    http::Request::builder()
+        .method(Method::GET)
+        .uri(url.as_str())
+        .header("Api-Key", api_key_header(api_key)?)
+        .body(())
+        .map_err(|err| NeshanError::Config(err.to_string()))
+}
+
+/// request of `Client::route_with`, failing when there are too many waypoints.
+pub fn build_route_request(
+    api_key: &str,
+    vehicle: Type,
+    origin: Point,
+    destination: Point,
+    options: &RouteOptions,
+) -> Result<http::Request<()>, NeshanError> {
+    options.check()?;
+
+    build(
+        api_key,
+        options.version.endpoint(),
+        &route_query(vehicle, origin, destination, options),
+    )
+}
+
+/// request of `Client::route_no_traffic`, on v4 of the direction api whatever the version of
+/// `options`.
+pub fn build_route_no_traffic_request(
+    api_key: &str,
+    vehicle: Type,
+    origin: Point,
+    destination: Point,
+    options: &RouteOptions,
+) -> Result<http::Request<()>, NeshanError> {
+    options.check()?;
+
+    let options = options.clone().version(DirectionVersion::V4);
+    build(
+        api_key,
+        Endpoint::RouteNoTraffic,
+        &route_query(vehicle, origin, destination, &options),
+    )
+}
+
+/// request of `Client::reverse_geocode`.
+pub fn build_reverse_geocode_request(
+    api_key: &str,
+    point: Point,
+) -> Result<http::Request<()>, NeshanError> {
+    build(
+        api_key,
+        Endpoint::ReverseGeocode,
+        &reverse_geocode_query(point),
+    )
+}
+
+/// request of `Client::reverse_geocode_v5`.
+pub fn build_reverse_geocode_v5_request(
+    api_key: &str,
+    point: Point,
+) -> Result<http::Request<()>, NeshanError> {
+    build(
+        api_key,
+        Endpoint::ReverseGeocodeV5,
+        &reverse_geocode_query(point),
+    )
+}
+
+/// request of `Client::distance_matrix`, failing when either list is empty.
+pub fn build_distance_matrix_request(
+    api_key: &str,
+    vehicle: Type,
+    origins: &[Point],
+    destinations: &[Point],
+) -> Result<http::Request<()>, NeshanError> {
+    crate::distance_matrix::check_sizes(origins, destinations)?;
+    build(
+        api_key,
+        Endpoint::DistanceMatrix,
+        &distance_matrix_query(vehicle, origins, destinations),
+    )
+}
+
+/// request of `Client::distance_matrix_no_traffic`, its response is read with
+/// `parse_distance_matrix_response` as well.
+pub fn build_distance_matrix_no_traffic_request(
+    api_key: &str,
+    vehicle: Type,
+    origins: &[Point],
+    destinations: &[Point],
+) -> Result<http::Request<()>, NeshanError> {
+    crate::distance_matrix::check_sizes(origins, destinations)?;
+    build(
+        api_key,
+        Endpoint::DistanceMatrixNoTraffic,
+        &distance_matrix_query(vehicle, origins, destinations),
+    )
+}
+
+/// request of `Client::map_match`. every point is sent, thin out long traces beforehand, see
+/// `MapMatchOptions`. fails when there are fewer than two points.
+pub fn build_map_match_request(
+    api_key: &str,
+    points: &[Point],
+) -> Result<http::Request<()>, NeshanError> {
+    crate::map_matching::check_trace(points)?;
+    build(api_key, Endpoint::MapMatching, &map_match_query(points))
+}
+
+/// request of `Client::search`.
+pub fn build_search_request(
+    api_key: &str,
+    term: &str,
+    near: Point,
+) -> Result<http::Request<()>, NeshanError> {
+    build(api_key, Endpoint::Search, &search_query(term, near))
+}
+
+/// request of `Client::geocode`.
+pub fn build_geocode_request(
+    api_key: &str,
+    address: &str,
+) -> Result<http::Request<()>, NeshanError> {
+    build(api_key, Endpoint::Geocode, &geocode_query(address))
+}
+
+/// request of `Client::trip`, failing when there are fewer than two waypoints.
+pub fn build_trip_request(
+    api_key: &str,
+    vehicle: Type,
+    waypoints: &[Point],
+    round_trip: bool,
+) -> Result<http::Request<()>, NeshanError> {
+    crate::optimize::check_waypoints(waypoints)?;
+    build(
+        api_key,
+        Endpoint::Trip,
+        &trip_query(vehicle, waypoints, round_trip),
+    )
+}
+
+/// request of `Client::static_map_to`, failing when it is over the limits of the endpoint,
+/// see `StaticMapRequest::validate`.
+pub fn build_static_map_request(
+    api_key: &str,
+    request: &StaticMapRequest,
+) -> Result<http::Request<()>, NeshanError> {
+    request.validate()?;
+
+    build(
+        api_key,
+        Endpoint::StaticMap,
+        &Query::from_pairs(&request.query()),
+    )
+}
+
+/// request of `Client::tile`, failing when the tile is off the map. the image comes back as
+/// the one of a static map, see `parse_static_map_response`.
+pub fn build_tile_request(
+    api_key: &str,
+    coord: TileCoord,
+    style: TileStyle,
+) -> Result<http::Request<()>, NeshanError> {
+    coord.validate()?;
+
+    build(api_key, Endpoint::Tile, &tile_query(coord, style))
+}
+
+/// headers the id of a request may come back in, the first one present wins.
+const REQUEST_ID_HEADERS: [&str; 4] = [
+    "x-request-id",
+    "request-id",
+    "x-correlation-id",
+    "correlation-id",
+];
+
+/// id of the request from the headers of its response, see `NeshanError::request_id`.
+pub(crate) fn request_id(headers: &HeaderMap) -> Option<&str> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|value| value.to_str().ok())
+        .map(str::trim)
+        .find(|id| !id.is_empty())
+}
+
+/// error of a failed response, from neshan's error body or the raw body text.
+pub(crate) fn api_error(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> NeshanError {
+    let retry_after = headers
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs);
+
+    let err = serde_json::from_slice::<Error>(body).unwrap_or_else(|_| {
+        Error::new(
+            i32::from(status.as_u16()),
+            String::from_utf8_lossy(body).into_owned(),
+        )
+    });
+    let quota = QuotaInfo::from_headers(headers);
+    let request_id = request_id(headers).map(Box::from);
+
+    NeshanError::from_api(ApiError::new(
+        status.as_u16(),
+        err,
+        retry_after,
+        quota,
+        request_id,
+    ))
+}
+
+/// turn error statuses, and error bodies sent with a success status, into errors.
+pub(crate) fn check(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), NeshanError> {
+    if !status.is_success() || is_error_envelope(body) {
+        return Err(api_error(status, headers, body));
+    }
+
+    Ok(())
+}
+
+/// fail unless the response has a content type starting with `expected`.
+pub(crate) fn check_content_type(headers: &HeaderMap, expected: &str) -> Result<(), NeshanError> {
+    let actual = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !actual.starts_with(expected) {
+        return Err(NeshanError::UnexpectedContentType {
+            content_type: actual.to_string(),
+            request_id: request_id(headers).map(str::to_string),
+        });
+    }
+
+    Ok(())
+}
+
+fn parse<T: DeserializeOwned>(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<T, NeshanError> {
+    check(status, headers, body)?;
+
+    serde_json::from_slice(body)
+        .map_err(|err| NeshanError::from(err).with_request_id(request_id(headers)))
+}
+
+/// result of a `build_route_request`.
+pub fn parse_route_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Routes, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// result of a `build_reverse_geocode_request`.
+pub fn parse_reverse_geocode_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<PostalAddress, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// result of a `build_reverse_geocode_v5_request`.
+pub fn parse_reverse_geocode_v5_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<PostalAddressV5, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// result of a `build_distance_matrix_request`.
+pub fn parse_distance_matrix_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<DistanceMatrix, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// result of a `build_map_match_request`, the original indices refer to the points that
+/// were sent.
+pub fn parse_map_match_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<MatchedTrace, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// result of a `build_search_request`.
+pub fn parse_search_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<SearchResults, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// result of a `build_geocode_request`, `None` when the address wasn't resolved.
+pub fn parse_geocode_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Option<GeocodeResult>, NeshanError> {
+    parse::<Geocoding>(status, headers, body).map(Geocoding::result)
+}
+
+/// result of a `build_trip_request`.
+pub fn parse_trip_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<OptimizedTrip, NeshanError> {
+    parse(status, headers, body)
+}
+
+/// image of a `build_static_map_request`, failing unless neshan answered with one.
+pub fn parse_static_map_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Vec<u8>, NeshanError> {
+    if !status.is_success() {
+        return Err(api_error(status, headers, body));
+    }
+    check_content_type(headers, "image/")?;
+
+    Ok(body.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::Marker;
+
+    const TEHRAN: Point = Point {
+        latitude: 35.6997,
+        longitude: 51.338,
+    };
+    const KARAJ: Point = Point {
+        latitude: 35.8355,
+        longitude: 50.9915,
+    };
+
+    fn json() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn query_matches_form_urlencoded() {
+        let points = [TEHRAN, KARAJ, Point::new_unchecked(-0.000_001, -179.5)];
+        let mut query = Query::new();
+        query
+            .push("term", "کافه & co=1+2")
+            .push_points("path", &points)
+            .push_coordinate("lat", f64::NAN)
+            .push_bool("roundTrip", true)
+            .push_integer("page", 7u32);
+
+        let path = points
+            .iter()
+            .map(Point::to_string)
+            .collect::<Vec<_>>()
+            .join("|");
+        let expected = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("term", "کافه & co=1+2")
+            .append_pair("path", &path)
+            .append_pair("lat", &crate::point::coordinate(f64::NAN))
+            .append_pair("roundTrip", "true")
+            .append_pair("page", "7")
+            .finish();
+        assert_eq!(query.as_str(), expected);
+
+        let mut url = Url::parse("https://api.neshan.org/v3/map-matching").unwrap();
+        url.query_pairs_mut().append_pair("path", &path);
+        assert_eq!(
+            super::url(BASE_URL, Endpoint::MapMatching, &map_match_query(&points)).unwrap(),
+            url
+        );
+    }
+
+    #[test]
+    fn route_option_queries() {
+        for traffic_zone in [false, true] {
+            for odd_even_zone in [false, true] {
+                for alternative in [false, true] {
+                    let options = RouteOptions::new()
+                        .avoid_traffic_zone(traffic_zone)
+                        .avoid_odd_even_zone(odd_even_zone)
+                        .alternative_paths(alternative);
+                    let expected = format!(
+                        "type=car&origin=35.699700%2C51.338000&destination=35.835500%2C50.991500\
+                         &avoid_traffic_zone={}&avoid_odd_even_zone={}&alternative={}",
+                        traffic_zone, odd_even_zone, alternative
+                    );
+
+                    let route = build_route_request("key", Type::Car, TEHRAN, KARAJ, &options);
+                    assert_eq!(route.unwrap().uri().query(), Some(expected.as_str()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn request_urls() {
+        let options = RouteOptions::new()
+            .avoid_traffic_zone(true)
+            .alternative_paths(true);
+        let route = build_route_request("key", Type::Motorcycle, TEHRAN, KARAJ, &options).unwrap();
+        assert_eq!(route.method(), Method::GET);
+        assert_eq!(
+            route.uri().to_string(),
+            "https://api.neshan.org/v3/direction?type=motorcycle\
+             &origin=35.699700%2C51.338000&destination=35.835500%2C50.991500\
+             &avoid_traffic_zone=true&avoid_odd_even_zone=false&alternative=true"
+        );
+        assert_eq!(route.headers()["api-key"], "key");
+        assert!(route.headers()["api-key"].is_sensitive());
+
+        let options = options.version(DirectionVersion::V4);
+        let route = build_route_request("key", Type::Motorcycle, TEHRAN, KARAJ, &options).unwrap();
+        assert_eq!(
+            route.uri().to_string(),
+            "https://api.neshan.org/v4/direction?type=motorcycle\
+             &origin=35.699700%2C51.338000&destination=35.835500%2C50.991500\
+             &avoidTrafficZone=true&avoidOddEvenZone=false&alternative=true"
+        );
+        let free_flow = build_route_no_traffic_request(
+            "key",
+            Type::Motorcycle,
+            TEHRAN,
+            KARAJ,
+            &RouteOptions::new(),
+        )
+        .unwrap();
+        assert_eq!(free_flow.uri().path(), "/v4/direction/no-traffic");
+        assert!(free_flow
+            .uri()
+            .query()
+            .unwrap()
+            .contains("&avoidOddEvenZone=false"));
+
+        let reverse = build_reverse_geocode_request("key", TEHRAN).unwrap();
+        assert_eq!(
+            reverse.uri().to_string(),
+            "https://api.neshan.org/v2/reverse?lat=35.699700&lng=51.338000"
+        );
+
+        let matrix =
+            build_distance_matrix_request("key", Type::Car, &[TEHRAN, KARAJ], &[KARAJ]).unwrap();
+        assert_eq!(
+            matrix.uri().to_string(),
+            "https://api.neshan.org/v1/distance-matrix?type=car\
+             &origins=35.699700%2C51.338000%7C35.835500%2C50.991500\
+             &destinations=35.835500%2C50.991500"
+        );
+
+        let matching = build_map_match_request("key", &[TEHRAN, KARAJ]).unwrap();
+        assert_eq!(
+            matching.uri().to_string(),
+            "https://api.neshan.org/v3/map-matching\
+             ?path=35.699700%2C51.338000%7C35.835500%2C50.991500"
+        );
+
+        let static_map = build_static_map_request(
+            "key",
+            &StaticMapRequest::new(TEHRAN, 14, 64, 32).marker(Marker::new(KARAJ)),
+        )
+        .unwrap();
+        assert_eq!(static_map.uri().path(), "/v4/static");
+        assert_eq!(
+            static_map.uri().query(),
+            Some(
+                "type=neshan&zoom=14&center=35.699700%2C51.338000&width=64&height=32\
+                 &markers=35.835500%2C50.991500"
+            )
+        );
+
+        let tile =
+            build_tile_request("key", TileCoord::new(12, 2632, 1612), TileStyle::Dreamy).unwrap();
+        assert_eq!(
+            tile.uri().to_string(),
+            "https://api.neshan.org/v4/tile?type=dreamy&z=12&x=2632&y=1612"
+        );
+        assert!(build_tile_request("key", TileCoord::new(1, 0, 2), TileStyle::Dreamy).is_err());
+    }
+
+    #[test]
+    fn invalid_requests() {
+        assert!(matches!(
+            build_reverse_geocode_request("bad\nkey", TEHRAN),
+            Err(NeshanError::Config(_))
+        ));
+        assert!(matches!(
+            build_static_map_request("key", &StaticMapRequest::new(TEHRAN, 14, 0, 32)),
+            Err(NeshanError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn parse_fixtures() {
+        let ok = |fixture: &str| (StatusCode::OK, json(), fixture.as_bytes().to_vec());
+
+        let (status, headers, body) = ok(include_str!("../fixtures/route.json"));
+        assert_eq!(
+            parse_route_response(status, &headers, &body).unwrap().len(),
+            2
+        );
+        let (status, headers, body) = ok(include_str!("../fixtures/route_empty.json"));
+        assert!(parse_route_response(status, &headers, &body)
+            .unwrap()
+            .is_empty());
+
+        for fixture in [
+            include_str!("../fixtures/reverse_geocode.json"),
+            include_str!("../fixtures/reverse_geocode_minimal.json"),
+        ] {
+            let (status, headers, body) = ok(fixture);
+            let address = parse_reverse_geocode_response(status, &headers, &body).unwrap();
+            assert_eq!(address, PostalAddress::from_json(fixture).unwrap());
+        }
+        let (status, headers, body) = ok(include_str!("../fixtures/reverse_geocode_v5_rural.json"));
+        let address = parse_reverse_geocode_v5_response(status, &headers, &body).unwrap();
+        assert_eq!(address.county.as_deref(), Some("نور"));
+
+        let (status, headers, body) = ok(include_str!("../fixtures/distance_matrix.json"));
+        let matrix = parse_distance_matrix_response(status, &headers, &body).unwrap();
+        assert_eq!(matrix.rows.len(), 2);
+
+        let (status, headers, body) = ok(include_str!("../fixtures/map_matching.json"));
+        let trace = parse_map_match_response(status, &headers, &body).unwrap();
+        assert!(!trace.snapped_points.is_empty());
+
+        let (status, headers, body) = ok(include_str!("../fixtures/search.json"));
+        let results = parse_search_response(status, &headers, &body).unwrap();
+        assert_eq!(results.items.len(), 3);
+
+        let (status, headers, body) = ok(include_str!("../fixtures/geocode.json"));
+        let result = parse_geocode_response(status, &headers, &body).unwrap();
+        assert_eq!(result.unwrap().title.as_deref(), Some("میدان آزادی"));
+        let (status, headers, body) = ok(include_str!("../fixtures/geocode_not_found.json"));
+        assert_eq!(
+            parse_geocode_response(status, &headers, &body).unwrap(),
+            None
+        );
+
+        let (status, headers, body) = ok(include_str!("../fixtures/trip_round.json"));
+        let trip = parse_trip_response(status, &headers, &body).unwrap();
+        assert_eq!(trip.order(), vec![0, 2, 3, 1]);
+    }
+
+    #[test]
+    fn parse_failures() {
+        let body = br#"{"status": "ERROR", "code": 480, "message": "Key not found"}"#;
+
+        let err = parse_reverse_geocode_response(StatusCode::from_u16(470).unwrap(), &json(), body)
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::Api(ref api) if api.error().code() == 480));
+        assert_eq!(err.status(), Some(470));
+
+        // neshan sends some errors with a success status.
+        let err = parse_route_response(StatusCode::OK, &json(), body).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Auth);
+
+        let mut headers = json();
+        headers.insert(header::RETRY_AFTER, "3".parse().unwrap());
+        let err = parse_route_response(StatusCode::TOO_MANY_REQUESTS, &headers, b"slow down")
+            .unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+
+        let err = parse_route_response(StatusCode::OK, &json(), b"{\"routes\": 1}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Decode);
+    }
+
+    #[test]
+    fn request_ids() {
+        let mut headers = json();
+        assert_eq!(request_id(&headers), None);
+        headers.insert("x-correlation-id", "corr-1".parse().unwrap());
+        assert_eq!(request_id(&headers), Some("corr-1"));
+        headers.insert("x-request-id", " ".parse().unwrap());
+        assert_eq!(request_id(&headers), Some("corr-1"));
+        headers.insert("x-request-id", "req-1".parse().unwrap());
+        assert_eq!(request_id(&headers), Some("req-1"));
+
+        let err = parse_route_response(StatusCode::OK, &headers, b"{\"routes\": 1}").unwrap_err();
+        assert_eq!(err.request_id(), Some("req-1"));
+    }
+
+    #[test]
+    fn parse_static_map() {
+        let mut png = HeaderMap::new();
+        png.insert(header::CONTENT_TYPE, "image/png".parse().unwrap());
+        assert_eq!(
+            parse_static_map_response(StatusCode::OK, &png, b"\x89PNG").unwrap(),
+            b"\x89PNG"
+        );
+
+        assert!(matches!(
+            parse_static_map_response(StatusCode::OK, &json(), b"{}"),
+            Err(NeshanError::UnexpectedContentType { ref content_type, .. }) if content_type == "application/json"
+        ));
+        let err = parse_static_map_response(StatusCode::BAD_GATEWAY, &png, b"").unwrap_err();
+        assert_eq!(err.status(), Some(502));
+    }
+}
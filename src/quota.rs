@@ -0,0 +1,63 @@
+use http::HeaderMap;
+use std::time::Duration;
+
+/// rate limit and quota state reported by neshan through the `X-RateLimit-*` headers.
+/// headers that were missing from the response are left as `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuotaInfo {
+    /// requests allowed in the current window.
+    pub limit: Option<u64>,
+    /// requests left in the current window.
+    pub remaining: Option<u64>,
+    /// time until the window resets.
+    pub reset: Option<Duration>,
+}
+
+impl QuotaInfo {
+    /// parse the quota headers, `None` when the response carries none of them.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<QuotaInfo> {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+
+        let quota = QuotaInfo {
+            limit: number("x-ratelimit-limit"),
+            remaining: number("x-ratelimit-remaining"),
+            reset: number("x-ratelimit-reset").map(Duration::from_secs),
+        };
+
+        if quota == QuotaInfo::default() {
+            None
+        } else {
+            Some(quota)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuotaInfo;
+    use http::{HeaderMap, HeaderValue};
+    use std::time::Duration;
+
+    #[test]
+    fn partial_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static("42"));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from_static("30"));
+        headers.insert("X-RateLimit-Limit", HeaderValue::from_static("many"));
+
+        assert_eq!(
+            QuotaInfo::from_headers(&headers),
+            Some(QuotaInfo {
+                limit: None,
+                remaining: Some(42),
+                reset: Some(Duration::from_secs(30)),
+            })
+        );
+        assert_eq!(QuotaInfo::from_headers(&HeaderMap::new()), None);
+    }
+}
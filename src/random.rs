@@ -0,0 +1,24 @@
+//! a fixed pseudo-random sequence for `fake` responses and tests, so they stay the same across
+//! releases and runs without a dependency on a random number crate.
+
+/// linear congruential generator with the constants of knuth's mmix.
+pub(crate) struct Lcg(u64);
+
+impl Lcg {
+    pub(crate) fn new(seed: u64) -> Lcg {
+        Lcg(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0
+    }
+
+    /// number in [0, 1), from the top 53 bits of the next value.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,267 @@
+use futures_util::future::{self, Either};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// waiting this long moves a request up by one priority level, so low priority requests
+/// are delayed but never starved by a steady stream of high priority ones.
+const AGING: Duration = Duration::from_secs(1);
+
+/// importance of a request when it waits for the rate limiter, see `Client::with_priority`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// user facing requests, admitted before everything else.
+    High,
+    #[default]
+    Normal,
+    /// background work, e.g. batches and prefetching.
+    Low,
+}
+
+impl Priority {
+    fn level(self) -> u128 {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+/// token bucket shared by every request of a client and its clones.
+///
+/// when the bucket is empty, waiters are admitted by priority and then by arrival. a waiter
+/// only takes its token once it is admitted, which means dropping a pending request gives
+/// its place back without consuming anything.
+pub(crate) struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+struct State {
+    tokens: f64,
+    updated: Instant,
+    waiters: Vec<Waiter>,
+    next_id: u64,
+}
+
+struct Waiter {
+    id: u64,
+    priority: Priority,
+    since: Instant,
+}
+
+impl State {
+    fn refill(&mut self, per_second: f64, burst: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.updated = now;
+    }
+
+    /// the waiter that gets the next token.
+    fn next(&self) -> Option<u64> {
+        let now = Instant::now();
+
+        self.waiters
+            .iter()
+            .min_by_key(|waiter| {
+                let aged = now.duration_since(waiter.since).as_nanos() / AGING.as_nanos();
+                let level = waiter.priority.level().saturating_sub(aged);
+                (level, waiter.since, waiter.id)
+            })
+            .map(|waiter| waiter.id)
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.waiters.retain(|waiter| waiter.id != id);
+    }
+}
+
+/// removes a waiter that gave up, letting the others take its place.
+struct Registration<'a> {
+    limiter: &'a RateLimiter,
+    id: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().remove(self.id);
+        self.limiter.changed.notify_waiters();
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_second: f64, burst: u32) -> RateLimiter {
+        let burst = f64::from(burst.max(1));
+
+        RateLimiter {
+            per_second,
+            burst,
+            state: Mutex::new(State {
+                tokens: burst,
+                updated: Instant::now(),
+                waiters: Vec::new(),
+                next_id: 0,
+            }),
+            changed: Notify::new(),
+        }
+    }
+
+    /// wait until a request may be sent.
+    pub(crate) async fn acquire(&self, priority: Priority) {
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push(Waiter {
+                id,
+                priority,
+                since: Instant::now(),
+            });
+            id
+        };
+        let _registration = Registration { limiter: self, id };
+
+        loop {
+            let changed = self.changed.notified();
+            futures_util::pin_mut!(changed);
+            changed.as_mut().enable();
+
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                state.refill(self.per_second, self.burst);
+
+                match state.tokens >= 1.0 {
+                    true if state.next() == Some(id) => {
+                        state.tokens -= 1.0;
+                        return;
+                    }
+                    // someone else is admitted first, wait for them to take their token.
+                    true => None,
+                    false => Some((1.0 - state.tokens) / self.per_second),
+                }
+            };
+
+            match wait {
+                Some(wait) => {
+                    let sleep = tokio::time::sleep(Duration::from_secs_f64(wait));
+                    futures_util::pin_mut!(sleep);
+                    if let Either::Left(_) = future::select(sleep, changed).await {
+                        continue;
+                    }
+                }
+                None => changed.await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Priority, RateLimiter};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_pace() {
+        let limiter = RateLimiter::new(10.0, 3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire(Priority::Normal).await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        for _ in 0..5 {
+            limiter.acquire(Priority::Normal).await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(500));
+        assert!(elapsed < Duration::from_millis(510));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_waiter_keeps_token() {
+        let limiter = Arc::new(RateLimiter::new(1.0, 1));
+        limiter.acquire(Priority::Normal).await;
+
+        // give up half way through the wait, the token must still be there for the next one.
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(500),
+            limiter.acquire(Priority::Normal),
+        )
+        .await;
+        assert!(waiting.is_err());
+
+        let start = Instant::now();
+        limiter.acquire(Priority::Normal).await;
+        assert!(start.elapsed() <= Duration::from_millis(500));
+    }
+
+    /// queue the waiters one after another and collect their admission order.
+    async fn admission_order(
+        limiter: Arc<RateLimiter>,
+        waiters: Vec<(&'static str, Priority, Duration)>,
+    ) -> Vec<&'static str> {
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let mut tasks = Vec::new();
+        for (name, priority, delay) in waiters {
+            tokio::time::sleep(delay).await;
+            let limiter = limiter.clone();
+            let order = order.clone();
+            tasks.push(tokio::spawn(async move {
+                limiter.acquire(priority).await;
+                order.lock().unwrap().push(name);
+            }));
+            tokio::task::yield_now().await;
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let order = order.lock().unwrap().clone();
+        order
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn higher_priority_goes_first() {
+        let limiter = Arc::new(RateLimiter::new(10.0, 1));
+        limiter.acquire(Priority::Normal).await;
+
+        let order = admission_order(
+            limiter,
+            vec![
+                ("low-1", Priority::Low, Duration::ZERO),
+                ("low-2", Priority::Low, Duration::ZERO),
+                ("normal", Priority::Normal, Duration::ZERO),
+                ("high", Priority::High, Duration::ZERO),
+            ],
+        )
+        .await;
+
+        assert_eq!(order, vec!["high", "normal", "low-1", "low-2"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn low_priority_is_not_starved() {
+        let limiter = Arc::new(RateLimiter::new(1.0, 1));
+        limiter.acquire(Priority::Normal).await;
+
+        // high priority requests keep arriving faster than the limiter admits them.
+        let mut waiters = vec![("low", Priority::Low, Duration::ZERO)];
+        waiters.extend((0..12).map(|_| ("high", Priority::High, Duration::from_millis(500))));
+        let order = admission_order(limiter, waiters).await;
+
+        let low = order.iter().position(|name| *name == "low").unwrap();
+        assert!(low < 6, "{:?}", order);
+    }
+}
@@ -0,0 +1,198 @@
+//! routing again after a driver left the planned route, see `Client::reroute`.
+
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::point::collect_points;
+use crate::progress::project;
+use crate::{Point, Route, RouteOptions, Routes, Type};
+
+/// the new routes of a `Client::reroute` with the waypoints it left out.
+#[derive(Debug, Clone)]
+pub struct Reroute {
+    /// from the current position through the remaining waypoints to the destination.
+    pub routes: Routes,
+    /// waypoints that were already passed on the old route, in their original order.
+    pub dropped: Vec<Point>,
+}
+
+/// meters along `line` to the point of it nearest to `point`, `None` for an empty line.
+pub(crate) fn progress_along(line: &[Point], point: Point) -> Option<f64> {
+    project(line, point).map(|projection| projection.along)
+}
+
+/// how many of the leading `waypoints` lie at or before `current` along `line`.
+fn passed(line: &[Point], current: Point, waypoints: &[Point]) -> usize {
+    let current = match progress_along(line, current) {
+        Some(progress) => progress,
+        None => return 0,
+    };
+
+    waypoints
+        .iter()
+        .take_while(|waypoint| progress_along(line, **waypoint).is_some_and(|p| p <= current))
+        .count()
+}
+
+impl Client {
+    /// route again from `current` to `destination` with the options of the original request,
+    /// e.g. after the driver left the planned route. `waypoints` are the stops that were still
+    /// ahead on it.
+    ///
+    /// with the `old_route`, the waypoints that lie before the point of its geometry nearest
+    /// to `current` are taken as passed and dropped. without it, or when it has no geometry,
+    /// every waypoint is kept. the remaining ones replace the waypoints of `options` in a
+    /// single `route_with` call, whose bearing is then the heading at `current`.
+    pub async fn reroute(
+        &self,
+        vehicle: Type,
+        current: impl Into<Point>,
+        destination: impl Into<Point>,
+        options: &RouteOptions,
+        waypoints: impl IntoIterator<Item = impl Into<Point>>,
+        old_route: Option<&Route>,
+    ) -> Result<Reroute, NeshanError> {
+        let (current, destination) = (current.into(), destination.into());
+        let waypoints = collect_points(waypoints);
+        let line = old_route
+            .and_then(|route| route.geometry().ok())
+            .unwrap_or_default();
+        let (dropped, remaining) = waypoints.split_at(passed(&line, current, &waypoints));
+
+        let options = options.clone().waypoints(remaining);
+        let routes = self
+            .route_with(vehicle, current, destination, &options)
+            .await?;
+
+        Ok(Reroute {
+            routes,
+            dropped: dropped.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{passed, progress_along};
+    use crate::client::Client;
+    use crate::polyline::{self, Precision};
+    use crate::{EncodedPolyline, Point, Route, RouteOptions, Type};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// a straight road heading east along 35.7, then north.
+    fn line() -> Vec<Point> {
+        vec![
+            Point::new_unchecked(35.70, 51.30),
+            Point::new_unchecked(35.70, 51.40),
+            Point::new_unchecked(35.80, 51.40),
+        ]
+    }
+
+    fn waypoints() -> Vec<Point> {
+        vec![
+            Point::new_unchecked(35.70, 51.32),
+            Point::new_unchecked(35.701, 51.35),
+            Point::new_unchecked(35.75, 51.40),
+        ]
+    }
+
+    #[test]
+    fn progress_of_nearest_point() {
+        let line = line();
+        let east = line[0].haversine_distance_to(&line[1]);
+
+        assert_eq!(progress_along(&[], line[0]), None);
+        assert_eq!(progress_along(&line, line[0]), Some(0.0));
+        // off the road to the south, level with its middle.
+        let middle = progress_along(&line, Point::new_unchecked(35.69, 51.35)).unwrap();
+        assert!((middle - east / 2.0).abs() < 5.0, "{}", middle);
+        let north = progress_along(&line, Point::new_unchecked(35.75, 51.41)).unwrap();
+        let expected = east + line[1].haversine_distance_to(&Point::new_unchecked(35.75, 51.40));
+        assert!((north - expected).abs() < 5.0, "{}", north);
+        // beyond the end of the road.
+        let beyond = progress_along(&line, Point::new_unchecked(35.90, 51.40)).unwrap();
+        assert!((beyond - (east + line[1].haversine_distance_to(&line[2]))).abs() < 1.0);
+    }
+
+    #[test]
+    fn skip_passed_waypoints() {
+        let line = line();
+        let waypoints = waypoints();
+
+        assert_eq!(
+            passed(&line, Point::new_unchecked(35.70, 51.31), &waypoints),
+            0
+        );
+        assert_eq!(
+            passed(&line, Point::new_unchecked(35.699, 51.33), &waypoints),
+            1
+        );
+        assert_eq!(
+            passed(&line, Point::new_unchecked(35.72, 51.405), &waypoints),
+            2
+        );
+        assert_eq!(
+            passed(&line, Point::new_unchecked(35.79, 51.40), &waypoints),
+            3
+        );
+        assert_eq!(
+            passed(&[], Point::new_unchecked(35.79, 51.40), &waypoints),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn reroute_from_current_position() {
+        let leg = |summary: &str| {
+            serde_json::json!({
+                "summary": summary,
+                "distance": {"value": 1000.0, "text": ""},
+                "duration": {"value": 60.0, "text": ""}
+            })
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("origin", "35.699000,51.360000"))
+            .and(query_param("destination", "35.800000,51.400000"))
+            .and(query_param("waypoints", "35.750000,51.400000"))
+            .and(query_param("avoid_traffic_zone", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": [{ "legs": [leg("to the waypoint"), leg("to the destination")] }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let old_route = Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: polyline::encode(&line(), Precision::Five),
+            }),
+        };
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        // the waypoints of the original request are replaced by the remaining ones.
+        let options = RouteOptions::new()
+            .avoid_traffic_zone(true)
+            .waypoints(&waypoints());
+        let reroute = client
+            .reroute(
+                Type::Car,
+                Point::new_unchecked(35.699, 51.36),
+                Point::new_unchecked(35.80, 51.40),
+                &options,
+                &waypoints(),
+                Some(&old_route),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(reroute.dropped, waypoints()[..2].to_vec());
+        let legs = &reroute.routes.routes[0].legs;
+        assert_eq!(legs.len(), 2);
+        assert_eq!(legs[1].summary, "to the destination");
+    }
+}
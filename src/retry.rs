@@ -0,0 +1,174 @@
+use crate::error::ErrorKind;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// retry policy for transient failures, configured with `ClientBuilder::retry`.
+///
+/// the backoff doubles on each retry, starting from `initial_backoff` and capped by
+/// `max_backoff`. when neshan sends a `Retry-After` header its delay is used instead, a delay
+/// longer than `max_backoff` is not waited for and the error is returned.
+/// auth and validation errors are never retried, whatever `retry_on` says.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    retry_on: Vec<ErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            retry_on: vec![
+                ErrorKind::Timeout,
+                ErrorKind::Connect,
+                ErrorKind::Server,
+                ErrorKind::RateLimited,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// create the default policy: 3 attempts, 200ms initial and 5s maximum backoff with jitter,
+    /// retrying timeouts, connect errors, 5xx and rate limiting.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// total number of attempts including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// delay before the first retry.
+    pub fn initial_backoff(mut self, backoff: Duration) -> RetryPolicy {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// upper bound for the exponential backoff.
+    pub fn max_backoff(mut self, backoff: Duration) -> RetryPolicy {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// randomize each delay between half and the whole of the computed backoff.
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// error kinds that trigger a retry.
+    pub fn retry_on(mut self, kinds: &[ErrorKind]) -> RetryPolicy {
+        self.retry_on = kinds.to_vec();
+        self
+    }
+
+    pub(crate) fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub(crate) fn should_retry(&self, kind: ErrorKind) -> bool {
+        match kind {
+            ErrorKind::Auth | ErrorKind::InvalidRequest => false,
+            kind => self.retry_on.contains(&kind),
+        }
+    }
+
+    /// delay before the given retry, the `Retry-After` of the error when there is one. `None`
+    /// when that is longer than `max_backoff`.
+    pub(crate) fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(delay) if delay > self.max_backoff => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(retry)),
+        }
+    }
+
+    /// delay before the given retry, counting from 1 for the first retry.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        if self.jitter {
+            backoff.mul_f64(0.5 + random() / 2.0)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// random number in [0, 1), good enough for spreading retries apart.
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::error::ErrorKind;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_growth() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500))
+            .jitter(false);
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn server_delay() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_secs(5))
+            .jitter(false);
+
+        assert_eq!(policy.delay(2, None), Some(Duration::from_millis(200)));
+        assert_eq!(
+            policy.delay(2, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(policy.delay(2, Some(Duration::from_secs(86400))), None);
+    }
+
+    #[test]
+    fn jitter_bounds() {
+        let policy = RetryPolicy::new().initial_backoff(Duration::from_millis(100));
+
+        for _ in 0..100 {
+            let backoff = policy.backoff(1);
+            assert!(backoff >= Duration::from_millis(50));
+            assert!(backoff <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn never_retry_auth_or_validation() {
+        let policy = RetryPolicy::new().retry_on(&[ErrorKind::Auth, ErrorKind::InvalidRequest]);
+
+        assert!(!policy.should_retry(ErrorKind::Auth));
+        assert!(!policy.should_retry(ErrorKind::InvalidRequest));
+        assert!(RetryPolicy::new().should_retry(ErrorKind::Server));
+    }
+}
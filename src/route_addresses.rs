@@ -0,0 +1,253 @@
+//! postal addresses sampled along a route, see `Client::addresses_along`.
+
+use crate::batch::BatchOptions;
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::{Point, PostalAddress, Route};
+
+/// the addresses a route passes through, in the order it passes them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteAddresses {
+    /// meters from the start of the route to each sample, with its address.
+    pub addresses: Vec<(f64, PostalAddress)>,
+    /// samples whose reverse geocoding failed, they are left out of `addresses`.
+    pub failed: usize,
+}
+
+impl RouteAddresses {
+    /// most samples of a single `Client::addresses_along`, each of them is a request.
+    pub const MAX_SAMPLES: usize = 1000;
+}
+
+/// points every `interval` meters along the line starting at its first point, with their
+/// distance from it. the last point of the line is always included. fails when the interval
+/// isn't positive or would give more than `RouteAddresses::MAX_SAMPLES` samples.
+pub(crate) fn resample(points: &[Point], interval: f64) -> Result<Vec<(f64, Point)>, NeshanError> {
+    if !(interval.is_finite() && interval > 0.0) {
+        return Err(NeshanError::InvalidRequest(format!(
+            "the sampling interval must be positive, got {}",
+            interval
+        )));
+    }
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Ok(Vec::new()),
+    };
+
+    // the start, one sample per interval and the end.
+    let length: f64 = points
+        .windows(2)
+        .map(|pair| pair[0].haversine_distance_to(&pair[1]))
+        .sum();
+    if length / interval + 2.0 > RouteAddresses::MAX_SAMPLES as f64 {
+        return Err(NeshanError::InvalidRequest(format!(
+            "a sample every {} m along {:.0} m is more than {} samples",
+            interval,
+            length,
+            RouteAddresses::MAX_SAMPLES
+        )));
+    }
+
+    let mut samples = vec![(0.0, first)];
+    let mut travelled = 0.0;
+    let mut next = interval;
+    for pair in points.windows(2) {
+        let length = pair[0].haversine_distance_to(&pair[1]);
+        let bearing = pair[0].bearing_to(&pair[1]);
+        while next <= travelled + length {
+            samples.push((next, pair[0].destination(bearing, next - travelled)));
+            next += interval;
+        }
+        travelled += length;
+    }
+
+    if samples[samples.len() - 1].0 < travelled {
+        samples.push((travelled, last));
+    }
+
+    Ok(samples)
+}
+
+impl Client {
+    /// addresses every `interval` meters along the geometry of the route, reverse geocoding
+    /// at most `concurrency` samples at the same time. a sample in the same neighbourhood and
+    /// on the same road as the one before it is left out, so each stretch of the route shows
+    /// up once.
+    ///
+    /// samples that fail are skipped and counted in `RouteAddresses::failed`. an interval that
+    /// isn't positive, or too short for `RouteAddresses::MAX_SAMPLES`, fails the call.
+    pub async fn addresses_along(
+        &self,
+        route: &Route,
+        interval: f64,
+        concurrency: usize,
+    ) -> Result<RouteAddresses, NeshanError> {
+        let points = route.geometry().map_err(|err| {
+            NeshanError::InvalidRequest(format!("the route geometry is malformed: {}", err))
+        })?;
+
+        let samples = resample(&points, interval)?;
+        let sampled: Vec<Point> = samples.iter().map(|(_, point)| *point).collect();
+        let results = self
+            .reverse_geocode_many(&sampled, &BatchOptions::new(concurrency))
+            .await;
+
+        let mut addresses: Vec<(f64, PostalAddress)> = Vec::new();
+        let mut failed = 0;
+        for ((distance, _), result) in samples.into_iter().zip(results) {
+            let address = match result {
+                Ok(address) => address,
+                Err(_) => {
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            let repeated = addresses.last().is_some_and(|(_, last)| {
+                last.neighbourhood == address.neighbourhood && last.route_name == address.route_name
+            });
+            if !repeated {
+                addresses.push((distance, address));
+            }
+        }
+
+        Ok(RouteAddresses { addresses, failed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resample;
+    use crate::client::Client;
+    use crate::error::NeshanError;
+    use crate::polyline::{self, Precision};
+    use crate::{EncodedPolyline, Point, Route};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// about 4.4 km north along a meridian, in two segments.
+    fn line() -> Vec<Point> {
+        vec![
+            Point::new_unchecked(35.70, 51.40),
+            Point::new_unchecked(35.72, 51.40),
+            Point::new_unchecked(35.74, 51.40),
+        ]
+    }
+
+    #[test]
+    fn resample_positions() {
+        let samples = resample(&line(), 1000.0).unwrap();
+        let distances: Vec<f64> = samples.iter().map(|(distance, _)| *distance).collect();
+        assert_eq!(&distances[..5], &[0.0, 1000.0, 2000.0, 3000.0, 4000.0]);
+        assert_eq!(distances.len(), 6);
+        assert!((distances[5] - line()[0].haversine_distance_to(&line()[2])).abs() < 1e-6);
+
+        // each sample is as far from the start as it says.
+        for (distance, point) in &samples {
+            assert!((line()[0].haversine_distance_to(point) - distance).abs() < 1e-3);
+        }
+        assert_eq!(samples[5].1, line()[2]);
+
+        assert!(resample(&[], 1000.0).unwrap().is_empty());
+        let single = resample(&line()[..1], 1000.0).unwrap();
+        assert_eq!(single, vec![(0.0, line()[0])]);
+    }
+
+    #[test]
+    fn resample_limits() {
+        for interval in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                resample(&line(), interval),
+                Err(NeshanError::InvalidRequest(_))
+            ));
+        }
+
+        // about 4.4 km, so 4.4 m is just over the limit and 5 m under it.
+        assert!(matches!(
+            resample(&line(), 4.4),
+            Err(NeshanError::InvalidRequest(ref message)) if message.contains("1000 samples")
+        ));
+        assert!(resample(&line(), 1e-300).is_err());
+        assert!(resample(&line(), 5.0).unwrap().len() <= super::RouteAddresses::MAX_SAMPLES);
+    }
+
+    /// answers with neighbourhood `a` below latitude 35.715, `b` below 35.73 and `c`
+    /// beyond, failing between 35.72 and 35.73.
+    fn respond(request: &wiremock::Request) -> ResponseTemplate {
+        let latitude: f64 = request
+            .url
+            .query_pairs()
+            .find(|(key, _)| key == "lat")
+            .map(|(_, value)| value.parse().unwrap())
+            .unwrap();
+        if latitude > 35.72 && latitude < 35.73 {
+            return ResponseTemplate::new(470);
+        }
+
+        let neighbourhood = match latitude {
+            latitude if latitude < 35.715 => "a",
+            latitude if latitude < 35.73 => "b",
+            _ => "c",
+        };
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": "OK",
+            "formatted_address": format!("{} street", neighbourhood),
+            "route_name": "vali asr",
+            "neighbourhood": neighbourhood,
+            "city": "tehran",
+            "state": "tehran",
+            "in_traffic_zone": false,
+            "in_odd_even_zone": false
+        }))
+    }
+
+    #[tokio::test]
+    async fn addresses_along() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/reverse"))
+            .respond_with(respond)
+            .expect(6)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: polyline::encode(&line(), Precision::Five),
+            }),
+        };
+        let along = client.addresses_along(&route, 1000.0, 3).await.unwrap();
+
+        let stretches: Vec<(f64, Option<&str>)> = along
+            .addresses
+            .iter()
+            .map(|(distance, address)| (*distance, address.neighbourhood.as_deref()))
+            .collect();
+        assert_eq!(
+            stretches,
+            vec![(0.0, Some("a")), (2000.0, Some("b")), (4000.0, Some("c"))]
+        );
+        assert_eq!(along.failed, 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_requests() {
+        let client = Client::new("key");
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: "_p~iF~ps|U_".to_string(),
+            }),
+        };
+        let err = client.addresses_along(&route, 1000.0, 1).await.unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)));
+
+        let err = client.addresses_along(&route, 0.0, 1).await.unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)));
+    }
+}
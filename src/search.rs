@@ -0,0 +1,408 @@
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::Point;
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// places matching a term of the search api, ordered by neshan's relevance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub count: usize,
+    #[serde(default)]
+    pub items: Vec<SearchItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchItem {
+    pub title: String,
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub neighbourhood: Option<String>,
+    #[serde(default)]
+    pub region: String,
+    /// kind of the place, e.g. `cafe`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub category: String,
+    /// sent by neshan as `{ "x": lng, "y": lat }`.
+    #[serde(with = "xy")]
+    pub location: Point,
+}
+
+/// points in the `x` and `y` form of the search and geocoding apis.
+pub(crate) mod xy {
+    use crate::Point;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    pub(crate) struct Xy {
+        x: f64,
+        y: f64,
+    }
+
+    impl From<Xy> for Point {
+        fn from(Xy { x, y }: Xy) -> Point {
+            Point::new_unchecked(y, x)
+        }
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        point: &Point,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Xy {
+            x: point.longitude,
+            y: point.latitude,
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Point, D::Error> {
+        Xy::deserialize(deserializer).map(Point::from)
+    }
+}
+
+impl SearchResults {
+    /// most pages of a single `Client::search_all`.
+    pub const MAX_PAGES: u32 = 20;
+
+    /// the places of a `/v1/search` body, e.g. results cached by the app.
+    pub fn from_json(json: &str) -> Result<SearchResults, NeshanError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// the `n` items closest to `point`, closest first.
+    pub fn nearest(&self, point: Point, n: usize) -> Vec<&SearchItem> {
+        let mut items: Vec<(f64, &SearchItem)> = self
+            .items
+            .iter()
+            .map(|item| (point.haversine_distance_to(&item.location), item))
+            .collect();
+        items.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        items.into_iter().take(n).map(|(_, item)| item).collect()
+    }
+}
+
+impl Client {
+    /// places matching `term` around `near`, e.g. `کافه`.
+    /// https://platform.neshan.org/api/search
+    pub async fn search(
+        &self,
+        term: &str,
+        near: impl Into<Point>,
+    ) -> Result<SearchResults, NeshanError> {
+        let near = near.into();
+        self.check(&[near])?;
+
+        self.search_page(term, near, 1).await
+    }
+
+    /// every place matching `term` around `near`, fetching the pages of the search api one
+    /// after the other as the stream is polled. the first page is the call of `search`, the
+    /// pages after it are asked for with a `page` parameter until one comes back empty,
+    /// shorter than the first one or the same as the one before it, which is what neshan
+    /// answers when it ignores the parameter, and at most `SearchResults::MAX_PAGES` of them.
+    /// a failed page is the last item of the stream.
+    pub fn search_all(
+        &self,
+        term: &str,
+        near: impl Into<Point>,
+    ) -> impl Stream<Item = Result<SearchItem, NeshanError>> + Send + 'static {
+        let pages = Pages {
+            client: self.clone(),
+            term: term.to_string(),
+            near: near.into(),
+            next: Some(1),
+            page_size: 0,
+            last: Vec::new(),
+            items: VecDeque::new(),
+        };
+
+        stream::unfold(pages, |mut pages| async move {
+            loop {
+                if let Some(item) = pages.items.pop_front() {
+                    return Some((Ok(item), pages));
+                }
+
+                let page = pages.next.take()?;
+                match pages.fetch(page).await {
+                    Ok(items) if items == pages.last => {}
+                    Ok(items) => {
+                        if page == 1 {
+                            pages.page_size = items.len();
+                        }
+                        if !items.is_empty()
+                            && items.len() >= pages.page_size
+                            && page < SearchResults::MAX_PAGES
+                        {
+                            pages.next = Some(page + 1);
+                        }
+                        pages.last = items.clone();
+                        pages.items = items.into();
+                    }
+                    Err(err) => return Some((Err(err), pages)),
+                }
+            }
+        })
+    }
+
+    async fn search_page(
+        &self,
+        term: &str,
+        near: Point,
+        page: u32,
+    ) -> Result<SearchResults, NeshanError> {
+        let query = match page {
+            1 => crate::protocol::search_query(term, near),
+            _ => crate::protocol::search_page_query(term, near, page),
+        };
+        let call = self.get(Endpoint::Search, &query);
+
+        crate::trace::instrument(Endpoint::Search, &[near], call)
+            .await
+            .map(|(results, _)| results)
+    }
+}
+
+/// state of `Client::search_all` between polls.
+struct Pages {
+    client: Client,
+    term: String,
+    near: Point,
+    /// `None` once the last page came back or a page failed.
+    next: Option<u32>,
+    /// items of the first page, a shorter page is the last one.
+    page_size: usize,
+    /// items of the page before, a page repeating them is the end.
+    last: Vec<SearchItem>,
+    items: VecDeque<SearchItem>,
+}
+
+impl Pages {
+    async fn fetch(&self, page: u32) -> Result<Vec<SearchItem>, NeshanError> {
+        if page == 1 {
+            self.client.check(&[self.near])?;
+        }
+
+        let results = self.client.search_page(&self.term, self.near, page).await?;
+        Ok(results.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchResults;
+    use crate::client::Client;
+    use crate::error::ErrorKind;
+    use crate::Point;
+    use futures_util::StreamExt;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// answer the given page with items titled after their page and position.
+    async fn page(server: &MockServer, page: Option<u32>, response: ResponseTemplate) {
+        let mock = Mock::given(method("GET")).and(path("/v1/search"));
+        let mock = match page {
+            Some(page) => mock.and(query_param("page", page.to_string())),
+            None => mock.and(query_param_is_missing("page")),
+        };
+        mock.respond_with(response).expect(1).mount(server).await;
+    }
+
+    fn items(page: u32, n: usize) -> ResponseTemplate {
+        let items: Vec<_> = (0..n)
+            .map(|i| {
+                serde_json::json!({
+                    "title": format!("{}-{}", page, i),
+                    "location": { "x": 51.338, "y": 35.6997 },
+                })
+            })
+            .collect();
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "count": n,
+            "items": items,
+        }))
+    }
+
+    async fn titles(client: &Client) -> Vec<Result<String, ErrorKind>> {
+        client
+            .search_all("رستوران", (35.6997, 51.338))
+            .map(|item| item.map(|item| item.title).map_err(|err| err.kind()))
+            .collect()
+            .await
+    }
+
+    #[test]
+    fn nearest_items() {
+        let results = SearchResults::from_json(include_str!("../fixtures/search.json")).unwrap();
+        let azadi = Point::new_unchecked(35.6997, 51.338);
+
+        let titles: Vec<&str> = results
+            .nearest(azadi, 2)
+            .into_iter()
+            .map(|item| item.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["کافه رستوران آزادی", "کافه لمیز"]);
+        assert_eq!(
+            results.items[0].location,
+            Point::new_unchecked(35.7008, 51.3912)
+        );
+        assert_eq!(results.items[2].neighbourhood, None);
+        assert!(results.nearest(azadi, 0).is_empty());
+    }
+
+    #[test]
+    fn optional_fields_and_persian_text() {
+        let results =
+            SearchResults::from_json(include_str!("../fixtures/search_minimal.json")).unwrap();
+        let park = &results.items[0];
+        assert_eq!(park.title, "پارک ملت");
+        assert_eq!(park.neighbourhood, None);
+        assert!(park.region.is_empty() && park.address.is_empty());
+
+        // persian is written as it is, not as escapes, and reads back the same.
+        let json = serde_json::to_string(&results).unwrap();
+        assert!(json.contains("\"title\":\"پارک ملت\""));
+        assert_eq!(SearchResults::from_json(&json).unwrap(), results);
+    }
+
+    #[tokio::test]
+    async fn search_query() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/search"))
+            .and(query_param("term", "کافه"))
+            .and(query_param("lat", "35.699700"))
+            .and(query_param("lng", "51.338000"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../fixtures/search.json"))
+                    .insert_header("content-type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let results = client.search("کافه", (35.6997, 51.338)).await.unwrap();
+        assert_eq!(results.count, 3);
+        assert_eq!(results.items[1].kind, "cafe");
+    }
+
+    #[tokio::test]
+    async fn search_every_page() {
+        let server = MockServer::start().await;
+        page(&server, None, items(1, 2)).await;
+        page(&server, Some(2), items(2, 2)).await;
+        // shorter than the first page, so there is no page 4 to ask for.
+        page(&server, Some(3), items(3, 1)).await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            titles(&client).await,
+            vec![
+                Ok("1-0".to_string()),
+                Ok("1-1".to_string()),
+                Ok("2-0".to_string()),
+                Ok("2-1".to_string()),
+                Ok("3-0".to_string())
+            ]
+        );
+
+        // pages are fetched as the items are consumed.
+        server.reset().await;
+        page(&server, None, items(1, 2)).await;
+        let mut stream = Box::pin(client.search_all("رستوران", (35.6997, 51.338)));
+        assert_eq!(stream.next().await.unwrap().unwrap().title, "1-0");
+        assert_eq!(stream.next().await.unwrap().unwrap().title, "1-1");
+        drop(stream);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_pages_end() {
+        let server = MockServer::start().await;
+        page(&server, None, items(1, 0)).await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        assert!(titles(&client).await.is_empty());
+
+        // a full last page is followed by an empty one.
+        server.reset().await;
+        page(&server, None, items(1, 2)).await;
+        page(&server, Some(2), items(2, 0)).await;
+        assert_eq!(titles(&client).await.len(), 2);
+
+        // a failed page ends the stream.
+        server.reset().await;
+        page(&server, None, items(1, 1)).await;
+        page(&server, Some(2), ResponseTemplate::new(500)).await;
+        assert_eq!(
+            titles(&client).await,
+            vec![Ok("1-0".to_string()), Err(ErrorKind::Server)]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_pages_repeat() {
+        // neshan ignoring the page parameter answers the first page every time.
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/search"))
+            .respond_with(items(1, 2))
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            titles(&client).await,
+            vec![Ok("1-0".to_string()), Ok("1-1".to_string())]
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // new full pages forever end at the last page.
+        server.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/search"))
+            .and(query_param_is_missing("page"))
+            .respond_with(items(1, 1))
+            .mount(&server)
+            .await;
+        for page in 2..=SearchResults::MAX_PAGES + 1 {
+            Mock::given(method("GET"))
+                .and(path("/v1/search"))
+                .and(query_param("page", page.to_string()))
+                .respond_with(items(page, 1))
+                .mount(&server)
+                .await;
+        }
+        let titles = titles(&client).await;
+        assert_eq!(titles.len(), SearchResults::MAX_PAGES as usize);
+        assert_eq!(
+            titles.last(),
+            Some(&Ok(format!("{}-0", SearchResults::MAX_PAGES)))
+        );
+        assert_eq!(
+            server.received_requests().await.unwrap().len(),
+            SearchResults::MAX_PAGES as usize
+        );
+    }
+}
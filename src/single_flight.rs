@@ -0,0 +1,57 @@
+use crate::error::NeshanError;
+use crate::middleware::Response;
+use futures_util::future::{BoxFuture, FutureExt, Shared, WeakShared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+type Request = BoxFuture<'static, Result<Response, NeshanError>>;
+
+/// coalesces identical in-flight requests into a single http call.
+///
+/// the request future is shared between every caller waiting for it, so it keeps going
+/// as long as one of them is still polling and the caller that started it may be dropped
+/// freely. only weak handles are kept here, when all callers are gone the request is
+/// dropped with them and the next caller starts over.
+#[derive(Default)]
+pub(crate) struct SingleFlight {
+    calls: Mutex<HashMap<String, WeakShared<Request>>>,
+}
+
+impl SingleFlight {
+    /// wait for the in-flight request with the same key, or start one using `request`.
+    pub(crate) async fn run<F>(&self, key: String, request: F) -> Result<Response, NeshanError>
+    where
+        F: Future<Output = Result<Response, NeshanError>> + Send + 'static,
+    {
+        let call = {
+            let mut calls = self.calls.lock().unwrap();
+
+            match calls.get(&key).and_then(WeakShared::upgrade) {
+                Some(call) => call,
+                None => {
+                    calls.retain(|_, call| call.upgrade().is_some());
+
+                    let call: Shared<Request> = request.boxed().shared();
+                    if let Some(weak) = call.downgrade() {
+                        calls.insert(key.clone(), weak);
+                    }
+                    call
+                }
+            }
+        };
+
+        let result = call.clone().await;
+
+        let mut calls = self.calls.lock().unwrap();
+        let finished = calls
+            .get(&key)
+            .and_then(WeakShared::upgrade)
+            .is_none_or(|current| Shared::ptr_eq(&current, &call));
+        if finished {
+            calls.remove(&key);
+        }
+
+        result
+    }
+}
@@ -0,0 +1,178 @@
+//! average speeds along a route, see `Route::speed_profile`.
+
+use crate::Route;
+
+/// stretch of a route with its average speed, one per step or per leg without steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedSegment {
+    /// meters from the start of the route to the start of the stretch.
+    pub start: f64,
+    /// meters from the start of the route to its end.
+    pub end: f64,
+    /// average speed in km/h, `None` when neshan gave the stretch no time to cover it.
+    pub speed: Option<f64>,
+    /// road of the step, or the summary of a leg without steps.
+    pub road: String,
+}
+
+impl SpeedSegment {
+    /// length of the stretch in meters.
+    pub fn length(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// km/h of covering `meters` in `seconds`, `None` unless both are finite and it takes time.
+fn speed(meters: f64, seconds: f64) -> Option<f64> {
+    if seconds > 0.0 && seconds.is_finite() && meters.is_finite() {
+        Some(meters * 3.6 / seconds)
+    } else {
+        None
+    }
+}
+
+impl Route {
+    /// average speed of every step in order, legs without steps count as a single one. slow
+    /// segments point at congestion on the planned route.
+    pub fn speed_profile(&self) -> Vec<SpeedSegment> {
+        let mut segments = Vec::new();
+        let mut start = 0.0;
+        let mut push = |meters: f64, seconds: f64, road: &str| {
+            segments.push(SpeedSegment {
+                start,
+                end: start + meters,
+                speed: speed(meters, seconds),
+                road: road.to_string(),
+            });
+            start += meters;
+        };
+
+        for leg in &self.legs {
+            if leg.steps.is_empty() {
+                push(leg.distance.value, leg.duration.value, &leg.summary);
+            }
+            for step in &leg.steps {
+                push(step.distance.value, step.duration.value, &step.name);
+            }
+        }
+
+        segments
+    }
+
+    /// the `n` segments with the lowest speed, slowest first. segments without a speed are
+    /// left out and ties keep the order along the route.
+    pub fn slowest_segments(&self, n: usize) -> Vec<SpeedSegment> {
+        let mut segments: Vec<SpeedSegment> = self
+            .speed_profile()
+            .into_iter()
+            .filter(|segment| segment.speed.is_some())
+            .collect();
+        segments.sort_by(|a, b| a.speed.partial_cmp(&b.speed).unwrap());
+        segments.truncate(n);
+
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Distance, Duration, Leg, Route, Step};
+
+    fn step(name: &str, meters: f64, seconds: f64) -> Step {
+        Step {
+            name: name.to_string(),
+            instruction: String::new(),
+            distance: Distance {
+                value: meters,
+                text: String::new(),
+            },
+            duration: Duration {
+                value: seconds,
+                text: String::new(),
+            },
+            polyline: None,
+            maneuver: None,
+            modifier: None,
+            exit: None,
+            bearing_after: None,
+            start_location: None,
+        }
+    }
+
+    fn leg(summary: &str, steps: Vec<Step>) -> Leg {
+        let meters = steps.iter().map(|step| step.distance.value).sum();
+        let seconds = steps.iter().map(|step| step.duration.value).sum();
+
+        Leg {
+            summary: summary.to_string(),
+            distance: Distance {
+                value: meters,
+                text: String::new(),
+            },
+            duration: Duration {
+                value: seconds,
+                text: String::new(),
+            },
+            steps,
+        }
+    }
+
+    fn route() -> Route {
+        let mut without_steps = leg("بزرگراه همت", vec![step("", 3000.0, 120.0)]);
+        without_steps.steps.clear();
+
+        Route {
+            legs: vec![
+                leg(
+                    "آزادی",
+                    vec![
+                        step("آزادی", 1000.0, 120.0),
+                        step("میدان آزادی", 0.0, 0.0),
+                        step("جناح", 500.0, 0.0),
+                        step("آزادگان", 2000.0, 360.0),
+                    ],
+                ),
+                without_steps,
+            ],
+            overview_polyline: None,
+        }
+    }
+
+    #[test]
+    fn profile_of_steps_and_legs() {
+        let profile = route().speed_profile();
+
+        let offsets: Vec<(f64, f64)> = profile.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(
+            offsets,
+            vec![
+                (0.0, 1000.0),
+                (1000.0, 1000.0),
+                (1000.0, 1500.0),
+                (1500.0, 3500.0),
+                (3500.0, 6500.0)
+            ]
+        );
+
+        let speeds: Vec<Option<f64>> = profile.iter().map(|s| s.speed).collect();
+        assert_eq!(speeds, vec![Some(30.0), None, None, Some(20.0), Some(90.0)]);
+        assert_eq!(profile[3].road, "آزادگان");
+        assert_eq!(profile[4].road, "بزرگراه همت");
+        assert_eq!(profile[2].length(), 500.0);
+    }
+
+    #[test]
+    fn slowest_first() {
+        let slowest = route().slowest_segments(2);
+        let roads: Vec<&str> = slowest.iter().map(|s| s.road.as_str()).collect();
+        assert_eq!(roads, vec!["آزادگان", "آزادی"]);
+
+        assert_eq!(route().slowest_segments(10).len(), 3);
+        assert!(Route {
+            legs: Vec::new(),
+            overview_polyline: None
+        }
+        .slowest_segments(3)
+        .is_empty());
+    }
+}
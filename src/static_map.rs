@@ -0,0 +1,1202 @@
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::{polyline, BoundingBox, EncodedPolyline, Point, Route};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use tokio::io::AsyncWrite;
+
+/// visual style of the map, sent as the `type` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MapStyle {
+    #[default]
+    Neshan,
+    Dreamy,
+    DreamyGold,
+    StandardDay,
+    StandardNight,
+}
+
+impl MapStyle {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            MapStyle::Neshan => "neshan",
+            MapStyle::Dreamy => "dreamy",
+            MapStyle::DreamyGold => "dreamy-gold",
+            MapStyle::StandardDay => "standard-day",
+            MapStyle::StandardNight => "standard-night",
+        }
+    }
+}
+
+/// a pin on a static map, sent in an unverified notation, see `StaticMapRequest`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    position: Point,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+}
+
+impl Marker {
+    pub fn new(position: Point) -> Marker {
+        Marker {
+            position,
+            label: None,
+            color: None,
+        }
+    }
+
+    /// text on the pin, at most `StaticMapRequest::MAX_LABEL` characters.
+    pub fn label(mut self, label: &str) -> Marker {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// color of the pin, either a name such as `red` or a hex value such as `0xff0000`.
+    pub fn color(mut self, color: &str) -> Marker {
+        self.color = Some(color.to_string());
+        self
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    fn parameter(&self) -> String {
+        let mut parameter = String::new();
+        if let Some(color) = &self.color {
+            parameter.push_str(&format!("color:{}|", color));
+        }
+        if let Some(label) = &self.label {
+            parameter.push_str(&format!("label:{}|", label));
+        }
+        crate::point::push_point(&mut parameter, self.position);
+        parameter
+    }
+}
+
+/// a line drawn over a static map, e.g. a route, sent in an unverified notation, see
+/// `StaticMapRequest`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathOverlay {
+    polyline: EncodedPolyline,
+    color: String,
+    width: u32,
+}
+
+impl PathOverlay {
+    /// line through the points of an encoded polyline with five decimals.
+    pub fn new(polyline: EncodedPolyline) -> PathOverlay {
+        PathOverlay {
+            polyline,
+            color: "0x0000ff".to_string(),
+            width: 4,
+        }
+    }
+
+    /// line through the points.
+    pub fn from_points(points: &[Point]) -> PathOverlay {
+        PathOverlay::new(EncodedPolyline {
+            points: polyline::encode(points, polyline::Precision::Five),
+        })
+    }
+
+    /// stroke color, either a name or a hex value, `0x0000ff` by default.
+    pub fn color(mut self, color: &str) -> PathOverlay {
+        self.color = color.to_string();
+        self
+    }
+
+    /// stroke width in pixels, 4 by default.
+    pub fn width(mut self, width: u32) -> PathOverlay {
+        self.width = width;
+        self
+    }
+
+    fn parameter(&self) -> String {
+        format!(
+            "color:{}|weight:{}|enc:{}",
+            self.color, self.width, self.polyline.points
+        )
+    }
+}
+
+/// parameters of a static map image.
+/// https://platform.neshan.org/api/static-map
+///
+/// markers and the path overlay are sent as `markers` and `path` parameters in the notation of
+/// google's static maps, e.g. `color:red|label:A|35.7,51.4`. neshan doesn't document these
+/// parameters, so the overlays are unverified and the api may ignore them.
+///
+/// neshan doesn't publish limits of the image either. `MAX_SIZE`, `MAX_MARKERS`, `MAX_LABEL`
+/// and `MAX_QUERY_LENGTH` are conservative bounds of this crate, checked before sending, see
+/// `validate`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StaticMapRequest {
+    center: Point,
+    zoom: u8,
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    style: MapStyle,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    markers: Vec<Marker>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<PathOverlay>,
+}
+
+impl StaticMapRequest {
+    /// largest width and height of an image in pixels the crate asks for.
+    pub const MAX_SIZE: u32 = 2048;
+    /// highest zoom level, the last one of the map tiles.
+    pub const MAX_ZOOM: u8 = 20;
+    /// most markers the crate sends on one image.
+    pub const MAX_MARKERS: usize = 50;
+    /// longest marker label in characters the crate sends.
+    pub const MAX_LABEL: usize = 16;
+    /// longest query string in bytes, beyond which urls are commonly cut by proxies.
+    pub const MAX_QUERY_LENGTH: usize = 8192;
+
+    /// map of `width` by `height` pixels around `center` at the given zoom level.
+    pub fn new(center: Point, zoom: u8, width: u32, height: u32) -> StaticMapRequest {
+        StaticMapRequest {
+            center,
+            zoom,
+            width,
+            height,
+            style: MapStyle::default(),
+            markers: Vec::new(),
+            path: None,
+        }
+    }
+
+    /// map of `width` by `height` pixels at the highest zoom level that shows the whole box.
+    pub fn fit(bounds: &BoundingBox, width: u32, height: u32) -> StaticMapRequest {
+        let (south_west, north_east) = (bounds.south_west(), bounds.north_east());
+        let (south, north) = (mercator(south_west.latitude), mercator(north_east.latitude));
+        let center = Point {
+            latitude: inverse_mercator((south + north) / 2.0),
+            longitude: (south_west.longitude + north_east.longitude) / 2.0,
+        };
+
+        // at zoom 0 the world is one 256 pixel tile, each zoom level doubles it.
+        let zoom = |pixels: u32, span: f64| {
+            if span <= 0.0 {
+                f64::INFINITY
+            } else {
+                (f64::from(pixels) / 256.0 / span).log2()
+            }
+        };
+        let zoom = zoom(width, (north_east.longitude - south_west.longitude) / 360.0)
+            .min(zoom(height, (north - south) / (2.0 * PI)))
+            .floor()
+            .clamp(0.0, f64::from(StaticMapRequest::MAX_ZOOM)) as u8;
+
+        StaticMapRequest::new(center, zoom, width, height)
+    }
+
+    pub fn style(mut self, style: MapStyle) -> StaticMapRequest {
+        self.style = style;
+        self
+    }
+
+    pub fn marker(mut self, marker: Marker) -> StaticMapRequest {
+        self.markers.push(marker);
+        self
+    }
+
+    pub fn path(mut self, path: PathOverlay) -> StaticMapRequest {
+        self.path = Some(path);
+        self
+    }
+
+    pub fn center(&self) -> Point {
+        self.center
+    }
+
+    pub fn zoom(&self) -> u8 {
+        self.zoom
+    }
+
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// check the request against the limits of the endpoint, `static_map_to` does so before
+    /// sending.
+    pub fn validate(&self) -> Result<(), NeshanError> {
+        let invalid = |message: String| Err(NeshanError::InvalidRequest(message));
+
+        if !(1..=StaticMapRequest::MAX_SIZE).contains(&self.width)
+            || !(1..=StaticMapRequest::MAX_SIZE).contains(&self.height)
+        {
+            return invalid(format!(
+                "image size {}x{} is out of range, expected 1 to {} pixels",
+                self.width,
+                self.height,
+                StaticMapRequest::MAX_SIZE
+            ));
+        }
+        if self.zoom > StaticMapRequest::MAX_ZOOM {
+            return invalid(format!(
+                "zoom {} is out of range, expected 0 to {}",
+                self.zoom,
+                StaticMapRequest::MAX_ZOOM
+            ));
+        }
+        if self.markers.len() > StaticMapRequest::MAX_MARKERS {
+            return invalid(format!(
+                "{} markers are too many, expected at most {}",
+                self.markers.len(),
+                StaticMapRequest::MAX_MARKERS
+            ));
+        }
+        for marker in &self.markers {
+            let label = marker.label.as_deref().unwrap_or_default();
+            if label.chars().count() > StaticMapRequest::MAX_LABEL || label.contains('|') {
+                return invalid(format!(
+                    "marker label {:?} is longer than {} characters or has a '|'",
+                    label,
+                    StaticMapRequest::MAX_LABEL
+                ));
+            }
+            if let Some(color) = &marker.color {
+                check_color(color)?;
+            }
+        }
+        if let Some(path) = &self.path {
+            check_color(&path.color)?;
+            if path.width == 0 {
+                return invalid("path width must be at least one pixel".to_string());
+            }
+        }
+
+        let length = self.query_length();
+        if length > StaticMapRequest::MAX_QUERY_LENGTH {
+            return invalid(format!(
+                "query of {} bytes is too long, expected at most {}",
+                length,
+                StaticMapRequest::MAX_QUERY_LENGTH
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// points the request sends, for validating and tracing them.
+    pub(crate) fn points(&self) -> Vec<Point> {
+        std::iter::once(self.center)
+            .chain(self.markers.iter().map(Marker::position))
+            .collect()
+    }
+
+    /// parameters in the order they are sent, e.g. for checking a request without sending it.
+    pub fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![
+            ("type", self.style.as_str().to_string()),
+            ("zoom", self.zoom.to_string()),
+            ("center", self.center.to_string()),
+            ("width", self.width.to_string()),
+            ("height", self.height.to_string()),
+        ];
+        query.extend(
+            self.markers
+                .iter()
+                .map(|marker| ("markers", marker.parameter())),
+        );
+        if let Some(path) = &self.path {
+            query.push(("path", path.parameter()));
+        }
+
+        query
+    }
+
+    fn query_length(&self) -> usize {
+        self.query()
+            .iter()
+            .map(|(name, value)| name.len() + value.len() + 2)
+            .sum()
+    }
+}
+
+/// a static map with markers and a path, framing them when no center or zoom is given.
+///
+/// ```
+/// use neshan_rs::{Marker, Point, StaticMapBuilder};
+///
+/// let request = StaticMapBuilder::new(600, 400)
+///     .marker(Marker::new(Point::new_unchecked(35.6997, 51.338)).label("A"))
+///     .marker(Marker::new(Point::new_unchecked(35.7575, 51.41)).label("B"))
+///     .build()
+///     .unwrap();
+/// assert_eq!(request.zoom(), 12);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticMapBuilder {
+    width: u32,
+    height: u32,
+    center: Option<Point>,
+    zoom: Option<u8>,
+    style: MapStyle,
+    markers: Vec<Marker>,
+    path: Option<PathOverlay>,
+}
+
+impl StaticMapBuilder {
+    /// highest zoom level the builder picks, about the level of a few streets, so that a
+    /// single marker isn't shown at `StaticMapRequest::MAX_ZOOM`.
+    pub const MAX_FIT_ZOOM: u8 = 16;
+    /// the farthest in pixels a simplified path strays from the original one.
+    pub const MAX_SIMPLIFY_PIXELS: u32 = 64;
+
+    /// image of `width` by `height` pixels.
+    pub fn new(width: u32, height: u32) -> StaticMapBuilder {
+        StaticMapBuilder {
+            width,
+            height,
+            center: None,
+            zoom: None,
+            style: MapStyle::default(),
+            markers: Vec::new(),
+            path: None,
+        }
+    }
+
+    /// center of the map, the middle of the overlays by default.
+    pub fn center(mut self, center: Point) -> StaticMapBuilder {
+        self.center = Some(center);
+        self
+    }
+
+    /// zoom level of the map, the highest one showing every overlay by default.
+    pub fn zoom(mut self, zoom: u8) -> StaticMapBuilder {
+        self.zoom = Some(zoom);
+        self
+    }
+
+    pub fn style(mut self, style: MapStyle) -> StaticMapBuilder {
+        self.style = style;
+        self
+    }
+
+    pub fn marker(mut self, marker: Marker) -> StaticMapBuilder {
+        self.markers.push(marker);
+        self
+    }
+
+    pub fn path(mut self, path: PathOverlay) -> StaticMapBuilder {
+        self.path = Some(path);
+        self
+    }
+
+    /// line through the points with the default color and width.
+    pub fn path_points(self, points: &[Point]) -> StaticMapBuilder {
+        self.path(PathOverlay::from_points(points))
+    }
+
+    /// the request for `Client::static_map_to`, checked with `StaticMapRequest::validate`.
+    ///
+    /// a path that makes the query longer than `StaticMapRequest::MAX_QUERY_LENGTH` is
+    /// simplified, keeping it within `MAX_SIMPLIFY_PIXELS` of the original line. it fails
+    /// when the path is still too long, or when there is nothing to center the map on.
+    pub fn build(&self) -> Result<StaticMapRequest, NeshanError> {
+        let line = match &self.path {
+            Some(path) => polyline::decode(&path.polyline.points, polyline::Precision::Five)
+                .map_err(|err| {
+                    NeshanError::InvalidRequest(format!("the path polyline is malformed: {}", err))
+                })?,
+            None => Vec::new(),
+        };
+
+        let (center, zoom) = match (self.center, self.zoom) {
+            (Some(center), Some(zoom)) => (center, zoom),
+            (center, zoom) => {
+                let fitted = self.fit(&line, center)?;
+                (
+                    center.unwrap_or_else(|| fitted.center()),
+                    zoom.unwrap_or_else(|| fitted.zoom().min(StaticMapBuilder::MAX_FIT_ZOOM)),
+                )
+            }
+        };
+
+        let mut request = StaticMapRequest::new(center, zoom, self.width, self.height);
+        request.style = self.style;
+        request.markers = self.markers.clone();
+        request.path = self.path.clone();
+
+        // each round lets the line stray twice as far, one pixel is as wide at the zoom level.
+        let pixel = 360.0 / 256.0 / 2f64.powi(i32::from(zoom));
+        let mut pixels = 1;
+        while request.query_length() > StaticMapRequest::MAX_QUERY_LENGTH {
+            let path = match &mut request.path {
+                Some(path) if pixels <= StaticMapBuilder::MAX_SIMPLIFY_PIXELS => path,
+                Some(_) => {
+                    return Err(NeshanError::InvalidRequest(format!(
+                        "the path of {} points is too long to draw, even simplified by {} pixels",
+                        line.len(),
+                        StaticMapBuilder::MAX_SIMPLIFY_PIXELS
+                    )))
+                }
+                None => break,
+            };
+            let simplified = simplify(&line, f64::from(pixels) * pixel);
+            path.polyline = EncodedPolyline {
+                points: polyline::encode(&simplified, polyline::Precision::Five),
+            };
+            pixels *= 2;
+        }
+
+        request.validate()?;
+        Ok(request)
+    }
+
+    /// map that shows the markers and the path, around `center` when there is one.
+    fn fit(&self, line: &[Point], center: Option<Point>) -> Result<StaticMapRequest, NeshanError> {
+        let mut points: Vec<Point> = self
+            .markers
+            .iter()
+            .map(Marker::position)
+            .chain(line.iter().copied())
+            .collect();
+        if let Some(center) = center {
+            // mirror the overlays around the center, so the box is centered on it.
+            let mirrored: Vec<Point> = points
+                .iter()
+                .map(|point| Point {
+                    latitude: (2.0 * center.latitude - point.latitude).clamp(-90.0, 90.0),
+                    longitude: 2.0 * center.longitude - point.longitude,
+                })
+                .collect();
+            points.extend(mirrored);
+            points.push(center);
+        }
+
+        let bounds = BoundingBox::from_points(&points).ok_or_else(|| {
+            NeshanError::InvalidRequest(
+                "a static map without a center needs a marker or a path to fit".to_string(),
+            )
+        })?;
+
+        // leave a margin so markers at the edges stay in the image.
+        Ok(StaticMapRequest::fit(
+            &bounds,
+            margin(self.width),
+            margin(self.height),
+        ))
+    }
+}
+
+/// nine tenths of `pixels`, rounded down without overflowing before the size is validated.
+fn margin(pixels: u32) -> u32 {
+    pixels / 10 * 9 + pixels % 10 * 9 / 10
+}
+
+/// the points of `line` that keep it within `tolerance` degrees of the original, with the
+/// algorithm of douglas and peucker.
+fn simplify(line: &[Point], tolerance: f64) -> Vec<Point> {
+    if line.len() < 3 {
+        return line.to_vec();
+    }
+
+    let mut keep = vec![false; line.len()];
+    keep[0] = true;
+    keep[line.len() - 1] = true;
+    let mut spans = vec![(0, line.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, offset(line[i], line[first], line[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                spans.push((first, i));
+                spans.push((i, last));
+            }
+        }
+    }
+
+    line.iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+/// distance in degrees of `point` from the segment between `start` and `end`.
+fn offset(point: Point, start: Point, end: Point) -> f64 {
+    let (x, y) = (
+        point.longitude - start.longitude,
+        point.latitude - start.latitude,
+    );
+    let (dx, dy) = (
+        end.longitude - start.longitude,
+        end.latitude - start.latitude,
+    );
+    let length = dx * dx + dy * dy;
+    let t = match length > 0.0 {
+        true => ((x * dx + y * dy) / length).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+
+    (x - t * dx).hypot(y - t * dy)
+}
+
+/// a color name of ascii letters or a hex value such as `0xff0000`.
+fn check_color(color: &str) -> Result<(), NeshanError> {
+    let valid = match color.strip_prefix("0x") {
+        Some(hex) => {
+            (hex.len() == 6 || hex.len() == 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(NeshanError::InvalidRequest(format!(
+            "color {:?} is neither a name nor a hex value such as 0xff0000",
+            color
+        )))
+    }
+}
+
+fn mercator(latitude: f64) -> f64 {
+    let latitude = latitude.clamp(-85.051_128_78, 85.051_128_78).to_radians();
+    (PI / 4.0 + latitude / 2.0).tan().ln()
+}
+
+fn inverse_mercator(y: f64) -> f64 {
+    (2.0 * y.exp().atan() - PI / 2.0).to_degrees()
+}
+
+/// a static map image of `Client::static_map_image`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticMapImage {
+    pub bytes: Vec<u8>,
+    /// content type neshan sent, e.g. `image/png`.
+    pub content_type: String,
+}
+
+impl Client {
+    /// download a static map image into `writer` chunk by chunk, returning its size in bytes.
+    ///
+    /// nothing is written unless neshan answers with an image. a connection that breaks half
+    /// way fails with `NeshanError::Interrupted`, which tells how much was written already.
+    pub async fn static_map_to(
+        &self,
+        request: &StaticMapRequest,
+        writer: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<u64, NeshanError> {
+        self.download_static_map(request, writer)
+            .await
+            .map(|(written, _)| written)
+    }
+
+    /// map of `width` by `height` pixels around `center`, e.g. for an email. the image is kept
+    /// in memory with its content type, use `static_map_to` for markers or large images.
+    ///
+    /// a zoom level over `StaticMapRequest::MAX_ZOOM` fails without sending a request, an
+    /// error status fails with the status and the body text neshan sent.
+    pub async fn static_map_image(
+        &self,
+        center: impl Into<Point>,
+        zoom: u8,
+        width: u32,
+        height: u32,
+    ) -> Result<StaticMapImage, NeshanError> {
+        let request = StaticMapRequest::new(center.into(), zoom, width, height);
+        let mut bytes = Vec::new();
+        let (_, content_type) = self.download_static_map(&request, &mut bytes).await?;
+
+        Ok(StaticMapImage {
+            bytes,
+            content_type,
+        })
+    }
+
+    async fn download_static_map(
+        &self,
+        request: &StaticMapRequest,
+        writer: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<(u64, String), NeshanError> {
+        let points = request.points();
+        self.check(&points)?;
+        request.validate()?;
+
+        let query = crate::protocol::Query::from_pairs(&request.query());
+        let call = self.download(Endpoint::StaticMap, &query, "image/", writer);
+
+        crate::trace::instrument(Endpoint::StaticMap, &points, call).await
+    }
+
+    /// image of the route framed by its bounds, with its overview geometry drawn and markers
+    /// at its start and end. fails without sending anything when the route has no geometry.
+    pub async fn static_map_of_route(
+        &self,
+        route: &Route,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, NeshanError> {
+        let overview = route.overview_polyline.as_ref().ok_or_else(|| {
+            NeshanError::InvalidRequest("the route has no geometry to draw".to_string())
+        })?;
+        let points = route.geometry().map_err(|err| {
+            NeshanError::InvalidRequest(format!("the route geometry is malformed: {}", err))
+        })?;
+        let (start, end, bounds) = match (
+            points.first(),
+            points.last(),
+            BoundingBox::from_points(&points),
+        ) {
+            (Some(start), Some(end), Some(bounds)) => (*start, *end, bounds),
+            _ => {
+                return Err(NeshanError::InvalidRequest(
+                    "the route has no geometry to draw".to_string(),
+                ))
+            }
+        };
+
+        // leave a margin so the markers at the edges of the route stay in the image.
+        let fitted = StaticMapRequest::fit(&bounds, margin(width), margin(height));
+        let request = StaticMapRequest::new(fitted.center(), fitted.zoom(), width, height)
+            .path(PathOverlay::new(overview.clone()))
+            .marker(Marker::new(start).color("green"))
+            .marker(Marker::new(end).color("red"));
+
+        let mut image = Vec::new();
+        self.static_map_to(&request, &mut image).await?;
+
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MapStyle, Marker, PathOverlay, StaticMapBuilder, StaticMapImage, StaticMapRequest,
+    };
+    use crate::client::Client;
+    use crate::error::{ErrorKind, NeshanError};
+    use crate::{polyline, BoundingBox, Point};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn request() -> StaticMapRequest {
+        StaticMapRequest::new(
+            Point {
+                latitude: 35.7,
+                longitude: 51.39,
+            },
+            15,
+            500,
+            400,
+        )
+    }
+
+    /// serve one png in http chunks, hanging up before the last one when `complete` is false.
+    async fn chunked_server(chunks: Vec<Vec<u8>>, complete: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 4096];
+            let _ = socket.read(&mut request).await.unwrap();
+
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-type: image/png\r\n\
+                      transfer-encoding: chunked\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            for chunk in &chunks {
+                socket
+                    .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                    .await
+                    .unwrap();
+                socket.write_all(chunk).await.unwrap();
+                socket.write_all(b"\r\n").await.unwrap();
+                socket.flush().await.unwrap();
+            }
+            if complete {
+                socket.write_all(b"0\r\n\r\n").await.unwrap();
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    fn client(base_url: &str) -> Client {
+        Client::builder("key").base_url(base_url).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn stream_chunks_into_writer() {
+        let chunks = vec![vec![0x89, b'P', b'N', b'G'], vec![1; 1000], vec![2; 3000]];
+        let base_url = chunked_server(chunks.clone(), true).await;
+
+        let mut image = Vec::new();
+        let written = client(&base_url)
+            .static_map_to(&request(), &mut image)
+            .await
+            .unwrap();
+
+        assert_eq!(written, 4004);
+        assert_eq!(image, chunks.concat());
+    }
+
+    #[tokio::test]
+    async fn report_bytes_written_before_interruption() {
+        let base_url = chunked_server(vec![vec![1; 100], vec![2; 200]], false).await;
+
+        let mut image = Vec::new();
+        let err = client(&base_url)
+            .static_map_to(&request(), &mut image)
+            .await
+            .unwrap_err();
+
+        match err {
+            NeshanError::Interrupted { written, .. } => assert_eq!(written, 300),
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(image.len(), 300);
+    }
+
+    #[tokio::test]
+    async fn reject_non_image_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/static"))
+            .and(header("api-key", "key"))
+            .and(query_param("center", "35.700000,51.390000"))
+            .and(query_param("zoom", "15"))
+            .and(query_param("width", "500"))
+            .and(query_param("height", "400"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut image = Vec::new();
+        let err = client(&server.uri())
+            .static_map_to(&request(), &mut image)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, NeshanError::UnexpectedContentType { .. }));
+        assert_eq!(err.kind(), ErrorKind::Decode);
+        assert!(image.is_empty());
+    }
+
+    #[tokio::test]
+    async fn surface_error_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/static"))
+            .respond_with(ResponseTemplate::new(403).set_body_string("forbidden"))
+            .mount(&server)
+            .await;
+
+        let mut image = Vec::new();
+        let err = client(&server.uri())
+            .static_map_to(&request(), &mut image)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status(), Some(403));
+        assert_eq!(err.kind(), ErrorKind::Auth);
+        assert!(image.is_empty());
+    }
+
+    #[tokio::test]
+    async fn image_in_memory() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/static"))
+            .and(query_param("center", "35.700000,51.390000"))
+            .and(query_param("zoom", "16"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3])
+                    .insert_header("content-type", "image/jpeg"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v4/static"))
+            .and(query_param("zoom", "10"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .set_body_string("<html>forbidden</html>")
+                    .insert_header("content-type", "text/html"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = client(&server.uri());
+        let image = client
+            .static_map_image((35.7, 51.39), 16, 300, 200)
+            .await
+            .unwrap();
+        assert_eq!(
+            image,
+            StaticMapImage {
+                bytes: vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3],
+                content_type: "image/jpeg".to_string(),
+            }
+        );
+
+        let err = client
+            .static_map_image((35.7, 51.39), 10, 300, 200)
+            .await
+            .unwrap_err();
+        match err {
+            NeshanError::Api(ref err) => {
+                assert_eq!(err.status(), 403);
+                assert_eq!(err.error().message(), "<html>forbidden</html>");
+            }
+            ref other => panic!("{}", other),
+        }
+        assert_eq!(err.kind(), ErrorKind::Auth);
+
+        let err = client
+            .static_map_image((35.7, 51.39), StaticMapRequest::MAX_ZOOM + 1, 300, 200)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)), "{}", err);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn markers_and_path_in_query() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/static"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/png")
+                    .set_body_bytes(vec![0x89, b'P', b'N', b'G']),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let request = request()
+            .style(MapStyle::StandardNight)
+            .marker(
+                Marker::new(Point::new_unchecked(35.7, 51.39))
+                    .label("A")
+                    .color("red"),
+            )
+            .marker(Marker::new(Point::new_unchecked(35.71, 51.4)).color("0x00ff00"))
+            .path(
+                PathOverlay::from_points(&[
+                    Point::new_unchecked(38.5, -120.2),
+                    Point::new_unchecked(40.7, -120.95),
+                    Point::new_unchecked(43.252, -126.453),
+                ])
+                .color("0xff0000")
+                .width(6),
+            );
+        let mut image = Vec::new();
+        client(&server.uri())
+            .static_map_to(&request, &mut image)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let query: Vec<(String, String)> = requests[0].url.query_pairs().into_owned().collect();
+        let expected = [
+            ("type", "standard-night"),
+            ("zoom", "15"),
+            ("center", "35.700000,51.390000"),
+            ("width", "500"),
+            ("height", "400"),
+            ("markers", "color:red|label:A|35.700000,51.390000"),
+            ("markers", "color:0x00ff00|35.710000,51.400000"),
+            (
+                "path",
+                "color:0xff0000|weight:6|enc:_p~iF~ps|U_ulLnnqC_mqNvxq`@",
+            ),
+        ];
+        assert_eq!(
+            query,
+            expected
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(image.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn reject_requests_over_the_limits() {
+        let marker = || Marker::new(Point::new_unchecked(35.7, 51.39));
+        let cases = [
+            (
+                StaticMapRequest::new(Point::new_unchecked(35.7, 51.39), 15, 0, 400),
+                "image size 0x400 is out of range, expected 1 to 2048 pixels",
+            ),
+            (
+                StaticMapRequest::new(Point::new_unchecked(35.7, 51.39), 21, 500, 400),
+                "zoom 21 is out of range, expected 0 to 20",
+            ),
+            (
+                (0..51).fold(request(), |request, _| request.marker(marker())),
+                "51 markers are too many, expected at most 50",
+            ),
+            (
+                request().marker(marker().label("a|b")),
+                "marker label \"a|b\" is longer than 16 characters or has a '|'",
+            ),
+            (
+                request().marker(marker().color("#ff0000")),
+                "color \"#ff0000\" is neither a name nor a hex value such as 0xff0000",
+            ),
+            (
+                request().path(PathOverlay::from_points(&[]).width(0)),
+                "path width must be at least one pixel",
+            ),
+        ];
+
+        for (request, message) in cases {
+            match request.validate() {
+                Err(NeshanError::InvalidRequest(err)) => assert_eq!(err, message),
+                result => panic!("{:?} for {:?}", result, message),
+            }
+        }
+
+        let points: Vec<Point> = (0..2000)
+            .map(|i| Point::new_unchecked(f64::from(i) / 100.0, f64::from(i) / 50.0))
+            .collect();
+        let err = request()
+            .path(PathOverlay::from_points(&points))
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidRequest);
+
+        // nothing is sent for an invalid request.
+        let mut image = Vec::new();
+        let err = client("http://127.0.0.1:9")
+            .static_map_to(&request().marker(marker().color("")), &mut image)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeshanError::InvalidRequest(_)));
+    }
+
+    #[test]
+    fn fit_bounds() {
+        let bounds = BoundingBox::new(
+            Point::new_unchecked(35.56, 51.09),
+            Point::new_unchecked(35.83, 51.61),
+        )
+        .unwrap();
+
+        let request = StaticMapRequest::fit(&bounds, 600, 400);
+        assert_eq!(request.zoom(), 10);
+        assert!((request.center().longitude - 51.35).abs() < 1e-9);
+        // the center is half way in the projection, slightly north of the middle latitude.
+        assert!(request.center().latitude > 35.695 && request.center().latitude < 35.696);
+
+        // a box twice as wide needs a zoom level less.
+        let wide = BoundingBox::new(
+            Point::new_unchecked(35.56, 50.83),
+            Point::new_unchecked(35.83, 51.87),
+        )
+        .unwrap();
+        assert_eq!(StaticMapRequest::fit(&wide, 600, 400).zoom(), 9);
+
+        let point = BoundingBox::from_points(&[Point::new_unchecked(35.7, 51.4)]).unwrap();
+        assert_eq!(
+            StaticMapRequest::fit(&point, 600, 400).zoom(),
+            StaticMapRequest::MAX_ZOOM
+        );
+    }
+
+    #[test]
+    fn builder_frames_overlays() {
+        let (azadi, tajrish) = (
+            Point::new_unchecked(35.6997, 51.338),
+            Point::new_unchecked(35.8045, 51.4337),
+        );
+        let request = StaticMapBuilder::new(600, 400)
+            .style(MapStyle::Dreamy)
+            .marker(Marker::new(azadi).label("A"))
+            .path_points(&[azadi, Point::new_unchecked(35.75, 51.41), tajrish])
+            .build()
+            .unwrap();
+        let bounds = BoundingBox::from_points(&[azadi, tajrish]).unwrap();
+        let fitted = StaticMapRequest::fit(&bounds, 540, 360);
+        assert_eq!(request.center(), fitted.center());
+        assert_eq!(request.zoom(), fitted.zoom());
+        assert!(bounds.contains(&request.center()));
+
+        let query = request.query();
+        assert_eq!(query[0], ("type", "dreamy".to_string()));
+        assert_eq!(
+            &query[3..5],
+            &[("width", "600".to_string()), ("height", "400".to_string())]
+        );
+        assert_eq!(
+            query[5],
+            ("markers", "label:A|35.699700,51.338000".to_string())
+        );
+        assert!(query[6].1.starts_with("color:0x0000ff|weight:4|enc:"));
+
+        // a given center and zoom are kept, a single marker isn't zoomed in all the way.
+        let builder = StaticMapBuilder::new(600, 400).marker(Marker::new(azadi));
+        let request = builder.clone().center(tajrish).zoom(11).build().unwrap();
+        assert_eq!((request.center(), request.zoom()), (tajrish, 11));
+        let request = builder.clone().build().unwrap();
+        assert_eq!((request.center(), request.zoom()), (azadi, 16));
+        // with only a center the zoom still shows the marker on the far side of it.
+        let request = builder.center(tajrish).build().unwrap();
+        assert_eq!(request.center(), tajrish);
+        assert!(request.zoom() < fitted.zoom());
+
+        let err = StaticMapBuilder::new(600, 400).build().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid request: a static map without a center needs a marker or a path to fit"
+        );
+        let err = StaticMapBuilder::new(0, 400).center(azadi).zoom(12).build();
+        assert!(matches!(err, Err(NeshanError::InvalidRequest(_))));
+        let err = StaticMapBuilder::new(u32::MAX, u32::MAX)
+            .marker(Marker::new(azadi))
+            .build();
+        assert!(matches!(err, Err(NeshanError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn margins() {
+        for pixels in [0, 1, 9, 10, 11, 599, 600, 2048, u32::MAX / 9, u32::MAX] {
+            assert_eq!(
+                u64::from(super::margin(pixels)),
+                u64::from(pixels) * 9 / 10,
+                "{}",
+                pixels
+            );
+        }
+    }
+
+    #[test]
+    fn builder_simplifies_long_paths() {
+        // points on a straight line, which simplify to its ends.
+        let line: Vec<Point> = (0..2000)
+            .map(|i| Point::new_unchecked(f64::from(i) / 100.0, f64::from(i) / 50.0))
+            .collect();
+        assert!(request()
+            .path(PathOverlay::from_points(&line))
+            .validate()
+            .is_err());
+
+        let request = StaticMapBuilder::new(600, 400)
+            .path_points(&line)
+            .build()
+            .unwrap();
+        let path = &request.query()[5].1;
+        let encoded = path.strip_prefix("color:0x0000ff|weight:4|enc:").unwrap();
+        let points = polyline::decode(encoded, polyline::Precision::Five).unwrap();
+        assert_eq!(points, vec![line[0], line[1999]]);
+
+        // a zigzag strays too far from any simpler line.
+        let zigzag: Vec<Point> = (0..2000)
+            .map(|i| {
+                Point::new_unchecked(
+                    35.7 + f64::from(i % 2) / 100.0,
+                    51.0 + f64::from(i) / 1000.0,
+                )
+            })
+            .collect();
+        let err = StaticMapBuilder::new(600, 400)
+            .center(Point::new_unchecked(35.7, 52.0))
+            .zoom(15)
+            .path_points(&zigzag)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid request: the path of 2000 points is too long to draw, even simplified by 64 \
+             pixels"
+        );
+    }
+
+    #[tokio::test]
+    async fn static_map_of_route() {
+        use crate::{EncodedPolyline, Route};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/static"))
+            .and(query_param(
+                "path",
+                "color:0x0000ff|weight:4|enc:_p~iF~ps|U_ulLnnqC_mqNvxq`@",
+            ))
+            .and(query_param("width", "300"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/png")
+                    .set_body_bytes(vec![0x89, b'P', b'N', b'G']),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: "_p~iF~ps|U_ulLnnqC_mqNvxq`@".to_string(),
+            }),
+        };
+        let image = client(&server.uri())
+            .static_map_of_route(&route, 300, 200)
+            .await
+            .unwrap();
+        assert_eq!(image, vec![0x89, b'P', b'N', b'G']);
+
+        let requests = server.received_requests().await.unwrap();
+        let markers: Vec<String> = requests[0]
+            .url
+            .query_pairs()
+            .filter(|(name, _)| name == "markers")
+            .map(|(_, value)| value.into_owned())
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                "color:green|38.500000,-120.200000",
+                "color:red|43.252000,-126.453000"
+            ]
+        );
+
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: None,
+        };
+        let err = client(&server.uri())
+            .static_map_of_route(&route, 300, 200)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid request: the route has no geometry to draw"
+        );
+
+        let route = Route {
+            legs: Vec::new(),
+            overview_polyline: Some(EncodedPolyline {
+                points: "_p~iF~ps|U_ulLnnqC_mqNvxq`@".to_string(),
+            }),
+        };
+        let err = client(&server.uri())
+            .static_map_of_route(&route, u32::MAX, 200)
+            .await;
+        assert!(matches!(err, Err(NeshanError::InvalidRequest(_))));
+    }
+}
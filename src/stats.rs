@@ -0,0 +1,200 @@
+use crate::endpoint::Endpoint;
+use crate::error::ErrorKind;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// usage counters of a single endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EndpointStats {
+    /// http calls sent, retries included.
+    pub requests: u64,
+    /// calls that neshan answered successfully.
+    pub successes: u64,
+    /// failed calls of each kind, kinds that never happened are left out.
+    pub errors: HashMap<ErrorKind, u64>,
+    /// response body bytes received, error responses included.
+    pub bytes_received: u64,
+    /// total time spent on the calls.
+    pub latency: Duration,
+}
+
+impl EndpointStats {
+    /// number of failed calls of every kind.
+    pub fn total_errors(&self) -> u64 {
+        self.errors.values().sum()
+    }
+}
+
+/// snapshot of the client usage, see `Client::stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    endpoints: Vec<(Endpoint, EndpointStats)>,
+}
+
+impl Stats {
+    /// counters of the given endpoint.
+    pub fn endpoint(&self, endpoint: Endpoint) -> &EndpointStats {
+        &self.endpoints[endpoint as usize].1
+    }
+
+    /// counters of every endpoint.
+    pub fn iter(&self) -> impl Iterator<Item = (Endpoint, &EndpointStats)> {
+        self.endpoints
+            .iter()
+            .map(|(endpoint, stats)| (*endpoint, stats))
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<26} {:>10} {:>10} {:>8} {:>12} {:>12}",
+            "endpoint", "requests", "successes", "errors", "bytes", "latency_ms"
+        )?;
+        for (endpoint, stats) in self.iter() {
+            writeln!(
+                f,
+                "{:<26} {:>10} {:>10} {:>8} {:>12} {:>12}",
+                endpoint.as_str(),
+                stats.requests,
+                stats.successes,
+                stats.total_errors(),
+                stats.bytes_received,
+                stats.latency.as_millis()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    successes: AtomicU64,
+    errors: [AtomicU64; ErrorKind::ALL.len()],
+    bytes_received: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> EndpointStats {
+        EndpointStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            successes: self.successes.load(Ordering::Relaxed),
+            errors: ErrorKind::ALL
+                .iter()
+                .map(|kind| (*kind, self.errors[*kind as usize].load(Ordering::Relaxed)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            latency: Duration::from_micros(self.latency_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        self.requests.store(0, Ordering::Relaxed);
+        self.successes.store(0, Ordering::Relaxed);
+        for count in &self.errors {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.bytes_received.store(0, Ordering::Relaxed);
+        self.latency_micros.store(0, Ordering::Relaxed);
+    }
+}
+
+/// usage counters of every endpoint, shared between clones of a client.
+#[derive(Default)]
+pub(crate) struct Usage {
+    endpoints: [Counters; Endpoint::ALL.len()],
+}
+
+impl Usage {
+    fn counters(&self, endpoint: Endpoint) -> &Counters {
+        &self.endpoints[endpoint as usize]
+    }
+
+    pub(crate) fn request(&self, endpoint: Endpoint) {
+        self.counters(endpoint)
+            .requests
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn received(&self, endpoint: Endpoint, bytes: usize) {
+        self.counters(endpoint)
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// record the outcome of a call, `None` meaning success.
+    pub(crate) fn finished(&self, endpoint: Endpoint, error: Option<ErrorKind>, latency: Duration) {
+        let counters = self.counters(endpoint);
+
+        match error {
+            Some(kind) => counters.errors[kind as usize].fetch_add(1, Ordering::Relaxed),
+            None => counters.successes.fetch_add(1, Ordering::Relaxed),
+        };
+        counters
+            .latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            endpoints: Endpoint::ALL
+                .iter()
+                .map(|endpoint| (*endpoint, self.counters(*endpoint).snapshot()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counters in &self.endpoints {
+            counters.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Usage;
+    use crate::endpoint::Endpoint;
+    use crate::error::ErrorKind;
+    use std::time::Duration;
+
+    #[test]
+    fn table() {
+        let usage = Usage::default();
+        usage.request(Endpoint::Route);
+        usage.received(Endpoint::Route, 1024);
+        usage.finished(Endpoint::Route, None, Duration::from_millis(120));
+        usage.request(Endpoint::ReverseGeocode);
+        usage.finished(
+            Endpoint::ReverseGeocode,
+            Some(ErrorKind::Auth),
+            Duration::from_millis(30),
+        );
+
+        assert_eq!(
+            usage.snapshot().to_string(),
+            "endpoint                     requests  successes   errors        bytes   latency_ms\n\
+             route                               1          1        0         1024          120\n\
+             route_v4                            0          0        0            0            0\n\
+             route_no_traffic                    0          0        0            0            0\n\
+             reverse_geocode                     1          0        1            0           30\n\
+             reverse_geocode_v5                  0          0        0            0            0\n\
+             static_map                          0          0        0            0            0\n\
+             distance_matrix                     0          0        0            0            0\n\
+             distance_matrix_no_traffic          0          0        0            0            0\n\
+             map_matching                        0          0        0            0            0\n\
+             search                              0          0        0            0            0\n\
+             geocode                             0          0        0            0            0\n\
+             trip                                0          0        0            0            0\n\
+             tile                                0          0        0            0            0\n\
+             custom                              0          0        0            0            0\n"
+        );
+    }
+}
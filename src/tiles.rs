@@ -0,0 +1,580 @@
+//! raster tiles of the neshan map in the slippy map scheme of web mercator, e.g. for an
+//! offline map view. tile `0/0/0` is the whole world, each zoom level splits every tile into
+//! four, `x` grows to the east and `y` to the south.
+
+use crate::client::Client;
+use crate::endpoint::Endpoint;
+use crate::error::NeshanError;
+use crate::{BoundingBox, MapStyle, Point};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// styles of the tiles, the same as of static maps.
+pub type TileStyle = MapStyle;
+
+/// latitude beyond which web mercator has no tiles, the poles fall on the first and last row.
+const MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// a tile at zoom level `z`, written as `z/x/y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TileCoord {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileCoord {
+    /// highest zoom level of the tiles.
+    pub const MAX_ZOOM: u8 = 20;
+
+    pub fn new(z: u8, x: u32, y: u32) -> TileCoord {
+        TileCoord { z, x, y }
+    }
+
+    /// the tile `point` is on at zoom level `z`. a longitude of 180 is on the last column,
+    /// other longitudes out of range wrap around the world, latitudes past the poles are on
+    /// the first or last row.
+    pub fn containing(point: Point, z: u8) -> TileCoord {
+        let n = tiles(z);
+        let longitude = if point.longitude == 180.0 {
+            360.0
+        } else {
+            (point.longitude + 180.0).rem_euclid(360.0)
+        };
+        let latitude = point
+            .latitude
+            .clamp(-MAX_LATITUDE, MAX_LATITUDE)
+            .to_radians();
+
+        let x = longitude / 360.0 * n;
+        let y = (1.0 - latitude.tan().asinh() / PI) / 2.0 * n;
+        let last = n - 1.0;
+        TileCoord {
+            z,
+            x: x.floor().clamp(0.0, last) as u32,
+            y: y.floor().clamp(0.0, last) as u32,
+        }
+    }
+
+    /// every tile with a part of `bounds` at the zoom levels, each one once, ordered by zoom
+    /// level, then by column and row. zoom levels above `MAX_ZOOM` are left out, check the
+    /// size of a large area with `count` first.
+    pub fn covering(bounds: &BoundingBox, zooms: RangeInclusive<u8>) -> Vec<TileCoord> {
+        let mut coords = BTreeSet::new();
+        for z in capped(zooms) {
+            let (north_west, south_east) = corners(bounds, z);
+            for x in north_west.x..=south_east.x {
+                coords.extend((north_west.y..=south_east.y).map(|y| TileCoord { z, x, y }));
+            }
+        }
+
+        coords.into_iter().collect()
+    }
+
+    /// the number of tiles of `covering`, without listing them.
+    pub fn count(bounds: &BoundingBox, zooms: RangeInclusive<u8>) -> u64 {
+        capped(zooms)
+            .map(|z| {
+                let (north_west, south_east) = corners(bounds, z);
+                let side = |first: u32, last: u32| {
+                    last.checked_sub(first)
+                        .map_or(0, |side| u64::from(side) + 1)
+                };
+                side(north_west.x, south_east.x) * side(north_west.y, south_east.y)
+            })
+            .sum()
+    }
+
+    /// the north west corner of the tile, which is the south east corner of the tile before it
+    /// in both directions.
+    pub fn north_west(&self) -> Point {
+        corner(self.z, f64::from(self.x), f64::from(self.y))
+    }
+
+    pub fn center(&self) -> Point {
+        corner(self.z, f64::from(self.x) + 0.5, f64::from(self.y) + 0.5)
+    }
+
+    /// the area of the tile.
+    pub fn bounds(&self) -> BoundingBox {
+        let (x, y) = (f64::from(self.x), f64::from(self.y));
+        let north_west = corner(self.z, x, y);
+        let south_east = corner(self.z, x + 1.0, y + 1.0);
+
+        BoundingBox::new(
+            Point::new_unchecked(south_east.latitude, north_west.longitude),
+            Point::new_unchecked(north_west.latitude, south_east.longitude),
+        )
+        .expect("the corners of a tile are in order")
+    }
+
+    /// check the zoom level and that the tile is on the map at it.
+    pub fn validate(&self) -> Result<(), NeshanError> {
+        if self.z > TileCoord::MAX_ZOOM {
+            return Err(NeshanError::InvalidRequest(format!(
+                "tile {} has zoom {}, expected 0 to {}",
+                self,
+                self.z,
+                TileCoord::MAX_ZOOM
+            )));
+        }
+        let n = 1u32 << self.z;
+        if self.x >= n || self.y >= n {
+            return Err(NeshanError::InvalidRequest(format!(
+                "tile {} is off the map, zoom {} has {} tiles a side",
+                self, self.z, n
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TileCoord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.z, self.x, self.y)
+    }
+}
+
+/// the zoom levels up to `TileCoord::MAX_ZOOM`.
+fn capped(zooms: RangeInclusive<u8>) -> RangeInclusive<u8> {
+    *zooms.start()..=(*zooms.end()).min(TileCoord::MAX_ZOOM)
+}
+
+/// the north west and south east tiles of `bounds` at zoom level `z`.
+fn corners(bounds: &BoundingBox, z: u8) -> (TileCoord, TileCoord) {
+    let north_west = TileCoord::containing(
+        Point::new_unchecked(bounds.north_east().latitude, bounds.south_west().longitude),
+        z,
+    );
+    let south_east = TileCoord::containing(
+        Point::new_unchecked(bounds.south_west().latitude, bounds.north_east().longitude),
+        z,
+    );
+
+    (north_west, south_east)
+}
+
+/// tiles a side at zoom level `z`.
+fn tiles(z: u8) -> f64 {
+    2f64.powi(i32::from(z))
+}
+
+/// the point at tile position `x`, `y`, which may be between tiles.
+fn corner(z: u8, x: f64, y: f64) -> Point {
+    let n = tiles(z);
+    let latitude = (PI * (1.0 - 2.0 * y / n)).sinh().atan().to_degrees();
+
+    Point::new_unchecked(latitude, x / n * 360.0 - 180.0)
+}
+
+/// a tile of `Client::tile` that failed, with its coordinate so that it can be told apart in
+/// a bulk download.
+#[derive(Debug)]
+pub struct TileError {
+    pub coord: TileCoord,
+    pub error: NeshanError,
+}
+
+impl fmt::Display for TileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tile {} failed: {}", self.coord, self.error)
+    }
+}
+
+impl std::error::Error for TileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// a tile of `Client::prefetch_tiles` as it finished, with the progress of the whole set.
+#[derive(Debug)]
+pub struct PrefetchedTile {
+    /// tiles finished so far, this one included.
+    pub done: usize,
+    pub total: usize,
+    pub result: Result<(TileCoord, Vec<u8>), TileError>,
+}
+
+impl PrefetchedTile {
+    /// most tiles of a single `Client::prefetch_tiles`, about a city down to its streets.
+    pub const MAX_TOTAL: u64 = 100_000;
+}
+
+impl Client {
+    /// the image of a raster tile, e.g. a png. a tile off the map fails without sending a
+    /// request.
+    pub async fn tile(&self, coord: TileCoord, style: TileStyle) -> Result<Vec<u8>, TileError> {
+        let fetch = async {
+            coord.validate()?;
+
+            let query = crate::protocol::tile_query(coord, style);
+            let mut image = Vec::new();
+            let call = self.download(Endpoint::Tile, &query, "image/", &mut image);
+            crate::trace::instrument(Endpoint::Tile, &[], call).await?;
+
+            Ok(image)
+        };
+
+        fetch.await.map_err(|error| TileError { coord, error })
+    }
+
+    /// download the tiles covering `bounds` at the zoom levels, e.g. a delivery zone for an
+    /// app that works offline, at most `concurrency` at the same time.
+    ///
+    /// the tiles come in the order they finish, each with the count done so far. a tile that
+    /// fails is an item of its own and the rest carry on, collect the errors to retry them.
+    ///
+    /// zoom levels above `TileCoord::MAX_ZOOM` and areas of more than
+    /// `PrefetchedTile::MAX_TOTAL` tiles fail before any request is sent.
+    pub fn prefetch_tiles(
+        &self,
+        bounds: &BoundingBox,
+        zooms: RangeInclusive<u8>,
+        style: TileStyle,
+        concurrency: usize,
+    ) -> Result<impl Stream<Item = PrefetchedTile> + Send + 'static, NeshanError> {
+        if *zooms.end() > TileCoord::MAX_ZOOM && !zooms.is_empty() {
+            return Err(NeshanError::InvalidRequest(format!(
+                "prefetch up to zoom {}, expected at most {}",
+                zooms.end(),
+                TileCoord::MAX_ZOOM
+            )));
+        }
+        let count = TileCoord::count(bounds, zooms.clone());
+        if count > PrefetchedTile::MAX_TOTAL {
+            return Err(NeshanError::InvalidRequest(format!(
+                "prefetch of {} tiles, expected at most {}",
+                count,
+                PrefetchedTile::MAX_TOTAL
+            )));
+        }
+
+        let coords = TileCoord::covering(bounds, zooms);
+        let total = coords.len();
+        let client = self.clone();
+
+        Ok(stream::iter(coords)
+            .map(move |coord| {
+                let client = client.clone();
+                async move {
+                    let image = client.tile(coord, style).await?;
+                    Ok((coord, image))
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .enumerate()
+            .map(move |(i, result)| PrefetchedTile {
+                done: i + 1,
+                total,
+                result,
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TileCoord, TileStyle};
+    use crate::client::Client;
+    use crate::error::{ErrorKind, NeshanError};
+    use crate::{BoundingBox, Point};
+    use futures_util::StreamExt;
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn assert_close(actual: Point, expected: Point) {
+        assert!(
+            (actual.latitude - expected.latitude).abs() < 1e-6
+                && (actual.longitude - expected.longitude).abs() < 1e-6,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn points_and_tiles() {
+        let tehran = Point::new_unchecked(35.6892, 51.389);
+        assert_eq!(
+            TileCoord::containing(tehran, 12),
+            TileCoord::new(12, 2632, 1612)
+        );
+        assert_eq!(TileCoord::containing(tehran, 0), TileCoord::new(0, 0, 0));
+        assert_eq!(
+            TileCoord::containing(tehran, 20),
+            TileCoord::new(20, 673_969, 412_877)
+        );
+
+        let tile = TileCoord::new(12, 2632, 1612);
+        assert_close(
+            tile.north_west(),
+            Point::new_unchecked(35.746_512, 51.328_125),
+        );
+        let bounds = tile.bounds();
+        assert!(bounds.contains(&tehran) && bounds.contains(&tile.center()));
+        assert_eq!(TileCoord::containing(tile.center(), 12), tile);
+        // the south east corner is the north west one of the next tile.
+        assert_eq!(
+            bounds.south_west().latitude,
+            TileCoord::new(12, 2632, 1613).north_west().latitude
+        );
+        assert_eq!(tile.to_string(), "12/2632/1612");
+    }
+
+    #[test]
+    fn wrap_around_and_poles() {
+        // the antimeridian is the west edge of the first column, 180 is on the last one.
+        let cases = [
+            ((0.0, -180.0), (0, 2)),
+            ((0.0, 180.0), (3, 2)),
+            ((0.0, 179.999), (3, 2)),
+            ((0.0, 541.0), (0, 2)),
+            ((0.0, -181.0), (3, 2)),
+            ((90.0, 0.0), (2, 0)),
+            ((85.1, 0.0), (2, 0)),
+            ((-90.0, 0.0), (2, 3)),
+        ];
+        for ((latitude, longitude), (x, y)) in cases {
+            assert_eq!(
+                TileCoord::containing(Point::new_unchecked(latitude, longitude), 2),
+                TileCoord::new(2, x, y),
+                "{}, {}",
+                latitude,
+                longitude
+            );
+        }
+
+        let world = TileCoord::new(0, 0, 0);
+        assert_close(world.north_west(), Point::new_unchecked(85.051_129, -180.0));
+        assert_close(world.center(), Point::new_unchecked(0.0, 0.0));
+
+        assert!(TileCoord::new(2, 3, 3).validate().is_ok());
+        for (tile, message) in [
+            (
+                TileCoord::new(2, 4, 0),
+                "tile 2/4/0 is off the map, zoom 2 has 4 tiles a side",
+            ),
+            (
+                TileCoord::new(21, 0, 0),
+                "tile 21/0/0 has zoom 21, expected 0 to 20",
+            ),
+        ] {
+            match tile.validate() {
+                Err(NeshanError::InvalidRequest(err)) => assert_eq!(err, message),
+                result => panic!("{:?} for {}", result, tile),
+            }
+        }
+    }
+
+    #[test]
+    fn cover_a_box() {
+        let tehran = BoundingBox::new(
+            Point::new_unchecked(35.56, 51.09),
+            Point::new_unchecked(35.83, 51.61),
+        )
+        .unwrap();
+        let counts: Vec<usize> = (10..=14)
+            .map(|z| TileCoord::covering(&tehran, z..=z).len())
+            .collect();
+        assert_eq!(counts, vec![4, 12, 35, 117, 384]);
+        let tiles = TileCoord::covering(&tehran, 10..=14);
+        assert_eq!(tiles.len(), 552);
+        assert_eq!(tiles[0], TileCoord::new(10, 657, 402));
+        assert_eq!(tiles[551], TileCoord::new(14, 10540, 6458));
+        assert!(tiles.iter().all(|tile| tile.bounds().intersects(&tehran)));
+
+        let point = BoundingBox::from_points(&[Point::new_unchecked(35.6892, 51.389)]).unwrap();
+        assert_eq!(
+            TileCoord::covering(&point, 12..=13),
+            vec![
+                TileCoord::new(12, 2632, 1612),
+                TileCoord::new(13, 5265, 3225)
+            ]
+        );
+        // a box up to the antimeridian ends on the last column.
+        let east = BoundingBox::new(
+            Point::new_unchecked(-10.0, 170.0),
+            Point::new_unchecked(10.0, 180.0),
+        )
+        .unwrap();
+        assert_eq!(
+            TileCoord::covering(&east, 2..=2),
+            vec![TileCoord::new(2, 3, 1), TileCoord::new(2, 3, 2)]
+        );
+        assert_eq!(TileCoord::count(&east, 2..=2), 2);
+        assert_eq!(
+            TileCoord::count(&east, 0..=12),
+            TileCoord::covering(&east, 0..=12).len() as u64
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let none = TileCoord::covering(&tehran, 14..=10);
+        assert!(none.is_empty());
+        #[allow(clippy::reversed_empty_ranges)]
+        let none = TileCoord::count(&tehran, 14..=10);
+        assert_eq!(none, 0);
+
+        // zoom levels past the last one have no tiles.
+        assert_eq!(TileCoord::count(&tehran, 10..=14), 552);
+        assert_eq!(
+            TileCoord::covering(&point, 19..=u8::MAX),
+            TileCoord::covering(&point, 19..=20)
+        );
+        assert_eq!(TileCoord::count(&point, 21..=u8::MAX), 0);
+        let world = BoundingBox::new(
+            Point::new_unchecked(-90.0, -180.0),
+            Point::new_unchecked(90.0, 180.0),
+        )
+        .unwrap();
+        assert_eq!(TileCoord::count(&world, 20..=20), 1 << 40);
+    }
+
+    #[test]
+    fn prefetch_limits() {
+        let client = Client::new("key");
+        let tehran = BoundingBox::new(
+            Point::new_unchecked(35.56, 51.09),
+            Point::new_unchecked(35.83, 51.61),
+        )
+        .unwrap();
+
+        for (zooms, message) in [
+            (12..=30, "prefetch up to zoom 30, expected at most 20"),
+            (10..=18, "prefetch of 123416 tiles, expected at most 100000"),
+        ] {
+            match client.prefetch_tiles(&tehran, zooms, TileStyle::default(), 4) {
+                Err(NeshanError::InvalidRequest(err)) => assert_eq!(err, message),
+                Err(err) => panic!("{:?}", err),
+                Ok(_) => panic!("prefetch of {}", message),
+            }
+        }
+        assert!(client
+            .prefetch_tiles(&tehran, 10..=17, TileStyle::default(), 4)
+            .is_ok());
+
+        // a box up to the antimeridian is counted like any other.
+        let east = BoundingBox::new(
+            Point::new_unchecked(-10.0, 170.0),
+            Point::new_unchecked(10.0, 180.0),
+        )
+        .unwrap();
+        assert!(client
+            .prefetch_tiles(&east, 0..=8, TileStyle::default(), 4)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn fetch_tiles() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/tile"))
+            .and(header("api-key", "key"))
+            .and(query_param("type", "standard-night"))
+            .and(query_param("z", "12"))
+            .and(query_param("x", "2632"))
+            .and(query_param("y", "1612"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/png")
+                    .set_body_bytes(vec![0x89, b'P', b'N', b'G']),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v4/tile"))
+            .and(query_param("x", "2633"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let image = client
+            .tile(TileCoord::new(12, 2632, 1612), TileStyle::StandardNight)
+            .await
+            .unwrap();
+        assert_eq!(image, b"\x89PNG");
+
+        let err = client
+            .tile(TileCoord::new(12, 2633, 1612), TileStyle::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.coord, TileCoord::new(12, 2633, 1612));
+        assert_eq!(err.error.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("tile 12/2633/1612 failed: "));
+
+        let err = client
+            .tile(TileCoord::new(1, 2, 0), TileStyle::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.error.kind(), ErrorKind::InvalidRequest);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn prefetch_with_bounded_concurrency() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v4/tile"))
+            .and(query_param("z", "14"))
+            .respond_with(ResponseTemplate::new(404).set_delay(Duration::from_millis(200)))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v4/tile"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/png")
+                    .set_body_bytes(vec![0x89, b'P', b'N', b'G'])
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+
+        // one tile a zoom level, eight in all, two at a time take four rounds.
+        let point = BoundingBox::from_points(&[Point::new_unchecked(35.6892, 51.389)]).unwrap();
+        let started = Instant::now();
+        let tiles: Vec<_> = client
+            .prefetch_tiles(&point, 10..=17, TileStyle::default(), 2)
+            .unwrap()
+            .collect()
+            .await;
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(800), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+
+        let progress: Vec<(usize, usize)> =
+            tiles.iter().map(|tile| (tile.done, tile.total)).collect();
+        assert_eq!(progress, (1..=8).map(|done| (done, 8)).collect::<Vec<_>>());
+
+        let mut zooms = Vec::new();
+        let mut failures = Vec::new();
+        for tile in tiles {
+            match tile.result {
+                Ok((coord, image)) => {
+                    assert_eq!(image, b"\x89PNG");
+                    zooms.push(coord.z);
+                }
+                Err(err) => failures.push(err),
+            }
+        }
+        zooms.sort_unstable();
+        assert_eq!(zooms, vec![10, 11, 12, 13, 15, 16, 17]);
+        assert_eq!(failures.len(), 1);
+        let failure = &failures[0];
+        assert_eq!(failure.coord, TileCoord::new(14, 10530, 6451));
+        assert_eq!(failure.error.kind(), ErrorKind::NotFound);
+    }
+}
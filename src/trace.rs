@@ -0,0 +1,226 @@
+//! `tracing` instrumentation of the endpoints, compiled to no-ops without the `tracing` feature.
+//!
+//! each endpoint call gets a `neshan.<endpoint>` span with its coordinates rounded to three
+//! decimals (about 100 meters), the http status, the request id neshan gave it, the retry
+//! attempt and the duration of the last attempt. only coordinates are recorded, the api key never reaches a span.
+
+use crate::endpoint::Endpoint;
+use crate::Point;
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(feature = "tracing")]
+fn coordinates(points: &[Point]) -> String {
+    points
+        .iter()
+        .map(|point| format!("{:.3},{:.3}", point.latitude, point.longitude))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+#[cfg(feature = "tracing")]
+fn span(endpoint: Endpoint, points: &[Point]) -> tracing::Span {
+    use tracing::field::Empty;
+
+    macro_rules! endpoint_span {
+        ($name:literal) => {
+            tracing::info_span!(
+                $name,
+                coordinates = %coordinates(points),
+                http.status = Empty,
+                request_id = Empty,
+                attempt = Empty,
+                duration_ms = Empty,
+            )
+        };
+    }
+
+    match endpoint {
+        Endpoint::Route => endpoint_span!("neshan.route"),
+        Endpoint::RouteV4 => endpoint_span!("neshan.route_v4"),
+        Endpoint::RouteNoTraffic => endpoint_span!("neshan.route_no_traffic"),
+        Endpoint::ReverseGeocode => endpoint_span!("neshan.reverse_geocode"),
+        Endpoint::ReverseGeocodeV5 => endpoint_span!("neshan.reverse_geocode_v5"),
+        Endpoint::StaticMap => endpoint_span!("neshan.static_map"),
+        Endpoint::DistanceMatrix => endpoint_span!("neshan.distance_matrix"),
+        Endpoint::DistanceMatrixNoTraffic => endpoint_span!("neshan.distance_matrix_no_traffic"),
+        Endpoint::MapMatching => endpoint_span!("neshan.map_matching"),
+        Endpoint::Search => endpoint_span!("neshan.search"),
+        Endpoint::Geocode => endpoint_span!("neshan.geocode"),
+        Endpoint::Trip => endpoint_span!("neshan.trip"),
+        Endpoint::Tile => endpoint_span!("neshan.tile"),
+        Endpoint::Custom => endpoint_span!("neshan.custom"),
+    }
+}
+
+/// run the endpoint call inside its span.
+#[cfg(feature = "tracing")]
+pub(crate) async fn instrument<F: Future>(
+    endpoint: Endpoint,
+    points: &[Point],
+    call: F,
+) -> F::Output {
+    use tracing::Instrument;
+
+    call.instrument(span(endpoint, points)).await
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) async fn instrument<F: Future>(
+    _endpoint: Endpoint,
+    _points: &[Point],
+    call: F,
+) -> F::Output {
+    call.await
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn attempt_started(endpoint: Endpoint, attempt: u32) {
+    tracing::Span::current().record("attempt", attempt);
+    tracing::debug!(
+        endpoint = endpoint.as_str(),
+        attempt,
+        "neshan request started"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn attempt_started(_endpoint: Endpoint, _attempt: u32) {}
+
+/// `status` is `None` when the attempt failed before neshan responded.
+#[cfg(feature = "tracing")]
+pub(crate) fn attempt_finished(
+    endpoint: Endpoint,
+    status: Option<u16>,
+    request_id: Option<&str>,
+    duration: Duration,
+) {
+    let span = tracing::Span::current();
+    let duration_ms = duration.as_millis() as u64;
+
+    if let Some(status) = status {
+        span.record("http.status", status);
+    }
+    if let Some(request_id) = request_id {
+        span.record("request_id", request_id);
+    }
+    span.record("duration_ms", duration_ms);
+
+    tracing::debug!(
+        endpoint = endpoint.as_str(),
+        status,
+        request_id,
+        duration_ms,
+        "neshan request finished"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn attempt_finished(
+    _endpoint: Endpoint,
+    _status: Option<u16>,
+    _request_id: Option<&str>,
+    _duration: Duration,
+) {
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::client::Client;
+    use crate::{Point, RouteOptions, Type};
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    type Recorded = HashMap<u64, (String, HashMap<String, String>)>;
+
+    #[derive(Default, Clone)]
+    struct Spans {
+        spans: Arc<Mutex<Recorded>>,
+    }
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for Spans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+            let mut fields = HashMap::new();
+            attrs.record(&mut Fields(&mut fields));
+            self.spans
+                .lock()
+                .unwrap()
+                .insert(id.into_u64(), (attrs.metadata().name().to_string(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+            if let Some((_, fields)) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+                values.record(&mut Fields(fields));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn route_span() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"routes": []}))
+                    .insert_header("X-Request-Id", "abc-123"),
+            )
+            .mount(&server)
+            .await;
+
+        let spans = Spans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let client = Client::builder("secret-api-key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        client
+            .route_with(
+                Type::Car,
+                Point {
+                    latitude: 35.731984409609694,
+                    longitude: 51.392684661470156,
+                },
+                Point {
+                    latitude: 35.723680037006304,
+                    longitude: 50.953103738230396,
+                },
+                &RouteOptions::new(),
+            )
+            .await
+            .unwrap();
+
+        let spans = spans.spans.lock().unwrap();
+        let (_, fields) = spans
+            .values()
+            .find(|(name, _)| name == "neshan.route")
+            .expect("route span");
+
+        assert_eq!(fields["coordinates"], "35.732,51.393;35.724,50.953");
+        assert_eq!(fields["http.status"], "200");
+        assert_eq!(fields["request_id"], "\"abc-123\"");
+        assert_eq!(fields["attempt"], "1");
+        assert!(fields.contains_key("duration_ms"));
+        assert!(fields
+            .values()
+            .all(|value| !value.contains("secret-api-key")));
+    }
+}
@@ -0,0 +1,456 @@
+//! journeys through several stops, see `Client::plan_trip`.
+
+use crate::batch::BatchOptions;
+use crate::client::Client;
+use crate::error::NeshanError;
+use crate::{Distance, Duration, Point, Route, RouteOptions, Routes, Type};
+use serde_json::{json, Value};
+use std::time::SystemTime;
+
+/// a stop of a trip, with an optional label such as a customer name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stop {
+    pub point: Point,
+    pub label: Option<String>,
+}
+
+impl Stop {
+    pub fn new(point: Point) -> Stop {
+        Stop { point, label: None }
+    }
+
+    pub fn label(mut self, label: &str) -> Stop {
+        self.label = Some(label.to_string());
+        self
+    }
+}
+
+impl From<Point> for Stop {
+    fn from(point: Point) -> Stop {
+        Stop::new(point)
+    }
+}
+
+/// the routes between two consecutive stops of a trip.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// index of the stop the segment starts from, it ends at the next one.
+    pub from: usize,
+    pub routes: Result<Routes, NeshanError>,
+}
+
+impl Segment {
+    /// the suggested route, `None` when the call failed or neshan found no route.
+    pub fn route(&self) -> Option<&Route> {
+        self.routes.as_ref().ok()?.get(0)
+    }
+
+    /// distance of the suggested route in meters.
+    pub fn distance(&self) -> Option<f64> {
+        self.route().map(|route| route.total_distance().value)
+    }
+
+    /// duration of the suggested route in seconds.
+    pub fn duration(&self) -> Option<f64> {
+        self.route().map(|route| route.total_duration().value)
+    }
+}
+
+/// stops of a journey in order, with the routed segment between each consecutive pair.
+#[derive(Debug, Clone)]
+pub struct Trip {
+    stops: Vec<Stop>,
+    segments: Vec<Segment>,
+}
+
+impl Trip {
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+
+    /// segments in order, one less than the stops.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// the segment from stop `index` to the next one.
+    pub fn segment(&self, index: usize) -> Option<&Segment> {
+        self.segments.get(index)
+    }
+
+    /// whether every segment has a route.
+    pub fn is_complete(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| segment.route().is_some())
+    }
+
+    /// distance of the routed segments, the text is in persian like the one of neshan.
+    /// segments without a route are left out, see `is_complete`.
+    pub fn total_distance(&self) -> Distance {
+        self.segments
+            .iter()
+            .filter_map(Segment::route)
+            .map(Route::total_distance)
+            .sum()
+    }
+
+    /// duration of the routed segments, the text is in persian like the one of neshan.
+    /// segments without a route are left out, see `is_complete`.
+    pub fn total_duration(&self) -> Duration {
+        self.segments
+            .iter()
+            .filter_map(Segment::route)
+            .map(Route::total_duration)
+            .sum()
+    }
+
+    /// arrival time at each stop when leaving the first one at `departure`, without any time
+    /// spent at the stops. stops after a segment without a route, or past the latest time the
+    /// platform can represent, have no arrival time.
+    pub fn etas(&self, departure: SystemTime) -> Vec<Option<SystemTime>> {
+        if self.stops.is_empty() {
+            return Vec::new();
+        }
+
+        let mut arrival = Some(departure);
+        let mut etas = vec![arrival];
+        for segment in &self.segments {
+            arrival = match (arrival, segment.duration()) {
+                (Some(arrival), Some(duration)) => arrival.checked_add(
+                    Duration {
+                        value: duration,
+                        text: String::new(),
+                    }
+                    .as_std(),
+                ),
+                _ => None,
+            };
+            etas.push(arrival);
+        }
+
+        etas
+    }
+
+    /// the trip as a geojson `FeatureCollection`: a `Point` feature per stop, then a
+    /// `LineString` feature per segment with the geometry of its suggested route. segments
+    /// without a route have a `null` geometry and an `error` property.
+    pub fn to_geojson(&self) -> Value {
+        let stops = self.stops.iter().enumerate().map(|(index, stop)| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [stop.point.longitude, stop.point.latitude],
+                },
+                "properties": {
+                    "stop": index,
+                    "label": stop.label,
+                },
+            })
+        });
+
+        let segments = self.segments.iter().map(|segment| {
+            let geometry = segment.route().and_then(|route| route.geometry().ok());
+            let error = match (&segment.routes, segment.route()) {
+                (Err(err), _) => Some(err.to_string()),
+                (Ok(_), None) => Some("no route found".to_string()),
+                (Ok(_), Some(_)) if geometry.is_none() => {
+                    Some("malformed route geometry".to_string())
+                }
+                _ => None,
+            };
+
+            let mut properties = json!({
+                "from": segment.from,
+                "to": segment.from + 1,
+                "distance": segment.distance(),
+                "duration": segment.duration(),
+            });
+            if let Some(error) = error {
+                properties["error"] = json!(error);
+            }
+
+            json!({
+                "type": "Feature",
+                "geometry": geometry.map(|points| json!({
+                    "type": "LineString",
+                    "coordinates": points
+                        .iter()
+                        .map(|point| [point.longitude, point.latitude])
+                        .collect::<Vec<_>>(),
+                })),
+                "properties": properties,
+            })
+        });
+
+        json!({
+            "type": "FeatureCollection",
+            "features": stops.chain(segments).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Client {
+    /// route every consecutive pair of stops at the same time. a segment that fails or has no
+    /// route is kept in the trip with its error instead of failing the whole trip. the stops
+    /// take the place of the waypoints of `options`, which are left out, and the bearing is
+    /// only sent for the first stop.
+    pub async fn plan_trip<S>(
+        &self,
+        vehicle: Type,
+        stops: impl IntoIterator<Item = S>,
+        options: &RouteOptions,
+    ) -> Trip
+    where
+        S: Into<Stop>,
+    {
+        let stops: Vec<Stop> = stops.into_iter().map(Into::into).collect();
+        let pairs: Vec<(Point, Point)> = stops
+            .windows(2)
+            .map(|pair| (pair[0].point, pair[1].point))
+            .collect();
+
+        let first = RouteOptions {
+            waypoints: Vec::new(),
+            ..options.clone()
+        };
+        let results = self
+            .route_pairs(
+                vehicle,
+                &pairs,
+                &first,
+                &options.between_stops(),
+                &BatchOptions::new(pairs.len()),
+            )
+            .await;
+
+        Trip {
+            stops,
+            segments: results
+                .into_iter()
+                .enumerate()
+                .map(|(from, pair)| Segment {
+                    from,
+                    routes: pair.result,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stop;
+    use crate::client::Client;
+    use crate::polyline::{self, Precision};
+    use crate::{Point, RouteOptions, Type};
+    use std::time::{Duration, SystemTime};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn stops() -> Vec<Stop> {
+        vec![
+            Stop::new(Point::new_unchecked(35.70, 51.30)).label("depot"),
+            Stop::new(Point::new_unchecked(35.71, 51.31)),
+            Stop::new(Point::new_unchecked(35.72, 51.32)).label("customer"),
+            Stop::new(Point::new_unchecked(35.73, 51.33)),
+        ]
+    }
+
+    async fn mock(server: &MockServer, origin: &str, meters: f64, seconds: f64) {
+        let polyline = polyline::encode(
+            &[
+                Point::new_unchecked(35.0, 51.0),
+                Point::new_unchecked(35.1, 51.1),
+            ],
+            Precision::Five,
+        );
+
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("origin", origin))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": [{
+                    "legs": [{
+                        "summary": origin,
+                        "distance": {"value": meters, "text": ""},
+                        "duration": {"value": seconds, "text": ""}
+                    }],
+                    "overview_polyline": {"points": polyline}
+                }]
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn plan_trip() {
+        let server = MockServer::start().await;
+        mock(&server, "35.700000,51.300000", 1000.0, 120.0).await;
+        mock(&server, "35.710000,51.310000", 2500.0, 300.0).await;
+        mock(&server, "35.720000,51.320000", 500.0, 60.0).await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let trip = client
+            .plan_trip(Type::Car, stops(), &RouteOptions::new())
+            .await;
+
+        assert_eq!(trip.stops().len(), 4);
+        assert_eq!(trip.segments().len(), 3);
+        assert!(trip.is_complete());
+        assert_eq!(trip.segment(1).unwrap().distance(), Some(2500.0));
+        assert_eq!(
+            trip.segment(2).unwrap().route().unwrap().legs[0].summary,
+            "35.720000,51.320000"
+        );
+        assert_eq!(trip.total_distance().value, 4000.0);
+        assert_eq!(trip.total_distance().text, "۴ کیلومتر");
+        assert_eq!(trip.total_duration().value, 480.0);
+
+        let departure = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let etas: Vec<u64> = trip
+            .etas(departure)
+            .into_iter()
+            .map(|eta| {
+                eta.unwrap()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            })
+            .collect();
+        assert_eq!(etas, vec![1_000, 1_120, 1_420, 1_480]);
+
+        let geojson = trip.to_geojson();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 7);
+        assert_eq!(features[0]["geometry"]["coordinates"][0], 51.3);
+        assert_eq!(features[0]["properties"]["label"], "depot");
+        assert_eq!(features[4]["geometry"]["type"], "LineString");
+        assert_eq!(features[4]["geometry"]["coordinates"][1][0], 51.1);
+        assert_eq!(features[5]["properties"]["to"], 2);
+    }
+
+    #[tokio::test]
+    async fn waypoints_and_bearing_of_segments() {
+        let server = MockServer::start().await;
+        mock(&server, "35.700000,51.300000", 1000.0, 120.0).await;
+        mock(&server, "35.710000,51.310000", 2500.0, 300.0).await;
+        mock(&server, "35.720000,51.320000", 500.0, 60.0).await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let options = RouteOptions::new()
+            .waypoints(&[Point::new_unchecked(35.705, 51.305)])
+            .bearing(45);
+        let trip = client.plan_trip(Type::Car, stops(), &options).await;
+
+        assert!(trip.is_complete());
+        let mut bearings = Vec::new();
+        for request in server.received_requests().await.unwrap() {
+            let query: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+            assert!(!query.iter().any(|(key, _)| key == "waypoints"));
+            let origin = query.iter().find(|(key, _)| key == "origin").unwrap();
+            let bearing = query.iter().find(|(key, _)| key == "bearing");
+            bearings.push((origin.1.clone(), bearing.map(|(_, value)| value.clone())));
+        }
+        bearings.sort();
+        assert_eq!(
+            bearings,
+            vec![
+                ("35.700000,51.300000".to_string(), Some("45".to_string())),
+                ("35.710000,51.310000".to_string(), None),
+                ("35.720000,51.320000".to_string(), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn etas_past_the_end_of_time() {
+        let server = MockServer::start().await;
+        mock(&server, "35.700000,51.300000", 1000.0, 120.0).await;
+        mock(&server, "35.710000,51.310000", 2500.0, 1e300).await;
+        mock(&server, "35.720000,51.320000", 500.0, 60.0).await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let trip = client
+            .plan_trip(Type::Car, stops(), &RouteOptions::new())
+            .await;
+
+        let etas = trip.etas(SystemTime::now());
+        assert!(etas[0].is_some() && etas[1].is_some());
+        assert!(etas[2].is_none() && etas[3].is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_segments_are_kept() {
+        let server = MockServer::start().await;
+        mock(&server, "35.700000,51.300000", 1000.0, 120.0).await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("origin", "35.710000,51.310000"))
+            .respond_with(ResponseTemplate::new(470))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v3/direction"))
+            .and(query_param("origin", "35.720000,51.320000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "routes": []
+            })))
+            .mount(&server)
+            .await;
+
+        let client = Client::builder("key")
+            .base_url(&server.uri())
+            .build()
+            .unwrap();
+        let trip = client
+            .plan_trip(Type::Car, stops(), &RouteOptions::new())
+            .await;
+
+        assert!(!trip.is_complete());
+        assert!(trip.segment(0).unwrap().route().is_some());
+        assert!(trip.segment(1).unwrap().routes.is_err());
+        assert!(trip.segment(2).unwrap().routes.is_ok());
+        assert!(trip.segment(2).unwrap().route().is_none());
+        assert_eq!(trip.total_distance().value, 1000.0);
+
+        let etas = trip.etas(SystemTime::UNIX_EPOCH);
+        assert!(etas[0].is_some() && etas[1].is_some());
+        assert!(etas[2].is_none() && etas[3].is_none());
+
+        let geojson = trip.to_geojson();
+        assert!(geojson["features"][5]["geometry"].is_null());
+        assert!(geojson["features"][5]["properties"]["error"].is_string());
+        assert_eq!(
+            geojson["features"][6]["properties"]["error"],
+            "no route found"
+        );
+    }
+
+    #[tokio::test]
+    async fn single_stop() {
+        let trip = Client::new("key")
+            .plan_trip(
+                Type::Car,
+                [Point::new_unchecked(35.7, 51.4)],
+                &RouteOptions::new(),
+            )
+            .await;
+
+        assert!(trip.segments().is_empty());
+        assert!(trip.is_complete());
+        assert_eq!(trip.total_duration().value, 0.0);
+        assert_eq!(trip.etas(SystemTime::UNIX_EPOCH).len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+//! typed units from uom, compiled only with the `uom` feature.
+
+use crate::{Distance, Duration, Point};
+use uom::si::angle::degree;
+use uom::si::f64::{Angle, Length, Time};
+use uom::si::length::meter;
+use uom::si::time::second;
+
+impl Distance {
+    pub fn length(&self) -> Length {
+        Length::new::<meter>(self.value)
+    }
+}
+
+impl Duration {
+    pub fn time(&self) -> Time {
+        Time::new::<second>(self.value)
+    }
+}
+
+impl Point {
+    /// same as `haversine_distance_to` as a typed length.
+    pub fn haversine_length_to(&self, other: &Point) -> Length {
+        Length::new::<meter>(self.haversine_distance_to(other))
+    }
+
+    /// same as `bearing_to` as a typed angle.
+    pub fn bearing_angle_to(&self, other: &Point) -> Angle {
+        Angle::new::<degree>(self.bearing_to(other))
+    }
+
+    /// same as `destination` with a typed bearing and distance.
+    pub fn destination_by(&self, bearing: Angle, distance: Length) -> Point {
+        self.destination(bearing.get::<degree>(), distance.get::<meter>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Distance, Duration, Point};
+    use uom::si::angle::{degree, radian};
+    use uom::si::f64::{Angle, Length};
+    use uom::si::length::{kilometer, meter};
+    use uom::si::time::{minute, second};
+
+    #[test]
+    fn distance_and_duration() {
+        let distance = Distance {
+            value: 12_500.0,
+            text: "۱۲.۵ کیلومتر".to_string(),
+        };
+        assert_eq!(distance.length().get::<meter>(), 12_500.0);
+        assert_eq!(distance.length().get::<kilometer>(), 12.5);
+
+        let duration = Duration {
+            value: 90.0,
+            text: "۱.۵ دقیقه".to_string(),
+        };
+        assert_eq!(duration.time().get::<second>(), 90.0);
+        assert_eq!(duration.time().get::<minute>(), 1.5);
+    }
+
+    #[test]
+    fn haversine_helpers() {
+        let tehran = Point::new_unchecked(35.6892, 51.3890);
+        let karaj = Point::new_unchecked(35.8400, 50.9391);
+
+        assert_eq!(
+            tehran.haversine_length_to(&karaj).get::<meter>(),
+            tehran.haversine_distance_to(&karaj)
+        );
+        assert_eq!(
+            tehran.bearing_angle_to(&karaj).get::<degree>(),
+            tehran.bearing_to(&karaj)
+        );
+
+        let reached = tehran.destination_by(
+            Angle::new::<radian>(std::f64::consts::FRAC_PI_2),
+            Length::new::<kilometer>(10.0),
+        );
+        assert_eq!(reached, tehran.destination(90.0, 10_000.0));
+    }
+}
@@ -0,0 +1,273 @@
+//! conversion between wgs84 points and utm coordinates, compiled only with the `utm` feature.
+//!
+//! the transverse mercator projection uses krüger's series to the fourth order as given by
+//! karney, which is accurate to well below a millimeter within a zone.
+//! <https://arxiv.org/abs/1002.1417>
+
+use crate::point::InvalidCoordinate;
+use crate::Point;
+use std::fmt;
+
+/// semi major axis of the wgs84 ellipsoid in meters.
+const A: f64 = 6_378_137.0;
+/// flattening of the wgs84 ellipsoid.
+const F: f64 = 1.0 / 298.257_223_563;
+/// scale factor on the central meridian of a zone.
+const K0: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.0;
+const FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// position in the universal transverse mercator grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Utm {
+    /// zone number from 1 to 60, iran spans zones 38 to 41.
+    pub zone: u8,
+    /// whether the position is north of the equator, which decides the false northing.
+    pub northern: bool,
+    pub easting: f64,
+    pub northing: f64,
+}
+
+/// error of converting utm coordinates to a `Point`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UtmError {
+    /// the zone is not between 1 and 60.
+    InvalidZone(u8),
+    /// the coordinates don't map to a valid point, e.g. because they are not finite.
+    InvalidCoordinate(InvalidCoordinate),
+}
+
+impl From<InvalidCoordinate> for UtmError {
+    fn from(err: InvalidCoordinate) -> UtmError {
+        UtmError::InvalidCoordinate(err)
+    }
+}
+
+impl fmt::Display for UtmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UtmError::InvalidZone(zone) => {
+                write!(f, "utm zone {} is out of range, expected 1 to 60", zone)
+            }
+            UtmError::InvalidCoordinate(err) => write!(f, "invalid utm coordinates: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for UtmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UtmError::InvalidZone(_) => None,
+            UtmError::InvalidCoordinate(err) => Some(err),
+        }
+    }
+}
+
+/// coefficients of the series, they only depend on the ellipsoid.
+struct Series {
+    /// radius of the rectifying sphere.
+    radius: f64,
+    alpha: [f64; 4],
+    beta: [f64; 4],
+    delta: [f64; 4],
+}
+
+fn series() -> Series {
+    let n = F / (2.0 - F);
+    let (n2, n3, n4) = (n * n, n * n * n, n * n * n * n);
+
+    Series {
+        radius: A / (1.0 + n) * (1.0 + n2 / 4.0 + n4 / 64.0),
+        alpha: [
+            n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0 + 41.0 * n4 / 180.0,
+            13.0 * n2 / 48.0 - 3.0 * n3 / 5.0 + 557.0 * n4 / 1440.0,
+            61.0 * n3 / 240.0 - 103.0 * n4 / 140.0,
+            49561.0 * n4 / 161_280.0,
+        ],
+        beta: [
+            n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0 - n4 / 360.0,
+            n2 / 48.0 + n3 / 15.0 - 437.0 * n4 / 1440.0,
+            17.0 * n3 / 480.0 - 37.0 * n4 / 840.0,
+            4397.0 * n4 / 161_280.0,
+        ],
+        delta: [
+            2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3 + 116.0 * n4 / 45.0,
+            7.0 * n2 / 3.0 - 8.0 * n3 / 5.0 - 227.0 * n4 / 45.0,
+            56.0 * n3 / 15.0 - 136.0 * n4 / 35.0,
+            4279.0 * n4 / 630.0,
+        ],
+    }
+}
+
+/// longitude of the central meridian of the zone in degrees.
+fn central_meridian(zone: u8) -> f64 {
+    f64::from(zone) * 6.0 - 183.0
+}
+
+impl Point {
+    /// point of the given utm coordinates.
+    pub fn from_utm(
+        zone: u8,
+        northern: bool,
+        easting: f64,
+        northing: f64,
+    ) -> Result<Point, UtmError> {
+        if !(1..=60).contains(&zone) {
+            return Err(UtmError::InvalidZone(zone));
+        }
+
+        let series = series();
+        let false_northing = if northern { 0.0 } else { FALSE_NORTHING_SOUTH };
+        let xi = (northing - false_northing) / (K0 * series.radius);
+        let eta = (easting - FALSE_EASTING) / (K0 * series.radius);
+
+        let (mut xi_prime, mut eta_prime) = (xi, eta);
+        for (j, beta) in series.beta.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * (k * xi).sin() * (k * eta).cosh();
+            eta_prime -= beta * (k * xi).cos() * (k * eta).sinh();
+        }
+
+        let chi = (xi_prime.sin() / eta_prime.cosh()).asin();
+        let mut latitude = chi;
+        for (j, delta) in series.delta.iter().enumerate() {
+            latitude += delta * (2.0 * (j + 1) as f64 * chi).sin();
+        }
+        let longitude =
+            central_meridian(zone).to_radians() + eta_prime.sinh().atan2(xi_prime.cos());
+
+        Ok(Point::new(latitude.to_degrees(), longitude.to_degrees())?)
+    }
+
+    /// utm coordinates of the point in its standard zone. the special zones around norway and
+    /// svalbard are not taken into account.
+    pub fn to_utm(&self) -> Utm {
+        let zone = (((self.longitude + 180.0) / 6.0).floor() as i32 + 1).clamp(1, 60) as u8;
+
+        let series = series();
+        let latitude = self.latitude.to_radians();
+        let longitude = (self.longitude - central_meridian(zone)).to_radians();
+
+        // conformal latitude, through its sinh for accuracy near the poles.
+        let e = (F * (2.0 - F)).sqrt();
+        let t = (latitude.sin().atanh() - e * (e * latitude.sin()).atanh()).sinh();
+        let xi_prime = t.atan2(longitude.cos());
+        let eta_prime = (longitude.sin() / (1.0 + t * t).sqrt()).atanh();
+
+        let (mut xi, mut eta) = (xi_prime, eta_prime);
+        for (j, alpha) in series.alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi += alpha * (k * xi_prime).sin() * (k * eta_prime).cosh();
+            eta += alpha * (k * xi_prime).cos() * (k * eta_prime).sinh();
+        }
+
+        let northern = self.latitude >= 0.0;
+        let false_northing = if northern { 0.0 } else { FALSE_NORTHING_SOUTH };
+
+        Utm {
+            zone,
+            northern,
+            easting: FALSE_EASTING + K0 * series.radius * eta,
+            northing: false_northing + K0 * series.radius * xi,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Utm, UtmError};
+    use crate::Point;
+
+    /// reference positions, computed independently with snyder's formulas from the usgs
+    /// "map projections: a working manual".
+    fn references() -> Vec<(Point, Utm)> {
+        let utm = |zone, northern, easting, northing| Utm {
+            zone,
+            northern,
+            easting,
+            northing,
+        };
+
+        vec![
+            // azadi tower, tehran.
+            (
+                Point::new_unchecked(35.6997, 51.3380),
+                utm(39, true, 530_578.267, 3_950_694.260),
+            ),
+            // imam reza shrine, mashhad, far from the central meridian of its zone.
+            (
+                Point::new_unchecked(36.2880, 59.6157),
+                utm(40, true, 734_911.320, 4_019_067.472),
+            ),
+            // tabriz.
+            (
+                Point::new_unchecked(38.0800, 46.2919),
+                utm(38, true, 613_304.360, 4_215_479.166),
+            ),
+            // zahedan.
+            (
+                Point::new_unchecked(29.4963, 60.8629),
+                utm(41, true, 292_823.784, 3_264_876.400),
+            ),
+            // sydney, for the southern hemisphere.
+            (
+                Point::new_unchecked(-33.8688, 151.2093),
+                utm(56, false, 334_368.634, 6_250_948.345),
+            ),
+        ]
+    }
+
+    #[test]
+    fn to_utm_matches_references() {
+        for (point, expected) in references() {
+            let utm = point.to_utm();
+
+            assert_eq!(utm.zone, expected.zone, "{}", point);
+            assert_eq!(utm.northern, expected.northern, "{}", point);
+            assert!((utm.easting - expected.easting).abs() < 0.05, "{:?}", utm);
+            assert!((utm.northing - expected.northing).abs() < 0.05, "{:?}", utm);
+        }
+    }
+
+    #[test]
+    fn from_utm_matches_references() {
+        for (expected, utm) in references() {
+            let point = Point::from_utm(utm.zone, utm.northern, utm.easting, utm.northing).unwrap();
+
+            assert!(point.haversine_distance_to(&expected) < 0.05, "{}", point);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for latitude in [25.0, 30.5, 35.6892, 39.7] {
+            for longitude in [44.1, 48.0, 51.389, 57.3, 59.6, 63.3] {
+                let point = Point::new_unchecked(latitude, longitude);
+                let utm = point.to_utm();
+                let back =
+                    Point::from_utm(utm.zone, utm.northern, utm.easting, utm.northing).unwrap();
+
+                // the truncated series round trips to within micrometers.
+                assert!(point.haversine_distance_to(&back) < 1e-3, "{}", point);
+            }
+        }
+    }
+
+    #[test]
+    fn reject_invalid_input() {
+        assert_eq!(
+            Point::from_utm(0, true, 500_000.0, 0.0),
+            Err(UtmError::InvalidZone(0))
+        );
+        assert_eq!(
+            Point::from_utm(61, true, 500_000.0, 0.0)
+                .unwrap_err()
+                .to_string(),
+            "utm zone 61 is out of range, expected 1 to 60"
+        );
+        assert!(matches!(
+            Point::from_utm(39, true, f64::NAN, 3_950_000.0),
+            Err(UtmError::InvalidCoordinate(_))
+        ));
+    }
+}
@@ -0,0 +1,326 @@
+//! offline check of tehran's traffic and odd-even zones, compiled only with the `zones`
+//! feature.
+//!
+//! the crate doesn't bundle the zone outlines, neshan and the municipality don't publish them
+//! in a machine readable form. load the ones you have with `Polygon::from_geojson` and set
+//! them with `set_traffic_zone` and `set_odd_even_zone`. the checks are meant for hot paths
+//! where a reverse geocode per point is too slow, `PostalAddress::in_traffic_zone` stays the
+//! authoritative answer.
+
+use crate::point::InvalidCoordinate;
+use crate::Point;
+use serde_json::Value;
+use std::fmt;
+use std::sync::RwLock;
+
+/// area bounded by an outer ring with optional holes.
+///
+/// coordinates are treated as planar, which is accurate enough at the scale of a city. points
+/// on an edge are inside the polygon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    outer: Vec<Point>,
+    holes: Vec<Vec<Point>>,
+}
+
+impl Polygon {
+    /// polygon of the given outer ring, the last point may repeat the first one.
+    pub fn new(outer: Vec<Point>) -> Result<Polygon, ZoneError> {
+        Polygon::with_holes(outer, Vec::new())
+    }
+
+    pub fn with_holes(outer: Vec<Point>, holes: Vec<Vec<Point>>) -> Result<Polygon, ZoneError> {
+        let ring = |points: Vec<Point>| -> Result<Vec<Point>, ZoneError> {
+            for point in &points {
+                point.validate()?;
+            }
+            let mut points = points;
+            if points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            if points.len() < 3 {
+                return Err(ZoneError::TooFewPoints(points.len()));
+            }
+            Ok(points)
+        };
+
+        Ok(Polygon {
+            outer: ring(outer)?,
+            holes: holes.into_iter().map(ring).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// polygon of a geojson `Polygon` geometry or of a `Feature` with one.
+    pub fn from_geojson(geojson: &str) -> Result<Polygon, ZoneError> {
+        let value: Value = serde_json::from_str(geojson)
+            .map_err(|err| ZoneError::InvalidGeoJson(err.to_string()))?;
+        polygon(&value)
+    }
+
+    /// whether the point is inside the polygon or on its boundary.
+    pub fn contains(&self, point: &Point) -> bool {
+        match ring_contains(&self.outer, point) {
+            Position::Outside => false,
+            Position::Boundary => true,
+            Position::Inside => self
+                .holes
+                .iter()
+                .all(|hole| ring_contains(hole, point) != Position::Inside),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Position {
+    Inside,
+    Boundary,
+    Outside,
+}
+
+/// even-odd ray casting towards the east.
+fn ring_contains(ring: &[Point], point: &Point) -> Position {
+    let (x, y) = (point.longitude, point.latitude);
+    let mut inside = false;
+
+    for (i, a) in ring.iter().enumerate() {
+        let b = &ring[(i + 1) % ring.len()];
+        let (ax, ay, bx, by) = (a.longitude, a.latitude, b.longitude, b.latitude);
+
+        let cross = (bx - ax) * (y - ay) - (by - ay) * (x - ax);
+        if cross == 0.0
+            && (ax.min(bx)..=ax.max(bx)).contains(&x)
+            && (ay.min(by)..=ay.max(by)).contains(&y)
+        {
+            return Position::Boundary;
+        }
+
+        if (ay > y) != (by > y) && x < ax + (y - ay) * (bx - ax) / (by - ay) {
+            inside = !inside;
+        }
+    }
+
+    if inside {
+        Position::Inside
+    } else {
+        Position::Outside
+    }
+}
+
+fn polygon(value: &Value) -> Result<Polygon, ZoneError> {
+    let invalid = |message: &str| ZoneError::InvalidGeoJson(message.to_string());
+
+    match value["type"].as_str() {
+        Some("Feature") => return polygon(&value["geometry"]),
+        Some("Polygon") => {}
+        Some(other) => {
+            return Err(ZoneError::InvalidGeoJson(format!(
+                "expected a Polygon, found {}",
+                other
+            )))
+        }
+        None => return Err(invalid("missing type")),
+    }
+
+    let mut rings = Vec::new();
+    for ring in value["coordinates"]
+        .as_array()
+        .ok_or_else(|| invalid("missing coordinates"))?
+    {
+        let mut points = Vec::new();
+        for position in ring
+            .as_array()
+            .ok_or_else(|| invalid("ring is not an array"))?
+        {
+            match position.as_array().map(Vec::as_slice) {
+                Some([longitude, latitude, ..]) => match (longitude.as_f64(), latitude.as_f64()) {
+                    (Some(longitude), Some(latitude)) => {
+                        points.push(Point::new_unchecked(latitude, longitude))
+                    }
+                    _ => return Err(invalid("position is not a pair of numbers")),
+                },
+                _ => return Err(invalid("position is not a pair of numbers")),
+            }
+        }
+        rings.push(points);
+    }
+
+    if rings.is_empty() {
+        return Err(invalid("polygon has no rings"));
+    }
+    let holes = rings.split_off(1);
+    Polygon::with_holes(rings.pop().unwrap_or_default(), holes)
+}
+
+static TRAFFIC: RwLock<Option<Polygon>> = RwLock::new(None);
+static ODD_EVEN: RwLock<Option<Polygon>> = RwLock::new(None);
+
+fn contains(zone: &RwLock<Option<Polygon>>, point: &Point) -> Option<bool> {
+    let zone = zone.read().unwrap_or_else(|err| err.into_inner());
+    zone.as_ref().map(|zone| zone.contains(point))
+}
+
+/// whether the point is inside the traffic zone (طرح ترافیک), `None` until it is set.
+pub fn in_traffic_zone(point: &Point) -> Option<bool> {
+    contains(&TRAFFIC, point)
+}
+
+/// whether the point is inside the odd-even zone (طرح زوج و فرد), `None` until it is set.
+pub fn in_odd_even_zone(point: &Point) -> Option<bool> {
+    contains(&ODD_EVEN, point)
+}
+
+/// use the polygon as the traffic zone in the whole process, `None` unsets it.
+pub fn set_traffic_zone(polygon: Option<Polygon>) {
+    *TRAFFIC.write().unwrap_or_else(|err| err.into_inner()) = polygon;
+}
+
+/// use the polygon as the odd-even zone in the whole process, `None` unsets it.
+pub fn set_odd_even_zone(polygon: Option<Polygon>) {
+    *ODD_EVEN.write().unwrap_or_else(|err| err.into_inner()) = polygon;
+}
+
+/// polygon rejected by `Polygon::new` or `Polygon::from_geojson`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZoneError {
+    /// a ring has less than three distinct points.
+    TooFewPoints(usize),
+    /// a vertex is not a valid point.
+    InvalidCoordinate(InvalidCoordinate),
+    /// the geojson is malformed or not a polygon.
+    InvalidGeoJson(String),
+}
+
+impl From<InvalidCoordinate> for ZoneError {
+    fn from(err: InvalidCoordinate) -> ZoneError {
+        ZoneError::InvalidCoordinate(err)
+    }
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZoneError::TooFewPoints(count) => {
+                write!(f, "a ring needs at least 3 points, found {}", count)
+            }
+            ZoneError::InvalidCoordinate(err) => write!(f, "invalid vertex: {}", err),
+            ZoneError::InvalidGeoJson(message) => write!(f, "invalid geojson: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ZoneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZoneError::InvalidCoordinate(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        in_odd_even_zone, in_traffic_zone, set_odd_even_zone, set_traffic_zone, Polygon, ZoneError,
+    };
+    use crate::Point;
+    use std::sync::Mutex;
+
+    /// tests that read or replace the process wide zones.
+    static ZONES: Mutex<()> = Mutex::new(());
+
+    fn square() -> Polygon {
+        Polygon::new(vec![
+            Point::new_unchecked(0.0, 0.0),
+            Point::new_unchecked(0.0, 1.0),
+            Point::new_unchecked(1.0, 1.0),
+            Point::new_unchecked(1.0, 0.0),
+            Point::new_unchecked(0.0, 0.0),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn boundary_is_inside() {
+        let square = square();
+        assert!(square.contains(&Point::new_unchecked(0.5, 0.5)));
+        assert!(square.contains(&Point::new_unchecked(0.0, 0.5)));
+        assert!(square.contains(&Point::new_unchecked(0.5, 1.0)));
+        assert!(square.contains(&Point::new_unchecked(1.0, 1.0)));
+        assert!(!square.contains(&Point::new_unchecked(1.0, 1.000001)));
+        assert!(!square.contains(&Point::new_unchecked(-0.5, 0.5)));
+        // on the line of an edge, but past its end.
+        assert!(!square.contains(&Point::new_unchecked(0.0, 1.5)));
+    }
+
+    #[test]
+    fn holes() {
+        let polygon = Polygon::from_geojson(
+            r#"{"type": "Feature", "properties": {}, "geometry": {"type": "Polygon", "coordinates": [
+                [[0, 0], [4, 0], [4, 4], [0, 4], [0, 0]],
+                [[1, 1], [3, 1], [3, 3], [1, 3], [1, 1]]
+            ]}}"#,
+        )
+        .unwrap();
+
+        assert!(polygon.contains(&Point::new_unchecked(0.5, 0.5)));
+        assert!(!polygon.contains(&Point::new_unchecked(2.0, 2.0)));
+        assert!(polygon.contains(&Point::new_unchecked(1.0, 2.0)));
+    }
+
+    #[test]
+    fn reject_invalid_polygons() {
+        assert_eq!(
+            Polygon::new(vec![
+                Point::new_unchecked(0.0, 0.0),
+                Point::new_unchecked(1.0, 1.0),
+                Point::new_unchecked(0.0, 0.0),
+            ]),
+            Err(ZoneError::TooFewPoints(2))
+        );
+        assert!(matches!(
+            Polygon::new(vec![Point::new_unchecked(95.0, 0.0); 3]),
+            Err(ZoneError::InvalidCoordinate(_))
+        ));
+        assert_eq!(
+            Polygon::from_geojson(r#"{"type": "Point", "coordinates": [51.4, 35.7]}"#)
+                .unwrap_err()
+                .to_string(),
+            "invalid geojson: expected a Polygon, found Point"
+        );
+        assert!(
+            Polygon::from_geojson(r#"{"type": "Polygon", "coordinates": [[[0, 0], [1]]]}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn set_zones() {
+        let _zones = ZONES.lock().unwrap_or_else(|err| err.into_inner());
+        let point = Point::new_unchecked(0.5, 0.5);
+        assert_eq!(in_traffic_zone(&point), None);
+
+        set_traffic_zone(Some(square()));
+        assert_eq!(in_traffic_zone(&point), Some(true));
+        assert_eq!(
+            in_traffic_zone(&Point::new_unchecked(35.6918, 51.4290)),
+            Some(false)
+        );
+        assert_eq!(in_odd_even_zone(&point), None);
+
+        set_odd_even_zone(
+            Polygon::from_geojson(
+                r#"{"type": "Polygon", "coordinates": [[[0, 0], [2, 0], [2, 2], [0, 2]]]}"#,
+            )
+            .ok(),
+        );
+        assert_eq!(
+            in_odd_even_zone(&Point::new_unchecked(1.5, 1.5)),
+            Some(true)
+        );
+
+        set_traffic_zone(None);
+        set_odd_even_zone(None);
+        assert_eq!(in_traffic_zone(&point), None);
+        assert_eq!(in_odd_even_zone(&point), None);
+    }
+}
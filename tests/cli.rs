@@ -0,0 +1,135 @@
+#![cfg(all(feature = "cli", feature = "test-utils"))]
+
+use assert_cmd::Command;
+use neshan_rs::{Endpoint, MockNeshan, MockResponse};
+
+fn neshan(mock: &MockNeshan) -> Command {
+    let mut command = Command::cargo_bin("neshan").unwrap();
+    command
+        .env("NESHAN_API_KEY", MockNeshan::API_KEY)
+        .env("NESHAN_BASE_URL", mock.uri());
+    command
+}
+
+fn stdout(output: &std::process::Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn route() {
+    let mock = MockNeshan::start().await;
+
+    let output = neshan(&mock)
+        .args(["route", "--from", "35.73,51.39", "--to", "35.72,50.95"])
+        .args(["--vehicle", "motorcycle", "--alternatives"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(
+        text.starts_with("1. آزادی - بزرگراه تهران کرج: 40.5 km"),
+        "{}",
+        text
+    );
+    assert!(text.lines().any(|line| line.starts_with("2. ")));
+
+    let requests = mock.requests_to(Endpoint::Route).await;
+    assert_eq!(requests[0].query("type"), Some("motorcycle"));
+    assert_eq!(requests[0].query("origin"), Some("35.730000,51.390000"));
+    assert_eq!(requests[0].query("alternative"), Some("true"));
+
+    let output = neshan(&mock)
+        .args([
+            "route",
+            "--from",
+            "35.73,51.39",
+            "--to",
+            "35.72,50.95",
+            "--json",
+        ])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["routes"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn reverse() {
+    let mock = MockNeshan::start().await;
+
+    let output = neshan(&mock)
+        .args(["reverse", "35.73,51.39"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert_eq!(
+        text.lines().next(),
+        Some("تهران، منطقه ۶، قزل قلعه، خیابان آزادی")
+    );
+    assert!(text.contains("in the traffic zone"));
+
+    let output = neshan(&mock)
+        .args(["reverse", "35.73,51.39", "--json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["city"], "تهران");
+}
+
+#[tokio::test]
+async fn exit_codes() {
+    let mock = MockNeshan::start().await;
+    let reverse = || {
+        neshan(&mock)
+            .args(["reverse", "35.73,51.39"])
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+
+    mock.respond(
+        Endpoint::ReverseGeocode,
+        MockResponse::error(480, 480, "Key not found"),
+    )
+    .await;
+    assert_eq!(reverse(), Some(3));
+
+    mock.respond(
+        Endpoint::ReverseGeocode,
+        MockResponse::error(481, 481, "Limit exceeded"),
+    )
+    .await;
+    assert_eq!(reverse(), Some(4));
+
+    mock.respond(
+        Endpoint::ReverseGeocode,
+        MockResponse::error(404, 404, "Not found"),
+    )
+    .await;
+    assert_eq!(reverse(), Some(5));
+
+    mock.respond(
+        Endpoint::Route,
+        MockResponse::json(serde_json::json!({"routes": []})),
+    )
+    .await;
+    let output = neshan(&mock)
+        .args(["route", "--from", "35.73,51.39", "--to", "35.72,50.95"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(5));
+
+    let output = neshan(&mock)
+        .env_remove("NESHAN_API_KEY")
+        .args(["reverse", "35.73,51.39"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let output = neshan(&mock)
+        .args(["reverse", "north of tehran"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}